@@ -0,0 +1,584 @@
+use crossterm::{
+  execute,
+  style::Stylize,
+  terminal::{DisableLineWrap, EnableLineWrap},
+};
+use std::{io::stdout, process::exit};
+
+use crate::{
+  APPNAME,
+  error::PuddlerError,
+  input::{
+    InteractiveOption, InteractiveOptionType, SeriesOptions, interactive_select, series_playlist,
+    series_select, series_structure,
+  },
+  media_center::{MediaCenter, ToStringAdv},
+  media_config::{Config, Objective},
+  media_item::{MediaItem, MediaKind, PlaybackSource},
+  mpv::{Player, Video},
+  offline::{ItemAction, choose_action},
+  printing::{PrintMessageType, print_message},
+  puddler_settings::PuddlerSettings,
+  queue::{add_to_queue, store_queue},
+  receiver::apply_remote_requests,
+};
+
+// Everything the menus and the player need from a media-center, whichever one it is.
+// Jellyfin, Emby and Plex get this through `MediaCenter` and keep its menu (search, queue, music ...).
+// `backend_menu` is only used by what implements this directly (DLNA, local folders), the series
+// selection and the player below are shared by all of them.
+pub trait Backend: Send {
+  fn get_config_handle(&mut self) -> &mut Config;
+  fn get_settings(&mut self) -> &mut PuddlerSettings;
+
+  // Logs in, or checks that the saved session is still valid.
+  fn re_authenticate(&mut self) -> Result<(), PuddlerError>;
+
+  // `None` lists the top level: libraries, shares, folders ...
+  fn browse(&mut self, parent: Option<&MediaItem>) -> Result<Vec<MediaItem>, PuddlerError>;
+
+  // Started, but not finished yet.
+  fn resume(&mut self) -> Result<Vec<MediaItem>, PuddlerError> {
+    Ok(vec![])
+  }
+
+  fn resolve_stream(&mut self, item: &MediaItem) -> Result<PlaybackSource, PuddlerError>;
+
+  // Where playback has been stopped, in seconds.
+  fn report_progress(&mut self, item: &MediaItem, position: u64) -> Result<(), PuddlerError>;
+
+  fn set_playstate(&mut self, item: &MediaItem, played: bool) -> Result<(), PuddlerError>;
+
+  // Episodes and seasons lead to their series, a series to itself.
+  fn series_of(&mut self, _item_id: &str) -> Result<Option<MediaItem>, PuddlerError> {
+    Ok(None)
+  }
+
+  // Nothing is reported while playing, only where it has been stopped (or that it has been played).
+  fn play(
+    &mut self,
+    player: &mut Player,
+    item: &MediaItem,
+    transcoding_settings: &mut Option<(bool, Option<u32>, Option<u32>, String)>,
+  ) -> Result<Video, PuddlerError> {
+    let source = self.resolve_stream(item)?;
+    player.set_video(item, source, transcoding_settings);
+    let video = player.play()?;
+    let result = if video.played {
+      self.set_playstate(item, true)
+    } else {
+      self.report_progress(item, video.playback_position)
+    };
+    if let Err(err) = result {
+      print_message(
+        PrintMessageType::Error,
+        format!("Failed to log playback progress: {}", err).as_str(),
+      );
+    }
+    Ok(video)
+  }
+
+  fn menu(&mut self) -> Result<(), PuddlerError> {
+    backend_menu(self)
+  }
+
+  fn modify(&mut self) {
+    modify_backend(self)
+  }
+
+  // The subcommands and the offline sync need the whole API.
+  fn as_media_center(&mut self) -> Option<&mut dyn MediaCenter> {
+    None
+  }
+}
+
+impl<M: MediaCenter> Backend for M {
+  fn get_config_handle(&mut self) -> &mut Config {
+    MediaCenter::get_config_handle(self)
+  }
+
+  fn get_settings(&mut self) -> &mut PuddlerSettings {
+    MediaCenter::get_settings(self)
+  }
+
+  fn re_authenticate(&mut self) -> Result<(), PuddlerError> {
+    MediaCenter::re_authenticate(self)
+  }
+
+  fn browse(&mut self, parent: Option<&MediaItem>) -> Result<Vec<MediaItem>, PuddlerError> {
+    MediaCenter::browse(self, parent)
+  }
+
+  fn resume(&mut self) -> Result<Vec<MediaItem>, PuddlerError> {
+    MediaCenter::resume(self)
+  }
+
+  fn resolve_stream(&mut self, item: &MediaItem) -> Result<PlaybackSource, PuddlerError> {
+    MediaCenter::resolve_stream(self, &item.id)
+  }
+
+  // Only used by `Backend::play`, the media-centers report the progress of their own sessions.
+  fn report_progress(&mut self, _item: &MediaItem, _position: u64) -> Result<(), PuddlerError> {
+    Ok(())
+  }
+
+  fn set_playstate(&mut self, item: &MediaItem, played: bool) -> Result<(), PuddlerError> {
+    MediaCenter::set_playstate(self, &item.id, played)
+  }
+
+  fn series_of(&mut self, item_id: &str) -> Result<Option<MediaItem>, PuddlerError> {
+    MediaCenter::series_of(self, item_id)
+  }
+
+  // The media-centers report the playback themselves.
+  fn play(
+    &mut self,
+    player: &mut Player,
+    item: &MediaItem,
+    transcoding_settings: &mut Option<(bool, Option<u32>, Option<u32>, String)>,
+  ) -> Result<Video, PuddlerError> {
+    self.play_media_item(player, item, transcoding_settings, false)
+  }
+
+  fn menu(&mut self) -> Result<(), PuddlerError> {
+    MediaCenter::menu(self)
+  }
+
+  fn modify(&mut self) {
+    MediaCenter::modify(self)
+  }
+
+  fn as_media_center(&mut self) -> Option<&mut dyn MediaCenter> {
+    Some(self)
+  }
+}
+
+pub fn backend_menu<B: Backend + ?Sized>(backend: &mut B) -> Result<(), PuddlerError> {
+  // every container that has been opened, the last one is shown
+  let mut path: Vec<MediaItem> = vec![];
+  loop {
+    let mut options: Vec<InteractiveOption> = vec![];
+    let mut total: Vec<MediaItem> = vec![];
+    if path.is_empty() {
+      let items = backend.resume().unwrap_or_else(|err| {
+        print_message(PrintMessageType::Error, &err.to_string());
+        vec![]
+      });
+      if !items.is_empty() {
+        options.push(InteractiveOption {
+          text: String::from("Continue Watching:"),
+          option_type: InteractiveOptionType::Header,
+        });
+        for item in &items {
+          options.push(InteractiveOption {
+            text: item.to_string_ext(),
+            option_type: InteractiveOptionType::Button,
+          });
+        }
+        options.push(InteractiveOption {
+          text: String::new(),
+          option_type: InteractiveOptionType::Header,
+        });
+      }
+      total.extend(items);
+    }
+    let resume_size = total.len();
+
+    // a container that can't be opened leaves the menu where it was
+    let items = match backend.browse(path.last()) {
+      Ok(items) => items,
+      Err(err) => {
+        print_message(PrintMessageType::Error, &err.to_string());
+        if path.pop().is_some() {
+          continue;
+        }
+        vec![]
+      },
+    };
+    let header = match path.last() {
+      Some(parent) => format!("{}:", parent),
+      None => String::from("Libraries:"),
+    };
+    options.push(InteractiveOption {
+      text: header,
+      option_type: InteractiveOptionType::Header,
+    });
+    for item in &items {
+      options.push(InteractiveOption {
+        text: item.to_string_ext(),
+        option_type: InteractiveOptionType::Button,
+      });
+    }
+    total.extend(items.clone());
+    options.push(InteractiveOption {
+      text: String::new(),
+      option_type: InteractiveOptionType::Header,
+    });
+    if path.is_empty() {
+      options.push(InteractiveOption {
+        text: format!("Return to {} Menu", APPNAME),
+        option_type: InteractiveOptionType::Special,
+      });
+    } else {
+      options.push(InteractiveOption {
+        text: String::from("Back"),
+        option_type: InteractiveOptionType::Special,
+      });
+    }
+
+    match interactive_select(options) {
+      ((index, _), _, InteractiveOptionType::Button) => {
+        let item = total[index].clone();
+        // episodes and tracks of a listing go on with whatever comes after them, movies don't
+        let following =
+          if index < resume_size || !matches!(item.kind, MediaKind::Episode | MediaKind::Track) {
+            vec![]
+          } else {
+            items[index - resume_size + 1..].to_vec()
+          };
+        let result = match item.kind {
+          MediaKind::Movie | MediaKind::Episode | MediaKind::Track | MediaKind::Series => {
+            process_item(backend, item, following)
+          },
+          _ => {
+            path.push(item);
+            Ok(())
+          },
+        };
+        if let Err(err) = result {
+          print_message(PrintMessageType::Error, &err.to_string());
+        }
+      },
+      (_, Some(option), InteractiveOptionType::Special) => {
+        if option == *"Back" {
+          path.pop();
+        } else {
+          return Ok(());
+        }
+      },
+      _ => (),
+    }
+  }
+}
+
+// Music goes to the music player, series to the episode selection. Everything else is played (or
+// downloaded, or queued) together with what comes after it.
+pub fn process_item<B: Backend + ?Sized>(
+  backend: &mut B,
+  item: MediaItem,
+  following: Vec<MediaItem>,
+) -> Result<(), PuddlerError> {
+  println!("Selected: {}", item.to_string_ext().cyan());
+  if matches!(
+    item.kind,
+    MediaKind::Artist | MediaKind::Album | MediaKind::Track
+  ) && let Some(media_center) = backend.as_media_center()
+  {
+    return media_center.play_music(&item);
+  }
+  let mut skip_played = true;
+  let mut playlist = match item.kind {
+    MediaKind::Episode => match backend.series_of(&item.id)? {
+      // the rest of the series, starting with this episode
+      Some(series) => series_seasons(backend, &series)?
+        .into_iter()
+        .flat_map(|(_, episodes)| episodes)
+        .skip_while(|episode| episode.id != item.id)
+        .collect(),
+      None => vec![],
+    },
+    MediaKind::Series | MediaKind::Season => {
+      let series = if item.kind == MediaKind::Series {
+        item.clone()
+      } else if let Some(series) = backend.series_of(&item.id)? {
+        series
+      } else {
+        return Err(PuddlerError::Unsupported(format!(
+          "\"{}\" is not part of a series.",
+          item
+        )));
+      };
+      let (playlist, skip) = choose_from_series(backend, &series)?;
+      skip_played = skip;
+      playlist
+    },
+    MediaKind::Movie | MediaKind::Track => vec![],
+    _ => return Ok(()),
+  };
+  if playlist.is_empty() && !matches!(item.kind, MediaKind::Series | MediaKind::Season) {
+    let kind = item.kind.clone();
+    playlist.push(item);
+    playlist.extend(following.into_iter().filter(|item| item.kind == kind));
+  }
+  if playlist.is_empty() {
+    return Ok(());
+  }
+  if let Some(media_center) = backend.as_media_center() {
+    match choose_action(playlist.len()) {
+      ItemAction::Play => (),
+      ItemAction::Download => return media_center.download_items(vec![playlist.remove(0)]),
+      ItemAction::DownloadAll => return media_center.download_items(playlist),
+      ItemAction::Queue => {
+        playlist.truncate(1);
+        add_to_queue(media_center.get_queue(), playlist);
+        store_queue(media_center);
+        return Ok(());
+      },
+      ItemAction::QueueAll => {
+        add_to_queue(media_center.get_queue(), playlist);
+        store_queue(media_center);
+        return Ok(());
+      },
+      ItemAction::Back => return Ok(()),
+    }
+  }
+  play_playlist(backend, playlist, skip_played)?;
+  Ok(())
+}
+
+// Seasons are the children of a series, episodes the children of a season.
+// Specials are watched last and episodes that are listed in several seasons only once.
+pub fn series_seasons<B: Backend + ?Sized>(
+  backend: &mut B,
+  series: &MediaItem,
+) -> Result<Vec<(MediaItem, Vec<MediaItem>)>, PuddlerError> {
+  let mut seasons: Vec<MediaItem> = backend
+    .browse(Some(series))?
+    .into_iter()
+    .filter(|season| season.kind == MediaKind::Season)
+    .collect();
+  if seasons
+    .first()
+    .is_some_and(|season| season_name(season) == "Specials")
+  {
+    let specials = seasons.remove(0);
+    seasons.push(specials);
+  }
+  let mut episode_ids: Vec<String> = vec![];
+  let mut structure: Vec<(MediaItem, Vec<MediaItem>)> = vec![];
+  for season in seasons {
+    let episodes: Vec<MediaItem> = backend
+      .browse(Some(&season))?
+      .into_iter()
+      .filter(|episode| episode.kind == MediaKind::Episode && !episode_ids.contains(&episode.id))
+      .collect();
+    episode_ids.extend(episodes.iter().map(|episode| episode.id.clone()));
+    if !episodes.is_empty() {
+      structure.push((season, episodes));
+    }
+  }
+  Ok(structure)
+}
+
+// The name of the season itself, without the series (see `ToStringAdv::to_string_split`).
+fn season_name(season: &MediaItem) -> String {
+  season.titles.last().cloned().unwrap_or_default()
+}
+
+// Shuffled selections come back in their playing order and shouldn't skip over watched episodes.
+fn choose_from_series<B: Backend + ?Sized>(
+  backend: &mut B,
+  series: &MediaItem,
+) -> Result<(Vec<MediaItem>, bool), PuddlerError> {
+  loop {
+    let seasons = series_seasons(backend, series)?;
+    if seasons.is_empty() {
+      return Err(PuddlerError::Unsupported(format!(
+        "\"{}\" doesn't have any episodes.",
+        series
+      )));
+    }
+    let structure = series_structure(
+      series.to_string(),
+      seasons
+        .iter()
+        .map(|(season, episodes)| {
+          (
+            season_name(season),
+            episodes
+              .iter()
+              .map(|episode| episode.to_string_ext())
+              .collect(),
+          )
+        })
+        .collect(),
+    );
+    let episodes: Vec<MediaItem> = seasons
+      .into_iter()
+      .flat_map(|(_, episodes)| episodes)
+      .collect();
+    match series_select(structure, &episodes) {
+      (SeriesOptions::Played, Some(indexes)) => {
+        for index in indexes {
+          backend.set_playstate(&episodes[index], true)?;
+        }
+      },
+      (SeriesOptions::UnPlayed, Some(indexes)) => {
+        for index in indexes {
+          backend.set_playstate(&episodes[index], false)?;
+        }
+      },
+      (SeriesOptions::Back, _) | (_, None) => return Ok((vec![], true)),
+      (mode, Some(indexes)) => return Ok(series_playlist(episodes, &mode, indexes)),
+    }
+  }
+}
+
+// Series skip over played episodes, queues and playlists are played as they are.
+// Returns the ids of every item that has been played to the end.
+pub fn play_playlist<B: Backend + ?Sized>(
+  backend: &mut B,
+  mut playlist: Vec<MediaItem>,
+  skip_played: bool,
+) -> Result<Vec<String>, PuddlerError> {
+  let settings = backend.get_settings().clone();
+  let mut player = Player::new(backend.get_config_handle().clone(), settings);
+
+  let mut transcoding_settings = None;
+  let mut index = 0;
+  let mut played: Vec<String> = vec![];
+  let mut stdout = stdout();
+  while let Some(item) = playlist.get(index).cloned() {
+    let mut next_index = index + 1;
+    let ret = backend.play(&mut player, &item, &mut transcoding_settings)?;
+    if ret.played {
+      played.push(item.id.clone());
+    }
+    if let Some((_, audio, subtitle, ..)) = transcoding_settings.as_mut() {
+      *audio = ret.preferred_audio_track;
+      *subtitle = ret.preferred_subtitle_track
+    }
+    // the dashboard (or another client) might have asked for a different item
+    if let Some(media_center) = backend.as_media_center()
+      && let Some(remote_index) = apply_remote_requests(
+        media_center,
+        &mut playlist,
+        index,
+        ret.remote_requests.clone(),
+      )
+    {
+      index = remote_index;
+      continue;
+    }
+    // "Finish" starts again where it has been stopped
+    if !ret.played {
+      playlist[index].playback_position = ret.playback_position;
+    }
+    'playback_done: loop {
+      let mut options: Vec<InteractiveOption> = vec![];
+      execute!(stdout, DisableLineWrap).unwrap();
+      if !ret.played {
+        options.append(&mut vec![
+          InteractiveOption {
+            text: format!("Finish: {}", item.to_string_ext()),
+            option_type: InteractiveOptionType::Button,
+          },
+          InteractiveOption {
+            text: format!("Mark as played: {}", item.to_string_ext()),
+            option_type: InteractiveOptionType::Button,
+          },
+        ]);
+      }
+      // skip every item that has been played already
+      // (might want to use unmark in the menu before watching a series again)
+      while skip_played && playlist.get(next_index).is_some_and(|next| next.played) {
+        next_index += 1;
+      }
+      if let Some(next_item) = playlist.get(next_index) {
+        options.push(InteractiveOption {
+          text: format!("Continue with: {}", next_item.to_string_ext()),
+          option_type: InteractiveOptionType::Button5s,
+        });
+      }
+      if options.is_empty() {
+        execute!(stdout, EnableLineWrap).unwrap();
+        print_message(
+          PrintMessageType::Warning,
+          "Playlist done. Returning to menu.",
+        );
+        return Ok(played);
+      }
+      options.append(&mut vec![
+        InteractiveOption {
+          text: "Back to Menu".to_string(),
+          option_type: InteractiveOptionType::Special,
+        },
+        InteractiveOption {
+          text: "Exit Application".to_string(),
+          option_type: InteractiveOptionType::Special,
+        },
+      ]);
+      let selection = interactive_select(options);
+      execute!(stdout, EnableLineWrap).unwrap();
+      match selection {
+        ((_, _), Some(text), InteractiveOptionType::Button) => {
+          if text.starts_with("Finish") {
+            if let Some(settings) = transcoding_settings.as_mut() {
+              settings.0 = true;
+            }
+            break 'playback_done;
+          } else if text.starts_with("Mark") {
+            if let Err(err) = backend.set_playstate(&item, true) {
+              print_message(
+                PrintMessageType::Error,
+                format!("Failed to mark item as Played: {}", err).as_str(),
+              );
+            }
+            continue 'playback_done;
+          } else if text.starts_with("Continue") {
+            index = next_index;
+            break 'playback_done;
+          }
+        },
+        ((_, _), Some(text), InteractiveOptionType::Special) => match text.as_str() {
+          "Back to Menu" => return Ok(played),
+          _ => exit(0),
+        },
+        _ => (),
+      }
+    }
+  }
+  Ok(played)
+}
+
+// Backends without users or transcoding can't be changed much.
+fn modify_backend<B: Backend + ?Sized>(backend: &mut B) {
+  loop {
+    let handle = backend.get_config_handle();
+    let settings = vec![
+      InteractiveOption {
+        text: format!("Settings: {}", handle.config.server_name.clone().cyan()),
+        option_type: InteractiveOptionType::Header,
+      },
+      InteractiveOption {
+        text: String::from("Change Name"),
+        option_type: InteractiveOptionType::Button,
+      },
+      InteractiveOption {
+        text: format!("{}", "Save".green()),
+        option_type: InteractiveOptionType::Button,
+      },
+      InteractiveOption {
+        text: format!("{}", "Delete".red()),
+        option_type: InteractiveOptionType::Button,
+      },
+      InteractiveOption {
+        text: String::from("Back"),
+        option_type: InteractiveOptionType::Special,
+      },
+    ];
+    match interactive_select(settings) {
+      ((0, _), _, InteractiveOptionType::Button) => {
+        handle.ask_for_setting(Objective::ServerName);
+        handle.save();
+      },
+      ((1, _), _, InteractiveOptionType::Button) => handle.save(),
+      ((2, _), _, InteractiveOptionType::Button) => {
+        handle.delete();
+        break;
+      },
+      (_, _, InteractiveOptionType::Special) => break,
+      _ => (),
+    }
+  }
+}
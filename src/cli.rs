@@ -0,0 +1,287 @@
+use clap::ArgMatches;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::{path::Path, process::ExitCode};
+
+use crate::{
+  backend::{Backend, series_seasons},
+  error::PuddlerError,
+  history::{History, format_seconds},
+  media_center::set_config,
+  media_config::{Config, UserConfig},
+  media_item::MediaItem,
+  printing::{PrintMessageType, print_message},
+  puddler_settings::PuddlerSettings,
+};
+
+#[derive(Debug, Clone)]
+pub enum Listing {
+  Search(String),
+  Resume,
+  NextUp,
+  Latest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+  Text,
+  Json,
+}
+
+// Implemented by every item type that can be printed by the cli.
+pub trait Listable: Serialize + ToString {
+  fn id(&self) -> String;
+  fn item_type(&self) -> String;
+}
+
+#[derive(Debug, Serialize)]
+struct ServerListing {
+  name: String,
+  media_center_type: String,
+  address: String,
+  users: Vec<String>,
+  default: bool,
+}
+
+// Entry point for all subcommands. Nothing in here is supposed to draw the interactive menu.
+pub fn run(command: &ArgMatches, settings: PuddlerSettings) -> ExitCode {
+  let (name, sub_command) = command.subcommand().unwrap();
+  let output = match sub_command.get_one::<String>("output").map(|o| o.as_str()) {
+    Some("json") => OutputFormat::Json,
+    _ => OutputFormat::Text,
+  };
+  if name == "list-servers" {
+    list_servers(&settings, output);
+    return ExitCode::SUCCESS;
+  }
+  // The history is local, no need to talk to any server.
+  if name == "history" {
+    return match list_history(sub_command, output) {
+      Ok(()) => ExitCode::SUCCESS,
+      Err(err) => {
+        print_message(PrintMessageType::Error, &err.to_string());
+        ExitCode::FAILURE
+      },
+    };
+  }
+
+  let handle = match find_config(sub_command, &settings) {
+    Ok(handle) => handle,
+    Err(err) => {
+      print_message(PrintMessageType::Error, &err);
+      return ExitCode::FAILURE;
+    },
+  };
+  let media_center_type = handle.config.media_center_type;
+  let mut backend = set_config(handle, settings);
+  let Some(center) = backend.as_media_center() else {
+    print_message(
+      PrintMessageType::Error,
+      format!(
+        "Subcommands aren't available for {} media-centers.",
+        media_center_type.to_string()
+      )
+      .as_str(),
+    );
+    return ExitCode::FAILURE;
+  };
+  if !center.restore_session() {
+    print_message(
+      PrintMessageType::Error,
+      "No valid session found. Please login through the interactive menu first.",
+    );
+    return ExitCode::FAILURE;
+  }
+
+  let result = match name {
+    "search" => center.print_listing(
+      Listing::Search(sub_command.get_one::<String>("term").unwrap().to_string()),
+      output,
+    ),
+    "resume" => center.print_listing(Listing::Resume, output),
+    "next-up" => center.print_listing(Listing::NextUp, output),
+    "latest" => center.print_listing(Listing::Latest, output),
+    "series" => print_series(
+      backend.as_mut(),
+      sub_command.get_one::<String>("item-id").unwrap(),
+      output,
+    ),
+    "play" => center.play_from_id(
+      sub_command
+        .get_one::<String>("item-id")
+        .unwrap()
+        .to_string(),
+    ),
+    "receiver" => center.receiver(),
+    _ => unreachable!(),
+  };
+
+  if let Err(err) = result {
+    print_message(PrintMessageType::Error, &err.to_string());
+    ExitCode::FAILURE
+  } else {
+    ExitCode::SUCCESS
+  }
+}
+
+// Tab separated, so that it's easy to use with cut/awk.
+pub fn print_items<T: Listable>(items: &[T], output: OutputFormat) {
+  match output {
+    OutputFormat::Json => print_json(&items),
+    OutputFormat::Text => {
+      for item in items {
+        println!("{}\t{}\t{}", item.id(), item.item_type(), item.to_string());
+      }
+    },
+  }
+}
+
+pub fn print_json<T: Serialize>(value: &T) {
+  println!("{}", serde_json::to_string_pretty(value).unwrap());
+}
+
+// Every episode of the series the item belongs to. The json output keeps them in their seasons.
+fn print_series(
+  backend: &mut dyn Backend,
+  item_id: &str,
+  output: OutputFormat,
+) -> Result<(), PuddlerError> {
+  let Some(series) = backend.series_of(item_id)? else {
+    return Err(PuddlerError::Unsupported(format!(
+      "\"{}\" is not part of a series.",
+      item_id
+    )));
+  };
+  let seasons = series_seasons(backend, &series)?;
+  match output {
+    OutputFormat::Json => print_json(&json!({
+      "series": series,
+      "seasons": seasons
+        .iter()
+        .map(|(season, episodes)| json!({ "season": season, "episodes": episodes }))
+        .collect::<Vec<Value>>(),
+    })),
+    OutputFormat::Text => {
+      let episodes: Vec<MediaItem> = seasons
+        .into_iter()
+        .flat_map(|(_, episodes)| episodes)
+        .collect();
+      print_items(&episodes, output);
+    },
+  }
+  Ok(())
+}
+
+fn list_history(command: &ArgMatches, output: OutputFormat) -> Result<(), PuddlerError> {
+  let server = command.get_one::<String>("server").map(|s| s.as_str());
+  let mut entries = History::new().entries(server)?;
+  if let Some(limit) = command.get_one::<usize>("limit") {
+    entries.truncate(*limit);
+  }
+  match output {
+    OutputFormat::Json => print_json(&entries),
+    OutputFormat::Text => {
+      for entry in entries {
+        println!(
+          "{}\t{}\t{}\t{}\t{}/{}\t{}",
+          entry.started_at.format("%Y-%m-%d %H:%M:%S"),
+          entry.server_name,
+          entry.item_id,
+          entry.title,
+          format_seconds(entry.stop_position),
+          format_seconds(entry.duration),
+          if entry.played { "played" } else { "" }
+        );
+      }
+    },
+  }
+  Ok(())
+}
+
+fn list_servers(settings: &PuddlerSettings, output: OutputFormat) {
+  let mut servers: Vec<ServerListing> = vec![];
+  for mut handle in Config::read_all() {
+    servers.push(ServerListing {
+      name: handle.config.server_name.clone(),
+      media_center_type: handle.config.media_center_type.to_string(),
+      // DLNA servers only have the location of their description
+      address: handle
+        .get_address()
+        .or_else(|| handle.get_location())
+        .unwrap_or_default(),
+      users: get_users(&handle)
+        .iter()
+        .map(|u| u.username.clone())
+        .collect(),
+      default: settings.default_media_server == Some(handle.path.clone()),
+    });
+  }
+  match output {
+    OutputFormat::Json => print_json(&servers),
+    OutputFormat::Text => {
+      for server in servers {
+        println!(
+          "{}{}\t{}\t{}\t{}",
+          server.name,
+          if server.default { "*" } else { "" },
+          server.media_center_type,
+          server.address,
+          server.users.join(",")
+        );
+      }
+    },
+  }
+}
+
+fn get_users(handle: &Config) -> Vec<UserConfig> {
+  if let Some(users) = handle.config.specific_values.get("users") {
+    serde_json::from_value::<Vec<UserConfig>>(users.clone()).unwrap_or_default()
+  } else {
+    vec![]
+  }
+}
+
+fn find_config(command: &ArgMatches, settings: &PuddlerSettings) -> Result<Config, String> {
+  let mut configs = Config::read_all();
+  let mut handle = if let Some(server) = command.get_one::<String>("server") {
+    // config files are named after the server, with underscores instead of spaces
+    let file_stem = server.replace(' ', "_");
+    if let Some(index) = configs.iter().position(|c| {
+      c.config.server_name.eq_ignore_ascii_case(server)
+        || Path::new(&c.path).file_stem().and_then(|s| s.to_str()) == Some(file_stem.as_str())
+    }) {
+      configs.remove(index)
+    } else {
+      return Err(format!("No media-center named \"{}\" found.", server));
+    }
+  } else if let Some(default_server) = &settings.default_media_server {
+    let mut handle = Config::default();
+    handle.path = default_server.to_string();
+    if let Err(e) = handle.read() {
+      return Err(format!("Failed reading config file: {:?}", e));
+    }
+    handle
+  } else if configs.len() == 1 {
+    configs.remove(0)
+  } else {
+    return Err(String::from(
+      "No default media-center set. Please choose one with \"--server\".",
+    ));
+  };
+
+  if let Some(username) = command.get_one::<String>("user") {
+    if let Some(user) = get_users(&handle)
+      .iter()
+      .find(|u| u.username.eq_ignore_ascii_case(username))
+    {
+      handle.set_active_user(user.access_token.clone());
+    } else {
+      return Err(format!(
+        "User \"{}\" is not logged in on \"{}\".",
+        username, handle.config.server_name
+      ));
+    }
+  }
+
+  Ok(handle)
+}
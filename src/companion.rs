@@ -0,0 +1,386 @@
+use reqwest::Url;
+use serde_json::json;
+use std::{collections::HashMap, net::Ipv4Addr, time::Duration};
+use tokio::{
+  io::{AsyncReadExt, AsyncWriteExt},
+  net::{TcpListener, TcpStream, UdpSocket},
+  sync::{mpsc, watch},
+  time::timeout,
+};
+
+use crate::{
+  APPNAME, VERSION,
+  error::PuddlerError,
+  mpv::WebSocketMessage,
+  printing::{PrintMessageType, print_message},
+  receiver::play_request,
+  remote::PlayRequest,
+};
+
+// Plex Companion: the player side of Plex' remote control.
+// Plex web and the mobile apps find us through GDM and then talk plain http to this listener.
+// Every command is turned into the same messages the Jellyfin websocket would send, so `remote` handles both.
+
+pub const COMPANION_PORT: u16 = 32500;
+const GDM_PORT: u16 = 32412;
+const GDM_MULTICAST: Ipv4Addr = Ipv4Addr::new(239, 0, 0, 250);
+const GDM_HELLO_PORT: u16 = 32413;
+// how long `/player/timeline/poll?wait=1` may be held open
+const POLL_TIMEOUT: Duration = Duration::from_secs(20);
+const CAPABILITIES: &str = "timeline,playback,playqueues";
+const CONTROLLABLE: &str = "playPause,stop,volume,stepBack,stepForward,seekTo,skipNext,skipPrevious,subtitleStream,audioStream";
+
+// A stream of the item that is currently playing, `id` is what the controllers refer to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompanionStream {
+  pub id: u32,
+  // 1 = video, 2 = audio, 3 = subtitle
+  pub stream_type: u8,
+  // position in the file, which is mpv's "src-id"
+  pub index: Option<u32>,
+  // only set for external subtitles
+  pub key: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompanionInfo {
+  // our own X-Plex-Client-Identifier
+  pub client_identifier: String,
+  pub server_identifier: String,
+  pub server_address: String,
+  pub streams: Vec<CompanionStream>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timeline {
+  // "playing", "paused" or "stopped"
+  pub state: String,
+  pub rating_key: Option<String>,
+  // milliseconds
+  pub time: u64,
+  pub duration: u64,
+  pub volume: u32,
+}
+
+impl Default for Timeline {
+  fn default() -> Self {
+    Timeline {
+      state: String::from("stopped"),
+      rating_key: None,
+      time: 0,
+      duration: 0,
+      volume: 100,
+    }
+  }
+}
+
+impl Timeline {
+  // Controllers are only woken up if something noteworthy changed, not every time the position moves on.
+  pub fn differs(&self, other: &Timeline) -> bool {
+    self.state != other.state
+      || self.rating_key != other.rating_key
+      || self.volume != other.volume
+      || self.time.abs_diff(other.time) > 2000
+  }
+}
+
+struct HttpRequest {
+  method: String,
+  path: String,
+  query: HashMap<String, String>,
+}
+
+fn parse_request(head: &str) -> Option<HttpRequest> {
+  let mut parts = head.lines().next()?.split(' ');
+  let method = parts.next()?.to_string();
+  let url = Url::parse(&format!("http://localhost{}", parts.next()?)).ok()?;
+  Some(HttpRequest {
+    method,
+    path: url.path().trim_end_matches('/').to_string(),
+    query: url.query_pairs().into_owned().collect(),
+  })
+}
+
+// Returns the message `remote::handle_message` needs to execute a `/player/playback/*` command.
+pub fn translate_command(
+  command: &str,
+  query: &HashMap<String, String>,
+  streams: &[CompanionStream],
+) -> Option<WebSocketMessage> {
+  let playstate = |name: &str| WebSocketMessage {
+    MessageType: String::from("Playstate"),
+    Data: json!({ "Command": name }),
+  };
+  let general = |name: &str, arguments: serde_json::Value| WebSocketMessage {
+    MessageType: String::from("GeneralCommand"),
+    Data: json!({ "Name": name, "Arguments": arguments }),
+  };
+  let number = |name: &str| query.get(name).and_then(|value| value.parse::<u64>().ok());
+  match command {
+    "play" => Some(playstate("Unpause")),
+    "pause" => Some(playstate("Pause")),
+    "stop" => Some(playstate("Stop")),
+    "skipNext" => Some(playstate("NextTrack")),
+    "skipPrevious" => Some(playstate("PreviousTrack")),
+    "stepForward" => Some(playstate("FastForward")),
+    "stepBack" => Some(playstate("Rewind")),
+    // plex uses milliseconds, jellyfin ticks
+    "seekTo" => number("offset").map(|offset| WebSocketMessage {
+      MessageType: String::from("Playstate"),
+      Data: json!({ "Command": "Seek", "SeekPositionTicks": offset * 10000 }),
+    }),
+    "setParameters" => {
+      number("volume").map(|volume| general("SetVolume", json!({ "Volume": volume.to_string() })))
+    },
+    "setStreams" => {
+      if let Some(id) = number("subtitleStreamID") {
+        // 0 turns subtitles off
+        let arguments = match streams
+          .iter()
+          .find(|stream| stream.stream_type == 3 && stream.id as u64 == id)
+        {
+          Some(stream) => json!({
+            // external subtitles don't have an index, they are found by their key instead
+            "Index": stream.index.unwrap_or(stream.id).to_string(),
+            "StreamKey": stream.key.clone().unwrap_or_default(),
+          }),
+          None => json!({ "Index": "-1" }),
+        };
+        Some(general("SetSubtitleStreamIndex", arguments))
+      } else if let Some(id) = number("audioStreamID") {
+        let stream = streams
+          .iter()
+          .find(|stream| stream.stream_type == 2 && stream.id as u64 == id)?;
+        Some(general(
+          "SetAudioStreamIndex",
+          json!({ "Index": stream.index?.to_string() }),
+        ))
+      } else {
+        None
+      }
+    },
+    "playMedia" => {
+      let key = query.get("key")?;
+      let rating_key = key.rsplit('/').next()?;
+      Some(WebSocketMessage {
+        MessageType: String::from("Play"),
+        Data: json!({
+          "ItemIds": [rating_key],
+          "PlayCommand": "PlayNow",
+          "StartPositionTicks": number("offset").map(|offset| offset * 10000),
+        }),
+      })
+    },
+    _ => None,
+  }
+}
+
+pub fn timeline_xml(timeline: &Timeline, info: &CompanionInfo, command_id: &str) -> String {
+  let mut video = format!(r#"<Timeline type="video" state="{}""#, timeline.state);
+  if let Some(rating_key) = &timeline.rating_key {
+    let server = Url::parse(&info.server_address).ok();
+    video += &format!(
+      r#" time="{}" duration="{}" ratingKey="{}" key="/library/metadata/{}" containerKey="/library/metadata/{}" machineIdentifier="{}" address="{}" port="{}" protocol="{}" volume="{}" controllable="{}""#,
+      timeline.time,
+      timeline.duration,
+      rating_key,
+      rating_key,
+      rating_key,
+      info.server_identifier,
+      server
+        .as_ref()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+        .unwrap_or_default(),
+      server
+        .as_ref()
+        .and_then(|url| url.port_or_known_default())
+        .unwrap_or(32400),
+      server
+        .as_ref()
+        .map(|url| url.scheme().to_string())
+        .unwrap_or_else(|| String::from("http")),
+      timeline.volume,
+      CONTROLLABLE
+    );
+  }
+  video += "/>";
+  format!(
+    r#"<?xml version="1.0" encoding="UTF-8"?><MediaContainer location="{}" commandID="{}"><Timeline type="music" state="stopped"/><Timeline type="photo" state="stopped"/>{}</MediaContainer>"#,
+    if timeline.rating_key.is_some() {
+      "fullScreenVideo"
+    } else {
+      "navigation"
+    },
+    command_id,
+    video
+  )
+}
+
+fn resources_xml(info: &CompanionInfo) -> String {
+  format!(
+    r#"<?xml version="1.0" encoding="UTF-8"?><MediaContainer><Player title="{}" machineIdentifier="{}" product="{}" version="{}" platform="{}" protocol="plex" protocolVersion="1" protocolCapabilities="{}" deviceClass="pc"/></MediaContainer>"#,
+    APPNAME,
+    info.client_identifier,
+    APPNAME,
+    VERSION,
+    std::env::consts::OS,
+    CAPABILITIES
+  )
+}
+
+// Serves controllers until the future gets dropped.
+// Commands are sent as serialized `WebSocketMessage`s, just like the websocket reader does.
+pub async fn serve(
+  listener: TcpListener,
+  info: CompanionInfo,
+  commands: mpsc::UnboundedSender<String>,
+  timeline: watch::Receiver<Timeline>,
+) {
+  loop {
+    let stream = match listener.accept().await {
+      Ok((stream, _)) => stream,
+      Err(_) => continue,
+    };
+    tokio::spawn(handle_connection(
+      stream,
+      info.clone(),
+      commands.clone(),
+      timeline.clone(),
+    ));
+  }
+}
+
+async fn handle_connection(
+  mut stream: TcpStream,
+  info: CompanionInfo,
+  commands: mpsc::UnboundedSender<String>,
+  mut timeline: watch::Receiver<Timeline>,
+) {
+  // none of the requests have a body worth reading
+  let mut head: Vec<u8> = vec![];
+  let mut buffer = [0; 1024];
+  while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+    match timeout(Duration::from_secs(5), stream.read(&mut buffer)).await {
+      Ok(Ok(read)) if read > 0 => head.extend_from_slice(&buffer[..read]),
+      _ => return,
+    }
+  }
+  let request = match parse_request(&String::from_utf8_lossy(&head)) {
+    Some(request) => request,
+    None => return,
+  };
+  let command_id = request.query.get("commandID").cloned().unwrap_or_default();
+
+  let (status, body) = if request.method == "OPTIONS" {
+    ("200 OK", String::new())
+  } else if request.path == "/resources" {
+    ("200 OK", resources_xml(&info))
+  } else if request.path == "/player/timeline/poll" {
+    if request.query.get("wait").map(|wait| wait.as_str()) == Some("1") {
+      timeline.mark_unchanged();
+      let _ = timeout(POLL_TIMEOUT, timeline.changed()).await;
+    }
+    let current = timeline.borrow().clone();
+    ("200 OK", timeline_xml(&current, &info, &command_id))
+  } else if let Some(command) = request.path.strip_prefix("/player/playback/") {
+    if let Some(message) = translate_command(command, &request.query, &info.streams) {
+      let _ = commands.send(serde_json::to_string(&message).unwrap());
+      ("200 OK", ok_xml())
+    } else {
+      ("400 Bad Request", String::new())
+    }
+  } else if request.path.starts_with("/player/timeline/")
+    || request.path.starts_with("/player/navigation/")
+  {
+    // subscriptions aren't supported, the controllers fall back to polling
+    ("200 OK", ok_xml())
+  } else {
+    ("404 Not Found", String::new())
+  };
+
+  let response = format!(
+    "HTTP/1.1 {}\r\nContent-Type: text/xml;charset=utf-8\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nAccess-Control-Allow-Methods: GET, POST, PUT, OPTIONS\r\nAccess-Control-Allow-Headers: *\r\nAccess-Control-Expose-Headers: X-Plex-Client-Identifier\r\nX-Plex-Client-Identifier: {}\r\nConnection: close\r\n\r\n{}",
+    status,
+    body.len(),
+    info.client_identifier,
+    body
+  );
+  let _ = stream.write_all(response.as_bytes()).await;
+}
+
+fn ok_xml() -> String {
+  String::from(r#"<?xml version="1.0" encoding="UTF-8"?><Response code="200" status="OK"/>"#)
+}
+
+fn gdm_message(info: &CompanionInfo) -> String {
+  format!(
+    "Content-Type: plex/media-player\r\nResource-Identifier: {}\r\nName: {}\r\nPort: {}\r\nProduct: {}\r\nVersion: {}\r\nProtocol: plex\r\nProtocol-Version: 1\r\nProtocol-Capabilities: {}\r\nDevice-Class: pc\r\n\r\n",
+    info.client_identifier, APPNAME, COMPANION_PORT, APPNAME, VERSION, CAPABILITIES
+  )
+}
+
+// Answers the M-SEARCH of controllers on the local network.
+pub async fn advertise(info: CompanionInfo) {
+  let socket = match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, GDM_PORT)).await {
+    Ok(socket) => socket,
+    Err(err) => {
+      print_message(
+        PrintMessageType::Warning,
+        format!("Plex apps on the local network won't find puddler: {}", err).as_str(),
+      );
+      return;
+    },
+  };
+  let _ = socket.join_multicast_v4(GDM_MULTICAST, Ipv4Addr::UNSPECIFIED);
+  let _ = socket
+    .send_to(
+      format!("HELLO * HTTP/1.0\r\n{}", gdm_message(&info)).as_bytes(),
+      (GDM_MULTICAST, GDM_HELLO_PORT),
+    )
+    .await;
+  let mut buffer = [0; 1024];
+  while let Ok((read, address)) = socket.recv_from(&mut buffer).await {
+    if buffer[..read].starts_with(b"M-SEARCH") {
+      let _ = socket
+        .send_to(
+          format!("HTTP/1.0 200 OK\r\n{}", gdm_message(&info)).as_bytes(),
+          address,
+        )
+        .await;
+    }
+  }
+}
+
+pub async fn bind() -> Result<TcpListener, PuddlerError> {
+  Ok(TcpListener::bind((Ipv4Addr::UNSPECIFIED, COMPANION_PORT)).await?)
+}
+
+// Used while nothing is playing: serves controllers until one of them sends `playMedia`.
+pub async fn wait_for_play_request(info: CompanionInfo) -> Result<PlayRequest, PuddlerError> {
+  let listener = bind().await?;
+  let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
+  let (_timeline_sender, timeline) = watch::channel(Timeline::default());
+  // neither of them is supposed to return, even if the advertisement fails
+  let server = async {
+    serve(listener, info.clone(), sender, timeline).await;
+    std::future::pending::<()>().await
+  };
+  let gdm = async {
+    advertise(info.clone()).await;
+    std::future::pending::<()>().await
+  };
+  tokio::select! {
+    _ = server => unreachable!(),
+    _ = gdm => unreachable!(),
+    request = async {
+      while let Some(message) = receiver.recv().await {
+        if let Ok(message) = serde_json::from_str::<WebSocketMessage>(&message)
+          && let Some(request) = play_request(&message)
+        {
+          return Ok(request);
+        }
+      }
+      Err(PuddlerError::Network(String::from("The companion listener stopped.")))
+    } => request,
+  }
+}
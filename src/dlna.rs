@@ -0,0 +1,396 @@
+use reqwest::{
+  StatusCode, Url,
+  blocking::{Client, Response},
+};
+use roxmltree::{Document, Node};
+use std::time::Duration;
+
+use crate::{
+  backend::Backend,
+  error::PuddlerError,
+  media_config::{Config, Playstate},
+  media_item::{MediaItem, MediaKind, PlaybackSource},
+  puddler_settings::PuddlerSettings,
+};
+
+const CONTENT_DIRECTORY: &str = "urn:schemas-upnp-org:service:ContentDirectory:1";
+
+// Some servers only hand out a few items at a time, no matter what has been requested.
+const PAGE_SIZE: u32 = 200;
+
+// UPnP/DLNA media servers. There are no users and no progress on the server, so the playstate of
+// every item is kept in the media-center config instead.
+pub struct DlnaServer {
+  config: Config,
+  settings: PuddlerSettings,
+  client: Client,
+  // found in the device description, see `re_authenticate`
+  control_url: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceDescription {
+  pub name: String,
+  pub udn: String,
+  pub control_url: String,
+}
+
+// A container or item of a "DIDL-Lite" document, the answer to every Browse request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DidlObject {
+  pub id: String,
+  pub title: String,
+  pub class: String,
+  pub container: bool,
+  pub artist: Option<String>,
+  pub album: Option<String>,
+  // the first resource, that's the original file on most servers
+  pub url: Option<String>,
+  // in seconds
+  pub duration: Option<u64>,
+}
+
+impl DidlObject {
+  // `None` for things that can't be browsed or played (pictures ...)
+  pub fn to_media_item(&self, playstate: Playstate) -> Option<MediaItem> {
+    let kind = if self.container {
+      if self
+        .class
+        .starts_with("object.container.person.musicArtist")
+      {
+        MediaKind::Artist
+      } else if self.class.starts_with("object.container.album.musicAlbum") {
+        MediaKind::Album
+      } else {
+        MediaKind::Other(String::from("folder"))
+      }
+    } else if self.class.starts_with("object.item.videoItem") {
+      MediaKind::Movie
+    } else if self.class.starts_with("object.item.audioItem") {
+      MediaKind::Track
+    } else {
+      return None;
+    };
+    // the player shows artist and title of tracks separately
+    let titles = if kind == MediaKind::Track {
+      let artist = self.artist.clone().unwrap_or_default();
+      let album = self.album.clone().unwrap_or_default();
+      vec![
+        format!("{} - {}", artist, self.title),
+        format!("{} ({})", artist, album),
+        self.title.clone(),
+      ]
+    } else {
+      vec![self.title.clone()]
+    };
+    Some(MediaItem {
+      id: self.id.clone(),
+      kind,
+      titles,
+      played: playstate.played,
+      playback_position: playstate.playback_position,
+      runtime: self.duration.unwrap_or(0),
+    })
+  }
+}
+
+impl DlnaServer {
+  pub fn new(config: Config, settings: PuddlerSettings) -> Self {
+    DlnaServer {
+      config,
+      settings,
+      client: Client::new(),
+      control_url: None,
+    }
+  }
+
+  fn control_url(&mut self) -> Result<String, PuddlerError> {
+    if self.control_url.is_none() {
+      self.re_authenticate()?;
+    }
+    Ok(self.control_url.clone().unwrap())
+  }
+
+  // Every page of a container, or just the object itself with `metadata`.
+  fn browse_objects(
+    &mut self,
+    object_id: &str,
+    metadata: bool,
+  ) -> Result<Vec<DidlObject>, PuddlerError> {
+    let control_url = self.control_url()?;
+    let mut objects: Vec<DidlObject> = vec![];
+    loop {
+      let body = browse_request(object_id, metadata, objects.len() as u32);
+      let response = self
+        .client
+        .post(&control_url)
+        .header("Content-Type", "text/xml; charset=\"utf-8\"")
+        .header("SOAPAction", format!("\"{}#Browse\"", CONTENT_DIRECTORY))
+        .body(body)
+        .send()?;
+      let (mut page, total) = parse_browse_response(&check_status(response)?.text()?)?;
+      let done = page.is_empty() || metadata;
+      objects.append(&mut page);
+      if done || objects.len() as u32 >= total {
+        return Ok(objects);
+      }
+    }
+  }
+
+  fn to_media_items(&self, objects: Vec<DidlObject>) -> Vec<MediaItem> {
+    let playstates = self.config.get_playstates();
+    objects
+      .iter()
+      .filter_map(|object| {
+        object.to_media_item(playstates.get(&object.id).cloned().unwrap_or_default())
+      })
+      .collect()
+  }
+}
+
+// Keeps what "Continue Watching" needs as well, see `resume`.
+fn cached_playstate(item: &MediaItem, played: bool, playback_position: u64) -> Playstate {
+  Playstate {
+    played,
+    playback_position,
+    titles: item.titles.clone(),
+    kind: Some(item.kind.clone()),
+    runtime: item.runtime,
+  }
+}
+
+impl Backend for DlnaServer {
+  fn get_config_handle(&mut self) -> &mut Config {
+    &mut self.config
+  }
+
+  fn get_settings(&mut self) -> &mut PuddlerSettings {
+    &mut self.settings
+  }
+
+  // Nothing to log into, but the server has to be up and its ContentDirectory has to be known.
+  fn re_authenticate(&mut self) -> Result<(), PuddlerError> {
+    let Some(location) = self.config.get_location() else {
+      return Err(PuddlerError::Config(String::from(
+        "The location of the device description is missing.",
+      )));
+    };
+    let description = fetch_description(&self.client, &location)?;
+    self.control_url = Some(description.control_url);
+    Ok(())
+  }
+
+  fn browse(&mut self, parent: Option<&MediaItem>) -> Result<Vec<MediaItem>, PuddlerError> {
+    // "0" is the root container of every server
+    let object_id = parent.map_or("0", |parent| parent.id.as_str());
+    let objects = self.browse_objects(object_id, false)?;
+    Ok(self.to_media_items(objects))
+  }
+
+  fn resume(&mut self) -> Result<Vec<MediaItem>, PuddlerError> {
+    let mut items: Vec<MediaItem> = vec![];
+    for (item_id, playstate) in self.config.get_playstates() {
+      if playstate.played || playstate.playback_position == 0 {
+        continue;
+      }
+      if let Some(kind) = playstate.kind {
+        items.push(MediaItem {
+          id: item_id,
+          kind,
+          titles: playstate.titles,
+          played: false,
+          playback_position: playstate.playback_position,
+          runtime: playstate.runtime,
+        });
+      }
+    }
+    items.sort_by(|a, b| a.titles[0].cmp(&b.titles[0]));
+    Ok(items)
+  }
+
+  fn resolve_stream(&mut self, item: &MediaItem) -> Result<PlaybackSource, PuddlerError> {
+    let objects = self.browse_objects(&item.id, true)?;
+    match objects.into_iter().next().and_then(|object| object.url) {
+      Some(stream_url) => Ok(PlaybackSource {
+        stream_url,
+        ..Default::default()
+      }),
+      None => Err(PuddlerError::Unsupported(format!(
+        "\"{}\" doesn't have any playable files.",
+        item
+      ))),
+    }
+  }
+
+  fn report_progress(&mut self, item: &MediaItem, position: u64) -> Result<(), PuddlerError> {
+    self
+      .config
+      .save_playstate(&item.id, cached_playstate(item, item.played, position));
+    Ok(())
+  }
+
+  fn set_playstate(&mut self, item: &MediaItem, played: bool) -> Result<(), PuddlerError> {
+    self
+      .config
+      .save_playstate(&item.id, cached_playstate(item, played, 0));
+    Ok(())
+  }
+}
+
+fn check_status(response: Response) -> Result<Response, PuddlerError> {
+  match response.status() {
+    StatusCode::OK => Ok(response),
+    status => Err(PuddlerError::from_status(
+      status,
+      response.text().unwrap_or_default(),
+    )),
+  }
+}
+
+fn child_text(node: Node, name: &str) -> Option<String> {
+  node
+    .children()
+    .find(|child| child.has_tag_name(name))
+    .and_then(|child| child.text())
+    .map(|text| text.trim().to_string())
+}
+
+pub fn fetch_description(
+  client: &Client,
+  location: &str,
+) -> Result<DeviceDescription, PuddlerError> {
+  let response = client
+    .get(location)
+    .timeout(Duration::from_secs(5))
+    .send()?;
+  parse_description(&check_status(response)?.text()?, location)
+}
+
+// The control url might be relative, either to <URLBase> or to the description itself.
+pub fn parse_description(xml: &str, location: &str) -> Result<DeviceDescription, PuddlerError> {
+  let document = Document::parse(xml)?;
+  let root = document.root_element();
+  let device = root
+    .descendants()
+    .find(|node| node.has_tag_name("device"))
+    .ok_or_else(|| PuddlerError::Deserialization(String::from("missing element `device`")))?;
+  let service = root
+    .descendants()
+    .filter(|node| node.has_tag_name("service"))
+    .find(|service| {
+      child_text(*service, "serviceType").is_some_and(|service_type| {
+        service_type.starts_with(&CONTENT_DIRECTORY[..CONTENT_DIRECTORY.len() - 1])
+      })
+    })
+    .ok_or_else(|| {
+      PuddlerError::Unsupported(String::from(
+        "This device doesn't share any media (no ContentDirectory).",
+      ))
+    })?;
+  let control_url = child_text(service, "controlURL")
+    .ok_or_else(|| PuddlerError::Deserialization(String::from("missing element `controlURL`")))?;
+  let base = child_text(root, "URLBase").unwrap_or(location.to_string());
+  let control_url = Url::parse(&base)
+    .and_then(|base| base.join(&control_url))
+    .map_err(|err| PuddlerError::Deserialization(err.to_string()))?;
+  Ok(DeviceDescription {
+    name: child_text(device, "friendlyName").unwrap_or(String::from("DLNA")),
+    udn: child_text(device, "UDN").unwrap_or_default(),
+    control_url: control_url.to_string(),
+  })
+}
+
+fn browse_request(object_id: &str, metadata: bool, start: u32) -> String {
+  let flag = if metadata {
+    "BrowseMetadata"
+  } else {
+    "BrowseDirectChildren"
+  };
+  format!(
+    r#"<?xml version="1.0" encoding="utf-8"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">
+  <s:Body>
+    <u:Browse xmlns:u="{}">
+      <ObjectID>{}</ObjectID>
+      <BrowseFlag>{}</BrowseFlag>
+      <Filter>*</Filter>
+      <StartingIndex>{}</StartingIndex>
+      <RequestedCount>{}</RequestedCount>
+      <SortCriteria></SortCriteria>
+    </u:Browse>
+  </s:Body>
+</s:Envelope>"#,
+    CONTENT_DIRECTORY,
+    escape_xml(object_id),
+    flag,
+    start,
+    PAGE_SIZE
+  )
+}
+
+fn escape_xml(value: &str) -> String {
+  value
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+// The objects of this page and how many there are in total.
+pub fn parse_browse_response(xml: &str) -> Result<(Vec<DidlObject>, u32), PuddlerError> {
+  let document = Document::parse(xml)?;
+  let response = document
+    .descendants()
+    .find(|node| node.has_tag_name("BrowseResponse"))
+    .ok_or_else(|| {
+      PuddlerError::Deserialization(String::from("missing element `BrowseResponse`"))
+    })?;
+  // the DIDL-Lite document is escaped into <Result>
+  let didl = child_text(response, "Result").unwrap_or_default();
+  let objects = if didl.is_empty() {
+    vec![]
+  } else {
+    parse_didl(&didl)?
+  };
+  let total = child_text(response, "TotalMatches")
+    .and_then(|total| total.parse().ok())
+    .unwrap_or(objects.len() as u32);
+  Ok((objects, total))
+}
+
+pub fn parse_didl(xml: &str) -> Result<Vec<DidlObject>, PuddlerError> {
+  let document = Document::parse(xml)?;
+  Ok(
+    document
+      .root_element()
+      .children()
+      .filter(|node| node.has_tag_name("container") || node.has_tag_name("item"))
+      .filter_map(|node| {
+        let resource = node.children().find(|child| child.has_tag_name("res"));
+        Some(DidlObject {
+          id: node.attribute("id")?.to_string(),
+          title: child_text(node, "title").unwrap_or(String::from("???")),
+          class: child_text(node, "class").unwrap_or_default(),
+          container: node.has_tag_name("container"),
+          artist: child_text(node, "artist").or_else(|| child_text(node, "creator")),
+          album: child_text(node, "album"),
+          url: resource
+            .and_then(|resource| resource.text())
+            .map(|url| url.trim().to_string()),
+          duration: resource
+            .and_then(|resource| resource.attribute("duration"))
+            .and_then(parse_duration),
+        })
+      })
+      .collect(),
+  )
+}
+
+// "H+:MM:SS[.F+]"
+pub fn parse_duration(duration: &str) -> Option<u64> {
+  let mut seconds = 0.0;
+  for part in duration.split(':') {
+    seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+  }
+  Some(seconds.round() as u64)
+}
@@ -1,9 +1,13 @@
+use reqwest::blocking::Client;
+
 use crate::{
   APPNAME, VERSION,
   media_center::{MediaCenter, MediaCenterValues, PlaybackInfo},
   media_config::Config,
+  media_item::MediaItem,
   mpv::Player,
   puddler_settings::PuddlerSettings,
+  queue::load_queue,
 };
 
 #[derive(Clone)]
@@ -13,6 +17,8 @@ pub struct EmbyServer {
   session_id: Option<String>,
   settings: PuddlerSettings,
   playback_info: Option<PlaybackInfo>,
+  client: Client,
+  queue: Vec<MediaItem>,
 }
 
 impl MediaCenter for EmbyServer {
@@ -31,6 +37,8 @@ impl MediaCenter for EmbyServer {
       session_id: None,
       settings,
       playback_info: None,
+      client: Client::new(),
+      queue: load_queue(&config),
     }
   }
 
@@ -46,6 +54,19 @@ impl MediaCenter for EmbyServer {
     self.headers.clone()
   }
 
+  fn get_client(&mut self) -> Client {
+    self.client.clone()
+  }
+
+  fn get_queue(&mut self) -> &mut Vec<MediaItem> {
+    &mut self.queue
+  }
+
+  #[cfg(test)]
+  fn set_client(&mut self, client: Client) {
+    self.client = client;
+  }
+
   fn insert_value(&mut self, value_type: MediaCenterValues, value: String) {
     match value_type {
       MediaCenterValues::SessionID => {
@@ -1,3 +1,6 @@
+use reqwest::StatusCode;
+use std::fmt;
+
 #[derive(Debug)]
 pub enum PuddlerSettingsError {
   Corrupt,
@@ -8,3 +11,100 @@ pub enum MediaCenterConfigError {
   Corrupt, // yeah lol I had planned more than just this but I guess it didn't really pan out
   MissingFile,
 }
+
+#[derive(Debug)]
+pub enum PuddlerError {
+  // The request didn't even reach the server (timeout, dns, refused connection ...)
+  Network(String),
+  // 401/403 or the session/token is gone
+  Auth(String),
+  // The server answered, but not with what we asked for
+  ServerApi(StatusCode, String),
+  Deserialization(String),
+  Mpv(String),
+  Config(String),
+  // Reading or writing local files (history ...)
+  Io(String),
+  // Things that can't be done with this item or this configuration
+  Unsupported(String),
+}
+
+impl PuddlerError {
+  pub fn from_status(status: StatusCode, body: String) -> Self {
+    match status {
+      StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => PuddlerError::Auth(status.to_string()),
+      _ => PuddlerError::ServerApi(status, body),
+    }
+  }
+}
+
+impl fmt::Display for PuddlerError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      PuddlerError::Network(err) => write!(f, "Failed to reach the server: {}", err),
+      PuddlerError::Auth(err) => write!(f, "Authentication failed: {}", err),
+      PuddlerError::ServerApi(status, body) => {
+        if body.trim().is_empty() {
+          write!(f, "The server responded with {}", status)
+        } else {
+          write!(f, "The server responded with {}: {}", status, body.trim())
+        }
+      },
+      PuddlerError::Deserialization(err) => {
+        write!(
+          f,
+          "The response from the server could not be processed: {}",
+          err
+        )
+      },
+      PuddlerError::Mpv(err) => write!(f, "mpv: {}", err),
+      PuddlerError::Config(err) => write!(f, "Configuration error: {}", err),
+      PuddlerError::Io(err) => write!(f, "Failed to access local files: {}", err),
+      PuddlerError::Unsupported(err) => write!(f, "{}", err),
+    }
+  }
+}
+
+impl std::error::Error for PuddlerError {}
+
+impl From<reqwest::Error> for PuddlerError {
+  fn from(err: reqwest::Error) -> Self {
+    if err.is_decode() {
+      PuddlerError::Deserialization(err.to_string())
+    } else if let Some(status) = err.status() {
+      PuddlerError::from_status(status, String::new())
+    } else {
+      PuddlerError::Network(err.to_string())
+    }
+  }
+}
+
+impl From<serde_json::Error> for PuddlerError {
+  fn from(err: serde_json::Error) -> Self {
+    PuddlerError::Deserialization(err.to_string())
+  }
+}
+
+impl From<roxmltree::Error> for PuddlerError {
+  fn from(err: roxmltree::Error) -> Self {
+    PuddlerError::Deserialization(err.to_string())
+  }
+}
+
+impl From<std::io::Error> for PuddlerError {
+  fn from(err: std::io::Error) -> Self {
+    PuddlerError::Io(err.to_string())
+  }
+}
+
+impl From<libmpv2::Error> for PuddlerError {
+  fn from(err: libmpv2::Error) -> Self {
+    PuddlerError::Mpv(err.to_string())
+  }
+}
+
+impl From<MediaCenterConfigError> for PuddlerError {
+  fn from(err: MediaCenterConfigError) -> Self {
+    PuddlerError::Config(format!("{:?}", err))
+  }
+}
@@ -0,0 +1,157 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+  fmt,
+  fs::{self, OpenOptions},
+  io::Write,
+  path::PathBuf,
+};
+
+use crate::{
+  APPNAME,
+  error::PuddlerError,
+  input::{InteractiveOption, InteractiveOptionType, interactive_select},
+  printing::{PrintMessageType, print_message},
+};
+
+// The interactive menu only shows the most recent sessions, the cli prints everything.
+const MENU_ENTRIES: usize = 15;
+
+// One line per finished playback session.
+// The servers only remember the last position, this keeps every session, across all servers.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HistoryEntry {
+  pub server_name: String,
+  pub media_center_type: String,
+  pub item_id: String,
+  pub title: String,
+  pub started_at: DateTime<Local>,
+  pub stopped_at: DateTime<Local>,
+  // all positions are in seconds
+  pub start_position: u64,
+  pub stop_position: u64,
+  pub duration: u64,
+  pub audio_track: Option<u32>,
+  pub subtitle_track: Option<u32>,
+  pub played: bool,
+}
+
+impl fmt::Display for HistoryEntry {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "{} | {} | {} ({}/{}){}",
+      self.started_at.format("%Y-%m-%d %H:%M"),
+      self.server_name,
+      self.title,
+      format_seconds(self.stop_position),
+      format_seconds(self.duration),
+      if self.played { " [Played]" } else { "" }
+    )
+  }
+}
+
+pub fn format_seconds(seconds: u64) -> String {
+  format!(
+    "{:02}:{:02}:{:02}",
+    seconds / 3600,
+    (seconds % 3600) / 60,
+    seconds % 60
+  )
+}
+
+pub fn get_history_path() -> PathBuf {
+  let config_path = dirs::config_dir().unwrap();
+  let mut history_path = format!(
+    "{}/{}/history.jsonl",
+    &config_path.display().to_string(),
+    APPNAME.to_lowercase()
+  );
+  if cfg!(windows) {
+    history_path = history_path.replace('/', "\\");
+  }
+  PathBuf::from(history_path)
+}
+
+pub struct History {
+  path: PathBuf,
+}
+
+impl History {
+  pub fn new() -> Self {
+    History {
+      path: get_history_path(),
+    }
+  }
+
+  #[cfg(test)]
+  pub fn at(path: PathBuf) -> Self {
+    History { path }
+  }
+
+  // Appending keeps older entries intact, even if puddler gets killed halfway through.
+  pub fn add(&self, entry: &HistoryEntry) -> Result<(), PuddlerError> {
+    if let Some(parent) = self.path.parent()
+      && !parent.exists()
+    {
+      fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&self.path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    Ok(())
+  }
+
+  // Newest entries first. Broken lines are skipped instead of making the whole history unreadable.
+  pub fn entries(&self, server_name: Option<&str>) -> Result<Vec<HistoryEntry>, PuddlerError> {
+    if !self.path.exists() {
+      return Ok(vec![]);
+    }
+    let content = fs::read_to_string(&self.path)?;
+    let mut entries: Vec<HistoryEntry> = content
+      .lines()
+      .filter_map(|line| serde_json::from_str::<HistoryEntry>(line).ok())
+      .filter(|entry| {
+        server_name.is_none_or(|name| {
+          entry
+            .server_name
+            .replace(' ', "_")
+            .eq_ignore_ascii_case(&name.replace(' ', "_"))
+        })
+      })
+      .collect();
+    entries.reverse();
+    Ok(entries)
+  }
+}
+
+pub fn history_menu() {
+  let entries = match History::new().entries(None) {
+    Ok(entries) => entries,
+    Err(err) => {
+      print_message(PrintMessageType::Error, &err.to_string());
+      return;
+    },
+  };
+  if entries.is_empty() {
+    print_message(PrintMessageType::Warning, "Nothing has been played yet.");
+    return;
+  }
+  let mut options: Vec<InteractiveOption> = vec![InteractiveOption {
+    text: String::from("History:"),
+    option_type: InteractiveOptionType::Header,
+  }];
+  for entry in entries.iter().take(MENU_ENTRIES) {
+    options.append(&mut vec![InteractiveOption {
+      text: entry.to_string(),
+      option_type: InteractiveOptionType::Header,
+    }]);
+  }
+  options.append(&mut vec![InteractiveOption {
+    text: String::from("Back"),
+    option_type: InteractiveOptionType::Special,
+  }]);
+  interactive_select(options);
+}
@@ -1,4 +1,8 @@
-use crate::{MenuOptions, media_center::Series, printing::INVALID_INPUT};
+use crate::{
+  MenuOptions,
+  media_item::MediaItem,
+  printing::{INVALID_INPUT, PrintMessageType, print_message},
+};
 use crossterm::{
   cursor::{
     EnableBlinking, Hide, MoveLeft, MoveTo, MoveToColumn, MoveToNextLine, MoveToPreviousLine,
@@ -12,6 +16,7 @@ use crossterm::{
     LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
   },
 };
+use rand::seq::SliceRandom;
 use std::{
   char,
   io::{self, prelude::*, stdin, stdout},
@@ -89,6 +94,9 @@ pub enum SeriesOptions {
   Played,
   UnPlayed,
   Play,
+  Shuffle,
+  ShuffleUnPlayed,
+  Random,
 }
 
 fn string_to_range(input: String) -> Vec<usize> {
@@ -159,17 +167,87 @@ fn string_to_range(input: String) -> Vec<usize> {
   minimum
 }
 
-struct Episode {
-  title: String,
-  watched: bool,
+// Out of range indexes are dropped, so are watched episodes if only unplayed ones are wanted.
+pub fn shuffle_episodes(selection: &[usize], watched: &[bool], unplayed_only: bool) -> Vec<usize> {
+  let mut indexes: Vec<usize> = selection
+    .iter()
+    .filter(|index| {
+      watched
+        .get(**index)
+        .is_some_and(|watched| !unplayed_only || !watched)
+    })
+    .copied()
+    .collect();
+  indexes.shuffle(&mut rand::rng());
+  indexes
+}
+
+// Unwatched episodes are picked first, watched ones only fill up the rest.
+pub fn random_episodes(count: usize, watched: &[bool]) -> Vec<usize> {
+  let all: Vec<usize> = (0..watched.len()).collect();
+  let mut indexes = shuffle_episodes(&all, watched, true);
+  let mut rewatch: Vec<usize> = shuffle_episodes(&all, watched, false)
+    .into_iter()
+    .filter(|index| watched[*index])
+    .collect();
+  indexes.append(&mut rewatch);
+  indexes.truncate(count);
+  indexes
+}
+
+// The tree `series_select` shows: the series, its seasons and their (already formatted) episodes.
+pub fn series_structure(title: String, seasons: Vec<(String, Vec<String>)>) -> Vec<String> {
+  let full_size: usize = seasons.iter().map(|(_, episodes)| episodes.len()).sum();
+  let zero_pad_amount = (full_size as f64).log10().floor() as usize + 1;
+  let mut just_text: Vec<String> = vec![format!(" {}", title.bold())];
+  let mut index = 0;
+  for (season_index, (season, episodes)) in seasons.iter().enumerate() {
+    let mut line: String = String::new();
+    if season_index == seasons.len() - 1 {
+      line.push_str("  └─ ");
+    } else {
+      line.push_str("  ├─ ");
+    }
+    line.push_str(format!("{}", season.clone().bold()).as_str());
+    just_text.push(line.clone());
+    line.clear();
+    for (episode_index, episode) in episodes.iter().enumerate() {
+      let prefix: &str = if season_index == seasons.len() - 1 {
+        "       "
+      } else {
+        "  │    "
+      };
+      if episode_index == episodes.len() - 1 {
+        line.push_str(format!("{}└── ", prefix).as_str());
+      } else {
+        line.push_str(format!("{}├── ", prefix).as_str());
+      }
+      line.push_str(format!("[{:0zero_pad_amount$}] ", index).as_str());
+      let terminal_size = terminal::size().map_or(80, |size| size.0 as usize);
+      if 13 + zero_pad_amount + index.to_string().len() + episode.len() > terminal_size {
+        line.push_str(format!("{}...", episode).as_str());
+      } else {
+        line.push_str(episode.as_str());
+      }
+      just_text.push(line.clone());
+      line.clear();
+      index += 1;
+    }
+  }
+  just_text
 }
 
-fn series_select(text: Vec<String>, episodes: Vec<Episode>) -> (SeriesOptions, Option<Vec<usize>>) {
+// Every episode of a series, however many seasons it has.
+pub fn series_select(
+  text: Vec<String>,
+  episodes: &[MediaItem],
+) -> (SeriesOptions, Option<Vec<usize>>) {
   let mut stdout = stdout();
   stdout.flush().expect("Failed to flush stdout");
   let main_options = format!(
-    "\nOptions: [{}oggle Watching-Status], [Left-Arrow to go back]",
-    "T".bold().grey()
+    "\nOptions: [{}oggle Watching-Status], [{}huffle], [Left-Arrow to go back]",
+    "T".bold().grey(),
+    "S".bold().grey()
   );
 
   execute!(stdout, EnterAlternateScreen, DisableLineWrap, Hide).unwrap();
@@ -234,18 +312,41 @@ fn series_select(text: Vec<String>, episodes: Vec<Episode>) -> (SeriesOptions, O
               mode = SeriesOptions::UnPlayed;
               update = true;
             }
+          } else if ch == 's' {
+            let (next, name) = match mode {
+              SeriesOptions::Shuffle => (SeriesOptions::ShuffleUnPlayed, "Shuffle Un-Played"),
+              SeriesOptions::ShuffleUnPlayed => (SeriesOptions::Random, "Random Episodes"),
+              _ => (SeriesOptions::Shuffle, "Shuffle"),
+            };
+            options = format!(
+              "\nMode: [{}] (Press '{}' again to switch between Shuffle, Shuffle Un-Played and Random Episodes)",
+              name.bold(),
+              ch.to_uppercase()
+            );
+            mode = next;
+            input.clear();
+            index_selection.clear();
+            update = true;
           } else if ch == '-'
             || ch == ',' && mode == SeriesOptions::Played
             || mode == SeriesOptions::UnPlayed
+            || ch == ',' && mode == SeriesOptions::Shuffle
+            || ch == ',' && mode == SeriesOptions::ShuffleUnPlayed
           {
             input.push(ch);
             update = true;
           }
         } else if KeyCode::Enter == code {
-          if input.is_empty() {
+          // Shuffling without a selection takes the whole series
+          if input.is_empty()
+            && (mode == SeriesOptions::Shuffle || mode == SeriesOptions::ShuffleUnPlayed)
+          {
+            index_selection = (0..episodes.len()).collect();
+          }
+          if input.is_empty() && mode != SeriesOptions::Random {
             if index_selection.is_empty() {
               for (index, episode) in episodes.iter().enumerate() {
-                if !episode.watched {
+                if !episode.played {
                   input = index.to_string();
                   break;
                 }
@@ -294,6 +395,11 @@ fn series_select(text: Vec<String>, episodes: Vec<Episode>) -> (SeriesOptions, O
       } else {
         text.iter().for_each(|l| println!("{}", l));
       }
+      let key = if mode == SeriesOptions::Played || mode == SeriesOptions::UnPlayed {
+        "T"
+      } else {
+        "S"
+      };
       if mode == SeriesOptions::Play {
         let selection = input.parse::<usize>().unwrap_or(episodes.len());
         print!(": {}", input);
@@ -301,18 +407,23 @@ fn series_select(text: Vec<String>, episodes: Vec<Episode>) -> (SeriesOptions, O
           print!(
             "{} = {}",
             " ".repeat(total_size - input.len()),
-            episodes[selection].title
+            episodes[selection]
           );
         }
+      } else if mode == SeriesOptions::Random {
+        print!("[{}]: {}", "S".bold().grey(), input);
+        if !input.is_empty() {
+          print!(" random episodes");
+        }
       } else if (input.ends_with(',') || input.ends_with('-')) && input.len() > 1 {
         print!(
           "[{}]: {}{}",
-          "T".bold().grey(),
+          key.bold().grey(),
           &input[..input.len() - 1],
           input[input.len() - 1..].to_string().underlined()
         );
       } else {
-        print!("[{}]: {}", "T".bold().grey(), input);
+        print!("[{}]: {}", key.bold().grey(), input);
         let range = string_to_range(input.clone());
         print!(" {:?}", range);
         index_selection = range;
@@ -325,7 +436,35 @@ fn series_select(text: Vec<String>, episodes: Vec<Episode>) -> (SeriesOptions, O
   }
   execute!(stdout, LeaveAlternateScreen, EnableLineWrap, Show).unwrap();
   disable_raw_mode().unwrap();
-  if input.is_empty() && index_selection.is_empty() {
+  let watched: Vec<bool> = episodes.iter().map(|episode| episode.played).collect();
+  let shuffled = match mode {
+    SeriesOptions::Shuffle | SeriesOptions::ShuffleUnPlayed if !index_selection.is_empty() => {
+      shuffle_episodes(
+        &index_selection,
+        &watched,
+        mode == SeriesOptions::ShuffleUnPlayed,
+      )
+    },
+    SeriesOptions::Random => match input.parse::<usize>() {
+      Ok(count) if count > 0 => random_episodes(count, &watched),
+      _ => return (SeriesOptions::Back, None),
+    },
+    SeriesOptions::Shuffle | SeriesOptions::ShuffleUnPlayed => return (SeriesOptions::Back, None),
+    _ => vec![],
+  };
+  if mode == SeriesOptions::Shuffle
+    || mode == SeriesOptions::ShuffleUnPlayed
+    || mode == SeriesOptions::Random
+  {
+    if shuffled.is_empty() {
+      print_message(
+        PrintMessageType::Warning,
+        "There are no unwatched episodes in this selection.",
+      );
+      return (SeriesOptions::Back, None);
+    }
+    (mode, Some(shuffled))
+  } else if input.is_empty() && index_selection.is_empty() {
     (SeriesOptions::Back, None)
   } else if index_selection.is_empty() {
     if let Ok(num) = input.parse::<usize>() {
@@ -338,38 +477,24 @@ fn series_select(text: Vec<String>, episodes: Vec<Episode>) -> (SeriesOptions, O
   }
 }
 
-pub fn plex_series_select(
-  text: Vec<String>,
-  series: crate::plex::Series,
-) -> (SeriesOptions, Option<Vec<usize>>) {
-  let mut episodes: Vec<Episode> = vec![];
-  for season in series.seasons.clone() {
-    for episode in season.episodes {
-      episodes.push(Episode {
-        title: episode.to_string(),
-        watched: episode.viewCount.is_some(),
-      });
-    }
-  }
-
-  series_select(text, episodes)
-}
-
-pub fn jelly_series_select(
-  text: Vec<String>,
-  series: Series,
-) -> (SeriesOptions, Option<Vec<usize>>) {
-  let mut episodes: Vec<Episode> = vec![];
-  for season in series.seasons.clone() {
-    for episode in season.episodes {
-      episodes.push(Episode {
-        title: episode.to_string(),
-        watched: episode.UserData.Played,
-      });
-    }
+// Playing from an episode goes on until the end of the series, shuffled selections are played as
+// they are. Only the former skips over watched episodes.
+pub fn series_playlist<T: Clone>(
+  episodes: Vec<T>,
+  mode: &SeriesOptions,
+  indexes: Vec<usize>,
+) -> (Vec<T>, bool) {
+  if *mode == SeriesOptions::Play {
+    (episodes.into_iter().skip(indexes[0]).collect(), true)
+  } else {
+    (
+      indexes
+        .into_iter()
+        .filter_map(|index| episodes.get(index).cloned())
+        .collect(),
+      false,
+    )
   }
-
-  series_select(text, episodes)
 }
 
 pub fn interactive_menuoption(options: Vec<MenuOptions>) -> MenuOptions {
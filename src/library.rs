@@ -0,0 +1,452 @@
+use colored::Colorize;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+  backend::process_item,
+  error::PuddlerError,
+  input::{InteractiveOption, InteractiveOptionType, interactive_select},
+  media_center::{MediaCenter, ToStringAdv},
+  media_item::{MediaItem, MediaKind},
+  printing::{PrintMessageType, print_message},
+};
+
+// Browsing the libraries of every media-center. The `MediaCenter` hooks default to Jellyfin and Emby,
+// Plex overrides them.
+
+pub const PAGE_SIZE: usize = 50;
+
+pub struct SortOrder {
+  pub name: &'static str,
+  pub sort_by: &'static str,
+  pub sort_order: &'static str,
+  pub plex_sort: &'static str,
+}
+
+pub const SORT_ORDERS: [SortOrder; 4] = [
+  SortOrder {
+    name: "Name",
+    sort_by: "SortName",
+    sort_order: "Ascending",
+    plex_sort: "titleSort",
+  },
+  SortOrder {
+    name: "Date added",
+    sort_by: "DateCreated,SortName",
+    sort_order: "Descending",
+    plex_sort: "addedAt:desc",
+  },
+  SortOrder {
+    name: "Year",
+    sort_by: "ProductionYear,SortName",
+    sort_order: "Descending",
+    plex_sort: "year:desc",
+  },
+  SortOrder {
+    name: "Rating",
+    sort_by: "CommunityRating,SortName",
+    sort_order: "Descending",
+    plex_sort: "rating:desc",
+  },
+];
+
+pub const BROWSE_OPTIONS: [&str; 6] = [
+  "All",
+  "Folders",
+  "Genres",
+  "Collections",
+  "Studios",
+  "Years",
+];
+
+pub enum PageAction {
+  Open(usize),
+  Sort(usize),
+  Next,
+  Previous,
+  Back,
+}
+
+// Libraries, genres, studios and years. Only the fields that are needed to filter by them.
+#[derive(Debug, Deserialize)]
+pub struct LibraryEntry {
+  pub Name: String,
+  pub Id: String,
+  pub CollectionType: Option<String>,
+}
+
+// A library (a section on Plex) and the urls of the ways to browse it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Library {
+  pub name: String,
+  pub id: String,
+  pub items_url: String,
+  pub folders_url: String,
+  pub collections_url: String,
+}
+
+// One genre, studio or year of a library (on any media-center) and the (unpaged) url of its items.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Category {
+  pub name: String,
+  pub url: String,
+}
+
+pub fn select_from_page(
+  header: String,
+  entries: &[String],
+  page: usize,
+  more: bool,
+  sortable: bool,
+) -> PageAction {
+  let mut options: Vec<InteractiveOption> = vec![InteractiveOption {
+    text: header,
+    option_type: InteractiveOptionType::Header,
+  }];
+  for entry in entries {
+    options.push(InteractiveOption {
+      text: entry.clone(),
+      option_type: InteractiveOptionType::Button,
+    });
+  }
+  if sortable {
+    options.push(InteractiveOption {
+      text: format!(
+        "Sort by:{}",
+        SORT_ORDERS
+          .iter()
+          .map(|order| order.name)
+          .collect::<Vec<&str>>()
+          .join(":")
+      ),
+      option_type: InteractiveOptionType::ListButtons,
+    });
+  }
+  if more {
+    options.push(InteractiveOption {
+      text: String::from("Next page"),
+      option_type: InteractiveOptionType::Special,
+    });
+  }
+  if page > 0 {
+    options.push(InteractiveOption {
+      text: String::from("Previous page"),
+      option_type: InteractiveOptionType::Special,
+    });
+  }
+  options.push(InteractiveOption {
+    text: String::from("Back"),
+    option_type: InteractiveOptionType::Special,
+  });
+  match interactive_select(options) {
+    ((index, _), _, InteractiveOptionType::Button) => PageAction::Open(index),
+    ((_, sort), _, InteractiveOptionType::ListButtons) => PageAction::Sort(sort - 1),
+    (_, Some(text), InteractiveOptionType::Special) => match text.as_str() {
+      "Next page" => PageAction::Next,
+      "Previous page" => PageAction::Previous,
+      _ => PageAction::Back,
+    },
+    _ => PageAction::Back,
+  }
+}
+
+// Genres, studios and years come as one list from the server. They're only paged here.
+pub fn choose_category(title: &str, names: &[String]) -> Option<usize> {
+  if names.is_empty() {
+    print_message(
+      PrintMessageType::Warning,
+      "There is nothing to choose from.",
+    );
+    return None;
+  }
+  let mut page = 0;
+  loop {
+    let start = page * PAGE_SIZE;
+    let end = names.len().min(start + PAGE_SIZE);
+    match select_from_page(
+      format!("{} (page {}):", title, page + 1),
+      &names[start..end],
+      page,
+      end < names.len(),
+      false,
+    ) {
+      PageAction::Open(index) => return Some(start + index),
+      PageAction::Next => page += 1,
+      PageAction::Previous => page -= 1,
+      _ => return None,
+    }
+  }
+}
+
+// Adds sorting and paging to any item query.
+pub fn page_url(url: &str, sort: &SortOrder, page: usize) -> String {
+  format!(
+    "{}&SortBy={}&SortOrder={}&StartIndex={}&Limit={}",
+    url,
+    sort.sort_by,
+    sort.sort_order,
+    page * PAGE_SIZE,
+    PAGE_SIZE
+  )
+}
+
+// Plex uses its own parameters, the url might not have a query yet.
+pub fn plex_page_url(url: &str, sort: Option<&SortOrder>, page: usize) -> String {
+  let mut url = format!(
+    "{}{}X-Plex-Container-Start={}&X-Plex-Container-Size={}",
+    url,
+    if url.contains('?') { "&" } else { "?" },
+    page * PAGE_SIZE,
+    PAGE_SIZE
+  );
+  if let Some(sort) = sort {
+    url += &format!("&sort={}", sort.plex_sort);
+  }
+  url
+}
+
+pub fn library_menu<M: MediaCenter>(media_center: &mut M) -> Result<(), PuddlerError> {
+  let libraries = media_center.libraries()?;
+  loop {
+    let mut options: Vec<InteractiveOption> = vec![InteractiveOption {
+      text: String::from("Libraries:"),
+      option_type: InteractiveOptionType::Header,
+    }];
+    for library in &libraries {
+      options.push(InteractiveOption {
+        text: library.name.clone(),
+        option_type: InteractiveOptionType::Button,
+      });
+    }
+    options.push(InteractiveOption {
+      text: String::from("Back"),
+      option_type: InteractiveOptionType::Special,
+    });
+    match interactive_select(options) {
+      ((index, _), _, InteractiveOptionType::Button) => {
+        if let Err(err) = browse_library(media_center, &libraries[index]) {
+          print_message(PrintMessageType::Error, &err.to_string());
+        }
+      },
+      _ => return Ok(()),
+    }
+  }
+}
+
+pub fn get_libraries<M: MediaCenter + ?Sized>(
+  media_center: &mut M,
+) -> Result<Vec<Library>, PuddlerError> {
+  let user_id = media_center
+    .get_config_handle()
+    .get_active_user()
+    .unwrap()
+    .user_id;
+  let entries = get_entries(media_center, format!("Users/{}/Views", user_id))?;
+  Ok(
+    entries
+      .iter()
+      .map(|entry| Library {
+        name: entry.Name.clone(),
+        id: entry.Id.clone(),
+        items_url: library_items_url(&user_id, entry),
+        folders_url: format!("Users/{}/Items?ParentId={}", user_id, entry.Id),
+        collections_url: format!(
+          "Users/{}/Items?Recursive=true&IncludeItemTypes=BoxSet",
+          user_id
+        ),
+      })
+      .collect(),
+  )
+}
+
+fn library_items_url(user_id: &str, library: &LibraryEntry) -> String {
+  let item_types = match library.CollectionType.as_deref() {
+    Some("movies") => "Movie",
+    Some("tvshows") => "Series",
+    Some("music") => "MusicAlbum",
+    Some("boxsets") => "BoxSet",
+    _ => "Movie,Series",
+  };
+  format!(
+    "Users/{}/Items?ParentId={}&Recursive=true&IncludeItemTypes={}",
+    user_id, library.Id, item_types
+  )
+}
+
+// `category` is "Genres", "Studios" or "Years", these are filters of the whole library.
+pub fn get_categories<M: MediaCenter + ?Sized>(
+  media_center: &mut M,
+  library: &Library,
+  category: &str,
+) -> Result<Vec<Category>, PuddlerError> {
+  let user_id = media_center
+    .get_config_handle()
+    .get_active_user()
+    .unwrap()
+    .user_id;
+  let filter = match category {
+    "Genres" => "GenreIds",
+    "Studios" => "StudioIds",
+    _ => "Years",
+  };
+  let entries = get_entries(
+    media_center,
+    format!(
+      "{}?UserId={}&ParentId={}&Recursive=true&SortBy=SortName",
+      category, user_id, library.id
+    ),
+  )?;
+  Ok(
+    entries
+      .into_iter()
+      .map(|entry| {
+        // years are filtered by their name, everything else by id
+        let value = if filter == "Years" {
+          &entry.Name
+        } else {
+          &entry.Id
+        };
+        Category {
+          url: format!(
+            "{}&{}={}",
+            library.items_url,
+            filter,
+            urlencoding::encode(value)
+          ),
+          name: entry.Name,
+        }
+      })
+      .collect(),
+  )
+}
+
+fn browse_library<M: MediaCenter>(
+  media_center: &mut M,
+  library: &Library,
+) -> Result<(), PuddlerError> {
+  loop {
+    let mut options: Vec<InteractiveOption> = vec![InteractiveOption {
+      text: format!("{}:", library.name.clone().cyan()),
+      option_type: InteractiveOptionType::Header,
+    }];
+    for option in BROWSE_OPTIONS {
+      options.push(InteractiveOption {
+        text: option.to_string(),
+        option_type: InteractiveOptionType::Button,
+      });
+    }
+    options.push(InteractiveOption {
+      text: String::from("Back"),
+      option_type: InteractiveOptionType::Special,
+    });
+    let selection = match interactive_select(options) {
+      ((index, _), _, InteractiveOptionType::Button) => BROWSE_OPTIONS[index],
+      _ => return Ok(()),
+    };
+    // genres, studios and years are categories, the rest are folders
+    match selection {
+      "All" => {
+        browse_items(
+          media_center,
+          library.name.clone(),
+          library.items_url.clone(),
+        )?;
+      },
+      "Folders" => media_center.browse_folders(library)?,
+      "Collections" => {
+        browse_items(
+          media_center,
+          String::from("Collections"),
+          library.collections_url.clone(),
+        )?;
+      },
+      category => {
+        let categories = media_center.library_categories(library, category)?;
+        let names: Vec<String> = categories
+          .iter()
+          .map(|category| category.name.clone())
+          .collect();
+        if let Some(index) = choose_category(category, &names) {
+          let category = categories[index].clone();
+          browse_items(media_center, category.name, category.url)?;
+        }
+      },
+    }
+  }
+}
+
+// One page of items at a time. Folders and collections are opened, everything else is played.
+pub fn browse_items<M: MediaCenter>(
+  media_center: &mut M,
+  title: String,
+  url: String,
+) -> Result<(), PuddlerError> {
+  let mut page = 0;
+  let mut sort = 0;
+  loop {
+    let items = media_center.library_page(&url, &SORT_ORDERS[sort], page)?;
+    let entries: Vec<String> = items
+      .iter()
+      .map(|item| match media_center.folder_url(item) {
+        Some(_) if is_collection(item) => format!("{} (Collection)", item),
+        Some(_) => format!("{}/", item),
+        None => item.to_string_ext(),
+      })
+      .collect();
+    match select_from_page(
+      format!(
+        "{} (page {}, sorted by {}):",
+        title.clone().cyan(),
+        page + 1,
+        SORT_ORDERS[sort].name
+      ),
+      &entries,
+      page,
+      items.len() == PAGE_SIZE,
+      true,
+    ) {
+      PageAction::Open(index) => {
+        let item = items[index].clone();
+        let result = if let Some(folder_url) = media_center.folder_url(&item) {
+          browse_items(media_center, item.to_string(), folder_url)
+        } else {
+          process_item(media_center, item, vec![])
+        };
+        if let Err(err) = result {
+          print_message(PrintMessageType::Error, &err.to_string());
+        }
+      },
+      PageAction::Sort(index) => {
+        sort = index;
+        page = 0;
+      },
+      PageAction::Next => page += 1,
+      PageAction::Previous => page -= 1,
+      PageAction::Back => return Ok(()),
+    }
+  }
+}
+
+// Jellyfin calls them "BoxSet", Plex "collection".
+fn is_collection(item: &MediaItem) -> bool {
+  matches!(&item.kind, MediaKind::Other(kind) if kind == "BoxSet" || kind == "collection")
+}
+
+// Jellyfin's folders, views and collections are browsed instead of played.
+pub fn is_folder(item: &MediaItem) -> bool {
+  matches!(
+    &item.kind,
+    MediaKind::Other(kind) if matches!(kind.as_str(), "Folder" | "CollectionFolder" | "UserView" | "BoxSet")
+  )
+}
+
+fn get_entries<M: MediaCenter + ?Sized>(
+  media_center: &mut M,
+  url: String,
+) -> Result<Vec<LibraryEntry>, PuddlerError> {
+  let response = media_center.get(url)?;
+  let mut json = serde_json::from_str::<Value>(&response.text()?)?;
+  Ok(serde_json::from_value::<Vec<LibraryEntry>>(
+    json["Items"].take(),
+  )?)
+}
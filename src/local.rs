@@ -0,0 +1,402 @@
+use regex::Regex;
+use std::{
+  collections::BTreeSet,
+  fs,
+  path::{Path, PathBuf},
+  sync::LazyLock,
+};
+
+use crate::{
+  backend::Backend,
+  error::PuddlerError,
+  media_config::{Config, Playstate},
+  media_item::{MediaItem, MediaKind, PlaybackSource},
+  puddler_settings::PuddlerSettings,
+};
+
+static EPISODE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+  Regex::new(
+    r"(?i)^(?P<series>.*?)\bs(?P<season>\d{1,3})[ ._-]?e(?P<episode>\d{1,4})(?:-?e\d{1,4})*\b(?P<title>.*)$",
+  )
+  .unwrap()
+});
+
+static SEASON_FOLDER_REGEX: LazyLock<Regex> =
+  LazyLock::new(|| Regex::new(r"(?i)^((season|staffel|s)[ ._-]*\d+|specials)$").unwrap());
+
+const VIDEO_EXTENSIONS: [&str; 13] = [
+  "avi", "flv", "m2ts", "m4v", "mkv", "mov", "mp4", "mpeg", "mpg", "ogv", "ts", "webm", "wmv",
+];
+
+// A folder full of video files, no server involved. Files named like "Show.Name.S01E02.mkv" are
+// grouped into series and seasons, everything else is a movie.
+// Just like DLNA, playstates are kept in the media-center config.
+pub struct LocalServer {
+  config: Config,
+  settings: PuddlerSettings,
+  // filled by `re_authenticate`
+  videos: Option<Vec<LocalVideo>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpisodeInfo {
+  pub series: String,
+  pub season: u32,
+  pub episode: u32,
+  // whatever comes after "SxxEyy", might be empty
+  pub title: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalVideo {
+  // relative to the library folder, this is the id of the item as well
+  pub path: String,
+  pub title: String,
+  pub episode: Option<EpisodeInfo>,
+}
+
+impl LocalVideo {
+  fn to_media_item(&self, playstate: Playstate) -> MediaItem {
+    let (kind, titles) = match &self.episode {
+      Some(info) => {
+        let number = if info.title.is_empty() {
+          format!("S{:02}E{:02}", info.season, info.episode)
+        } else {
+          format!("S{:02}E{:02} ({})", info.season, info.episode, info.title)
+        };
+        (
+          MediaKind::Episode,
+          vec![
+            format!("{} - {}", info.series, number),
+            info.series.clone(),
+            number,
+          ],
+        )
+      },
+      None => (MediaKind::Movie, vec![self.title.clone()]),
+    };
+    MediaItem {
+      id: self.path.clone(),
+      kind,
+      titles,
+      played: playstate.played,
+      playback_position: playstate.playback_position,
+      // mpv finds out once the file is loaded
+      runtime: 0,
+    }
+  }
+}
+
+impl LocalServer {
+  pub fn new(config: Config, settings: PuddlerSettings) -> Self {
+    LocalServer {
+      config,
+      settings,
+      videos: None,
+    }
+  }
+
+  fn folder(&self) -> Result<PathBuf, PuddlerError> {
+    match self.config.get_folder() {
+      Some(folder) if Path::new(&folder).is_dir() => Ok(PathBuf::from(folder)),
+      Some(folder) => Err(PuddlerError::Config(format!(
+        "\"{}\" is not a folder (anymore).",
+        folder
+      ))),
+      None => Err(PuddlerError::Config(String::from(
+        "The library folder is missing.",
+      ))),
+    }
+  }
+
+  // Marking a series or a season marks all of its episodes, anything else is marked by itself.
+  fn episodes_of(&mut self, item: &MediaItem) -> Result<Vec<MediaItem>, PuddlerError> {
+    let children = match item.kind {
+      MediaKind::Series | MediaKind::Season => self.browse(Some(item))?,
+      _ => vec![],
+    };
+    if children.is_empty() {
+      return Ok(vec![item.clone()]);
+    }
+    let mut episodes = vec![];
+    for child in children {
+      episodes.extend(self.episodes_of(&child)?);
+    }
+    Ok(episodes)
+  }
+
+  fn videos(&mut self) -> Result<Vec<LocalVideo>, PuddlerError> {
+    if self.videos.is_none() {
+      self.re_authenticate()?;
+    }
+    Ok(self.videos.clone().unwrap())
+  }
+}
+
+impl Backend for LocalServer {
+  fn get_config_handle(&mut self) -> &mut Config {
+    &mut self.config
+  }
+
+  fn get_settings(&mut self) -> &mut PuddlerSettings {
+    &mut self.settings
+  }
+
+  // Nothing to log into, the folder is (re-)indexed instead.
+  fn re_authenticate(&mut self) -> Result<(), PuddlerError> {
+    self.videos = Some(index_folder(&self.folder()?)?);
+    Ok(())
+  }
+
+  // Series and movies on the top level, then seasons and their episodes.
+  fn browse(&mut self, parent: Option<&MediaItem>) -> Result<Vec<MediaItem>, PuddlerError> {
+    let videos = self.videos()?;
+    let playstates = self.config.get_playstates();
+    let playstate = |video: &LocalVideo| playstates.get(&video.path).cloned().unwrap_or_default();
+    let played = |videos: &[&LocalVideo]| videos.iter().all(|video| playstate(video).played);
+    match parent {
+      None => {
+        let mut series: Vec<(String, Vec<&LocalVideo>)> = vec![];
+        for video in &videos {
+          let Some(info) = &video.episode else {
+            continue;
+          };
+          match series.iter_mut().find(|(name, _)| *name == info.series) {
+            Some((_, episodes)) => episodes.push(video),
+            None => series.push((info.series.clone(), vec![video])),
+          }
+        }
+        series.sort_by_key(|(name, _)| name.to_lowercase());
+        let mut movies: Vec<&LocalVideo> = videos
+          .iter()
+          .filter(|video| video.episode.is_none())
+          .collect();
+        movies.sort_by_key(|video| video.title.to_lowercase());
+
+        let mut items: Vec<MediaItem> = series
+          .iter()
+          .map(|(name, episodes)| MediaItem {
+            id: name.to_lowercase(),
+            kind: MediaKind::Series,
+            titles: vec![name.clone()],
+            played: played(episodes),
+            playback_position: 0,
+            runtime: 0,
+          })
+          .collect();
+        items.extend(
+          movies
+            .into_iter()
+            .map(|video| video.to_media_item(playstate(video))),
+        );
+        Ok(items)
+      },
+      Some(series) if series.kind == MediaKind::Series => {
+        let episodes: Vec<&LocalVideo> = videos
+          .iter()
+          .filter(|video| {
+            video
+              .episode
+              .as_ref()
+              .is_some_and(|info| info.series.to_lowercase() == series.id)
+          })
+          .collect();
+        let seasons: BTreeSet<u32> = episodes
+          .iter()
+          .filter_map(|video| video.episode.as_ref().map(|info| info.season))
+          .collect();
+        Ok(
+          seasons
+            .into_iter()
+            .map(|season| {
+              let season_episodes: Vec<&LocalVideo> = episodes
+                .iter()
+                .filter(|video| video.episode.as_ref().unwrap().season == season)
+                .copied()
+                .collect();
+              MediaItem {
+                // the series might contain a colon, the season number doesn't
+                id: format!("{}:{}", season, series.id),
+                kind: MediaKind::Season,
+                titles: vec![if season == 0 {
+                  String::from("Specials")
+                } else {
+                  format!("Season {}", season)
+                }],
+                played: played(&season_episodes),
+                playback_position: 0,
+                runtime: 0,
+              }
+            })
+            .collect(),
+        )
+      },
+      Some(season) if season.kind == MediaKind::Season => {
+        let Some((number, series)) = season.id.split_once(':') else {
+          return Ok(vec![]);
+        };
+        let mut episodes: Vec<&LocalVideo> = videos
+          .iter()
+          .filter(|video| {
+            video.episode.as_ref().is_some_and(|info| {
+              info.series.to_lowercase() == series && info.season.to_string() == number
+            })
+          })
+          .collect();
+        episodes.sort_by_key(|video| video.episode.as_ref().unwrap().episode);
+        Ok(
+          episodes
+            .into_iter()
+            .map(|video| video.to_media_item(playstate(video)))
+            .collect(),
+        )
+      },
+      Some(_) => Ok(vec![]),
+    }
+  }
+
+  fn resume(&mut self) -> Result<Vec<MediaItem>, PuddlerError> {
+    let playstates = self.config.get_playstates();
+    Ok(
+      self
+        .videos()?
+        .iter()
+        .filter_map(|video| {
+          let playstate = playstates.get(&video.path)?;
+          if playstate.played || playstate.playback_position == 0 {
+            return None;
+          }
+          Some(video.to_media_item(playstate.clone()))
+        })
+        .collect(),
+    )
+  }
+
+  fn resolve_stream(&mut self, item: &MediaItem) -> Result<PlaybackSource, PuddlerError> {
+    let path = self.folder()?.join(&item.id);
+    if !path.is_file() {
+      return Err(PuddlerError::Io(format!(
+        "\"{}\" doesn't exist anymore.",
+        path.display()
+      )));
+    }
+    // mpv picks up subtitles next to the file by itself
+    Ok(PlaybackSource {
+      stream_url: path.display().to_string(),
+      ..Default::default()
+    })
+  }
+
+  fn report_progress(&mut self, item: &MediaItem, position: u64) -> Result<(), PuddlerError> {
+    self.config.save_playstate(
+      &item.id,
+      Playstate {
+        played: item.played,
+        playback_position: position,
+        ..Default::default()
+      },
+    );
+    Ok(())
+  }
+
+  fn set_playstate(&mut self, item: &MediaItem, played: bool) -> Result<(), PuddlerError> {
+    let playstate = Playstate {
+      played,
+      playback_position: 0,
+      ..Default::default()
+    };
+    let playstates = self
+      .episodes_of(item)?
+      .into_iter()
+      .map(|episode| (episode.id, playstate.clone()))
+      .collect();
+    self.config.save_playstates(playstates);
+    Ok(())
+  }
+}
+
+// Every video file below `folder`, hidden files and folders are left out.
+pub fn index_folder(folder: &Path) -> Result<Vec<LocalVideo>, PuddlerError> {
+  let mut paths: Vec<String> = vec![];
+  let mut folders: Vec<PathBuf> = vec![folder.to_path_buf()];
+  while let Some(current) = folders.pop() {
+    for entry in fs::read_dir(&current)? {
+      let path = entry?.path();
+      if path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+      {
+        continue;
+      }
+      if path.is_dir() {
+        folders.push(path);
+      } else if path.extension().is_some_and(|extension| {
+        VIDEO_EXTENSIONS.contains(&extension.to_string_lossy().to_lowercase().as_str())
+      }) && let Ok(relative) = path.strip_prefix(folder)
+      {
+        paths.push(relative.to_string_lossy().to_string());
+      }
+    }
+  }
+  paths.sort();
+  let mut videos: Vec<LocalVideo> = paths.iter().map(|path| parse_video(path)).collect();
+  // "Dark.S01E01.mkv" and "dark.s01e02.mkv" are the same series, the first spelling is kept
+  let mut names: Vec<String> = vec![];
+  for info in videos.iter_mut().filter_map(|video| video.episode.as_mut()) {
+    match names
+      .iter()
+      .find(|name| name.to_lowercase() == info.series.to_lowercase())
+    {
+      Some(name) => info.series = name.clone(),
+      None => names.push(info.series.clone()),
+    }
+  }
+  Ok(videos)
+}
+
+// "The.Wire.S01E02.The.Detail.mkv", "Season 1/S01E02.mkv" (the series is the folder then) ...
+pub fn parse_video(path: &str) -> LocalVideo {
+  let path_buf = Path::new(path);
+  let stem = path_buf
+    .file_stem()
+    .map(|stem| stem.to_string_lossy().to_string())
+    .unwrap_or_default();
+  let episode = EPISODE_REGEX.captures(&stem).map(|captures| {
+    let mut series = clean_name(&captures["series"]);
+    if series.is_empty() {
+      series = series_from_folders(path_buf);
+    }
+    EpisodeInfo {
+      series,
+      season: captures["season"].parse().unwrap_or(0),
+      episode: captures["episode"].parse().unwrap_or(0),
+      title: clean_name(&captures["title"]),
+    }
+  });
+  LocalVideo {
+    path: path.to_string(),
+    title: clean_name(&stem),
+    episode,
+  }
+}
+
+// The closest folder that isn't a season folder.
+fn series_from_folders(path: &Path) -> String {
+  path
+    .ancestors()
+    .skip(1)
+    .filter_map(|folder| folder.file_name())
+    .map(|name| clean_name(&name.to_string_lossy()))
+    .find(|name| !name.is_empty() && !SEASON_FOLDER_REGEX.is_match(name))
+    .unwrap_or(String::from("???"))
+}
+
+fn clean_name(name: &str) -> String {
+  name
+    .replace(['.', '_'], " ")
+    .split_whitespace()
+    .collect::<Vec<&str>>()
+    .join(" ")
+    .trim_matches(|c: char| c == '-' || c == ' ')
+    .to_string()
+}
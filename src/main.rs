@@ -1,36 +1,63 @@
 #![allow(non_snake_case)]
 use clap::{Arg, ArgAction, Command};
 use colored::Colorize;
+use crossterm::{
+  cursor::{MoveToColumn, Show},
+  execute,
+  terminal::{Clear, ClearType},
+};
 use puddler_settings::PuddlerSettings;
-use std::process::{ExitCode, exit};
+use std::{io::stdout, process::ExitCode};
 
 use crate::{
+  backend::Backend,
+  error::PuddlerError,
   input::{InteractiveOption, InteractiveOptionType, interactive_menuoption, interactive_select},
-  media_center::{MediaCenter, set_config},
-  media_config::{Config, MediaCenterType, Objective},
+  media_center::set_config,
+  media_config::{Config, Objective},
   printing::{PrintMessageType, print_message},
 };
 
 const APPNAME: &str = "Puddler";
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+mod backend;
+mod cli;
+mod companion;
 mod discord;
+mod dlna;
 mod emby;
 mod error;
+mod history;
 mod input;
 mod jellyfin;
+mod library;
+mod local;
 mod media_center;
 mod media_config;
+mod media_item;
 mod mpv;
+mod music;
+mod offline;
 mod plex;
 mod printing;
 mod puddler_settings;
+mod queue;
+mod receiver;
+mod remote;
+mod search;
+mod segments;
+mod syncplay;
+#[cfg(test)]
+mod tests;
 
 #[derive(Debug, Clone)]
 pub enum MenuOptions {
   Default(String),
   Choose,
   Setup,
+  History,
+  Offline,
   Setting,
   Exit,
 }
@@ -47,6 +74,8 @@ impl ToString for MenuOptions {
       },
       MenuOptions::Choose => String::from("View Media-Centers"),
       MenuOptions::Setup => String::from("Add new Media-Center"),
+      MenuOptions::History => String::from("History"),
+      MenuOptions::Offline => String::from("Offline library"),
       MenuOptions::Setting => String::from("Settings"),
       MenuOptions::Exit => String::from("Exit puddler"),
     }
@@ -56,7 +85,9 @@ impl ToString for MenuOptions {
 fn main() -> ExitCode {
   let command = Command::new("puddler")
     .display_name("Puddler")
-    .about("A simplistic command-line client for Jellyfin, Emby and Plex.")
+    .about(
+      "A simplistic command-line client for Jellyfin, Emby, Plex, DLNA servers and local folders.",
+    )
     .version(VERSION)
     .arg(
       Arg::new("glsl-shaders")
@@ -73,6 +104,67 @@ fn main() -> ExitCode {
         .required(false)
         .action(ArgAction::SetTrue),
     )
+    .arg(
+      Arg::new("server")
+        .long("server")
+        .help("Name of the media-center used by subcommands. Defaults to the default media-center.")
+        .required(false)
+        .global(true)
+        .action(ArgAction::Set),
+    )
+    .arg(
+      Arg::new("user")
+        .long("user")
+        .help("Username used by subcommands. Defaults to the active user of the media-center.")
+        .required(false)
+        .global(true)
+        .action(ArgAction::Set),
+    )
+    .arg(
+      Arg::new("output")
+        .long("output")
+        .help("Output format of subcommands.")
+        .required(false)
+        .global(true)
+        .value_parser(["text", "json"])
+        .default_value("text")
+        .action(ArgAction::Set),
+    )
+    .subcommand(
+      Command::new("search")
+        .about("Search for movies and series.")
+        .arg(Arg::new("term").required(true).action(ArgAction::Set)),
+    )
+    .subcommand(
+      Command::new("play")
+        .about("Play a movie or episode by its id (direct-play only).")
+        .arg(Arg::new("item-id").required(true).action(ArgAction::Set)),
+    )
+    .subcommand(Command::new("resume").about("List items that can be resumed."))
+    .subcommand(Command::new("next-up").about("List the next episodes of series in progress."))
+    .subcommand(Command::new("latest").about("List recently added episodes and movies."))
+    .subcommand(
+      Command::new("series")
+        .about("List all episodes of a series, season or episode id.")
+        .arg(Arg::new("item-id").required(true).action(ArgAction::Set)),
+    )
+    .subcommand(
+      Command::new("receiver")
+        .about("Wait for other clients (or Plex apps) to play something on this device."),
+    )
+    .subcommand(Command::new("list-servers").about("List all configured media-centers."))
+    .subcommand(
+      Command::new("history")
+        .about("List previously played items of all media-centers (or only --server).")
+        .arg(
+          Arg::new("limit")
+            .long("limit")
+            .help("Only list the most recent entries.")
+            .required(false)
+            .value_parser(clap::value_parser!(usize))
+            .action(ArgAction::Set),
+        ),
+    )
     .get_matches();
 
   let mut settings: PuddlerSettings = PuddlerSettings::new().unwrap();
@@ -89,6 +181,10 @@ fn main() -> ExitCode {
     settings.mpv_debug_log = true;
   }
 
+  if command.subcommand().is_some() {
+    return cli::run(&command, settings);
+  }
+
   let mut options: Vec<MenuOptions> = vec![];
 
   if let Some(ref default_server) = settings.default_media_server {
@@ -106,6 +202,8 @@ fn main() -> ExitCode {
   options.append(&mut vec![
     MenuOptions::Choose,
     MenuOptions::Setup,
+    MenuOptions::History,
+    MenuOptions::Offline,
     MenuOptions::Setting,
     MenuOptions::Exit,
   ]);
@@ -125,7 +223,7 @@ fn main() -> ExitCode {
   loop {
     let mut handle = Config::default();
     let path: String;
-    let mut center: Box<dyn MediaCenter>;
+    let mut center: Box<dyn Backend>;
     match interactive_menuoption(options.clone()) {
       MenuOptions::Default(_) => {
         path = settings.default_media_server.clone().unwrap();
@@ -139,44 +237,58 @@ fn main() -> ExitCode {
         }
       },
       MenuOptions::Choose => {
-        if let Ok(configs) = handle.new() {
-          if configs.is_empty() {
+        let configs = match handle.new() {
+          Ok(configs) => configs,
+          Err(err) => {
+            print_message(
+              PrintMessageType::Error,
+              &PuddlerError::from(err).to_string(),
+            );
             continue;
-          }
-          let mut configs_str: Vec<InteractiveOption> = vec![];
-          for config in configs.clone() {
-            configs_str.append(&mut vec![InteractiveOption {
-              text: format!(
-                "{} - {}:Modify",
-                config.config.server_name,
-                config.config.media_center_type.to_string()
-              ),
-              option_type: InteractiveOptionType::MultiButton,
-            }]);
-          }
+          },
+        };
+        if configs.is_empty() {
+          continue;
+        }
+        let mut configs_str: Vec<InteractiveOption> = vec![];
+        for config in configs.clone() {
           configs_str.append(&mut vec![InteractiveOption {
-            text: String::from("Back"),
-            option_type: InteractiveOptionType::Special,
+            text: format!(
+              "{} - {}:Modify",
+              config.config.server_name,
+              config.config.media_center_type.to_string()
+            ),
+            option_type: InteractiveOptionType::MultiButton,
           }]);
-          match interactive_select(configs_str) {
-            ((i1, i2), _, InteractiveOptionType::MultiButton) => {
-              handle = configs.get(i1).unwrap().clone();
-              if i2 == 1 {
-                center = set_config(handle, settings.clone());
-                center.modify();
-                continue;
-              }
-            },
-            (_, Some(option), InteractiveOptionType::Special) => {
-              if option == *"Back" {
-                continue;
-              }
-            },
-            _ => (),
-          }
-        } else {
-          exit(1);
         }
+        configs_str.append(&mut vec![InteractiveOption {
+          text: String::from("Back"),
+          option_type: InteractiveOptionType::Special,
+        }]);
+        match interactive_select(configs_str) {
+          ((i1, i2), _, InteractiveOptionType::MultiButton) => {
+            handle = configs.get(i1).unwrap().clone();
+            if i2 == 1 {
+              center = set_config(handle, settings.clone());
+              center.modify();
+              continue;
+            }
+          },
+          (_, Some(option), InteractiveOptionType::Special) => {
+            if option == *"Back" {
+              continue;
+            }
+          },
+          _ => (),
+        }
+      },
+      MenuOptions::History => {
+        history::history_menu();
+        continue;
+      },
+      MenuOptions::Offline => {
+        offline::offline_menu(&settings);
+        continue;
       },
       MenuOptions::Setting => {
         settings.change_menu();
@@ -185,16 +297,29 @@ fn main() -> ExitCode {
       MenuOptions::Exit => return ExitCode::SUCCESS,
       MenuOptions::Setup => {
         handle.ask_for_setting(Objective::MediaCenterType);
-        match handle.config.media_center_type {
-          MediaCenterType::Plex => (),
-          _ => handle.ask_for_setting(Objective::SearchLocalInstance),
-        }
+        handle.ask_for_setting(Objective::SearchLocalInstance);
       },
     };
 
     center = set_config(handle, settings.clone());
 
-    center.re_authenticate();
-    center.menu();
+    // the server is reachable again, so this is the time to sync items that were played offline
+    let result = center.re_authenticate().and_then(|_| {
+      if let Some(media_center) = center.as_media_center() {
+        offline::sync_playstate(media_center);
+      }
+      center.menu()
+    });
+    if let Err(err) = result {
+      // the loading indicator of the menu might still be there
+      execute!(
+        stdout(),
+        Show,
+        MoveToColumn(0),
+        Clear(ClearType::CurrentLine)
+      )
+      .unwrap();
+      print_message(PrintMessageType::Error, &err.to_string());
+    }
   }
 }
@@ -5,10 +5,10 @@ use crossterm::{
   execute,
   style::Stylize,
   terminal::{
-    self, Clear, ClearType, DisableLineWrap, EnableLineWrap, EnterAlternateScreen,
-    LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+    Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
   },
 };
+use if_addrs::IfAddr;
 use isolanguage_1::LanguageCode;
 use regex::Regex;
 use reqwest::{
@@ -18,35 +18,61 @@ use reqwest::{
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::{
+  collections::HashMap,
   fmt,
   io::{Write, stdin, stdout},
-  net::UdpSocket,
+  net::{IpAddr, Ipv4Addr, UdpSocket},
   process::exit,
-  str::{FromStr, from_utf8},
+  str::FromStr,
   sync::{Arc, Mutex},
   thread,
-  time::Duration,
+  time::{Duration, Instant},
 };
 
 use crate::{
   APPNAME, VERSION,
+  backend::{Backend, process_item},
+  cli::{Listable, Listing, OutputFormat, print_items},
+  dlna::{DlnaServer, fetch_description},
   emby::EmbyServer,
+  error::PuddlerError,
   input::{
-    InteractiveOption, InteractiveOptionType, SeriesOptions, getch, hidden_string_input,
-    interactive_select, jelly_series_select, take_string_input,
+    InteractiveOption, InteractiveOptionType, getch, hidden_string_input, interactive_select,
+    take_string_input,
   },
   jellyfin::JellyfinServer,
+  library::{
+    Category, Library, PAGE_SIZE, SortOrder, browse_items, get_categories, get_libraries,
+    is_folder, library_menu, page_url,
+  },
+  local::LocalServer,
   media_config::{Config, MediaCenterType, Objective, UserConfig},
-  mpv::Player,
+  media_item::{MediaItem, MediaKind, PlaybackSource},
+  mpv::{Player, Video},
+  music::{music_menu, process_music_item},
+  offline::{Download, DownloadSubtitle, OfflineLibrary},
   plex::PlexServer,
   printing::{PrintMessageType, print_message},
   puddler_settings::PuddlerSettings,
+  queue::queue_menu,
+  receiver::run_receiver,
+  search::{SearchQuery, parse_search, search_options},
+  segments::{Chapter, Segment, Trickplay, parse_media_segments},
+  syncplay::syncplay_menu,
 };
 
+// How long servers have to answer a local search.
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UDPAnswer {
   pub Address: String,
   pub Name: String,
+  // the server id, or Plex' machine identifier
+  #[serde(default)]
+  pub Id: String,
+  #[serde(default)]
+  pub Version: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -62,7 +88,7 @@ pub enum MediaCenterValues {
   PlaybackInfo,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct Item {
   pub Name: String,
   pub Id: String,
@@ -81,6 +107,30 @@ pub struct Item {
   pub Status: Option<String>,
   pub EndDate: Option<String>,
   pub MediaSources: Option<Vec<MediaSourceInfo>>,
+  // music only
+  pub Album: Option<String>,
+  pub AlbumId: Option<String>,
+  pub AlbumArtist: Option<String>,
+  // only for a single item, see `get_item`
+  pub Chapters: Option<Vec<ChapterInfo>>,
+  // media-source id -> thumbnail width -> tiles (Jellyfin only)
+  pub Trickplay: Option<HashMap<String, HashMap<String, TrickplayInfo>>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ChapterInfo {
+  pub StartPositionTicks: u64,
+  pub Name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct TrickplayInfo {
+  pub Width: u32,
+  pub Height: u32,
+  pub TileWidth: u32,
+  pub TileHeight: u32,
+  pub ThumbnailCount: u32,
+  pub Interval: u32,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
@@ -243,7 +293,7 @@ impl IsNumeric for str {
   }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct UserData {
   pub PlayedPercentage: Option<f64>,
   pub PlaybackPositionTicks: u64,
@@ -311,18 +361,6 @@ pub struct UserConfiguration {
   SubtitleLanguagePreference: Option<String>,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Clone)]
-pub struct Series {
-  item_id: String,
-  pub seasons: Vec<Season>,
-}
-
-#[derive(Debug, Deserialize, PartialEq, Clone)]
-pub struct Season {
-  pub item: Item,
-  pub episodes: Vec<Item>,
-}
-
 pub trait ToStringAdv {
   fn to_string_split(&self) -> Vec<String>;
   fn to_string_full(&self) -> String;
@@ -368,6 +406,18 @@ impl ToStringAdv for Item {
       "Movie" | "Series" => {
         vec![format!("{} {}", name, time)]
       },
+      "MusicArtist" | "MusicAlbum" => vec![self.to_string()],
+      "Audio" => {
+        vec![
+          self.to_string(),
+          format!(
+            "{} - {}",
+            self.AlbumArtist.clone().unwrap_or(String::from("???")),
+            self.Album.clone().unwrap_or(String::from("???"))
+          ),
+          format!("{:02}. {}", self.IndexNumber.unwrap_or(0), self.Name),
+        ]
+      },
       "Season" => {
         vec![
           self.to_string(),
@@ -425,6 +475,16 @@ impl ToStringAdv for Item {
   }
 }
 
+impl Listable for Item {
+  fn id(&self) -> String {
+    self.Id.clone()
+  }
+
+  fn item_type(&self) -> String {
+    self.Type.clone()
+  }
+}
+
 // Properly compiles titles, dates and other metadata into one string.
 impl ToString for Item {
   fn to_string(&self) -> String {
@@ -460,6 +520,20 @@ impl ToString for Item {
       "Movie" | "Series" => {
         format!("{} {}", name, time)
       },
+      "MusicArtist" => name,
+      "MusicAlbum" => format!(
+        "{} - {} {}",
+        self.AlbumArtist.clone().unwrap_or(String::from("???")),
+        name,
+        time
+      ),
+      "Audio" => format!(
+        "{} - {} - {:02}. {}",
+        self.AlbumArtist.clone().unwrap_or(String::from("???")),
+        self.Album.clone().unwrap_or(String::from("???")),
+        self.IndexNumber.unwrap_or(0),
+        self.Name
+      ),
       "Season" => {
         format!("{} {} - {}", name, time, self.Name.clone())
       },
@@ -491,15 +565,18 @@ impl ToString for Item {
   }
 }
 
-pub fn set_config(handle: Config, settings: PuddlerSettings) -> Box<dyn MediaCenter> {
+pub fn set_config(handle: Config, settings: PuddlerSettings) -> Box<dyn Backend> {
   match handle.config.media_center_type {
     MediaCenterType::Emby => Box::new(EmbyServer::new(handle, settings)),
     MediaCenterType::Jellyfin => Box::new(JellyfinServer::new(handle, settings)),
+    MediaCenterType::Dlna => Box::new(DlnaServer::new(handle, settings)),
+    MediaCenterType::Local => Box::new(LocalServer::new(handle, settings)),
     _ => Box::new(PlexServer::new(handle, settings)),
   }
 }
 
 // Defaulting to a Jellyfin/Emby instance. Other API's need to re-implement several if not all traits and structs for full functionality.
+// Servers that can't do much more than browsing and streaming are better off with just `Backend`.
 #[async_trait]
 pub trait MediaCenter: Send {
   fn new(config: Config, settings: PuddlerSettings) -> Self
@@ -509,6 +586,10 @@ pub trait MediaCenter: Send {
   fn get_config_handle(&mut self) -> &mut Config;
   fn get_headers(&mut self) -> Vec<(String, String)>;
   fn get_settings(&mut self) -> &mut PuddlerSettings;
+  fn get_client(&mut self) -> Client;
+  fn get_queue(&mut self) -> &mut Vec<MediaItem>;
+  #[cfg(test)]
+  fn set_client(&mut self, client: Client);
 
   fn modify(&mut self) {
     loop {
@@ -604,134 +685,59 @@ pub trait MediaCenter: Send {
     }
   }
 
-  fn menu(&mut self) {
+  fn menu(&mut self) -> Result<(), PuddlerError>
+  where
+    Self: Sized,
+  {
     let media_center_type = self.get_config_handle().config.media_center_type;
-    let user = self.get_config_handle().get_active_user().unwrap();
     let mut stdout = stdout();
     execute!(stdout, Hide).unwrap();
-    print!("Loading menu [0/4]");
+    print!("Loading menu [0/2]");
     stdout.flush().unwrap();
 
-    let mut total: Vec<Item> = vec![];
+    let mut total: Vec<MediaItem> = vec![];
     let mut options: Vec<InteractiveOption> = vec![];
-    if let Ok(items) = self.get_items(
-      format!(
-        "Users/{}/Items/Resume?Limit=15&MediaTypes=Video",
-        user.user_id
-      ),
-      false,
-    ) {
-      if !items.is_empty() {
-        options.append(&mut vec![InteractiveOption {
-          text: String::from("Continue Watching:"),
-          option_type: InteractiveOptionType::Header,
-        }]);
-      }
-      for item in items.clone() {
-        options.append(&mut vec![InteractiveOption {
-          text: item.to_string_ext(),
-          option_type: InteractiveOptionType::Button,
-        }]);
-      }
-      total.extend(items);
-    } else {
-      exit(1);
-    };
-
-    enable_raw_mode().unwrap();
-    execute!(stdout, MoveToColumn(0)).unwrap();
-    disable_raw_mode().unwrap();
-    print!("Loading menu [1/4]");
-    stdout.flush().unwrap();
-
-    if media_center_type == MediaCenterType::Jellyfin {
-      if let Ok(mut items) = self.get_items(format!("Shows/NextUp?UserId={}", user.user_id), false)
-      {
-        if total.is_empty() && !items.is_empty() {
-          options.append(&mut vec![InteractiveOption {
-            text: String::from("Continue Watching:"),
-            option_type: InteractiveOptionType::Header,
-          }]);
-        }
-        items.retain(|i| !total.contains(i));
-        for item in items.clone() {
-          options.append(&mut vec![InteractiveOption {
-            text: item.to_string_ext(),
-            option_type: InteractiveOptionType::Button,
-          }]);
-        }
-        total.extend(items);
-      } else {
-        exit(1);
-      };
+    let items = MediaCenter::resume(self)?;
+    if !items.is_empty() {
+      options.append(&mut vec![InteractiveOption {
+        text: String::from("Continue Watching:"),
+        option_type: InteractiveOptionType::Header,
+      }]);
     }
+    for item in items.clone() {
+      options.append(&mut vec![InteractiveOption {
+        text: item.to_string_ext(),
+        option_type: InteractiveOptionType::Button,
+      }]);
+    }
+    total.extend(items);
 
     enable_raw_mode().unwrap();
     execute!(stdout, MoveToColumn(0)).unwrap();
     disable_raw_mode().unwrap();
-    print!("Loading menu [2/4]");
-    stdout.flush().unwrap();
-
-    let latest_episode_size = if let Ok(items) = self.get_items(
-      format!(
-        "Users/{}/Items/Latest?Limit=10&IncludeItemTypes=Episode",
-        user.user_id
-      ),
-      true,
-    ) {
-      if !total.is_empty() {
-        options.append(&mut vec![InteractiveOption {
-          text: String::new(),
-          option_type: InteractiveOptionType::Header,
-        }]);
-      }
-      if !items.is_empty() {
-        options.append(&mut vec![InteractiveOption {
-          text: String::from("Latest:"),
-          option_type: InteractiveOptionType::Header,
-        }]);
-      }
-      for item in items.clone() {
-        options.append(&mut vec![InteractiveOption {
-          text: item.to_string_ext(),
-          option_type: InteractiveOptionType::Button,
-        }]);
-      }
-      total.extend(items.clone());
-      items.len()
-    } else {
-      exit(1);
-    };
-
-    enable_raw_mode().unwrap();
-    execute!(stdout, MoveToColumn(0)).unwrap();
-    disable_raw_mode().unwrap();
-    print!("Loading menu [3/4]");
+    print!("Loading menu [1/2]");
     stdout.flush().unwrap();
 
-    if let Ok(items) = self.get_items(
-      format!(
-        "Users/{}/Items/Latest?Limit=10&IncludeItemTypes=Movie",
-        user.user_id
-      ),
-      true,
-    ) {
-      if latest_episode_size == 0 && !items.is_empty() {
-        options.append(&mut vec![InteractiveOption {
-          text: String::from("Latest:"),
-          option_type: InteractiveOptionType::Header,
-        }]);
-      }
-      for item in items.clone() {
-        options.append(&mut vec![InteractiveOption {
-          text: item.to_string_ext(),
-          option_type: InteractiveOptionType::Button,
-        }]);
-      }
-      total.extend(items);
-    } else {
-      exit(1);
-    };
+    let items = self.latest_items()?;
+    if !total.is_empty() {
+      options.append(&mut vec![InteractiveOption {
+        text: String::new(),
+        option_type: InteractiveOptionType::Header,
+      }]);
+    }
+    if !items.is_empty() {
+      options.append(&mut vec![InteractiveOption {
+        text: String::from("Latest:"),
+        option_type: InteractiveOptionType::Header,
+      }]);
+    }
+    for item in items.clone() {
+      options.append(&mut vec![InteractiveOption {
+        text: item.to_string_ext(),
+        option_type: InteractiveOptionType::Button,
+      }]);
+    }
+    total.extend(items);
 
     enable_raw_mode().unwrap();
     execute!(stdout, MoveToColumn(0), Clear(ClearType::FromCursorDown)).unwrap();
@@ -747,62 +753,97 @@ pub trait MediaCenter: Send {
         option_type: InteractiveOptionType::TextInput,
       },
       InteractiveOption {
-        text: format!("Return to {} Menu", APPNAME),
+        text: String::from("Up next"),
+        option_type: InteractiveOptionType::Special,
+      },
+      InteractiveOption {
+        text: String::from("Libraries"),
+        option_type: InteractiveOptionType::Special,
+      },
+      InteractiveOption {
+        text: String::from("Music"),
         option_type: InteractiveOptionType::Special,
       },
     ]);
+    if media_center_type == MediaCenterType::Jellyfin {
+      options.append(&mut vec![InteractiveOption {
+        text: String::from("SyncPlay"),
+        option_type: InteractiveOptionType::Special,
+      }]);
+    }
+    options.append(&mut vec![InteractiveOption {
+      text: format!("Return to {} Menu", APPNAME),
+      option_type: InteractiveOptionType::Special,
+    }]);
 
     let menu = options.clone();
 
     let mut current_items = total.clone();
+    // the text that was entered, the parsed query and the last loaded page
+    let mut current_search: Option<(String, SearchQuery, usize)> = None;
     loop {
       match interactive_select(options.clone()) {
         (selection, _, InteractiveOptionType::Button) => {
-          self.process_item(current_items[selection.0].clone());
+          if let Err(err) = process_item(self, current_items[selection.0].clone(), vec![]) {
+            print_message(PrintMessageType::Error, &err.to_string());
+          }
           continue;
         },
         (_, Some(mut search), InteractiveOptionType::TextInput) => {
           search = search.trim().to_owned();
-          if let Ok(items) = self.get_items(
-            format!(
-              "Items?SearchTerm={}&UserId={}&Recursive=true&IncludeItemTypes=Series,Movie",
-              urlencoding::encode(&search),
-              user.user_id
-            ),
-            false,
-          ) {
-            options.clear();
-            options.append(&mut vec![InteractiveOption {
-              text: format!("Search-Result for \"{}\":", search.cyan()),
-              option_type: InteractiveOptionType::Header,
-            }]);
-            for item in items.clone() {
-              options.append(&mut vec![InteractiveOption {
-                text: item.to_string_ext(),
-                option_type: InteractiveOptionType::Button,
-              }]);
-            }
-            options.append(&mut vec![
-              InteractiveOption {
-                text: String::from("Back"),
-                option_type: InteractiveOptionType::Special,
-              },
-              InteractiveOption {
-                text: format!("Return to {} Menu", APPNAME),
-                option_type: InteractiveOptionType::Special,
-              },
-            ]);
-            current_items = items;
-          } else {
-            exit(1);
+          let query = match parse_search(&search) {
+            Ok(query) => query,
+            Err(err) => {
+              print_message(PrintMessageType::Error, &err.to_string());
+              continue;
+            },
           };
+          match self.search_items(&query, 0) {
+            Ok((items, more)) => {
+              let entries: Vec<String> = items.iter().map(|i| i.to_string_ext()).collect();
+              options = search_options(&search, &entries, more);
+              current_items = items;
+              current_search = Some((search, query, 0));
+            },
+            Err(err) => print_message(PrintMessageType::Error, &err.to_string()),
+          }
         },
         (_, Some(option), InteractiveOptionType::Special) => {
           if option == *"Back" {
             options = menu.clone();
             current_items = total.clone();
+            current_search = None;
+          } else if option == *"Load more" {
+            if let Some((search, query, page)) = current_search.as_mut() {
+              match self.search_items(query, *page + 1) {
+                Ok((items, more)) => {
+                  *page += 1;
+                  current_items.extend(items);
+                  let entries: Vec<String> =
+                    current_items.iter().map(|i| i.to_string_ext()).collect();
+                  options = search_options(search, &entries, more);
+                },
+                Err(err) => print_message(PrintMessageType::Error, &err.to_string()),
+              }
+            }
+          } else if option == *"Up next" {
+            if let Err(err) = queue_menu(self) {
+              print_message(PrintMessageType::Error, &err.to_string());
+            }
+          } else if option == *"Libraries" {
+            if let Err(err) = self.library_menu() {
+              print_message(PrintMessageType::Error, &err.to_string());
+            }
+          } else if option == *"Music" {
+            if let Err(err) = self.music_menu() {
+              print_message(PrintMessageType::Error, &err.to_string());
+            }
+          } else if option == *"SyncPlay" {
+            if let Err(err) = syncplay_menu(self) {
+              print_message(PrintMessageType::Error, &err.to_string());
+            }
           } else if option == format!("Return to {} Menu", APPNAME) {
-            return;
+            return Ok(());
           }
         },
         _ => panic!("UNKOWN OPTION TYPE"),
@@ -810,169 +851,548 @@ pub trait MediaCenter: Send {
     }
   }
 
-  fn process_item(&mut self, item: Item) {
-    println!("Selected: {}", item.to_string_ext().cyan());
-    let mut playlist: Vec<Item> = vec![];
-    match item.Type.as_str() {
-      "Movie" => {
-        playlist.push(item);
+  fn library_menu(&mut self) -> Result<(), PuddlerError>
+  where
+    Self: Sized,
+  {
+    library_menu(self)
+  }
+
+  fn libraries(&mut self) -> Result<Vec<Library>, PuddlerError> {
+    get_libraries(self)
+  }
+
+  // `category` is "Genres", "Studios" or "Years".
+  fn library_categories(
+    &mut self,
+    library: &Library,
+    category: &str,
+  ) -> Result<Vec<Category>, PuddlerError> {
+    get_categories(self, library, category)
+  }
+
+  // One sorted page of the items behind a url of `libraries` or `library_categories`.
+  fn library_page(
+    &mut self,
+    url: &str,
+    sort: &SortOrder,
+    page: usize,
+  ) -> Result<Vec<MediaItem>, PuddlerError> {
+    let items = self.get_items(page_url(url, sort, page), false)?;
+    Ok(items.iter().map(MediaItem::from).collect())
+  }
+
+  // Where the content of a folder or collection is listed, `None` for everything that is played.
+  fn folder_url(&mut self, item: &MediaItem) -> Option<String> {
+    if !is_folder(item) {
+      return None;
+    }
+    let user = self.get_config_handle().get_active_user().unwrap();
+    Some(format!("Users/{}/Items?ParentId={}", user.user_id, item.id))
+  }
+
+  fn browse_folders(&mut self, library: &Library) -> Result<(), PuddlerError>
+  where
+    Self: Sized,
+  {
+    browse_items(self, library.name.clone(), library.folders_url.clone())
+  }
+
+  fn music_menu(&mut self) -> Result<(), PuddlerError> {
+    music_menu(self)
+  }
+
+  // Artists, albums and tracks that have been picked anywhere but the music menu.
+  fn play_music(&mut self, item: &MediaItem) -> Result<(), PuddlerError> {
+    process_music_item(self, item.clone())
+  }
+
+  // `None` lists the artists, an artist its albums and an album its tracks. A track leads to every
+  // track of its album.
+  fn music_children(&mut self, parent: Option<&MediaItem>) -> Result<Vec<MediaItem>, PuddlerError> {
+    let user = self.get_config_handle().get_active_user().unwrap();
+    let album_id = match parent {
+      None => {
+        let artists = self.get_items(
+          format!(
+            "Artists/AlbumArtists?UserId={}&SortBy=SortName&SortOrder=Ascending",
+            user.user_id
+          ),
+          false,
+        )?;
+        return Ok(artists.iter().map(MediaItem::from).collect());
       },
-      "Episode" => {
-        let series = self.resolve_series(item.clone());
-        let mut found = false;
-        for season in series.seasons {
-          if found {
-            playlist.extend(season.episodes);
-          } else {
-            for episode in season.episodes {
-              if found {
-                playlist.push(episode);
-              } else if episode.Id == item.Id {
-                found = true;
-                playlist.push(episode);
-              }
-            }
-          }
-        }
+      Some(artist) if artist.kind == MediaKind::Artist => {
+        let albums = self.get_items(
+          format!(
+            "Users/{}/Items?IncludeItemTypes=MusicAlbum&Recursive=true&AlbumArtistIds={}&SortBy=ProductionYear,SortName",
+            user.user_id, artist.id
+          ),
+          false,
+        )?;
+        return Ok(albums.iter().map(MediaItem::from).collect());
       },
-      "Series" | "Season" => {
-        let series = self.resolve_series(item);
-        playlist = self.choose_from_series(series);
+      Some(album) if album.kind == MediaKind::Album => album.id.clone(),
+      Some(track) => match self.get_item(track.id.clone())?.AlbumId {
+        Some(album_id) => album_id,
+        None => return Ok(vec![]),
       },
-      _ => (),
+    };
+    let tracks = self.get_items(
+      format!(
+        "Users/{}/Items?ParentId={}&IncludeItemTypes=Audio&Recursive=true&SortBy=ParentIndexNumber,IndexNumber,SortName",
+        user.user_id, album_id
+      ),
+      false,
+    )?;
+    Ok(tracks.iter().map(MediaItem::from).collect())
+  }
+
+  // Where a track is streamed from, `None` if the server has no source for it.
+  fn track_source(&mut self, track: &MediaItem) -> Result<Option<PlaybackSource>, PuddlerError> {
+    let user = self.get_config_handle().get_active_user().unwrap();
+    let response = self.get(format!(
+      "Items/{}/PlaybackInfo?UserId={}",
+      track.id, user.user_id
+    ))?;
+    let playback_info = serde_json::from_str::<PlaybackInfo>(&response.text()?)?;
+    let Some(media_source) = playback_info.MediaSources.first() else {
+      return Ok(None);
+    };
+    let headers = self.get_headers();
+    let auth_token = headers.get(2).unwrap().1.clone();
+    Ok(Some(PlaybackSource {
+      stream_url: format!(
+        "{}Audio/{}/stream?Static=true&MediaSourceId={}&api_key={}",
+        self.get_address(),
+        track.id,
+        media_source.Id,
+        auth_token
+      ),
+      external_subtitles: vec![],
+      playback_info: Some(serde_json::to_string(&playback_info).unwrap()),
+    }))
+  }
+
+  // The newest episodes and movies, ten of each.
+  fn latest_items(&mut self) -> Result<Vec<MediaItem>, PuddlerError> {
+    let items = self.get_latest_items()?;
+    Ok(items.iter().map(MediaItem::from).collect())
+  }
+
+  // The whole items, since the cli prints them as they are.
+  fn get_latest_items(&mut self) -> Result<Vec<Item>, PuddlerError> {
+    let user = self.get_config_handle().get_active_user().unwrap();
+    let mut items = self.get_items(
+      format!(
+        "Users/{}/Items/Latest?Limit=10&IncludeItemTypes=Episode",
+        user.user_id
+      ),
+      true,
+    )?;
+    items.extend(self.get_items(
+      format!(
+        "Users/{}/Items/Latest?Limit=10&IncludeItemTypes=Movie",
+        user.user_id
+      ),
+      true,
+    )?);
+    Ok(items)
+  }
+
+  // Jellyfin adds the next episodes of the series that are being watched.
+  fn get_resume_items(&mut self) -> Result<Vec<Item>, PuddlerError> {
+    let user = self.get_config_handle().get_active_user().unwrap();
+    let mut items = self.get_items(
+      format!(
+        "Users/{}/Items/Resume?Limit=15&MediaTypes=Video",
+        user.user_id
+      ),
+      false,
+    )?;
+    if self.get_config_handle().config.media_center_type == MediaCenterType::Jellyfin {
+      let mut next_up = self.get_items(format!("Shows/NextUp?UserId={}", user.user_id), false)?;
+      next_up.retain(|i| !items.contains(i));
+      items.extend(next_up);
+    }
+    Ok(items)
+  }
+
+  // `search` keeps the whole items for the cli, the menu only needs these.
+  fn search_items(
+    &mut self,
+    query: &SearchQuery,
+    page: usize,
+  ) -> Result<(Vec<MediaItem>, bool), PuddlerError> {
+    let (items, more) = self.search(query, page)?;
+    Ok((items.iter().map(MediaItem::from).collect(), more))
+  }
+
+  // The MediaItem only tells where to start, everything else is looked up again.
+  // Without anybody in front of the screen, the first file is played instead of asking for one.
+  fn play_media_item(
+    &mut self,
+    player: &mut Player,
+    item: &MediaItem,
+    transcoding_settings: &mut Option<(bool, Option<u32>, Option<u32>, String)>,
+    unattended: bool,
+  ) -> Result<Video, PuddlerError> {
+    let mut full_item = self.get_item(item.id.clone())?;
+    full_item.UserData.PlaybackPositionTicks = item.playback_position * 10000000;
+    if unattended && let Some(media_sources) = full_item.MediaSources.as_mut() {
+      media_sources.truncate(1);
     }
-    if playlist.is_empty() {
-      return;
+    self.play_item(player, full_item, transcoding_settings)
+  }
+
+  // Folders, series and the like can't be played directly (used by remote control).
+  fn get_playable_item(&mut self, item_id: &str) -> Result<MediaItem, PuddlerError> {
+    let item = self.get_item(item_id.to_string())?;
+    if item
+      .MediaSources
+      .as_ref()
+      .is_none_or(|sources| sources.is_empty())
+    {
+      return Err(PuddlerError::Unsupported(format!(
+        "\"{}\" can't be played.",
+        item.Name
+      )));
     }
+    Ok(MediaItem::from(&item))
+  }
+
+  fn get_playlists(&mut self) -> Result<Vec<MediaItem>, PuddlerError> {
+    let user = self.get_config_handle().get_active_user().unwrap();
+    let playlists = self.get_items(
+      format!(
+        "Users/{}/Items?IncludeItemTypes=Playlist&Recursive=true&SortBy=SortName",
+        user.user_id
+      ),
+      false,
+    )?;
+    Ok(
+      playlists
+        .into_iter()
+        .map(|playlist| MediaItem {
+          id: playlist.Id,
+          kind: MediaKind::Other(String::from("playlist")),
+          titles: vec![playlist.Name],
+          played: false,
+          playback_position: 0,
+          runtime: 0,
+        })
+        .collect(),
+    )
+  }
+
+  // Only what can be played by the normal player, music has its own queue.
+  fn get_playlist_items(&mut self, playlist_id: &str) -> Result<Vec<MediaItem>, PuddlerError> {
+    let user = self.get_config_handle().get_active_user().unwrap();
+    let items = self.get_items(
+      format!("Playlists/{}/Items?UserId={}", playlist_id, user.user_id),
+      false,
+    )?;
+    Ok(
+      items
+        .iter()
+        .filter(|item| item.Type == "Movie" || item.Type == "Episode")
+        .map(MediaItem::from)
+        .collect(),
+    )
+  }
+
+  // Emby doesn't read the body, query parameters work on both.
+  fn save_playlist(&mut self, name: &str, items: &[MediaItem]) -> Result<(), PuddlerError> {
+    let user = self.get_config_handle().get_active_user().unwrap();
+    self.post(
+      format!(
+        "Playlists?Name={}&Ids={}&UserId={}&MediaType=Video",
+        urlencoding::encode(name),
+        items
+          .iter()
+          .map(|item| item.id.clone())
+          .collect::<Vec<String>>()
+          .join(","),
+        user.user_id
+      ),
+      String::new(),
+    )?;
+    Ok(())
+  }
 
+  fn play_item(
+    &mut self,
+    player: &mut Player,
+    mut item: Item,
+    transcoding_settings: &mut Option<(bool, Option<u32>, Option<u32>, String)>,
+  ) -> Result<Video, PuddlerError> {
+    let playback_info = self.post_playbackinfo(&mut item, transcoding_settings)?;
+    self.insert_value(
+      MediaCenterValues::PlaybackInfo,
+      serde_json::to_string(&playback_info).unwrap(),
+    );
+    let segments = self.get_segments(&item.Id);
+    let (chapters, trickplay) = self.get_chapters(&item, &playback_info.MediaSources[0].Id);
+    let source = self.playback_source(&item, &playback_info);
+    self.update_player(player);
+    player.set_video(&MediaItem::from(&item), source, transcoding_settings);
+    player.set_timeline(segments, chapters, trickplay);
+    player.play()
+  }
+
+  // Transcodes come with their own url, everything else is streamed as it is.
+  fn playback_source(&mut self, item: &Item, playback_info: &PlaybackInfo) -> PlaybackSource {
     let headers = self.get_headers();
     let auth_token = &headers.get(2).unwrap().1;
     let server_address = self.get_address();
+    let transcoding = self.get_config_handle().config.transcoding;
+    let media_source = playback_info.MediaSources.first().unwrap();
+    let stream_url = if let Some(transcoding_url) = &media_source.TranscodingUrl {
+      format!(
+        "{}{}",
+        server_address,
+        transcoding_url.trim_start_matches('/')
+      )
+    } else if item.Type == "Audio" {
+      format!(
+        "{}Audio/{}/stream?Static=true&MediaSourceId={}&api_key={}",
+        server_address, item.Id, media_source.Id, auth_token
+      )
+    } else {
+      format!(
+        "{}Videos/{}/stream?Container=mkv&Static=true&api_key={}",
+        server_address, media_source.Id, auth_token
+      )
+    };
 
-    let settings = self.get_settings().clone();
-    let mut player = Player::new(self.get_config_handle().clone(), settings.clone());
-
-    let mut transcoding_settings = None;
-    let mut index = 0;
-    let mut stdout = stdout();
-    while index < playlist.len() {
-      let item = playlist[index].clone();
-      let mut next_index = index + 1;
-      let mut streamable_item = item.clone();
-      if let Ok(playback_info) =
-        self.post_playbackinfo(&mut streamable_item, &mut transcoding_settings)
-      {
-        self.insert_value(
-          MediaCenterValues::PlaybackInfo,
-          serde_json::to_string(&playback_info).unwrap(),
-        );
-        self.update_player(&mut player);
-        player.set_jellyfin_video(
-          streamable_item,
-          playback_info,
-          server_address.clone(),
-          auth_token.to_string(),
-          &mut transcoding_settings,
+    let mut external_subtitles: Vec<[String; 4]> = vec![];
+    for (index, stream) in media_source.MediaStreams.iter().enumerate() {
+      if stream.IsExternal && stream.SupportsExternalStream {
+        let extension = if let Some(path) = &stream.Path {
+          path.split('.').next_back().unwrap().to_string()
+        } else {
+          stream.Codec.as_ref().unwrap().to_owned()
+        };
+        let mut media_url = format!(
+          "{}Videos/{}/{}/Subtitles/{}/Stream.{}?api_key={}",
+          server_address, item.Id, media_source.Id, index, extension, auth_token
         );
-        let ret = player.play();
-        if let Some((_, audio, subtitle, ..)) = transcoding_settings.as_mut() {
-          *audio = ret.preferred_audio_track;
-          *subtitle = ret.preferred_subtitle_track
-        }
-        'playback_done: loop {
-          let mut options: Vec<InteractiveOption> = vec![];
-          execute!(stdout, DisableLineWrap).unwrap();
-          if !ret.played {
-            if let Ok(updated_item) = self.get_item(item.Id.clone()) {
-              playlist[index] = updated_item;
-            } else {
-              print_message(
-                PrintMessageType::Error,
-                format!(
-                  "Failed to get updated information for {}.",
-                  item.to_string()
-                )
-                .as_str(),
-              )
-            }
-            options.append(&mut vec![
-              InteractiveOption {
-                text: format!("Finish: {}", item.to_string_ext()),
-                option_type: InteractiveOptionType::Button,
-              },
-              InteractiveOption {
-                text: format!("Mark as played: {}", item.to_string_ext()),
-                option_type: InteractiveOptionType::Button,
-              },
-            ]);
-          }
-          while let Some(next_item) = playlist.get(index + 1) {
-            // skip every item that has been played already
-            // (might want to use unmark in the menu before watching a series again)
-            if !next_item.UserData.Played {
-              options.push(InteractiveOption {
-                text: format!("Continue with: {}", next_item.to_string_ext()),
-                option_type: InteractiveOptionType::Button5s,
-              });
-              break;
-            }
-            next_index += 1;
-          }
-          if options.is_empty() {
-            print_message(
-              PrintMessageType::Warning,
-              "Playlist done. Returning to menu.",
-            );
-            return;
-          }
-          options.append(&mut vec![
-            InteractiveOption {
-              text: "Back to Menu".to_string(),
-              option_type: InteractiveOptionType::Special,
-            },
-            InteractiveOption {
-              text: "Exit Application".to_string(),
-              option_type: InteractiveOptionType::Special,
-            },
-          ]);
-          match interactive_select(options) {
-            ((_, _), Some(text), InteractiveOptionType::Button) => {
-              if text.starts_with("Finish") {
-                transcoding_settings.as_mut().unwrap().0 = true;
-                break 'playback_done;
-              } else if text.starts_with("Mark") {
-                self.item_set_playstate(item.Id.clone(), true);
-                continue 'playback_done;
-              } else if text.starts_with("Continue") {
-                index = next_index;
-                break 'playback_done;
-              }
-            },
-            ((_, _), Some(text), InteractiveOptionType::Special) => match text.as_str() {
-              "Back to Menu" => {
-                execute!(stdout, EnableLineWrap).unwrap();
-                return;
-              },
-              _ => {
-                execute!(stdout, EnableLineWrap).unwrap();
-                exit(0)
-              },
-            },
-            _ => (),
-          }
-          execute!(stdout, EnableLineWrap).unwrap();
+        if item.UserData.PlaybackPositionTicks != 0 && transcoding {
+          media_url +=
+            &("&StartPositionTicks=".to_owned() + &item.UserData.PlaybackPositionTicks.to_string());
         }
+        let language = if let Some(language) = stream.Language.clone() {
+          language
+        } else {
+          String::from("und")
+        };
+        let title = if let Some(title) = stream.DisplayTitle.clone() {
+          title
+        } else {
+          String::from("Undefined")
+        };
+        let formatted_title = format!(r#""{}""#, title);
+        external_subtitles.push([media_url, "auto".to_string(), formatted_title, language]);
       }
     }
+
+    PlaybackSource {
+      stream_url,
+      external_subtitles,
+      playback_info: None,
+    }
   }
 
-  fn update_player(&mut self, player: &mut Player);
+  fn download_items(&mut self, items: Vec<MediaItem>) -> Result<(), PuddlerError> {
+    let mut library = OfflineLibrary::open()?;
+    for item in items {
+      let download = self.create_download(item.id)?;
+      library.download(self.get_config_handle(), download)?;
+    }
+    Ok(())
+  }
 
-  fn post_playbackinfo(
-    &mut self,
+  // Always the original file (static stream) of the first media-source, never a transcode.
+  fn create_download(&mut self, item_id: String) -> Result<Download, PuddlerError> {
+    let item = self.get_item(item_id)?;
+    let headers = self.get_headers();
+    let auth_token = &headers.get(2).unwrap().1;
+    let server_address = self.get_address();
+    let media_source = if let Some(media_source) = item
+      .MediaSources
+      .as_ref()
+      .and_then(|media_sources| media_sources.first())
+    {
+      media_source
+    } else {
+      return Err(PuddlerError::Unsupported(format!(
+        "\"{}\" doesn't have any downloadable files.",
+        item.Name
+      )));
+    };
+    let extension = media_source
+      .Path
+      .as_ref()
+      .and_then(|path| path.rsplit_once('.'))
+      .map(|(_, extension)| extension.to_string())
+      .unwrap_or(String::from("mkv"));
+
+    let mut subtitles: Vec<DownloadSubtitle> = vec![];
+    for stream in &media_source.MediaStreams {
+      if stream.IsExternal && stream.SupportsExternalStream {
+        let extension = if let Some(path) = &stream.Path {
+          path.rsplit('.').next().unwrap().to_string()
+        } else {
+          stream.Codec.clone().unwrap_or(String::from("srt"))
+        };
+        subtitles.push(DownloadSubtitle {
+          url: format!(
+            "{}Videos/{}/{}/Subtitles/{}/Stream.{}?api_key={}",
+            server_address, item.Id, media_source.Id, stream.Index, extension, auth_token
+          ),
+          extension,
+          title: stream
+            .DisplayTitle
+            .clone()
+            .unwrap_or(String::from("Undefined")),
+          language: stream.Language.clone().unwrap_or(String::from("und")),
+        });
+      }
+    }
+
+    Ok(Download {
+      url: format!(
+        "{}Videos/{}/stream?Static=true&MediaSourceId={}&api_key={}",
+        server_address, item.Id, media_source.Id, auth_token
+      ),
+      extension,
+      subtitles,
+      title: item.to_string_split(),
+      movie: item.Type == "Movie",
+      total_runtime: item.RunTimeTicks.unwrap_or(0) / 10000000,
+      playback_position: item.UserData.PlaybackPositionTicks / 10000000,
+      played: item.UserData.Played,
+      item_id: item.Id,
+    })
+  }
+
+  // Used by the cli. Plays exactly one item without asking for anything.
+  fn play_from_id(&mut self, item_id: String) -> Result<(), PuddlerError> {
+    if self.get_config_handle().config.transcoding {
+      return Err(PuddlerError::Unsupported(String::from(
+        "Transcoding requires user input. Please disable it or use the interactive menu.",
+      )));
+    }
+    let mut item = self.get_item(item_id)?;
+    if item.Type != "Movie" && item.Type != "Episode" {
+      return Err(PuddlerError::Unsupported(format!(
+        "\"{}\" is a {} and cannot be played directly.",
+        item.Name, item.Type
+      )));
+    }
+    // Don't ask which file should be played.
+    if let Some(media_sources) = item.MediaSources.as_mut() {
+      media_sources.truncate(1);
+    }
+    let settings = self.get_settings().clone();
+    let mut player = Player::new(self.get_config_handle().clone(), settings);
+    self.play_item(&mut player, item, &mut None)?;
+    Ok(())
+  }
+
+  // Plex has its own, since it isn't controlled through a websocket.
+  fn receiver(&mut self) -> Result<(), PuddlerError> {
+    run_receiver(self)
+  }
+
+  fn print_listing(&mut self, listing: Listing, output: OutputFormat) -> Result<(), PuddlerError> {
+    let user = self.get_config_handle().get_active_user().unwrap();
+    let items = match listing {
+      Listing::Search(term) => self.search(&parse_search(&term)?, 0)?.0,
+      Listing::Resume => self.get_resume_items()?,
+      Listing::NextUp => self.get_items(format!("Shows/NextUp?UserId={}", user.user_id), false)?,
+      Listing::Latest => self.get_latest_items()?,
+    };
+    print_items(&items, output);
+    Ok(())
+  }
+
+  // Returns one page of results and whether there are more.
+  fn search(
+    &mut self,
+    query: &SearchQuery,
+    page: usize,
+  ) -> Result<(Vec<Item>, bool), PuddlerError> {
+    let user = self.get_config_handle().get_active_user().unwrap();
+    let items = self.get_items(query.jellyfin_url(&user.user_id, page), false)?;
+    let more = items.len() == PAGE_SIZE;
+    Ok((items, more))
+  }
+
+  fn update_player(&mut self, player: &mut Player);
+
+  // Only Jellyfin 10.10+ knows about them, everything else (Emby included) just plays without.
+  fn get_segments(&mut self, item_id: &str) -> Vec<Segment> {
+    self
+      .get(format!(
+        "MediaSegments/{}?includeSegmentTypes=Intro,Outro,Recap,Preview,Commercial",
+        item_id
+      ))
+      .and_then(|response| Ok(response.text()?))
+      .and_then(|text| parse_media_segments(&text))
+      .unwrap_or_default()
+  }
+
+  // Chapters of the item, and trickplay tiles of the media-source that's about to be played.
+  // `get_item` asks for both already.
+  fn get_chapters(
+    &mut self,
+    item: &Item,
+    media_source_id: &str,
+  ) -> (Vec<Chapter>, Option<Trickplay>) {
+    let chapters: Vec<Chapter> = item
+      .Chapters
+      .clone()
+      .unwrap_or_default()
+      .into_iter()
+      .enumerate()
+      .map(|(index, chapter)| Chapter {
+        title: chapter.Name.unwrap_or(format!("Chapter {}", index + 1)),
+        start: chapter.StartPositionTicks as f64 / 10000000.0,
+      })
+      .collect();
+    let headers = self.get_headers();
+    let auth_token = &headers.get(2).unwrap().1;
+    // the smallest thumbnails are plenty for a preview
+    let trickplay = item
+      .Trickplay
+      .as_ref()
+      .and_then(|sources| sources.get(media_source_id).cloned())
+      .and_then(|sizes| sizes.into_values().min_by_key(|info| info.Width))
+      .map(|info| Trickplay {
+        url: format!(
+          "{}Videos/{}/Trickplay/{}/%d.jpg?MediaSourceId={}&api_key={}",
+          self.get_address(),
+          item.Id,
+          info.Width,
+          media_source_id,
+          auth_token
+        ),
+        width: info.Width,
+        height: info.Height,
+        tile_width: info.TileWidth,
+        tile_height: info.TileHeight,
+        thumbnail_count: info.ThumbnailCount,
+        interval: info.Interval,
+      });
+    (chapters, trickplay)
+  }
+
+  fn post_playbackinfo(
+    &mut self,
     item: &mut Item,
     previous_settings: &mut Option<(bool, Option<u32>, Option<u32>, String)>,
-  ) -> Result<PlaybackInfo, ()> {
+  ) -> Result<PlaybackInfo, PuddlerError> {
     let mut handle = self.get_config_handle().clone();
     let user_id = handle.get_active_user().unwrap().user_id;
     let mut stdout = stdout();
@@ -982,7 +1402,10 @@ pub trait MediaCenter: Send {
     let mediasource_list: Vec<MediaSourceInfo> = if let Some(mediasources) = &item.MediaSources {
       mediasources.to_vec()
     } else {
-      return Err(());
+      return Err(PuddlerError::Unsupported(format!(
+        "\"{}\" doesn't have any playable files.",
+        item.Name
+      )));
     };
 
     // This is the only setting which isn't saved across the playlist. Don't really see the point in that tbh.
@@ -1017,7 +1440,7 @@ pub trait MediaCenter: Send {
       let url = format!("Users/{}", user_id);
       match self.get(url) {
         Ok(res) => {
-          let user = serde_json::from_str::<UserDto>(&res.text().unwrap()).unwrap();
+          let user = serde_json::from_str::<UserDto>(&res.text()?)?;
           let mut audio_streams: Vec<MediaStream> = vec![];
           let mut subtitle_streams: Vec<MediaStream> = vec![];
           let audio_language = if let Some(pref) = user.Configuration.AudioLanguagePreference {
@@ -1077,7 +1500,7 @@ pub trait MediaCenter: Send {
         Err(err) => {
           print_message(
             PrintMessageType::Error,
-            format!("Failed to get user prefences: {}", err.status()).as_str(),
+            format!("Failed to get user prefences: {}", err).as_str(),
           );
         },
       }
@@ -1185,15 +1608,11 @@ pub trait MediaCenter: Send {
       println!();
       let media_source = &mediasource_list[mediasource_index];
       if !media_source.SupportsTranscoding {
-        print_message(
-          PrintMessageType::Error,
-          format!(
-            "MediaSource \"{}\" does not support transcoding. Trying next ...",
-            media_source.Id
-          )
-          .as_str(),
-        );
-        exit(1);
+        execute!(stdout, LeaveAlternateScreen).unwrap();
+        return Err(PuddlerError::Unsupported(format!(
+          "MediaSource \"{}\" does not support transcoding.",
+          media_source.Id
+        )));
       }
       let mut audio_tracks: Vec<MediaStream> = vec![];
       let mut subtitle_tracks: Vec<MediaStream> = vec![];
@@ -1361,269 +1780,113 @@ pub trait MediaCenter: Send {
       };
 
       let url = format!("Items/{}/PlaybackInfo?UserId={}", item.Id, user_id);
-      match self.post(url, serde_json::to_string(&session_capabilities).unwrap()) {
-        Ok(res) => {
-          let search_text: &String = &res.text().unwrap();
-          Ok(serde_json::from_str::<PlaybackInfo>(search_text).unwrap())
-        },
-        Err(err) => {
-          print_message(
-            PrintMessageType::Error,
-            format!("Failed to post playback information: {}", err).as_str(),
-          );
-          Err(())
-        },
-      }
+      let res = self.post(url, serde_json::to_string(&session_capabilities).unwrap())?;
+      Ok(serde_json::from_str::<PlaybackInfo>(&res.text()?)?)
     } else {
       let url = format!("Items/{}/PlaybackInfo?UserId={}", item.Id, user_id);
-      match self.get(url) {
-        Ok(res) => {
-          let search_text: &String = &res.text().unwrap();
-          // Jellyfin/Emby have all Mediasources in the response. We have to make it so that only the selected one from before is inside.
-          let mut playbackinfo = serde_json::from_str::<PlaybackInfo>(search_text).unwrap();
-          playbackinfo.MediaSources = vec![playbackinfo.MediaSources[mediasource_index].clone()];
-          Ok(playbackinfo)
-        },
-        Err(err) => {
-          print_message(
-            PrintMessageType::Error,
-            format!(
-              "Failed to post playback information: {}",
-              err.text().unwrap()
-            )
-            .as_str(),
-          );
-          Err(())
-        },
+      let res = self.get(url)?;
+      // Jellyfin/Emby have all Mediasources in the response. We have to make it so that only the selected one from before is inside.
+      let mut playbackinfo = serde_json::from_str::<PlaybackInfo>(&res.text()?)?;
+      if let Some(media_source) = playbackinfo.MediaSources.get(mediasource_index) {
+        playbackinfo.MediaSources = vec![media_source.clone()];
+        Ok(playbackinfo)
+      } else {
+        Err(PuddlerError::ServerApi(
+          StatusCode::OK,
+          String::from("The selected MediaSource is missing from the PlaybackInfo."),
+        ))
       }
     }
   }
 
-  fn series_set_playstate(&mut self, series: Series, indexes: Vec<usize>, played: bool) {
-    let mut index = 0;
-    for season in series.seasons {
-      for episode in season.episodes {
-        if indexes.contains(&index) {
-          self.item_set_playstate(episode.Id, played);
-        }
-        index += 1;
-      }
-    }
-  }
+  // Everything below is what `Backend` needs (see backend.rs).
 
-  fn item_set_playstate(&mut self, item_id: String, played: bool) {
-    let status_str = if played { "Played" } else { "Un-Played" };
-    let current_time = chrono::Local::now();
-    let format_string = "%Y%m%d%H%M%S";
-    let formatted_time = current_time.format(format_string);
+  // `None` lists the libraries.
+  fn browse(&mut self, parent: Option<&MediaItem>) -> Result<Vec<MediaItem>, PuddlerError> {
     let user = self.get_config_handle().get_active_user().unwrap();
-    let url = format!(
-      "Users/{}/PlayedItems/{}?DatePlayed={}",
-      user.user_id, item_id, formatted_time
-    );
-    let req = if played {
-      self.post(url, String::new())
+    let url = if let Some(parent) = parent {
+      format!("Users/{}/Items?ParentId={}", user.user_id, parent.id)
     } else {
-      self.delete(url, String::new())
+      format!("Users/{}/Views", user.user_id)
     };
-    if let Err(err) = req {
-      print_message(
-        PrintMessageType::Error,
-        format!("Failed to mark item as {}: {}", status_str, err).as_str(),
-      );
-    }
+    let items = self.get_items(url, false)?;
+    Ok(items.iter().map(MediaItem::from).collect())
   }
 
-  fn choose_from_series(&mut self, mut series: Series) -> Vec<Item> {
-    loop {
-      let mut selection: usize;
-      match jelly_series_select(self.generate_series_structure(&series), series.clone()) {
-        (SeriesOptions::Back, _) => {
-          return vec![];
-        },
-        (SeriesOptions::Play, Some(index)) => {
-          selection = index[0];
-        },
-        (SeriesOptions::Played, Some(indexes)) => {
-          self.series_set_playstate(series.clone(), indexes, true);
-          series = self.resolve_series(series.seasons[0].episodes[0].clone());
-          continue;
-        },
-        (SeriesOptions::UnPlayed, Some(indexes)) => {
-          self.series_set_playstate(series.clone(), indexes, false);
-          series = self.resolve_series(series.seasons[0].episodes[0].clone());
-          continue;
-        },
-        _ => panic!("What?!"),
-      }
-      let mut items: Vec<Item> = vec![];
-      let mut record = false;
-      for season in series.seasons.clone() {
-        if record {
-          items.extend(season.episodes);
-        } else if season.episodes.len() > selection {
-          for episode in season.episodes {
-            if record {
-              items.push(episode.clone());
-            } else if selection == 0 {
-              record = true;
-              items.push(episode.clone());
-            } else {
-              selection -= 1;
-            }
-          }
-        } else {
-          selection -= season.episodes.len();
-        }
-      }
-      return items;
-    }
+  fn resume(&mut self) -> Result<Vec<MediaItem>, PuddlerError> {
+    let items = self.get_resume_items()?;
+    Ok(items.iter().map(MediaItem::from).collect())
   }
 
-  fn generate_series_structure(&mut self, series: &Series) -> Vec<String> {
-    let mut trans_item = series.seasons[0].item.clone();
-    trans_item.Type = String::from("Series");
-    trans_item.Name = trans_item.clone().SeriesName.unwrap_or("???".to_string());
-
-    let full_size = {
-      let mut size = 0;
-      series.seasons.iter().for_each(|s| size += s.episodes.len());
-      size
-    };
-    let zero_pad_amount = (full_size as f64).log10().floor() as usize + 1;
-    let mut just_text: Vec<String> = vec![format!(" {}", trans_item.to_string().bold())];
-    let mut index = 0;
-    for (season_index, season) in series.seasons.iter().enumerate() {
-      let mut line: String = String::new();
-      if season_index == series.seasons.len() - 1 {
-        line.push_str("  └─ ");
-      } else {
-        line.push_str("  ├─ ");
-      }
-      line.push_str(format!("{}", season.item.Name.clone().bold()).as_str());
-      just_text.push(line.clone());
-      line.clear();
-      for (episode_index, episode) in season.clone().episodes.iter().enumerate() {
-        let prefix: &str = if season_index == series.seasons.len() - 1 {
-          "       "
-        } else {
-          "  │    "
-        };
-        if episode_index == season.episodes.len() - 1 {
-          line.push_str(format!("{}└── ", prefix).as_str());
-        } else {
-          line.push_str(format!("{}├── ", prefix).as_str());
-        }
-        line.push_str(format!("[{:0zero_pad_amount$}] ", index).as_str());
-        let terminal_size = terminal::size().unwrap().0 as usize;
-        if 13 + zero_pad_amount + index.to_string().len() + episode.to_string_ext().len()
-          > terminal_size
-        {
-          line.push_str(format!("{}...", episode.to_string_ext()).as_str());
-          just_text.push(line.clone());
-          line.clear();
-        } else {
-          line.push_str(episode.to_string_ext().as_str());
-          just_text.push(line.clone());
-          line.clear();
-        }
-        index += 1;
-      }
+  // Direct-play only, like `play_from_id`.
+  fn resolve_stream(&mut self, item_id: &str) -> Result<PlaybackSource, PuddlerError> {
+    if self.get_config_handle().config.transcoding {
+      return Err(PuddlerError::Unsupported(String::from(
+        "Transcoding requires user input. Please disable it or use the interactive menu.",
+      )));
     }
-    just_text
+    let item = self.get_item(item_id.to_string())?;
+    let media_source = match item
+      .MediaSources
+      .as_ref()
+      .and_then(|sources| sources.first())
+    {
+      Some(media_source) => media_source.clone(),
+      None => {
+        return Err(PuddlerError::Unsupported(format!(
+          "\"{}\" doesn't have any playable files.",
+          item.Name
+        )));
+      },
+    };
+    let playback_info = PlaybackInfo {
+      MediaSources: vec![media_source],
+      PlaySessionId: String::new(),
+    };
+    Ok(self.playback_source(&item, &playback_info))
   }
 
-  fn resolve_series(&mut self, item: Item) -> Series {
-    if item.Type != "Season" && item.Type != "Episode" && item.Type != "Series" {
-      panic!("This object cannot be part of a series.");
-    }
+  fn set_playstate(&mut self, item_id: &str, played: bool) -> Result<(), PuddlerError> {
+    let current_time = chrono::Local::now();
+    let format_string = "%Y%m%d%H%M%S";
+    let formatted_time = current_time.format(format_string);
     let user = self.get_config_handle().get_active_user().unwrap();
-
-    let mut series = Series {
-      item_id: item.SeriesId.clone().unwrap_or(item.Id),
-      seasons: vec![],
-    };
-
-    if let Ok(items) = self.get_items(
-      format!("Users/{}/Items?ParentId={}", user.user_id, series.item_id),
-      false,
-    ) {
-      for season in items {
-        series.seasons.append(&mut vec![Season {
-          item: season,
-          episodes: vec![],
-        }]);
-      }
+    let url = format!(
+      "Users/{}/PlayedItems/{}?DatePlayed={}",
+      user.user_id, item_id, formatted_time
+    );
+    if played {
+      self.post(url, String::new())?;
     } else {
-      exit(1);
-    };
-
-    if series.seasons[0].item.Name == *"Specials" {
-      let specials = series.seasons.remove(0);
-      series.seasons.push(specials);
+      self.delete(url, String::new())?;
     }
+    Ok(())
+  }
 
-    let mut episode_ids: Vec<String> = vec![];
-    for (season_index, season) in series.seasons.clone().iter().enumerate() {
-      if let Ok(items) = self.get_items(
-        format!("Users/{}/Items?ParentId={}", user.user_id, season.item.Id),
-        false,
-      ) {
-        for episode in items {
-          if !episode_ids.contains(&episode.Id) {
-            episode_ids.push(episode.Id.clone());
-            series.seasons[season_index]
-              .episodes
-              .append(&mut vec![episode]);
-          }
-        }
-
-        if series.seasons[season_index].episodes.is_empty() {
-          series.seasons.remove(season_index);
-        }
-      } else {
-        exit(1);
-      };
+  // Episodes and seasons lead to their series, a series to itself.
+  fn series_of(&mut self, item_id: &str) -> Result<Option<MediaItem>, PuddlerError> {
+    let item = self.get_item(item_id.to_string())?;
+    match (item.Type.as_str(), &item.SeriesId) {
+      ("Series", _) => Ok(Some(MediaItem::from(&item))),
+      ("Season" | "Episode", Some(series_id)) => {
+        let series = self.get_item(series_id.clone())?;
+        Ok(Some(MediaItem::from(&series)))
+      },
+      _ => Ok(None),
     }
-
-    series
   }
 
-  fn get_item(&mut self, item_id: String) -> Result<Item, ()> {
+  fn get_item(&mut self, item_id: String) -> Result<Item, PuddlerError> {
     let url = format!(
-      "Users/{}/Items/{}?Fields=PremiereDate,MediaSources,Status,ProductionYear&collapseBoxSetItems=False&IsMissing=False",
+      "Users/{}/Items/{}?Fields=PremiereDate,MediaSources,Status,ProductionYear,Chapters,Trickplay&collapseBoxSetItems=False&IsMissing=False",
       self.get_config_handle().get_active_user().unwrap().user_id,
       item_id
     );
-    match self.get(url.clone()) {
-      Ok(result) => {
-        if let Ok(json) = serde_json::from_str::<Value>(&result.text().unwrap()) {
-          if let Ok(item_list) = serde_json::from_value::<Item>(json) {
-            return Ok(item_list);
-          } else {
-            print_message(
-              PrintMessageType::Error,
-              "Failed to serialize the json response into an item.",
-            );
-          }
-        } else {
-          print_message(
-            PrintMessageType::Error,
-            "Failed to convert response into json.",
-          );
-        }
-      },
-      Err(e) => {
-        print_message(
-          PrintMessageType::Error,
-          format!("Failed to get item at \"{}\"\n{}\n", url, e.text().unwrap()).as_str(),
-        );
-      },
-    }
-    Err(())
+    let result = self.get(url)?;
+    Ok(serde_json::from_str::<Item>(&result.text()?)?)
   }
 
-  fn get_items(&mut self, mut url: String, raw: bool) -> Result<Vec<Item>, ()> {
+  fn get_items(&mut self, mut url: String, raw: bool) -> Result<Vec<Item>, PuddlerError> {
     if !url.contains('?') {
       url.push('?')
     } else if !url.ends_with('&') {
@@ -1633,40 +1896,12 @@ pub trait MediaCenter: Send {
       "{}Fields=PremiereDate,MediaSources,Status,ProductionYear&collapseBoxSetItems=False&IsMissing=False",
       url
     );
-    match self.get(modded_url.clone()) {
-      Ok(result) => {
-        if let Ok(mut json) = serde_json::from_str::<Value>(&result.text().unwrap()) {
-          if !raw {
-            json = json.get("Items").unwrap().clone();
-          }
-          if let Ok(item_list) = serde_json::from_value::<Vec<Item>>(json) {
-            return Ok(item_list);
-          } else {
-            print_message(
-              PrintMessageType::Error,
-              "Failed to serialize the json response into an item list.",
-            );
-          }
-        } else {
-          print_message(
-            PrintMessageType::Error,
-            "Failed to convert response into json.",
-          );
-        }
-      },
-      Err(e) => {
-        print_message(
-          PrintMessageType::Error,
-          format!(
-            "Failed to get item list at \"{}\"\n{}\n",
-            url,
-            e.text().unwrap()
-          )
-          .as_str(),
-        );
-      },
+    let result = self.get(modded_url)?;
+    let mut json = serde_json::from_str::<Value>(&result.text()?)?;
+    if !raw {
+      json = json["Items"].take();
     }
-    Err(())
+    Ok(serde_json::from_value::<Vec<Item>>(json)?)
   }
 
   async fn stop_playback(
@@ -1769,6 +2004,30 @@ pub trait MediaCenter: Send {
     }
   }
 
+  // No questions or messages for music, the server decides on its own whether a track has been played.
+  async fn stop_track_playback(&mut self, item_id: String, time_pos: f64) {
+    let playback_info = self.get_playback_info();
+    let session_id = self.get_session_id().expect("This shouldn't be a None!");
+    let finished_obj = PlaybackStopInfo {
+      ItemId: item_id,
+      PlaySessionId: playback_info.PlaySessionId.to_string(),
+      SessionId: session_id,
+      MediaSourceId: playback_info.MediaSources[0].Id.to_string(),
+      PositionTicks: ((time_pos * 10000000.0).round() as u64).to_string(),
+      Failed: false,
+    };
+    let url = "Sessions/Playing/Stopped".to_string();
+    if let Err(err) = self
+      .async_post(url, serde_json::to_string(&finished_obj).unwrap())
+      .await
+    {
+      print_message(
+        PrintMessageType::Error,
+        format!("Failed to log playback progress to your server: {}", err).as_str(),
+      );
+    }
+  }
+
   fn get_playback_info(&mut self) -> PlaybackInfo;
 
   async fn report_playback(
@@ -1935,9 +2194,17 @@ pub trait MediaCenter: Send {
     UserCredentials { username, password }
   }
 
-  fn re_authenticate(&mut self) {
+  fn re_authenticate(&mut self) -> Result<(), PuddlerError> {
+    if !self.restore_session() {
+      self.login();
+    }
+    Ok(())
+  }
+
+  // Tries to continue with the active user. Returns false if a new login is required.
+  fn restore_session(&mut self) -> bool {
     if let Some(user) = self.get_config_handle().get_active_user() {
-      print!(
+      eprint!(
         "Logging in with {} on {} ",
         user.username.cyan(),
         self.get_config_handle().config.server_name.clone().cyan()
@@ -1948,24 +2215,30 @@ pub trait MediaCenter: Send {
         self.write_headers();
         match self.get(url) {
           Ok(response) => {
-            let json_response = serde_json::from_str::<Value>(&response.text().unwrap()).unwrap();
-            if let Some(id) = json_response[0].get("Id") {
-              println!("{}\n", "🗸".green());
-              self.insert_value(
-                MediaCenterValues::SessionID,
-                id.as_str().unwrap().to_string(),
-              );
+            let json_response = response
+              .text()
+              .ok()
+              .and_then(|text| serde_json::from_str::<Value>(&text).ok())
+              .unwrap_or_default();
+            if let Some(id) = json_response[0].get("Id").and_then(|id| id.as_str()) {
+              eprintln!("{}\n", "🗸".green());
+              self.insert_value(MediaCenterValues::SessionID, id.to_string());
               if let Some(support) = json_response[0].get("SupportedCommands") {
                 if !support.to_string().contains("PlayState") {
                   // yea that should be sufficient
-                  self.report_session_capabilities().unwrap();
+                  if let Err(err) = self.report_session_capabilities() {
+                    print_message(
+                      PrintMessageType::Error,
+                      format!("Failed to post remote control support: {}", err).as_str(),
+                    );
+                  }
                 }
               }
-              return;
+              return true;
             } else if self.report_session_capabilities().is_ok() {
               continue;
             } else {
-              println!("{}", "𐄂".red());
+              eprintln!("{}", "𐄂".red());
               print_message(
                 PrintMessageType::Error,
                 "Creating a new session failed. Please login again.",
@@ -1973,26 +2246,23 @@ pub trait MediaCenter: Send {
               self.get_config_handle().remove_user(user.access_token);
             }
           },
+          Err(PuddlerError::Auth(status)) => {
+            eprintln!("{}", "𐄂".red());
+            print_message(
+              PrintMessageType::Error,
+              format!("{}: This session expired. Please login again.", status).as_str(),
+            );
+            self.get_config_handle().remove_user(user.access_token);
+          },
           Err(e) => {
-            println!("{}", "𐄂".red());
-            if e.status() == StatusCode::UNAUTHORIZED {
-              print_message(
-                PrintMessageType::Error,
-                format!("{}: This session expired. Please login again.", e.status()).as_str(),
-              );
-              self.get_config_handle().remove_user(user.access_token);
-            } else {
-              print_message(
-                PrintMessageType::Error,
-                format!("{}: {}", e.status(), e.text().unwrap()).as_str(),
-              );
-            }
+            eprintln!("{}", "𐄂".red());
+            print_message(PrintMessageType::Error, &e.to_string());
           },
         }
         break;
       }
     }
-    self.login();
+    false
   }
 
   fn write_headers(&mut self) {
@@ -2032,68 +2302,82 @@ pub trait MediaCenter: Send {
 
   fn login(&mut self) {
     loop {
-      let url = "Users/AuthenticateByName".to_string();
       let server_name = self.get_config_handle().config.server_name.clone();
       let creds = self.create_user_credentials();
-      let body = format!(
-        "{{\"Username\":\"{}\",\"pw\":\"{}\"}}",
-        creds.username, creds.password
-      );
       print!(
         "Logging in with {} on {} ",
         creds.username.clone().cyan(),
         server_name.clone().cyan()
       );
-      match self.post(url.clone(), body.clone()) {
-        Ok(res) => {
+      match self.authenticate(creds) {
+        Ok(()) => {
           println!("{}", "🗸".green());
-          let json_response = serde_json::from_str::<Value>(&res.text().unwrap()).unwrap();
-          let session_obj = json_response.get("SessionInfo").unwrap();
-          let user = UserConfig {
-            access_token: json_response["AccessToken"].as_str().unwrap().to_string(),
-            username: session_obj["UserName"].as_str().unwrap().to_string(),
-            user_id: session_obj["UserId"].as_str().unwrap().to_string(),
-          };
-          let device_id = session_obj["DeviceId"].as_str().unwrap().to_string();
-          let config = self.get_config_handle();
-          config.insert_specific_value(Objective::DeviceID, device_id);
-          config.insert_specific_value(Objective::User, serde_json::to_string(&user).unwrap());
-          config.set_active_user(user.access_token);
-          config.save();
-          self.write_headers();
-          let session_id = session_obj["Id"].as_str().unwrap().to_string();
-          self.insert_value(MediaCenterValues::SessionID, session_id);
+          self.get_config_handle().save();
           break;
         },
+        Err(PuddlerError::Network(err)) => {
+          println!("{}", "𐄂".red());
+          print_message(
+            PrintMessageType::Error,
+            format!("Failed to send login request: {}", err).as_str(),
+          );
+          let config = self.get_config_handle();
+          config.ask_for_setting(Objective::ServerName);
+          config.ask_for_setting(Objective::Address);
+        },
         Err(e) => {
           println!("{}", "𐄂".red());
-          match e.as_str() {
-            "Failed to send request" => {
-              print_message(PrintMessageType::Error, "Failed to send login request.");
-              let config = self.get_config_handle();
-              config.ask_for_setting(Objective::ServerName);
-              config.ask_for_setting(Objective::Address);
-            },
-            _ => {
-              print_message(PrintMessageType::Error, "Login failed! Please try again.");
-            },
-          }
+          print_message(
+            PrintMessageType::Error,
+            format!("{}\nLogin failed! Please try again.", e).as_str(),
+          );
         },
       }
     }
-    self.report_session_capabilities().unwrap();
+    if let Err(err) = self.report_session_capabilities() {
+      print_message(
+        PrintMessageType::Error,
+        format!("Failed to post remote control support: {}", err).as_str(),
+      );
+    }
+  }
+
+  // The non-interactive part of `login()`.
+  fn authenticate(&mut self, creds: UserCredentials) -> Result<(), PuddlerError> {
+    let url = "Users/AuthenticateByName".to_string();
+    let body = format!(
+      "{{\"Username\":\"{}\",\"pw\":\"{}\"}}",
+      creds.username, creds.password
+    );
+    let res = self.post(url, body)?;
+    let json_response = serde_json::from_str::<Value>(&res.text()?)?;
+    let session_obj = &json_response["SessionInfo"];
+    let user = UserConfig {
+      access_token: json_string(&json_response, "AccessToken")?,
+      username: json_string(session_obj, "UserName")?,
+      user_id: json_string(session_obj, "UserId")?,
+    };
+    let device_id = json_string(session_obj, "DeviceId")?;
+    let config = self.get_config_handle();
+    config.insert_specific_value(Objective::DeviceID, device_id);
+    config.insert_specific_value(Objective::User, serde_json::to_string(&user).unwrap());
+    config.set_active_user(user.access_token);
+    self.write_headers();
+    let session_id = json_string(session_obj, "Id")?;
+    self.insert_value(MediaCenterValues::SessionID, session_id);
+    Ok(())
   }
 
   fn get_session_id(&mut self) -> Option<String>;
 
-  fn report_session_capabilities(&mut self) -> Result<(), ()> {
+  fn report_session_capabilities(&mut self) -> Result<(), PuddlerError> {
     let config = &self.get_config_handle().config;
     if config.media_center_type == MediaCenterType::Plex {
       panic!("What in the?!");
     }
 
     let capabilities = Capabilities {
-      PlayableMediaTypes: "Video".to_string(),
+      PlayableMediaTypes: "Video,Audio".to_string(),
       SupportsMediaControl: true,
       SupportedCommands: vec![
         String::from("MoveUp"),
@@ -2115,6 +2399,7 @@ pub trait MediaCenter: Send {
         String::from("Unmute"),
         String::from("SetVolume"),
         String::from("DisplayContent"),
+        String::from("DisplayMessage"),
         String::from("Play"),
         String::from("Playstate"),
         String::from("PlayNext"),
@@ -2129,19 +2414,11 @@ pub trait MediaCenter: Send {
       url = "Sessions/Capabilities/Full".to_string();
     }
 
-    match self.post(url, serde_json::to_string(&capabilities).unwrap()) {
-      Ok(_) => Ok(()),
-      Err(err) => {
-        print_message(
-          PrintMessageType::Error,
-          format!("Failed to post remote control support: {}.", err).as_str(),
-        );
-        Err(())
-      },
-    }
+    self.post(url, serde_json::to_string(&capabilities).unwrap())?;
+    Ok(())
   }
 
-  fn get(&mut self, url: String) -> Result<Response, Response> {
+  fn get(&mut self, url: String) -> Result<Response, PuddlerError> {
     let url = format!("{}{}", self.get_address(), url);
     let headers = self.get_headers();
     let authorization_2 = if let Some(header) = headers.get(1) {
@@ -2154,33 +2431,29 @@ pub trait MediaCenter: Send {
     } else {
       panic!("Request header missing!! Make sure you are running write_headers().");
     };
-    let client = Client::new();
-    let request = client
+    let client = self.get_client();
+    let response = client
       .get(url)
       .timeout(Duration::from_secs(15))
       .header(authorization_2.clone().0, authorization_2.clone().1)
       .header(String::from("X-Application"), request_headers.clone().0)
       .header(String::from("X-Emby-Token"), request_headers.clone().1)
       .header("Content-Type", "application/json")
-      .send();
-
-    let response = if let Err(res) = request {
-      print_message(PrintMessageType::Error, res.to_string().as_str());
-      exit(1);
-    } else {
-      request.unwrap()
-    };
+      .send()?;
 
     match response.status() {
       StatusCode::OK => Ok(response),
-      _ => Err(response),
+      status => Err(PuddlerError::from_status(
+        status,
+        response.text().unwrap_or_default(),
+      )),
     }
   }
 
-  fn delete(&mut self, url: String, body: String) -> Result<Response, String> {
+  fn delete(&mut self, url: String, body: String) -> Result<Response, PuddlerError> {
     let url = format!("{}{}", self.get_address(), url);
     let headers = self.get_headers();
-    let client = Client::new();
+    let client = self.get_client();
     let mut builder = client.delete(url).timeout(Duration::from_secs(15));
     if headers.len() == 1 {
       let authorization_1 = headers.get(0).unwrap();
@@ -2192,28 +2465,24 @@ pub trait MediaCenter: Send {
       builder = builder.header(String::from("X-Application"), request_headers.clone().0);
       builder = builder.header(String::from("X-Emby-Token"), request_headers.clone().1);
     }
-    let request = builder
+    let response = builder
       .header("Content-Type", "application/json")
       .body(body)
-      .send();
-
-    let response = if let Err(res) = request {
-      print_message(PrintMessageType::Error, res.to_string().as_str());
-      exit(1);
-    } else {
-      request.unwrap()
-    };
+      .send()?;
 
     match response.status() {
       StatusCode::OK => Ok(response),
-      _ => Err(response.text().unwrap()),
+      status => Err(PuddlerError::from_status(
+        status,
+        response.text().unwrap_or_default(),
+      )),
     }
   }
 
-  fn post(&mut self, url: String, body: String) -> Result<Response, String> {
+  fn post(&mut self, url: String, body: String) -> Result<Response, PuddlerError> {
     let url = format!("{}{}", self.get_address(), url);
     let headers = self.get_headers();
-    let client = Client::new();
+    let client = self.get_client();
     let mut builder = client.post(url).timeout(Duration::from_secs(15));
     if headers.len() == 1 {
       let authorization_1 = headers.get(0).unwrap();
@@ -2225,25 +2494,26 @@ pub trait MediaCenter: Send {
       builder = builder.header(String::from("X-Application"), request_headers.clone().0);
       builder = builder.header(String::from("X-Emby-Token"), request_headers.clone().1);
     }
-    let request = builder
+    let response = builder
       .header("Content-Type", "application/json")
       .body(body)
-      .send();
-
-    let response = if let Err(res) = request {
-      return Err(res.to_string());
-    } else {
-      request.unwrap()
-    };
+      .send()?;
 
     match response.status() {
       StatusCode::OK | StatusCode::NO_CONTENT => Ok(response),
-      _ => Err(response.text().unwrap()),
+      status => Err(PuddlerError::from_status(
+        status,
+        response.text().unwrap_or_default(),
+      )),
     }
   }
 
   // Since reqwest::blocking::client isn't allowed in an asynchronous context >~<
-  async fn async_post(&mut self, url: String, body: String) -> Result<reqwest::Response, String> {
+  async fn async_post(
+    &mut self,
+    url: String,
+    body: String,
+  ) -> Result<reqwest::Response, PuddlerError> {
     let url = format!("{}{}", self.get_address(), url);
     let headers = self.get_headers();
     let client = reqwest::Client::new();
@@ -2258,29 +2528,34 @@ pub trait MediaCenter: Send {
       builder = builder.header(String::from("X-Application"), request_headers.clone().0);
       builder = builder.header(String::from("X-Emby-Token"), request_headers.clone().1);
     }
-    let request = builder
+    let response = builder
       .header("Content-Type", "application/json")
       .body(body)
       .send()
-      .await;
-
-    let response = if let Err(res) = request {
-      return Err(res.to_string());
-    } else {
-      request.unwrap()
-    };
+      .await?;
 
     match response.status() {
       StatusCode::OK | StatusCode::NO_CONTENT => Ok(response),
-      _ => Err(response.text().await.unwrap()),
+      status => Err(PuddlerError::from_status(
+        status,
+        response.text().await.unwrap_or_default(),
+      )),
     }
   }
 
   fn insert_value(&mut self, value_type: MediaCenterValues, value: String);
 }
 
+// Missing fields mean the server api changed, that's no reason to panic.
+fn json_string(value: &Value, key: &str) -> Result<String, PuddlerError> {
+  value[key]
+    .as_str()
+    .map(|v| v.to_string())
+    .ok_or_else(|| PuddlerError::Deserialization(format!("missing field `{}`", key)))
+}
+
 pub fn broadcast_search(media_center_type: MediaCenterType) -> Option<UDPAnswer> {
-  let address: Arc<Mutex<Option<UDPAnswer>>> = Arc::new(Mutex::new(None));
+  let answers: Arc<Mutex<Vec<UDPAnswer>>> = Arc::new(Mutex::new(vec![]));
   let who_is = if media_center_type == MediaCenterType::Jellyfin {
     "who is JellyfinServer?"
   } else {
@@ -2291,13 +2566,21 @@ pub fn broadcast_search(media_center_type: MediaCenterType) -> Option<UDPAnswer>
     "Searching for local media-centers (5s timeout).\nPress any key to interrupt and for manual input."
   );
   let handle = thread::spawn({
-    let address_clone = Arc::clone(&address);
+    let answers_clone = Arc::clone(&answers);
     move || {
-      let res = broadcast(who_is);
-      if let Ok(answer) = res {
-        let mut address = address_clone.lock().unwrap();
-        *address = Some(answer);
+      let mut found = match media_center_type {
+        MediaCenterType::Plex => gdm_search(),
+        MediaCenterType::Dlna => ssdp_search(),
+        _ => broadcast(who_is),
+      };
+      // GDM answers contain the version already, DLNA servers don't have one
+      for answer in found
+        .iter_mut()
+        .filter(|answer| answer.Version.is_none() && media_center_type != MediaCenterType::Dlna)
+      {
+        answer.Version = fetch_version(&answer.Address);
       }
+      *answers_clone.lock().unwrap() = found;
     }
   });
 
@@ -2326,54 +2609,214 @@ pub fn broadcast_search(media_center_type: MediaCenterType) -> Option<UDPAnswer>
         } else {
           disable_raw_mode().unwrap();
           println!();
-          break;
+          return None;
         }
       }
     }
   }
 
-  let obj = address.lock().unwrap().clone();
-  if let Some(answer) = obj {
-    println!();
-    print!(
-      "Is this IP-Adress/Domain the correct one: {}\n (Y)es / (N)o",
-      answer.Address
-    );
-    match getch("YyNn") {
-      'Y' | 'y' => {
-        println!();
-        Some(answer)
-      },
-      _ => {
-        println!();
-        None
-      },
-    }
-  } else {
+  let answers = answers.lock().unwrap().clone();
+  if answers.is_empty() {
     println!("No local media-instances found.\n");
-    None
+    return None;
+  }
+  let mut options: Vec<InteractiveOption> = vec![InteractiveOption {
+    text: String::from("Found media-centers:"),
+    option_type: InteractiveOptionType::Header,
+  }];
+  for answer in &answers {
+    options.push(InteractiveOption {
+      text: format!(
+        "{} - {}{}",
+        answer.Name,
+        answer.Address,
+        answer
+          .Version
+          .as_ref()
+          .map(|version| format!(" (v{})", version))
+          .unwrap_or_default()
+      ),
+      option_type: InteractiveOptionType::Button,
+    });
+  }
+  options.push(InteractiveOption {
+    text: String::from("Manual input"),
+    option_type: InteractiveOptionType::Special,
+  });
+  match interactive_select(options) {
+    ((index, _), _, InteractiveOptionType::Button) => answers.get(index).cloned(),
+    _ => None,
   }
 }
 
-fn broadcast(message: &str) -> Result<UDPAnswer, ()> {
+// Every answer is collected until the timeout, there might be more than one server on the network.
+fn udp_search<F>(message: &str, port: u16, multicast: Option<Ipv4Addr>, parse: F) -> Vec<UDPAnswer>
+where
+  F: Fn(&str, IpAddr) -> Option<UDPAnswer>,
+{
   let socket: UdpSocket = UdpSocket::bind("0.0.0.0:0").expect("Failed to bind to network socket.");
-  socket
-    .set_read_timeout(Some(Duration::new(5, 0)))
-    .expect("Failed to set timeout.");
   socket
     .set_broadcast(true)
     .expect("Failed to start broadcast.");
-  socket
-    .send_to(&String::from(message).into_bytes(), "255.255.255.255:7359")
-    .expect("Failed to send broadcast.");
+  let mut targets = vec![Ipv4Addr::BROADCAST];
+  targets.extend(multicast);
+  targets.extend(broadcast_addresses());
+  for target in targets {
+    // some interfaces just don't allow broadcasts, that's fine as long as one of them does
+    let _ = socket.send_to(message.as_bytes(), (target, port));
+  }
+
+  let deadline = Instant::now() + SEARCH_TIMEOUT;
+  let mut answers: Vec<UDPAnswer> = vec![];
   let mut buf = [0; 4096];
-  if socket.recv_from(&mut buf).is_ok() {
-    let parsed = from_utf8(&buf)
-      .expect("Failed to read buffer into &str")
-      .trim_matches(char::from(0));
-    let udp_answer: UDPAnswer = serde_json::from_str(parsed).unwrap();
-    Ok(udp_answer)
-  } else {
-    Err(())
+  loop {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+      break;
+    }
+    socket
+      .set_read_timeout(Some(remaining))
+      .expect("Failed to set timeout.");
+    match socket.recv_from(&mut buf) {
+      Ok((length, sender)) => {
+        if let Some(answer) = parse(&String::from_utf8_lossy(&buf[..length]), sender.ip()) {
+          answers.push(answer);
+        }
+      },
+      Err(_) => break,
+    }
+  }
+  dedup_answers(answers)
+}
+
+// The directed broadcast address of every (ipv4) network interface.
+// Some routers and VPNs drop packets sent to 255.255.255.255.
+pub fn broadcast_addresses() -> Vec<Ipv4Addr> {
+  let mut addresses: Vec<Ipv4Addr> = vec![];
+  for interface in if_addrs::get_if_addrs().unwrap_or_default() {
+    if interface.is_loopback() {
+      continue;
+    }
+    if let IfAddr::V4(address) = interface.addr {
+      let broadcast = address
+        .broadcast
+        .unwrap_or_else(|| Ipv4Addr::from(u32::from(address.ip) | !u32::from(address.netmask)));
+      if !addresses.contains(&broadcast) {
+        addresses.push(broadcast);
+      }
+    }
+  }
+  addresses
+}
+
+// The same server answers once per interface it has been reached through.
+pub fn dedup_answers(answers: Vec<UDPAnswer>) -> Vec<UDPAnswer> {
+  let mut unique: Vec<UDPAnswer> = vec![];
+  for answer in answers {
+    if !unique.iter().any(|known| {
+      (!answer.Id.is_empty() && known.Id == answer.Id)
+        || known.Address.trim_end_matches('/') == answer.Address.trim_end_matches('/')
+    }) {
+      unique.push(answer);
+    }
+  }
+  unique
+}
+
+fn fetch_version(address: &str) -> Option<String> {
+  let response = Client::new()
+    .get(format!(
+      "{}/System/Info/Public",
+      address.trim_end_matches('/')
+    ))
+    .timeout(Duration::from_secs(2))
+    .send()
+    .ok()?;
+  let info = serde_json::from_str::<Value>(&response.text().ok()?).ok()?;
+  info["Version"].as_str().map(|version| version.to_string())
+}
+
+// Plex servers answer GDM's M-SEARCH with http-like headers instead of json.
+// Multicast is what Plex' own apps use, the broadcasts are for networks that filter it.
+fn gdm_search() -> Vec<UDPAnswer> {
+  udp_search(
+    "M-SEARCH * HTTP/1.1\r\n\r\n",
+    32414,
+    Some(Ipv4Addr::new(239, 0, 0, 250)),
+    |message, ip| parse_gdm_answer(message, &ip.to_string()),
+  )
+}
+
+pub fn parse_gdm_answer(message: &str, ip: &str) -> Option<UDPAnswer> {
+  let mut lines = message.lines();
+  if !lines.next()?.contains("200 OK") {
+    return None;
   }
+  let headers: HashMap<String, String> = lines
+    .filter_map(|line| line.split_once(':'))
+    .map(|(key, value)| (key.trim().to_lowercase(), value.trim().to_string()))
+    .collect();
+  // players answer as well, f.e. other puddler instances
+  if headers.get("content-type")? != "plex/media-server" {
+    return None;
+  }
+  Some(UDPAnswer {
+    Address: format!(
+      "http://{}:{}/",
+      ip,
+      headers
+        .get("port")
+        .map(|port| port.as_str())
+        .unwrap_or("32400")
+    ),
+    Name: headers.get("name")?.clone(),
+    Id: headers.get("resource-identifier")?.clone(),
+    Version: headers.get("version").cloned(),
+  })
+}
+
+// DLNA servers only answer with the location of their description, the name is in there.
+fn ssdp_search() -> Vec<UDPAnswer> {
+  let client = Client::new();
+  udp_search(
+    "M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: urn:schemas-upnp-org:device:MediaServer:1\r\n\r\n",
+    1900,
+    Some(Ipv4Addr::new(239, 255, 255, 250)),
+    |message, _| parse_ssdp_answer(message),
+  )
+  .into_iter()
+  .filter_map(|mut answer| {
+    answer.Name = fetch_description(&client, &answer.Address).ok()?.name;
+    Some(answer)
+  })
+  .collect()
+}
+
+// The address of these is the location of the device description.
+pub fn parse_ssdp_answer(message: &str) -> Option<UDPAnswer> {
+  let mut lines = message.lines();
+  if !lines.next()?.contains("200 OK") {
+    return None;
+  }
+  let headers: HashMap<String, String> = lines
+    .filter_map(|line| line.split_once(':'))
+    .map(|(key, value)| (key.trim().to_lowercase(), value.trim().to_string()))
+    .collect();
+  if !headers.get("st")?.contains(":device:MediaServer:") {
+    return None;
+  }
+  // "uuid:...::urn:schemas-upnp-org:device:MediaServer:1"
+  let usn = headers.get("usn")?;
+  Some(UDPAnswer {
+    Address: headers.get("location")?.clone(),
+    Name: String::new(),
+    Id: usn.split("::").next().unwrap_or(usn).to_string(),
+    Version: None,
+  })
+}
+
+fn broadcast(message: &str) -> Vec<UDPAnswer> {
+  udp_search(message, 7359, None, |message, _| {
+    serde_json::from_str::<UDPAnswer>(message.trim_matches(char::from(0))).ok()
+  })
 }
@@ -2,6 +2,7 @@
 use serde_derive::{Deserialize, Serialize};
 use serde_json::Value;
 use std::{
+  collections::HashMap,
   fmt::Debug,
   fs::{self, remove_file},
   path::{Path, PathBuf},
@@ -13,6 +14,7 @@ use crate::{
   error::MediaCenterConfigError,
   input::{getch, take_string_input},
   media_center::broadcast_search,
+  media_item::MediaKind,
   printing::{PrintMessageType, print_message},
 };
 
@@ -21,6 +23,8 @@ pub enum MediaCenterType {
   Jellyfin,
   Emby,
   Plex,
+  Dlna,
+  Local,
 }
 
 impl ToString for MediaCenterType {
@@ -29,6 +33,8 @@ impl ToString for MediaCenterType {
       MediaCenterType::Jellyfin => String::from("Jellyfin"),
       MediaCenterType::Emby => String::from("Emby"),
       MediaCenterType::Plex => String::from("Plex"),
+      MediaCenterType::Dlna => String::from("DLNA"),
+      MediaCenterType::Local => String::from("Local"),
     }
   }
 }
@@ -53,6 +59,33 @@ pub struct ServerConfig {
   pub device_id: String,
   pub address: String,
   pub users: Vec<UserConfig>,
+  // Plex only, found through GDM
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  pub machine_identifier: String,
+  // DLNA only, the device description of the server
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  pub location: String,
+  // Local only, the root of the library
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  pub folder: String,
+  // servers that don't keep track of what has been played (DLNA, Local)
+  #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+  pub playstates: HashMap<String, Playstate>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct Playstate {
+  pub played: bool,
+  // in seconds
+  pub playback_position: u64,
+  // The item as it was when the playstate got saved, so "Continue Watching" doesn't have to ask the
+  // server about every single one of them. Only DLNA fills these.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub titles: Vec<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub kind: Option<MediaKind>,
+  #[serde(default)]
+  pub runtime: u64,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -71,6 +104,10 @@ pub enum Objective {
   Users,
   SearchLocalInstance,
   User,
+  MachineIdentifier,
+  Location,
+  Folder,
+  Playstate,
 }
 
 pub fn get_mediacenter_folder() -> PathBuf {
@@ -89,6 +126,10 @@ pub fn get_mediacenter_folder() -> PathBuf {
   PathBuf::from(media_center_path)
 }
 
+fn queue_path(config_path: &str) -> String {
+  format!("{}.queue", config_path.trim_end_matches(".json"))
+}
+
 impl Config {
   pub fn default() -> Self {
     Config {
@@ -112,31 +153,35 @@ impl Config {
         "No media center configuration found. Creating new config...",
       );
       self.ask_for_setting(Objective::MediaCenterType);
-      match self.config.media_center_type {
-        MediaCenterType::Plex => (),
-        _ => self.ask_for_setting(Objective::SearchLocalInstance),
-      }
+      self.ask_for_setting(Objective::SearchLocalInstance);
       self.save();
     } else {
-      for file in fs::read_dir(media_center_folder).unwrap() {
-        let file_path = file.unwrap().path().display().to_string();
-        if !file_path.ends_with(".json") {
-          continue;
-        }
-        let content = fs::read_to_string(file_path.clone()).unwrap();
-        if let Ok(serialized) = serde_json::from_str::<MediaCenterConfig>(&content) {
-          files.append(&mut vec![Config {
-            path: file_path,
-            config: serialized,
-            old_path: None,
-          }]);
-        }
-      }
+      files = Config::read_all();
     }
 
     Ok(files)
   }
 
+  // Same as `new()`, but without asking for a new config if the folder is empty.
+  pub fn read_all() -> Vec<Config> {
+    let mut files: Vec<Config> = vec![];
+    for file in fs::read_dir(get_mediacenter_folder()).unwrap() {
+      let file_path = file.unwrap().path().display().to_string();
+      if !file_path.ends_with(".json") {
+        continue;
+      }
+      let content = fs::read_to_string(file_path.clone()).unwrap();
+      if let Ok(serialized) = serde_json::from_str::<MediaCenterConfig>(&content) {
+        files.append(&mut vec![Config {
+          path: file_path,
+          config: serialized,
+          old_path: None,
+        }]);
+      }
+    }
+    files
+  }
+
   pub fn read(&mut self) -> Result<(), MediaCenterConfigError> {
     if let Ok(content) = fs::read_to_string(self.path.clone()) {
       if let Ok(serialized) = serde_json::from_str::<MediaCenterConfig>(&content) {
@@ -168,10 +213,18 @@ impl Config {
     } else {
       vec![]
     };
+    let machine_identifier = self.get_machine_identifier().unwrap_or_default();
+    let location = self.get_location().unwrap_or_default();
+    let folder = self.get_folder().unwrap_or_default();
+    let playstates = self.get_playstates();
     let mut temp = ServerConfig {
       address,
       device_id,
       users,
+      machine_identifier,
+      location,
+      folder,
+      playstates,
     };
     match setting {
       Objective::DeviceID => {
@@ -216,10 +269,18 @@ impl Config {
     } else {
       vec![]
     };
+    let machine_identifier = self.get_machine_identifier().unwrap_or_default();
+    let location = self.get_location().unwrap_or_default();
+    let folder = self.get_folder().unwrap_or_default();
+    let playstates = self.get_playstates();
     let mut temp = ServerConfig {
       address,
       device_id,
       users,
+      machine_identifier,
+      location,
+      folder,
+      playstates,
     };
     match setting {
       Objective::DeviceID => {
@@ -252,19 +313,36 @@ impl Config {
           .users
           .append(&mut vec![serde_json::from_str(&value).unwrap()]);
       },
+      Objective::MachineIdentifier => {
+        temp.machine_identifier = value;
+      },
+      Objective::Location => {
+        temp.location = value;
+      },
+      Objective::Folder => {
+        temp.folder = value;
+      },
+      Objective::Playstate => {
+        // nothing worth remembering, if it's neither played nor started
+        let (item_id, playstate) = serde_json::from_str::<(String, Playstate)>(&value).unwrap();
+        if !playstate.played && playstate.playback_position == 0 {
+          temp.playstates.remove(&item_id);
+        } else {
+          temp.playstates.insert(item_id, playstate);
+        }
+      },
       _ => eprintln!("THAT is not a specific config setting."),
     }
     self.config.specific_values = serde_json::to_value(temp).unwrap();
   }
 
   pub fn save(&mut self) {
-    match fs::write(
-      self.path.clone(),
-      serde_json::to_string_pretty(&self.config).unwrap(),
-    ) {
+    match self.write() {
       Ok(()) => {
         print_message(PrintMessageType::Warning, "Saved media-center config.");
         if let Some(old_path) = &self.old_path {
+          // the queue follows its media-center, there might not be one
+          let _ = fs::rename(queue_path(old_path), queue_path(&self.path));
           if remove_file(old_path).is_ok() {
             print_message(
               PrintMessageType::Warning,
@@ -286,6 +364,14 @@ impl Config {
     }
   }
 
+  // Just the file, `save` is the one that tells about it.
+  fn write(&self) -> std::io::Result<()> {
+    fs::write(
+      self.path.clone(),
+      serde_json::to_string_pretty(&self.config).unwrap(),
+    )
+  }
+
   pub fn delete(&mut self) {
     print!(
       "Are you sure you want to delete \"{}\"?\n (Y)es / (N)o",
@@ -294,12 +380,18 @@ impl Config {
     match getch("YyNn") {
       'Y' | 'y' => {
         fs::remove_file(self.path.clone()).unwrap();
+        let _ = fs::remove_file(self.queue_path());
       },
       _ => (),
     };
     println!();
   }
 
+  // The "Up next" queue is kept in a file of its own, see queue.rs.
+  pub fn queue_path(&self) -> String {
+    queue_path(&self.path)
+  }
+
   pub fn get_device_id(&mut self) -> String {
     if let Some(device_id) = self.config.specific_values.get("device_id") {
       let device_id_string = device_id.as_str().unwrap().to_string();
@@ -320,6 +412,7 @@ impl Config {
         MediaCenterType::Plex => Some(address.to_string()),
         MediaCenterType::Emby => Some(address.to_owned() + "emby/"),
         MediaCenterType::Jellyfin => Some(address.to_string()),
+        MediaCenterType::Dlna | MediaCenterType::Local => Some(address.to_string()),
       }
     } else {
       None
@@ -347,6 +440,67 @@ impl Config {
     }
   }
 
+  pub fn get_machine_identifier(&self) -> Option<String> {
+    self
+      .config
+      .specific_values
+      .get("machine_identifier")
+      .and_then(|value| value.as_str())
+      .filter(|value| !value.is_empty())
+      .map(|value| value.to_string())
+  }
+
+  pub fn get_location(&self) -> Option<String> {
+    self
+      .config
+      .specific_values
+      .get("location")
+      .and_then(|value| value.as_str())
+      .filter(|value| !value.is_empty())
+      .map(|value| value.to_string())
+  }
+
+  pub fn get_folder(&self) -> Option<String> {
+    self
+      .config
+      .specific_values
+      .get("folder")
+      .and_then(|value| value.as_str())
+      .filter(|value| !value.is_empty())
+      .map(|value| value.to_string())
+  }
+
+  // Only stored for items that have been started or played.
+  pub fn save_playstate(&mut self, item_id: &str, playstate: Playstate) {
+    self.save_playstates(vec![(item_id.to_string(), playstate)]);
+  }
+
+  // All of them with a single write, like the episodes of a whole series.
+  pub fn save_playstates(&mut self, playstates: Vec<(String, Playstate)>) {
+    for playstate in playstates {
+      self.insert_specific_value(
+        Objective::Playstate,
+        serde_json::to_string(&playstate).unwrap(),
+      );
+    }
+    // this happens after every playback, no need to announce it
+    if let Err(err) = self.write() {
+      print_message(
+        PrintMessageType::Error,
+        format!("Failed to save the playstate: {}", err).as_str(),
+      );
+    }
+  }
+
+  pub fn get_playstates(&self) -> HashMap<String, Playstate> {
+    self
+      .config
+      .specific_values
+      .get("playstates")
+      .and_then(|value| serde_json::from_value(value.clone()).ok())
+      .unwrap_or_default()
+  }
+
   pub fn get_active_user(&mut self) -> Option<UserConfig> {
     if let Some(value) = self.config.specific_values.get("users") {
       if let Ok(user) = serde_json::from_value::<UserConfig>(value[0].clone()) {
@@ -360,7 +514,12 @@ impl Config {
     match self.config.media_center_type {
       MediaCenterType::Plex => panic!("this too"),
       _ => {
-        self.remove_specific_value(Objective::User, identifier);
+        if let Some(users) = self.config.specific_values.get("users") {
+          let users = serde_json::from_value::<Vec<UserConfig>>(users.clone()).unwrap();
+          if let Some(user) = users.iter().find(|u| u.access_token == identifier) {
+            self.remove_specific_value(Objective::User, serde_json::to_string(user).unwrap());
+          }
+        }
       },
     }
   }
@@ -369,12 +528,14 @@ impl Config {
     match setting {
       Objective::MediaCenterType => {
         print!(
-          "Which kind of server do you want to stream from?\n  [1] Jellyfin\n  [2] Emby\n  [3] Plex"
+          "Which kind of server do you want to stream from?\n  [1] Jellyfin\n  [2] Emby\n  [3] Plex\n  [4] DLNA\n  [5] Local folder"
         );
-        self.config.media_center_type = match getch("123") {
+        self.config.media_center_type = match getch("12345") {
           '1' => MediaCenterType::Jellyfin,
           '2' => MediaCenterType::Emby,
-          _ => MediaCenterType::Plex,
+          '3' => MediaCenterType::Plex,
+          '4' => MediaCenterType::Dlna,
+          _ => MediaCenterType::Local,
         };
       },
       Objective::SearchLocalInstance => {
@@ -397,7 +558,30 @@ impl Config {
               self.ask_for_setting(Objective::Address);
             }
           },
-          _ => panic!("Plex servers do not support this feature."),
+          MediaCenterType::Plex => {
+            // without a local server, the server is chosen from plex.tv after the login
+            if let Some(server_info) = broadcast_search(self.config.media_center_type) {
+              self.config.server_name = server_info.Name;
+              self.insert_specific_value(Objective::Address, server_info.Address);
+              self.insert_specific_value(Objective::MachineIdentifier, server_info.Id);
+            } else {
+              return;
+            }
+          },
+          MediaCenterType::Dlna => {
+            if let Some(server_info) = broadcast_search(self.config.media_center_type) {
+              self.config.server_name = server_info.Name;
+              self.insert_specific_value(Objective::Location, server_info.Address);
+            } else {
+              self.ask_for_setting(Objective::ServerName);
+              self.ask_for_setting(Objective::Location);
+            }
+          },
+          // nothing to search for
+          MediaCenterType::Local => {
+            self.ask_for_setting(Objective::ServerName);
+            self.ask_for_setting(Objective::Folder);
+          },
         }
         if self.check_existing_config() {
           print_message(
@@ -449,6 +633,32 @@ impl Config {
         }
         self.insert_specific_value(Objective::Address, address);
       },
+      Objective::Location => {
+        println!(
+          "Enter the url of the server's device description (example: http://192.168.1.2:8200/rootDesc.xml):"
+        );
+        loop {
+          let location = take_string_input(vec![]);
+          if location.starts_with("http://") || location.starts_with("https://") {
+            self.insert_specific_value(Objective::Location, location);
+            break;
+          } else {
+            println!("That is not a valid url. Please try again.");
+          }
+        }
+      },
+      Objective::Folder => {
+        println!("Enter the path of the folder with your movies and series:");
+        loop {
+          let folder = take_string_input(vec![]);
+          if Path::new(&folder).is_dir() {
+            self.insert_specific_value(Objective::Folder, folder);
+            break;
+          } else {
+            println!("That is not a folder. Please try again.");
+          }
+        }
+      },
       _ => {},
     }
     println!();
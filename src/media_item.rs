@@ -0,0 +1,160 @@
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::{
+  cli::Listable,
+  media_center::{Item, ToStringAdv},
+  offline::OfflineItem,
+  plex::PlexItem,
+};
+
+// The items of every media-center in one shape. Whatever is written against these (series selection,
+// the player, ...) works the same for Jellyfin, Emby and Plex.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKind {
+  Movie,
+  Episode,
+  Series,
+  Season,
+  Artist,
+  Album,
+  Track,
+  Other(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MediaItem {
+  pub id: String,
+  pub kind: MediaKind,
+  // see `ToStringAdv::to_string_split`, the first one is the whole title
+  pub titles: Vec<String>,
+  pub played: bool,
+  // in seconds
+  pub playback_position: u64,
+  pub runtime: u64,
+}
+
+// Where the player gets an item from.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PlaybackSource {
+  pub stream_url: String,
+  // the arguments of mpv's "sub-add": url, flags, title and language
+  pub external_subtitles: Vec<[String; 4]>,
+  // queued tracks bring their own, it's handed to the media-center once they start
+  pub playback_info: Option<String>,
+}
+
+impl From<&Item> for MediaItem {
+  fn from(item: &Item) -> Self {
+    MediaItem {
+      id: item.Id.clone(),
+      kind: match item.Type.as_str() {
+        "Movie" => MediaKind::Movie,
+        "Episode" => MediaKind::Episode,
+        "Series" => MediaKind::Series,
+        "Season" => MediaKind::Season,
+        "MusicArtist" => MediaKind::Artist,
+        "MusicAlbum" => MediaKind::Album,
+        "Audio" => MediaKind::Track,
+        other => MediaKind::Other(other.to_string()),
+      },
+      titles: item.to_string_split(),
+      played: item.UserData.Played,
+      playback_position: item.UserData.PlaybackPositionTicks / 10000000,
+      runtime: item.RunTimeTicks.unwrap_or(0) / 10000000,
+    }
+  }
+}
+
+impl From<&PlexItem> for MediaItem {
+  fn from(item: &PlexItem) -> Self {
+    MediaItem {
+      id: item.ratingKey.clone(),
+      kind: match item.r#type.as_str() {
+        "movie" => MediaKind::Movie,
+        "episode" => MediaKind::Episode,
+        "show" => MediaKind::Series,
+        "season" => MediaKind::Season,
+        "artist" => MediaKind::Artist,
+        "album" => MediaKind::Album,
+        "track" => MediaKind::Track,
+        other => MediaKind::Other(other.to_string()),
+      },
+      titles: item.to_string_split(),
+      played: item.viewCount.is_some(),
+      playback_position: item.viewOffset.unwrap_or(0) / 1000,
+      runtime: item.duration.unwrap_or(0) / 1000,
+    }
+  }
+}
+
+impl From<&OfflineItem> for MediaItem {
+  fn from(item: &OfflineItem) -> Self {
+    MediaItem {
+      id: item.item_id.clone(),
+      kind: if item.movie {
+        MediaKind::Movie
+      } else {
+        MediaKind::Episode
+      },
+      titles: item.title.clone(),
+      played: item.played,
+      playback_position: item.playback_position,
+      runtime: item.total_runtime,
+    }
+  }
+}
+
+impl fmt::Display for MediaItem {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.titles.first().cloned().unwrap_or_default())
+  }
+}
+
+impl ToStringAdv for MediaItem {
+  fn to_string_split(&self) -> Vec<String> {
+    self.titles.clone()
+  }
+
+  fn to_string_full(&self) -> String {
+    if self.played {
+      format!("{} - {}", self, "(Played)".green())
+    } else {
+      self.to_string()
+    }
+  }
+
+  fn to_string_ext(&self) -> String {
+    let full = self.to_string_full();
+    if self.playback_position != 0 && self.runtime != 0 {
+      format!(
+        "{} {}%",
+        full,
+        (self.playback_position as f64 / self.runtime as f64 * 100.0).round()
+      )
+    } else {
+      full
+    }
+  }
+}
+
+impl Listable for MediaItem {
+  fn id(&self) -> String {
+    self.id.clone()
+  }
+
+  fn item_type(&self) -> String {
+    match &self.kind {
+      MediaKind::Movie => String::from("movie"),
+      MediaKind::Episode => String::from("episode"),
+      MediaKind::Series => String::from("series"),
+      MediaKind::Season => String::from("season"),
+      MediaKind::Artist => String::from("artist"),
+      MediaKind::Album => String::from("album"),
+      MediaKind::Track => String::from("track"),
+      MediaKind::Other(other) => other.to_lowercase(),
+    }
+  }
+}
@@ -8,7 +8,10 @@ use serde_json::Value;
 use std::time::Duration;
 use tokio::{
   net::TcpStream,
-  sync::mpsc::{self},
+  sync::{
+    mpsc::{self},
+    watch,
+  },
   time::interval,
 };
 use tokio_tungstenite::{
@@ -18,21 +21,29 @@ use tokio_tungstenite::{
 
 use crate::{
   APPNAME,
+  companion::{self, CompanionInfo, Timeline},
   discord::DiscordClient,
+  error::PuddlerError,
+  history::{History, HistoryEntry},
   input::clear_stdin,
-  media_center::ToStringAdv,
-  media_center::{Item, MediaCenter, PlaybackInfo},
+  media_center::{MediaCenter, MediaCenterValues},
   media_config::Config,
   media_config::MediaCenterType,
-  plex::PlexItem,
+  media_item::{MediaItem, MediaKind, PlaybackSource},
+  music::listened_enough,
+  offline::{OfflineItem, OfflineLibrary, watched_enough},
   printing::{PrintMessageType, print_message},
   puddler_settings::PuddlerSettings,
+  remote::{self, RemoteRequest},
+  segments::{Chapter, Segment, Trickplay, chapters_file, segment_at},
+  syncplay::SyncPlay,
 };
 
 #[derive(Clone, PartialEq)]
 pub enum VideoType {
   Movie,
   Episode,
+  Track,
 }
 
 #[derive(Clone, PartialEq)]
@@ -41,12 +52,50 @@ pub struct Video {
   id: String,
   video_type: VideoType,
   stream_url: String,
-  playback_position: u64,
+  // where playback starts, and once `play` returns, where it stopped
+  pub playback_position: u64,
   total_runtime: u64,
   external_media: Option<Vec<[String; 4]>>,
   pub played: bool,
   pub preferred_audio_track: Option<u32>,
   pub preferred_subtitle_track: Option<u32>,
+  // filled by remote control commands that need a different item, handled once `play` returns
+  pub remote_requests: Vec<RemoteRequest>,
+  // queued tracks bring their own, it's handed to the media-center once they start
+  playback_info: Option<String>,
+  segments: Vec<Segment>,
+  chapters: Vec<Chapter>,
+  trickplay: Option<Trickplay>,
+}
+
+impl Video {
+  fn new(item: &MediaItem, source: PlaybackSource) -> Self {
+    Video {
+      title: item.titles.clone(),
+      id: item.id.clone(),
+      video_type: match item.kind {
+        MediaKind::Movie => VideoType::Movie,
+        MediaKind::Track => VideoType::Track,
+        _ => VideoType::Episode,
+      },
+      stream_url: source.stream_url,
+      playback_position: item.playback_position,
+      total_runtime: item.runtime,
+      external_media: if source.external_subtitles.is_empty() {
+        None
+      } else {
+        Some(source.external_subtitles)
+      },
+      played: true,
+      preferred_audio_track: None,
+      preferred_subtitle_track: None,
+      remote_requests: vec![],
+      playback_info: source.playback_info,
+      segments: vec![],
+      chapters: vec![],
+      trickplay: None,
+    }
+  }
 }
 
 pub struct Player {
@@ -54,6 +103,10 @@ pub struct Player {
   media_center: Option<Box<dyn MediaCenter>>,
   settings: PuddlerSettings,
   video: Option<Video>,
+  syncplay: Option<SyncPlay>,
+  companion: Option<CompanionInfo>,
+  // played right after `video`, in the same mpv instance
+  queue: Vec<Video>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -69,190 +122,100 @@ impl Player {
       settings,
       video: None,
       media_center: None,
+      syncplay: None,
+      companion: None,
+      queue: vec![],
     }
   }
 
+  pub fn set_syncplay(&mut self, syncplay: SyncPlay) {
+    self.syncplay = Some(syncplay);
+  }
+
+  pub fn take_syncplay(&mut self) -> Option<SyncPlay> {
+    self.syncplay.take()
+  }
+
+  // Plex only, lets the apps control playback like the Jellyfin websocket does.
+  pub fn set_companion(&mut self, companion: CompanionInfo) {
+    self.companion = Some(companion);
+  }
+
   pub fn set_media_center(&mut self, media_center: Box<dyn MediaCenter>) {
     self.media_center = Some(media_center);
   }
 
-  pub fn set_plex_video(
+  // Everything that's played goes through here, whichever media-center it's from.
+  pub fn set_video(
     &mut self,
-    item: PlexItem,
-    server_address: String,
-    auth: String,
-    transcoding_settings: &mut Option<(bool, Option<u32>, Option<u32>, String)>,
+    item: &MediaItem,
+    source: PlaybackSource,
+    transcoding_settings: &Option<(bool, Option<u32>, Option<u32>, String)>,
   ) {
-    let handle = &mut self.media_center_config;
-    if handle.config.media_center_type != MediaCenterType::Plex {
-      panic!("What da hell?!");
+    let mut video = Video::new(item, source);
+    if let Some(settings) = transcoding_settings {
+      video.preferred_audio_track = settings.1;
+      video.preferred_subtitle_track = settings.2;
     }
+    self.video = Some(video);
+    self.queue = vec![];
+  }
 
-    let stream_url = if handle.config.transcoding {
-      format!(
-        "{}video/:/transcode/universal/start.mkv?{}&path={}&subtitles=embedded&directPlay=0&directStream=1&session={}&protocol=http&X-Plex-Platform={}&fastSeek=1&offset={}",
-        server_address,
-        auth,
-        urlencoding::encode(format!("/library/metadata/{}", item.ratingKey).as_str()),
-        handle.get_device_id(),
-        urlencoding::encode("Plex Home Theater"),
-        item.viewOffset.unwrap_or(0) / 1000
-      )
-    } else {
-      format!(
-        "{}{}?{}&X-Plex-Platform={}",
-        server_address,
-        item.Media.clone().unwrap()[0].Part[0]
-          .key
-          .trim_start_matches('/'),
-        auth,
-        urlencoding::encode("Plex Home Theater")
-      )
-    };
-
-    let mut commands: Vec<[String; 4]> = vec![];
-    if !handle.config.transcoding {
-      for stream in item.Media.clone().unwrap()[0].Part[0]
-        .Stream
-        .clone()
-        .unwrap()
-      {
-        if let Some(key) = &stream.key {
-          let media_url = format!("{}{}?{}", server_address, key.trim_start_matches('/'), auth);
-          let language = if let Some(language) = stream.language.clone() {
-            language
-          } else {
-            String::from("und")
-          };
-          let title = if let Some(title) = stream.displayTitle.clone() {
-            title
-          } else {
-            String::from("Undefined")
-          };
-          let formatted_title = format!(r#""{}""#, title);
-          let command: [String; 4] = [media_url, "auto".to_string(), formatted_title, language];
-          commands.push(command);
-        }
-      }
+  // Music. All tracks end up in one mpv playlist, so there are no gaps between them.
+  pub fn set_tracks(&mut self, tracks: Vec<(MediaItem, PlaybackSource)>) {
+    let mut tracks: Vec<Video> = tracks
+      .into_iter()
+      .map(|(item, source)| Video::new(&item, source))
+      .collect();
+    if tracks.is_empty() {
+      panic!("There has to be at least one track.");
     }
-
-    let preferred_tracks = if let Some(settings) = transcoding_settings {
-      (settings.1, settings.2)
-    } else {
-      (None, None)
-    };
-
-    self.video = Some(Video {
-      title: item.to_string_split(),
-      stream_url,
-      id: item.ratingKey,
-      video_type: if item.r#type == "movie" {
-        VideoType::Movie
-      } else {
-        VideoType::Episode
-      },
-      playback_position: item.viewOffset.unwrap_or(0) / 1000,
-      total_runtime: item.duration.unwrap_or(0) / 1000,
-      external_media: if commands.is_empty() {
-        None
-      } else {
-        Some(commands)
-      },
-      played: true,
-      preferred_audio_track: preferred_tracks.0,
-      preferred_subtitle_track: preferred_tracks.1,
-    });
+    self.video = Some(tracks.remove(0));
+    self.queue = tracks;
   }
 
-  pub fn set_jellyfin_video(
+  // Intros, credits and the like of the current video are skipped, and end up in the chapters with
+  // the server's own. Trickplay tiles are left to thumbnail scripts, see "user-data/puddler/trickplay".
+  pub fn set_timeline(
     &mut self,
-    item: Item,
-    playback_info: PlaybackInfo,
-    server_address: String,
-    auth_token: String,
-    transcoding_settings: &mut Option<(bool, Option<u32>, Option<u32>, String)>,
+    segments: Vec<Segment>,
+    chapters: Vec<Chapter>,
+    trickplay: Option<Trickplay>,
   ) {
-    let handle = &self.media_center_config;
-    if handle.config.media_center_type == MediaCenterType::Plex {
-      panic!("What da hell?!");
+    if let Some(video) = self.video.as_mut() {
+      video.segments = segments;
+      video.chapters = chapters;
+      video.trickplay = trickplay;
     }
+  }
 
-    let media_source = playback_info.MediaSources.first().unwrap();
-    let stream_url = if let Some(transcoding_url) = &media_source.TranscodingUrl {
-      format!(
-        "{}{}",
-        server_address,
-        transcoding_url.trim_start_matches('/')
-      )
-    } else {
-      format!(
-        "{}Videos/{}/stream?Container=mkv&Static=true&api_key={}",
-        server_address, media_source.Id, auth_token
-      )
+  // Offline items are played without any media-center, nothing gets reported.
+  pub fn set_offline_video(&mut self, item: &OfflineItem, library: &OfflineLibrary) {
+    let source = PlaybackSource {
+      stream_url: library.get_path(&item.file).display().to_string(),
+      external_subtitles: item
+        .subtitles
+        .iter()
+        .map(|subtitle| {
+          [
+            library.get_path(&subtitle.file).display().to_string(),
+            "auto".to_string(),
+            format!(r#""{}""#, subtitle.title),
+            subtitle.language.clone(),
+          ]
+        })
+        .collect(),
+      playback_info: None,
     };
-
-    let mut commands: Vec<[String; 4]> = vec![];
-    for (index, stream) in media_source.MediaStreams.iter().enumerate() {
-      if stream.IsExternal && stream.SupportsExternalStream {
-        let extension = if let Some(path) = &stream.Path {
-          path.split('.').last().unwrap().to_string()
-        } else {
-          stream.Codec.as_ref().unwrap().to_owned()
-        };
-        let mut media_url = format!(
-          "{}Videos/{}/{}/Subtitles/{}/Stream.{}?api_key={}",
-          server_address, item.Id, media_source.Id, index, extension, auth_token
-        );
-        if item.UserData.PlaybackPositionTicks != 0 && handle.config.transcoding {
-          media_url +=
-            &("&StartPositionTicks=".to_owned() + &item.UserData.PlaybackPositionTicks.to_string());
-        }
-        let language = if let Some(language) = stream.Language.clone() {
-          language
-        } else {
-          String::from("und")
-        };
-        let title = if let Some(title) = stream.DisplayTitle.clone() {
-          title
-        } else {
-          String::from("Undefined")
-        };
-        let formatted_title = format!(r#""{}""#, title);
-        let command: [String; 4] = [media_url, "auto".to_string(), formatted_title, language];
-        commands.push(command);
-      }
+    self.media_center = None;
+    self.set_video(&MediaItem::from(item), source, &None);
+    if let Some(video) = self.video.as_mut() {
+      video.played = item.played;
     }
-
-    let preferred_tracks = if let Some(settings) = transcoding_settings {
-      (settings.1, settings.2)
-    } else {
-      (None, None)
-    };
-
-    self.video = Some(Video {
-      title: item.to_string_split(),
-      stream_url,
-      id: item.Id,
-      video_type: if item.Type == "Movie" {
-        VideoType::Movie
-      } else {
-        VideoType::Episode
-      },
-      playback_position: item.UserData.PlaybackPositionTicks / 10000000,
-      total_runtime: item.RunTimeTicks.unwrap() / 10000000,
-      external_media: if commands.is_empty() {
-        None
-      } else {
-        Some(commands)
-      },
-      played: true,
-      preferred_audio_track: preferred_tracks.0,
-      preferred_subtitle_track: preferred_tracks.1,
-    });
   }
 
   #[tokio::main]
-  pub async fn play(&mut self) -> Video {
+  pub async fn play(&mut self) -> Result<Video, PuddlerError> {
     // any time vars in here are in seconds
     let mut video: Video;
     if let Some(vid) = &self.video {
@@ -260,33 +223,18 @@ impl Player {
     } else {
       panic!("You must've forgotten to set the video.");
     }
+    let mut tracks = vec![video.clone()];
+    tracks.extend(self.queue.clone());
+    let mut track_index = 0;
     let handle = &mut self.media_center_config;
-    let media_center: &mut Box<dyn MediaCenter> = self.media_center.as_mut().unwrap();
+    let mut media_center: Option<&mut Box<dyn MediaCenter>> = self.media_center.as_mut();
 
     let mut websocket_reader = None;
     let mut websocket_sender = None;
-    if handle.config.media_center_type != MediaCenterType::Plex {
-      let http_address = media_center.get_address();
-      let protocol = if http_address.contains("https") {
-        "wss"
-      } else {
-        "ws"
-      };
-      let address: String = http_address
-        .trim_start_matches("https")
-        .trim_start_matches("http")
-        .trim_end_matches('/')
-        .to_string();
-      let headers = media_center.get_headers();
-      let token = &headers.get(2).unwrap().1;
-      // the "/socket" is mandatory for jellyfin; emby doesn't care either way. documentation on this is fucking terrible
-      let url = format!(
-        "{}{}/socket?api_key={}&deviceId={}",
-        protocol,
-        address,
-        token,
-        handle.get_device_id()
-      );
+    if handle.config.media_center_type != MediaCenterType::Plex
+      && let Some(media_center) = media_center.as_mut()
+    {
+      let url = websocket_url(media_center.as_mut());
       if let Ok((socket, _)) = connect_async(url).await {
         let (sender, reader) = socket.split();
         websocket_reader = Some(reader);
@@ -299,6 +247,24 @@ impl Player {
 
     let (input, _websocket_output) = mpsc::unbounded_channel::<String>();
     let (websocket_input, mut output) = mpsc::unbounded_channel();
+    let (timeline_sender, timeline_receiver) = watch::channel(Timeline::default());
+    if let Some(info) = self.companion.clone() {
+      match companion::bind().await {
+        Ok(listener) => {
+          tokio::spawn(companion::serve(
+            listener,
+            info.clone(),
+            websocket_input.clone(),
+            timeline_receiver,
+          ));
+          tokio::spawn(companion::advertise(info));
+        },
+        Err(err) => print_message(
+          PrintMessageType::Warning,
+          format!("Remote control is not available: {}", err).as_str(),
+        ),
+      }
+    }
     let websocket_read_handle = tokio::spawn(async move {
       websocket_read(websocket_reader, websocket_input).await;
     });
@@ -320,59 +286,119 @@ impl Player {
       config.media_center_type.to_string()
     );
 
-    let mut mpv = Mpv::new().expect("Failed to create mpv handle!");
+    let mut mpv = Mpv::new()?;
 
     if let Some(path) = &self.settings.mpv_config_location {
-      mpv.set_property("config-dir", path.clone()).unwrap();
-      mpv.set_property("config", true).unwrap();
+      mpv.set_property("config-dir", path.clone())?;
+      mpv.set_property("config", true)?;
     }
 
-    mpv.set_property("input-default-bindings", "yes").unwrap();
-    mpv.set_property("input-vo-keyboard", "yes").unwrap();
-    mpv.set_property("osc", true).unwrap();
+    mpv.set_property("input-default-bindings", "yes")?;
+    mpv.set_property("input-vo-keyboard", "yes")?;
+    mpv.set_property("osc", true)?;
 
     if self.settings.mpv_debug_log {
-      mpv.set_property("log-file", "./mpv.log").unwrap();
+      mpv.set_property("log-file", "./mpv.log")?;
     }
 
-    if self.settings.fullscreen {
-      mpv
-        .set_property("fullscreen", "yes")
-        .expect("Failed to configure fullscreen.");
+    if video.video_type == VideoType::Track {
+      // there is no window for music, mpv is controlled through the terminal instead
+      mpv.set_property("audio-display", "no")?;
+      mpv.set_property("terminal", true)?;
+      mpv.set_property("input-terminal", true)?;
+      mpv.set_property("gapless-audio", "yes")?;
+      mpv.set_property("prefetch-playlist", true)?;
+      // quit after the last track, instead of waiting for more
+      mpv.set_property("idle", "no")?;
+    } else if self.settings.fullscreen {
+      mpv.set_property("fullscreen", "yes")?;
     }
 
     if self.settings.gpu {
-      mpv
-        .set_property("hwdec", "auto-safe")
-        .expect("Failed to configure hardware-decoding.")
+      mpv.set_property("hwdec", "auto-safe")?;
     }
 
-    mpv
-      .set_property("user-agent", APPNAME)
-      .expect("Failed to configure user-agent.");
-    mpv
-      .set_property("force-media-title", media_title)
-      .expect("Failed to configure force-media-title.");
-    mpv
-      .set_property("title", mpv_title)
-      .expect("Failed to configure title.");
+    mpv.set_property("user-agent", APPNAME)?;
+    mpv.set_property("force-media-title", media_title)?;
+    mpv.set_property("title", mpv_title)?;
 
-    mpv
-      .disable_deprecated_events()
-      .expect("Failed to disable deprecated events.");
+    mpv.disable_deprecated_events()?;
 
-    mpv
-      .command("loadfile", &[&video.stream_url])
-      .expect("Failed to load file.");
+    // mpv starts at 0 when transcoding, but remote control seeks are relative to the whole item
+    let transcode_offset = if config.transcoding {
+      video.playback_position
+    } else {
+      0
+    };
+    let chapters_path = std::env::temp_dir().join(format!("puddler-chapters-{}.txt", video.id));
+    let has_chapters = !video.segments.is_empty() || !video.chapters.is_empty();
+    if has_chapters {
+      // this replaces the chapters of the file itself
+      let label = if video.video_type == VideoType::Movie {
+        "Movie"
+      } else {
+        "Episode"
+      };
+      let chapters = chapters_file(
+        &video.chapters,
+        &video.segments,
+        video.total_runtime,
+        transcode_offset,
+        label,
+      );
+      if std::fs::write(&chapters_path, chapters).is_ok() {
+        mpv.set_property("chapters-file", chapters_path.display().to_string())?;
+      }
+    }
+    // "keybind" is fairly new, older mpv versions skip the segments automatically instead
+    let mut skip_with_tab = false;
+    if !video.segments.is_empty() && !self.settings.skip_segments {
+      match mpv.command("keybind", &["TAB", "script-message puddler-skip"]) {
+        Ok(()) => skip_with_tab = true,
+        Err(err) => print_message(
+          PrintMessageType::Warning,
+          format!(
+            "Skipping with TAB isn't available, segments are skipped automatically: {}",
+            err
+          )
+          .as_str(),
+        ),
+      }
+    }
+    // "user-data" needs mpv 0.36, it's only there for thumbnail scripts anyway
+    if let Some(trickplay) = &video.trickplay
+      && let Err(err) = mpv.set_property(
+        "user-data/puddler/trickplay",
+        serde_json::to_string(trickplay).unwrap(),
+      )
+    {
+      print_message(
+        PrintMessageType::Warning,
+        format!("Trickplay tiles aren't available: {}", err).as_str(),
+      );
+    }
 
-    media_center
-      .start_playback(video.clone().id, video.playback_position)
-      .await;
+    mpv.command("loadfile", &[&video.stream_url])?;
+    for track in tracks.iter().skip(1) {
+      mpv.command("loadfile", &[&track.stream_url, "append"])?;
+    }
+    if let Some(syncplay) = self.syncplay.as_mut() {
+      syncplay.start(&mpv);
+    }
+
+    if let Some(media_center) = media_center.as_mut() {
+      media_center
+        .start_playback(video.clone().id, video.playback_position)
+        .await;
+    }
 
     for shader in &self.settings.glsl_shaders {
-      mpv
-        .command("change-list", &["glsl-shaders", "append", shader.as_str()])
-        .expect("Failed to add glsl-shader file");
+      if let Err(err) = mpv.command("change-list", &["glsl-shaders", "append", shader.as_str()]) {
+        print_message(
+          PrintMessageType::Error,
+          format!("Failed to add glsl-shader file \"{}\": {}", shader, err).as_str(),
+        );
+      }
     }
 
     let mut discord: DiscordClient = DiscordClient::new();
@@ -380,13 +406,14 @@ impl Player {
       discord.start();
     }
 
-    let total_runtime: f64 = if config.transcoding {
+    let mut total_runtime: f64 = if config.transcoding {
       video.total_runtime as f64 - video.playback_position as f64
     } else {
       video.total_runtime as f64
     };
 
     let resume_progress = video.playback_position;
+    let mut started_at = chrono::Local::now();
     let mut paused = false;
     let mut old_pos: f64 = -15.0;
     let mut last_time_update: f64 = 0.0;
@@ -394,30 +421,47 @@ impl Player {
     let mut sub_track: u32 = 0;
     let mut volume_level: u32 = 0;
     let mut muted: bool = false;
+    // whether the current track has been reported as stopped already
+    let mut finished = false;
+    // the segment that is playing right now, and the ones that have been skipped automatically
+    let mut current_segment: Option<usize> = None;
+    let mut skipped_segments: Vec<usize> = vec![];
     let initial_preferences = (video.preferred_audio_track, video.preferred_subtitle_track);
+    let mut initial_load_done = false;
     'main: loop {
       if let Ok(msg) = output.try_recv() {
         if let Ok(json_message) = serde_json::from_str::<WebSocketMessage>(&msg) {
-          if json_message.MessageType == "Playstate" {
-            match json_message.Data.get("Command").unwrap().as_str().unwrap() {
-              "PlayPause" => {
-                if paused {
-                  mpv.set_property("pause", false).unwrap();
-                  paused = false;
-                  old_pos -= 16.0;
-                } else {
-                  mpv.set_property("pause", true).unwrap();
-                  paused = true;
-                }
-              },
-              "Stop" => {
-                mpv.command("quit", &["0"]).unwrap();
-              },
-              _ => (),
+          // skipping within the queue doesn't need to stop the player
+          if tracks.len() > 1
+            && let Some(command) = remote::playlist_command(&json_message)
+          {
+            if let Err(err) = mpv.command(command, &[]) {
+              print_message(
+                PrintMessageType::Error,
+                format!("Remote control: \"{}\" failed: {}", command, err).as_str(),
+              );
             }
+          } else if remote::handle_message(
+            &mpv,
+            &json_message,
+            transcode_offset,
+            &mut video.remote_requests,
+          ) {
+            paused = mpv.get_property("pause").unwrap_or(paused);
+            // report the new state right away
+            old_pos -= 16.0;
+          }
+          if let Some(syncplay) = self.syncplay.as_mut() {
+            syncplay.receive(&json_message);
           }
         }
       }
+      if let Some(syncplay) = self.syncplay.as_mut()
+        && let Some(media_center) = media_center.as_mut()
+        && syncplay.update(&mpv, media_center).await
+      {
+        mpv.command("quit", &["0"])?;
+      }
       while let Some(event_res) = mpv.wait_event(0.0) {
         let event = if let Ok(event) = event_res {
           event
@@ -425,43 +469,160 @@ impl Player {
           eprintln!("No idea why this would happen. Please create an issue. :)");
           break 'main;
         };
+        let shutdown = matches!(event, Event::Shutdown);
         match event {
+          Event::StartFile => {
+            // mpv moved on to another track of the queue
+            if let Some(position) = mpv
+              .get_property::<i64>("playlist-pos")
+              .ok()
+              .and_then(|position| usize::try_from(position).ok())
+              && position != track_index
+              && let Some(track) = tracks.get(position)
+            {
+              track_index = position;
+              let remote_requests = std::mem::take(&mut video.remote_requests);
+              video = track.clone();
+              video.remote_requests = remote_requests;
+              finished = false;
+              started_at = chrono::Local::now();
+              total_runtime = video.total_runtime as f64;
+              old_pos = -15.0;
+              last_time_update = 0.0;
+              mpv.set_property(
+                "force-media-title",
+                format!(
+                  "{} | {}",
+                  video.title[0],
+                  config.media_center_type.to_string()
+                ),
+              )?;
+              if let Some(media_center) = media_center.as_mut() {
+                if let Some(playback_info) = &video.playback_info {
+                  media_center.insert_value(MediaCenterValues::PlaybackInfo, playback_info.clone());
+                }
+                media_center
+                  .start_playback(video.clone().id, video.playback_position)
+                  .await;
+              }
+            }
+          },
           Event::FileLoaded => {
-            if resume_progress != 0 && !config.transcoding {
-              mpv
-                .command("seek", &[&resume_progress.to_string()])
-                .expect("Failed to seek");
+            // local files don't come with a runtime
+            if video.total_runtime == 0
+              && let Ok(duration) = mpv.get_property::<f64>("duration")
+            {
+              video.total_runtime = duration.round() as u64;
+              total_runtime = video.total_runtime as f64;
             }
             // let's hope loading external subs isn't async ...
-            load_external_subtitles(self.video.clone().unwrap(), &mpv);
-            if let Some(audio_track_) = initial_preferences.0 {
-              mpv
-                .set_property("aid", audio_track_ as i64)
-                .expect("Failed to set preferred audio track.");
+            load_external_subtitles(video.clone(), &mpv);
+            // the resume position and the preferred tracks belong to the first item only
+            if track_index == 0 && !initial_load_done {
+              initial_load_done = true;
+              if resume_progress != 0
+                && !config.transcoding
+                && let Err(err) = mpv.command("seek", &[&resume_progress.to_string()])
+              {
+                print_message(
+                  PrintMessageType::Error,
+                  format!("Failed to seek: {}", err).as_str(),
+                );
+              }
+              if let Some(audio_track_) = initial_preferences.0
+                && let Err(err) = mpv.set_property("aid", audio_track_ as i64)
+              {
+                print_message(
+                  PrintMessageType::Error,
+                  format!("Failed to set preferred audio track: {}", err).as_str(),
+                );
+              }
+              if let Some(subtitle_track_) = initial_preferences.1
+                && let Err(err) = mpv.set_property("sid", subtitle_track_ as i64)
+              {
+                print_message(
+                  PrintMessageType::Error,
+                  format!("Failed to set preferred subtitle track: {}", err).as_str(),
+                );
+              }
             }
-            if let Some(subtitle_track_) = initial_preferences.1 {
-              mpv
-                .set_property("sid", subtitle_track_ as i64)
-                .expect("Failed to set preferred subtitle track.");
+            if let Some(syncplay) = self.syncplay.as_mut() {
+              syncplay.file_loaded();
             }
           },
           Event::Shutdown | Event::EndFile(_) => {
-            video.played = media_center
-              .stop_playback(
-                video.clone().id,
-                video.clone().playback_position,
-                video.clone().total_runtime,
-                old_pos,
-              )
-              .await;
-            if self.settings.discord_presence {
-              discord.stop();
+            if !finished {
+              finished = true;
+              // tracks are too short to wait for the next report
+              let position = if video.video_type == VideoType::Track {
+                last_time_update
+              } else {
+                old_pos
+              };
+              video.played = if video.video_type == VideoType::Track {
+                let listened = listened_enough(video.total_runtime, position);
+                if let Some(media_center) = media_center.as_mut() {
+                  media_center
+                    .stop_track_playback(video.clone().id, position)
+                    .await;
+                }
+                listened
+              } else if let Some(media_center) = media_center.as_mut() {
+                media_center
+                  .stop_playback(
+                    video.clone().id,
+                    video.clone().playback_position,
+                    video.clone().total_runtime,
+                    old_pos,
+                  )
+                  .await
+              } else {
+                watched_enough(video.total_runtime, old_pos)
+              };
+              if self.settings.discord_presence {
+                discord.stop();
+              }
+              let mut stop_position = position.max(0.0) as u64;
+              if config.transcoding {
+                stop_position += video.playback_position;
+              }
+              let entry = HistoryEntry {
+                server_name: config.server_name.clone(),
+                media_center_type: config.media_center_type.to_string(),
+                item_id: video.id.clone(),
+                title: video.title[0].clone(),
+                started_at,
+                stopped_at: chrono::Local::now(),
+                start_position: video.playback_position,
+                stop_position,
+                duration: video.total_runtime,
+                // 0 means that no track was selected
+                audio_track: video.preferred_audio_track.filter(|track| *track != 0),
+                subtitle_track: video.preferred_subtitle_track.filter(|track| *track != 0),
+                played: video.played,
+              };
+              if let Err(err) = History::new().add(&entry) {
+                print_message(
+                  PrintMessageType::Error,
+                  format!("Failed to save playback history: {}", err).as_str(),
+                );
+              }
+              video.playback_position = stop_position;
+            }
+            // the next track starts by itself, mpv quits after the last one
+            if tracks.len() > 1 && !shutdown {
+              continue;
             }
             break 'main;
           },
           Event::Seek | Event::PlaybackRestart => {
             old_pos -= 16.0;
           },
+          Event::ClientMessage(message) if message.first() == Some(&"puddler-skip") => {
+            if let Some(segment) = current_segment.map(|index| &video.segments[index]) {
+              skip_segment(&mpv, segment, transcode_offset);
+            }
+          },
           _ => {
             // println!("{:#?}", event); // for debugging
           },
@@ -469,6 +630,26 @@ impl Player {
       }
       let result: Result<f64, libmpv2::Error> = mpv.get_property("time-pos");
       if let Ok(current_time) = result {
+        let segment = segment_at(&video.segments, current_time + transcode_offset as f64);
+        if segment != current_segment {
+          current_segment = segment;
+          if let Some(index) = segment {
+            let segment = &video.segments[index];
+            if skip_with_tab {
+              let prompt = format!("Press TAB to skip the {}", segment.segment_type);
+              if let Err(err) = mpv.command("show-text", &[&prompt, "5000"]) {
+                print_message(
+                  PrintMessageType::Error,
+                  format!("Failed to show the skip prompt: {}", err).as_str(),
+                );
+              }
+            } else if !skipped_segments.contains(&index) {
+              // seeking back into a skipped segment plays it
+              skipped_segments.push(index);
+              skip_segment(&mpv, segment, transcode_offset);
+            }
+          }
+        }
         if let Ok(track) = mpv.get_property::<String>("current-tracks/audio/src-id") {
           audio_track = track.parse::<u32>().unwrap();
         }
@@ -491,22 +672,40 @@ impl Player {
         } else {
           video.preferred_subtitle_track = Some(0);
         }
+        timeline_sender.send_if_modified(|timeline| {
+          let current = Timeline {
+            state: if mpv.get_property("pause").unwrap_or(false) {
+              String::from("paused")
+            } else {
+              String::from("playing")
+            },
+            rating_key: Some(video.id.clone()),
+            time: ((current_time + transcode_offset as f64) * 1000.0) as u64,
+            duration: video.total_runtime * 1000,
+            volume: volume_level,
+          };
+          let changed = current.differs(timeline);
+          *timeline = current;
+          changed
+        });
         if current_time > old_pos + 15.0 {
           if paused {
             paused = false;
           }
-          media_center
-            .report_playback(
-              video.clone().id,
-              video.playback_position,
-              current_time,
-              audio_track,
-              sub_track,
-              paused,
-              muted,
-              volume_level,
-            )
-            .await;
+          if let Some(media_center) = media_center.as_mut() {
+            media_center
+              .report_playback(
+                video.clone().id,
+                video.playback_position,
+                current_time,
+                audio_track,
+                sub_track,
+                paused,
+                muted,
+                volume_level,
+              )
+              .await;
+          }
           if self.settings.discord_presence {
             if video.video_type == VideoType::Movie {
               discord.update_presence(
@@ -532,18 +731,20 @@ impl Player {
             continue;
           }
           paused = true;
-          media_center
-            .report_playback(
-              video.clone().id,
-              video.playback_position,
-              current_time,
-              audio_track,
-              sub_track,
-              paused,
-              muted,
-              volume_level,
-            )
-            .await;
+          if let Some(media_center) = media_center.as_mut() {
+            media_center
+              .report_playback(
+                video.clone().id,
+                video.playback_position,
+                current_time,
+                audio_track,
+                sub_track,
+                paused,
+                muted,
+                volume_level,
+              )
+              .await;
+          }
           if self.settings.discord_presence {
             if video.video_type == VideoType::Movie {
               discord.pause(
@@ -569,10 +770,52 @@ impl Player {
       input.send("stop".to_string()).unwrap()
     }
     drop(mpv);
+    if has_chapters {
+      let _ = std::fs::remove_file(chapters_path);
+    }
     // websocket_write_handle.abort();
     websocket_read_handle.abort();
     clear_stdin();
-    video
+    Ok(video)
+  }
+}
+
+pub fn websocket_url<M: MediaCenter + ?Sized>(media_center: &mut M) -> String {
+  let http_address = media_center.get_address();
+  let protocol = if http_address.contains("https") {
+    "wss"
+  } else {
+    "ws"
+  };
+  let address: String = http_address
+    .trim_start_matches("https")
+    .trim_start_matches("http")
+    .trim_end_matches('/')
+    .to_string();
+  let headers = media_center.get_headers();
+  let token = &headers.get(2).unwrap().1;
+  // the "/socket" is mandatory for jellyfin; emby doesn't care either way. documentation on this is fucking terrible
+  format!(
+    "{}{}/socket?api_key={}&deviceId={}",
+    protocol,
+    address,
+    token,
+    media_center.get_config_handle().get_device_id()
+  )
+}
+
+fn skip_segment(mpv: &Mpv, segment: &Segment, transcode_offset: u64) {
+  let target = (segment.end - transcode_offset as f64).max(0.0);
+  if let Err(err) = mpv.command("seek", &[&target.to_string(), "absolute"]) {
+    print_message(
+      PrintMessageType::Error,
+      format!("Failed to skip the {}: {}", segment.segment_type, err).as_str(),
+    );
+  } else {
+    let _ = mpv.command(
+      "show-text",
+      &[&format!("Skipped the {}", segment.segment_type), "2000"],
+    );
   }
 }
 
@@ -580,9 +823,16 @@ fn load_external_subtitles(video: Video, mpv: &Mpv) {
   if let Some(commands) = video.external_media {
     for command in commands {
       let [media_url, auto, formatted_title, language] = command;
-      mpv
-        .command("sub-add", &[&media_url, &auto, &formatted_title, &language])
-        .unwrap();
+      if let Err(err) = mpv.command("sub-add", &[&media_url, &auto, &formatted_title, &language]) {
+        print_message(
+          PrintMessageType::Error,
+          format!(
+            "Failed to load external subtitle {}: {}",
+            formatted_title, err
+          )
+          .as_str(),
+        );
+      }
     }
   }
 }
@@ -0,0 +1,162 @@
+use colored::Colorize;
+
+use crate::{
+  error::PuddlerError,
+  input::{InteractiveOption, InteractiveOptionType, interactive_select},
+  media_center::{MediaCenter, MediaCenterValues},
+  media_item::{MediaItem, MediaKind, PlaybackSource},
+  mpv::Player,
+  printing::{PrintMessageType, print_message},
+};
+
+// Shared by every media-center. They only list the children of an item (`MediaCenter::music_children`)
+// and tell where a track is streamed from (`MediaCenter::track_source`).
+
+// Servers count a track as played once 90% of it have been listened to.
+pub fn listened_enough(total_runtime: u64, time_pos: f64) -> bool {
+  time_pos >= total_runtime as f64 * 0.9
+}
+
+pub fn music_menu<M: MediaCenter + ?Sized>(media_center: &mut M) -> Result<(), PuddlerError> {
+  let artists = media_center.music_children(None)?;
+  if artists.is_empty() {
+    print_message(
+      PrintMessageType::Warning,
+      "There is no music on this media-center.",
+    );
+    return Ok(());
+  }
+  loop {
+    let mut options: Vec<InteractiveOption> = vec![InteractiveOption {
+      text: String::from("Artists:"),
+      option_type: InteractiveOptionType::Header,
+    }];
+    for artist in &artists {
+      options.push(InteractiveOption {
+        text: artist.to_string(),
+        option_type: InteractiveOptionType::Button,
+      });
+    }
+    options.push(InteractiveOption {
+      text: String::from("Back"),
+      option_type: InteractiveOptionType::Special,
+    });
+    match interactive_select(options) {
+      ((index, _), _, InteractiveOptionType::Button) => {
+        if let Err(err) = process_music_item(media_center, artists[index].clone()) {
+          print_message(PrintMessageType::Error, &err.to_string());
+        }
+      },
+      _ => return Ok(()),
+    }
+  }
+}
+
+// Artists lead to their albums, albums to their tracks. Tracks are always played with the rest of their album.
+pub fn process_music_item<M: MediaCenter + ?Sized>(
+  media_center: &mut M,
+  item: MediaItem,
+) -> Result<(), PuddlerError> {
+  match item.kind {
+    MediaKind::Artist => {
+      let albums = media_center.music_children(Some(&item))?;
+      let mut options: Vec<InteractiveOption> = vec![InteractiveOption {
+        text: format!("Albums of {}:", item.to_string().cyan()),
+        option_type: InteractiveOptionType::Header,
+      }];
+      for album in &albums {
+        options.push(InteractiveOption {
+          text: album.to_string(),
+          option_type: InteractiveOptionType::Button,
+        });
+      }
+      options.push(InteractiveOption {
+        text: String::from("Back"),
+        option_type: InteractiveOptionType::Special,
+      });
+      match interactive_select(options) {
+        ((index, _), _, InteractiveOptionType::Button) => {
+          process_music_item(media_center, albums[index].clone())
+        },
+        _ => Ok(()),
+      }
+    },
+    MediaKind::Album => {
+      let tracks = media_center.music_children(Some(&item))?;
+      let mut options: Vec<InteractiveOption> = vec![
+        InteractiveOption {
+          text: format!("{}:", item.to_string().cyan()),
+          option_type: InteractiveOptionType::Header,
+        },
+        InteractiveOption {
+          text: String::from("Play album"),
+          option_type: InteractiveOptionType::Button,
+        },
+      ];
+      for track in &tracks {
+        // the last title is "01. Name"
+        options.push(InteractiveOption {
+          text: track.titles.last().cloned().unwrap_or_default(),
+          option_type: InteractiveOptionType::Button,
+        });
+      }
+      options.push(InteractiveOption {
+        text: String::from("Back"),
+        option_type: InteractiveOptionType::Special,
+      });
+      match interactive_select(options) {
+        ((0, _), _, InteractiveOptionType::Button) => play_tracks(media_center, tracks),
+        ((index, _), _, InteractiveOptionType::Button) => {
+          play_tracks(media_center, tracks[index - 1..].to_vec())
+        },
+        _ => Ok(()),
+      }
+    },
+    MediaKind::Track => {
+      let mut tracks = media_center.music_children(Some(&item))?;
+      if let Some(position) = tracks.iter().position(|track| track.id == item.id) {
+        tracks.drain(..position);
+      } else {
+        tracks = vec![item];
+      }
+      play_tracks(media_center, tracks)
+    },
+    _ => Ok(()),
+  }
+}
+
+// Music is always direct-played, there is nothing to transcode.
+pub fn play_tracks<M: MediaCenter + ?Sized>(
+  media_center: &mut M,
+  tracks: Vec<MediaItem>,
+) -> Result<(), PuddlerError> {
+  let mut queue: Vec<(MediaItem, PlaybackSource)> = vec![];
+  for mut track in tracks {
+    let Some(source) = media_center.track_source(&track)? else {
+      print_message(
+        PrintMessageType::Warning,
+        format!("\"{}\" can't be played.", track).as_str(),
+      );
+      continue;
+    };
+    // tracks always start from the beginning
+    track.playback_position = 0;
+    queue.push((track, source));
+  }
+  let Some((_, first)) = queue.first() else {
+    return Ok(());
+  };
+  if let Some(playback_info) = first.playback_info.clone() {
+    media_center.insert_value(MediaCenterValues::PlaybackInfo, playback_info);
+  }
+  let settings = media_center.get_settings().clone();
+  let mut player = Player::new(media_center.get_config_handle().clone(), settings);
+  media_center.update_player(&mut player);
+  println!(
+    "Playing {} tracks. Use < and > to skip, q to stop.",
+    queue.len()
+  );
+  player.set_tracks(queue);
+  player.play()?;
+  Ok(())
+}
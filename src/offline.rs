@@ -0,0 +1,443 @@
+use colored::Colorize;
+use reqwest::{StatusCode, blocking::Client};
+use serde::{Deserialize, Serialize};
+use std::{
+  fmt,
+  fs::{self, File},
+  io::{Read, Write, stdout},
+  path::{Path, PathBuf},
+};
+
+use crate::{
+  APPNAME,
+  error::PuddlerError,
+  input::{InteractiveOption, InteractiveOptionType, interactive_select},
+  media_center::MediaCenter,
+  media_config::{Config, MediaCenterType},
+  mpv::Player,
+  printing::{PrintMessageType, print_message},
+  puddler_settings::PuddlerSettings,
+};
+
+// Everything the media-centers need to tell us about an item, before it can be downloaded.
+pub struct Download {
+  pub item_id: String,
+  pub title: Vec<String>,
+  pub movie: bool,
+  pub extension: String,
+  pub url: String,
+  pub subtitles: Vec<DownloadSubtitle>,
+  pub total_runtime: u64,
+  pub playback_position: u64,
+  pub played: bool,
+}
+
+pub struct DownloadSubtitle {
+  pub url: String,
+  pub extension: String,
+  pub title: String,
+  pub language: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OfflineSubtitle {
+  pub file: String,
+  pub title: String,
+  pub language: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OfflineItem {
+  // path of the media-center config, used to sync the playstate later on
+  pub server_path: String,
+  pub server_name: String,
+  pub media_center_type: MediaCenterType,
+  pub item_id: String,
+  pub title: Vec<String>,
+  pub movie: bool,
+  // file names are relative to the offline folder
+  pub file: String,
+  pub subtitles: Vec<OfflineSubtitle>,
+  // in seconds
+  pub total_runtime: u64,
+  pub playback_position: u64,
+  pub played: bool,
+  // false, if the item has been marked as played offline and the server doesn't know yet
+  pub synced: bool,
+}
+
+impl fmt::Display for OfflineItem {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "{} ({}){}{}",
+      self.title[0],
+      self.server_name,
+      if self.played { " [Played]" } else { "" },
+      if self.synced { "" } else { " [Not synced]" }
+    )
+  }
+}
+
+pub enum ItemAction {
+  Play,
+  Download,
+  DownloadAll,
+  Queue,
+  QueueAll,
+  Back,
+}
+
+pub fn get_offline_folder() -> PathBuf {
+  let cache_path = dirs::cache_dir().unwrap();
+  let mut offline_path = format!(
+    "{}/{}/offline",
+    &cache_path.display().to_string(),
+    APPNAME.to_lowercase()
+  );
+  if cfg!(windows) {
+    offline_path = offline_path.replace('/', "\\");
+  }
+  PathBuf::from(offline_path)
+}
+
+// The files and the library index live in the same folder, so deleting the folder resets everything.
+pub struct OfflineLibrary {
+  folder: PathBuf,
+  pub items: Vec<OfflineItem>,
+}
+
+impl OfflineLibrary {
+  pub fn open() -> Result<Self, PuddlerError> {
+    Self::at(get_offline_folder())
+  }
+
+  pub fn at(folder: PathBuf) -> Result<Self, PuddlerError> {
+    let index = folder.join("library.json");
+    let items = if index.exists() {
+      serde_json::from_str::<Vec<OfflineItem>>(&fs::read_to_string(index)?)?
+    } else {
+      vec![]
+    };
+    Ok(OfflineLibrary { folder, items })
+  }
+
+  pub fn save(&self) -> Result<(), PuddlerError> {
+    fs::create_dir_all(&self.folder)?;
+    fs::write(
+      self.folder.join("library.json"),
+      serde_json::to_string_pretty(&self.items)?,
+    )?;
+    Ok(())
+  }
+
+  pub fn get_path(&self, file: &str) -> PathBuf {
+    self.folder.join(file)
+  }
+
+  // Existing downloads of the same item are replaced.
+  pub fn download(&mut self, config: &Config, download: Download) -> Result<(), PuddlerError> {
+    let item_folder = format!(
+      "{}-{}",
+      config.config.server_name.replace(['/', '\\', ' '], "_"),
+      download.item_id
+    );
+    fs::create_dir_all(self.folder.join(&item_folder))?;
+    // the urls contain the access token already
+    let client = Client::builder().timeout(None).build()?;
+
+    let file = format!("{}/video.{}", item_folder, download.extension);
+    println!("Downloading: {}", download.title[0].cyan());
+    download_file(&client, &download.url, &self.get_path(&file))?;
+
+    let mut subtitles: Vec<OfflineSubtitle> = vec![];
+    for (index, subtitle) in download.subtitles.iter().enumerate() {
+      let subtitle_file = format!("{}/subtitle-{}.{}", item_folder, index, subtitle.extension);
+      if let Err(err) = download_file(&client, &subtitle.url, &self.get_path(&subtitle_file)) {
+        print_message(
+          PrintMessageType::Error,
+          format!(
+            "Failed to download subtitle \"{}\": {}",
+            subtitle.title, err
+          )
+          .as_str(),
+        );
+        continue;
+      }
+      subtitles.push(OfflineSubtitle {
+        file: subtitle_file,
+        title: subtitle.title.clone(),
+        language: subtitle.language.clone(),
+      });
+    }
+
+    self
+      .items
+      .retain(|item| !(item.server_path == config.path && item.item_id == download.item_id));
+    self.items.push(OfflineItem {
+      server_path: config.path.clone(),
+      server_name: config.config.server_name.clone(),
+      media_center_type: config.config.media_center_type,
+      item_id: download.item_id,
+      title: download.title,
+      movie: download.movie,
+      file,
+      subtitles,
+      total_runtime: download.total_runtime,
+      playback_position: download.playback_position,
+      played: download.played,
+      synced: true,
+    });
+    self.save()?;
+    print_message(PrintMessageType::Success, "Download finished.");
+    Ok(())
+  }
+
+  pub fn remove(&mut self, index: usize) -> Result<(), PuddlerError> {
+    let item = self.items.remove(index);
+    if let Some(item_folder) = self.get_path(&item.file).parent()
+      && item_folder.exists()
+    {
+      fs::remove_dir_all(item_folder)?;
+    }
+    self.save()
+  }
+
+  // Only "played" can be synced, since `set_playstate` doesn't know about positions.
+  // Items that fail are tried again the next time.
+  pub fn sync_playstate(&mut self, media_center: &mut dyn MediaCenter) -> Result<(), PuddlerError> {
+    let server_path = media_center.get_config_handle().path.clone();
+    let mut changed = false;
+    for item in self
+      .items
+      .iter_mut()
+      .filter(|item| item.server_path == server_path && !item.synced)
+    {
+      match media_center.set_playstate(&item.item_id, item.played) {
+        Ok(()) => {
+          item.synced = true;
+          changed = true;
+        },
+        Err(err) => print_message(
+          PrintMessageType::Error,
+          format!(
+            "Failed to sync \"{}\": {}",
+            item.title.first().cloned().unwrap_or_default(),
+            err
+          )
+          .as_str(),
+        ),
+      }
+    }
+    if changed {
+      print_message(
+        PrintMessageType::Success,
+        "Synced the playstate of offline items.",
+      );
+      self.save()?;
+    }
+    Ok(())
+  }
+}
+
+fn download_file(client: &Client, url: &str, path: &Path) -> Result<(), PuddlerError> {
+  let mut response = client.get(url).send()?;
+  if response.status() != StatusCode::OK {
+    return Err(PuddlerError::from_status(
+      response.status(),
+      response.text().unwrap_or_default(),
+    ));
+  }
+  let total = response.content_length();
+  // write to a temporary file first, so that aborted downloads don't look finished
+  let part_path = path.with_extension("part");
+  let mut file = File::create(&part_path)?;
+  let mut buffer = vec![0; 1024 * 64];
+  let mut downloaded: u64 = 0;
+  let mut stdout = stdout();
+  loop {
+    let read = response.read(&mut buffer)?;
+    if read == 0 {
+      break;
+    }
+    file.write_all(&buffer[..read])?;
+    downloaded += read as u64;
+    if let Some(total) = total {
+      print!("\r  {:.1}%", downloaded as f64 / total as f64 * 100.0);
+      stdout.flush()?;
+    }
+  }
+  if total.is_some() {
+    println!();
+  }
+  fs::rename(part_path, path)?;
+  Ok(())
+}
+
+// Asked before anything is played, so that the items can be downloaded or queued instead.
+pub fn choose_action(playlist_len: usize) -> ItemAction {
+  let mut options: Vec<InteractiveOption> = vec![
+    InteractiveOption {
+      text: String::from("Play"),
+      option_type: InteractiveOptionType::Button,
+    },
+    InteractiveOption {
+      text: String::from("Download"),
+      option_type: InteractiveOptionType::Button,
+    },
+  ];
+  if playlist_len > 1 {
+    options.push(InteractiveOption {
+      text: format!("Download all ({} items)", playlist_len),
+      option_type: InteractiveOptionType::Button,
+    });
+  }
+  options.push(InteractiveOption {
+    text: String::from("Add to queue"),
+    option_type: InteractiveOptionType::Button,
+  });
+  if playlist_len > 1 {
+    options.push(InteractiveOption {
+      text: format!("Add all to queue ({} items)", playlist_len),
+      option_type: InteractiveOptionType::Button,
+    });
+  }
+  options.push(InteractiveOption {
+    text: String::from("Back"),
+    option_type: InteractiveOptionType::Special,
+  });
+  match interactive_select(options) {
+    ((0, _), ..) => ItemAction::Play,
+    (_, Some(text), InteractiveOptionType::Button) => match text.as_str() {
+      "Download" => ItemAction::Download,
+      "Add to queue" => ItemAction::Queue,
+      text if text.starts_with("Download all") => ItemAction::DownloadAll,
+      text if text.starts_with("Add all") => ItemAction::QueueAll,
+      _ => ItemAction::Back,
+    },
+    _ => ItemAction::Back,
+  }
+}
+
+// Called once the media-center is reachable again.
+pub fn sync_playstate(media_center: &mut dyn MediaCenter) {
+  match OfflineLibrary::open() {
+    Ok(mut library) => {
+      if let Err(err) = library.sync_playstate(media_center) {
+        print_message(
+          PrintMessageType::Error,
+          format!("Failed to sync offline items: {}", err).as_str(),
+        );
+      }
+    },
+    Err(err) => print_message(
+      PrintMessageType::Error,
+      format!("Failed to read the offline library: {}", err).as_str(),
+    ),
+  }
+}
+
+pub fn offline_menu(settings: &PuddlerSettings) {
+  let mut library = match OfflineLibrary::open() {
+    Ok(library) => library,
+    Err(err) => {
+      print_message(PrintMessageType::Error, &err.to_string());
+      return;
+    },
+  };
+  loop {
+    if library.items.is_empty() {
+      print_message(
+        PrintMessageType::Warning,
+        "Nothing has been downloaded yet.",
+      );
+      return;
+    }
+    let mut options: Vec<InteractiveOption> = vec![InteractiveOption {
+      text: String::from("Offline library:"),
+      option_type: InteractiveOptionType::Header,
+    }];
+    for item in &library.items {
+      options.push(InteractiveOption {
+        text: item.to_string(),
+        option_type: InteractiveOptionType::Button,
+      });
+    }
+    options.push(InteractiveOption {
+      text: String::from("Back"),
+      option_type: InteractiveOptionType::Special,
+    });
+    let index = match interactive_select(options) {
+      ((index, _), _, InteractiveOptionType::Button) => index,
+      _ => return,
+    };
+
+    let options = vec![
+      InteractiveOption {
+        text: format!("Selected: {}", library.items[index].title[0].clone().cyan()),
+        option_type: InteractiveOptionType::Header,
+      },
+      InteractiveOption {
+        text: String::from("Play"),
+        option_type: InteractiveOptionType::Button,
+      },
+      InteractiveOption {
+        text: String::from("Delete"),
+        option_type: InteractiveOptionType::Button,
+      },
+      InteractiveOption {
+        text: String::from("Back"),
+        option_type: InteractiveOptionType::Special,
+      },
+    ];
+    let result = match interactive_select(options) {
+      ((0, _), _, InteractiveOptionType::Button) => {
+        play_offline_item(&mut library, index, settings)
+      },
+      ((1, _), _, InteractiveOptionType::Button) => library.remove(index),
+      _ => Ok(()),
+    };
+    if let Err(err) = result {
+      print_message(PrintMessageType::Error, &err.to_string());
+    }
+  }
+}
+
+fn play_offline_item(
+  library: &mut OfflineLibrary,
+  index: usize,
+  settings: &PuddlerSettings,
+) -> Result<(), PuddlerError> {
+  let item = library.items[index].clone();
+  let mut handle = Config::default();
+  handle.path = item.server_path.clone();
+  if handle.read().is_err() {
+    // the media-center has been removed since, that's fine as long as we don't need to sync
+    handle.config.server_name = item.server_name.clone();
+    handle.config.media_center_type = item.media_center_type;
+  }
+  // there is nothing to transcode
+  handle.config.transcoding = false;
+
+  let mut player = Player::new(handle, settings.clone());
+  player.set_offline_video(&item, library);
+  let ret = player.play()?;
+
+  let offline_item = &mut library.items[index];
+  if ret.played {
+    offline_item.playback_position = 0;
+    if !offline_item.played {
+      offline_item.played = true;
+      offline_item.synced = false;
+    }
+  } else {
+    offline_item.playback_position = ret.playback_position;
+  }
+  library.save()
+}
+
+// Same rules as the media-centers: less than 15% or 5 minutes left.
+pub fn watched_enough(total_runtime: u64, time_pos: f64) -> bool {
+  let remaining_time = total_runtime as f64 - time_pos;
+  remaining_time / (total_runtime as f64) < 0.15 || remaining_time <= 60.0 * 5.0
+}
@@ -5,14 +5,13 @@ use crossterm::{
   execute,
   style::Stylize,
   terminal::{
-    self, Clear, ClearType, DisableLineWrap, EnableLineWrap, EnterAlternateScreen,
-    LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+    Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
   },
 };
 use isolanguage_1::LanguageCode;
 use regex::Regex;
 use reqwest::{
-  StatusCode,
+  Method, StatusCode,
   blocking::{Client, Response},
 };
 use serde::{Deserialize, Serialize};
@@ -20,26 +19,36 @@ use serde_json::Value;
 use std::{
   fmt,
   io::{Write, stdin, stdout},
-  process::exit,
   str::FromStr,
   sync::mpsc,
   thread::{self, sleep},
   time::Duration,
 };
+use tokio::runtime::Runtime;
 
 use crate::{
   APPNAME, VERSION,
-  input::{
-    InteractiveOption, InteractiveOptionType, SeriesOptions, getch, interactive_select,
-    plex_series_select, take_string_input,
-  },
+  backend::process_item,
+  cli::{Listable, Listing, OutputFormat, print_items},
+  companion::{self, CompanionInfo, CompanionStream},
+  error::PuddlerError,
+  input::{InteractiveOption, InteractiveOptionType, getch, interactive_select, take_string_input},
+  library::{Category, Library, PAGE_SIZE, PageAction, SortOrder, plex_page_url, select_from_page},
   media_center::{IsNumeric, MediaCenter, MediaCenterValues, ToStringAdv},
   media_config::{Config, Objective, UserConfig},
-  mpv::Player,
+  media_item::{MediaItem, MediaKind, PlaybackSource},
+  mpv::{Player, Video},
+  offline::{Download, DownloadSubtitle},
   printing::{PrintMessageType, print_message},
   puddler_settings::PuddlerSettings,
+  queue::load_queue,
+  receiver::{RECONNECT_DELAY, play_request_items},
+  search::{SearchQuery, parse_search},
+  segments::{Chapter, Segment, SegmentType},
 };
 
+const PLEX_TV_ADDRESS: &str = "https://plex.tv/";
+
 const PLEX_CLIENT_PROFILES: &str = "add-direct-play-profile(
 type=videoProfile
 &protocol=http
@@ -81,6 +90,7 @@ struct MediaContainer {
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 struct PlexHub {
   r#type: String,
+  hubIdentifier: Option<String>,
   size: u32,
   Metadata: Option<Vec<PlexItem>>,
 }
@@ -105,6 +115,39 @@ pub struct PlexItem {
   viewedLeafCount: Option<u32>,
   index: Option<u32>,
   pub viewOffset: Option<u64>,
+  // only with "includeMarkers=1" and "includeChapters=1"
+  Marker: Option<Vec<PlexMarker>>,
+  Chapter: Option<Vec<PlexChapter>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+struct PlexChapter {
+  tag: Option<String>,
+  index: u32,
+  startTimeOffset: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+struct PlexMarker {
+  r#type: String,
+  startTimeOffset: u64,
+  endTimeOffset: u64,
+}
+
+impl PlexMarker {
+  fn to_segment(&self) -> Option<Segment> {
+    let segment_type = match self.r#type.as_str() {
+      "intro" => SegmentType::Intro,
+      "credits" => SegmentType::Outro,
+      "commercial" => SegmentType::Commercial,
+      _ => return None,
+    };
+    Some(Segment {
+      segment_type,
+      start: self.startTimeOffset as f64 / 1000.0,
+      end: self.endTimeOffset as f64 / 1000.0,
+    })
+  }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
@@ -112,9 +155,10 @@ pub struct PlexMediaFile {
   id: u64,
   duration: u64,
   bitrate: u64,
-  width: u64,
-  height: u64,
-  videoCodec: String,
+  // not there for music
+  width: Option<u64>,
+  height: Option<u64>,
+  videoCodec: Option<String>,
   deletedAt: Option<u64>,
   pub Part: Vec<PlexMediaPart>,
 }
@@ -229,18 +273,11 @@ pub struct PlexServer {
   session_id: Option<String>,
   settings: PuddlerSettings,
   playback_info: Option<PlexItem>,
-}
-
-#[derive(Debug, Deserialize, PartialEq, Clone)]
-pub struct Series {
-  item_id: String,
-  pub seasons: Vec<Season>,
-}
-
-#[derive(Debug, Deserialize, PartialEq, Clone)]
-pub struct Season {
-  pub item: PlexItem,
-  pub episodes: Vec<PlexItem>,
+  client: Client,
+  plex_tv_address: String,
+  // only needed for the companion timeline and playlists
+  machine_identifier: Option<String>,
+  queue: Vec<MediaItem>,
 }
 
 impl ToStringAdv for PlexItem {
@@ -268,6 +305,18 @@ impl ToStringAdv for PlexItem {
       "movie" | "show" => {
         vec![format!("{} {}", name, time)]
       },
+      "artist" | "album" => vec![self.to_string()],
+      "track" => {
+        vec![
+          self.to_string(),
+          format!(
+            "{} - {}",
+            self.grandparentTitle.clone().unwrap_or(String::from("???")),
+            self.parentTitle.clone().unwrap_or(String::from("???"))
+          ),
+          format!("{:02}. {}", self.index.unwrap_or(0), self.title),
+        ]
+      },
       "season" => {
         vec![
           self.to_string(),
@@ -325,6 +374,16 @@ impl ToStringAdv for PlexItem {
   }
 }
 
+impl Listable for PlexItem {
+  fn id(&self) -> String {
+    self.ratingKey.clone()
+  }
+
+  fn item_type(&self) -> String {
+    self.r#type.clone()
+  }
+}
+
 impl ToString for PlexItem {
   fn to_string(&self) -> String {
     let time = if let Some(production_year) = &self.year {
@@ -350,6 +409,20 @@ impl ToString for PlexItem {
       "movie" | "show" => {
         format!("{} {}", name, time)
       },
+      "artist" => name,
+      "album" => format!(
+        "{} - {} {}",
+        self.parentTitle.clone().unwrap_or(String::from("???")),
+        name,
+        time
+      ),
+      "track" => format!(
+        "{} - {} - {:02}. {}",
+        self.grandparentTitle.clone().unwrap_or(String::from("???")),
+        self.parentTitle.clone().unwrap_or(String::from("???")),
+        self.index.unwrap_or(0),
+        self.title
+      ),
       "season" => {
         format!("{} {} - {}", name, time, self.title.clone())
       },
@@ -385,6 +458,10 @@ impl MediaCenter for PlexServer {
       session_id: None,
       settings,
       playback_info: None,
+      client: Client::new(),
+      plex_tv_address: String::from(PLEX_TV_ADDRESS),
+      machine_identifier: config.get_machine_identifier(),
+      queue: load_queue(&config),
     }
   }
 
@@ -400,6 +477,15 @@ impl MediaCenter for PlexServer {
     self.headers.clone()
   }
 
+  fn get_client(&mut self) -> Client {
+    self.client.clone()
+  }
+
+  #[cfg(test)]
+  fn set_client(&mut self, client: Client) {
+    self.client = client;
+  }
+
   fn insert_value(&mut self, value_type: MediaCenterValues, value: String) {
     match value_type {
       MediaCenterValues::SessionID => {
@@ -423,6 +509,10 @@ impl MediaCenter for PlexServer {
     panic!("You might instead want to call: \"get_plex_playback_info()\"");
   }
 
+  fn get_queue(&mut self) -> &mut Vec<MediaItem> {
+    &mut self.queue
+  }
+
   fn get_session_id(&mut self) -> Option<String> {
     self
       .session_id
@@ -434,124 +524,108 @@ impl MediaCenter for PlexServer {
     player.set_media_center(Box::new(self.clone()));
   }
 
-  fn re_authenticate(&mut self) {
+  fn restore_session(&mut self) -> bool {
     let config = self.get_config_handle();
-    if config.get_active_user().is_some() && self.check_token_valid() {
-      return;
+    config.get_active_user().is_some() && self.check_token_valid()
+  }
+
+  fn re_authenticate(&mut self) -> Result<(), PuddlerError> {
+    if self.restore_session() {
+      return Ok(());
     }
-    let access_token = self.create_plex_user();
+    let access_token = self.create_plex_user()?;
     let config = self.get_config_handle();
     if config.get_address().unwrap().is_empty() {
-      self.choose_servers(access_token.clone());
-      self.get_username(access_token);
-    } else {
-      let mut user = config.get_active_user().unwrap();
+      self.choose_servers(access_token.clone())?;
+      self.get_username(access_token)?;
+    } else if let Some(mut user) = config.get_active_user() {
       config.remove_specific_value(Objective::User, serde_json::to_string(&user).unwrap());
       user.access_token = access_token;
       config.insert_specific_value(Objective::User, serde_json::to_string(&user).unwrap());
       config.set_active_user(user.access_token);
       config.save();
+    } else {
+      // the server has been found through GDM, but nobody is logged in yet
+      self.get_username(access_token)?;
     }
+    Ok(())
   }
 
-  fn menu(&mut self) {
-    let mut stdout = stdout();
-    execute!(stdout, Hide).unwrap();
-    print!("Loading menu [0/1]");
-    stdout.flush().unwrap();
-
-    let mut total: Vec<PlexItem> = vec![];
-    let mut options: Vec<InteractiveOption> = vec![];
-    if let Ok(mut items) = self.get_items("library/recentlyAdded".to_string(), false) {
-      items.drain(20..);
-      if !items.is_empty() {
-        options.append(&mut vec![InteractiveOption {
-          text: String::from("Recently Added:"),
-          option_type: InteractiveOptionType::Header,
-        }]);
-      }
-      for item in items.clone() {
-        options.append(&mut vec![InteractiveOption {
-          text: item.to_string_ext(),
-          option_type: InteractiveOptionType::Button,
-        }]);
-      }
-      total.extend(items);
-    } else {
-      exit(1);
-    };
-
-    options.append(&mut vec![
-      InteractiveOption {
-        text: String::new(),
-        option_type: InteractiveOptionType::Header,
-      },
-      InteractiveOption {
-        text: String::from("Search"),
-        option_type: InteractiveOptionType::TextInput,
-      },
-      InteractiveOption {
-        text: format!("Return to {} Menu", APPNAME),
-        option_type: InteractiveOptionType::Special,
-      },
-    ]);
-
-    let menu = options.clone();
-
-    let mut current_items = total.clone();
+  fn play_from_id(&mut self, item_id: String) -> Result<(), PuddlerError> {
+    if self.get_config_handle().config.transcoding {
+      return Err(PuddlerError::Unsupported(String::from(
+        "Transcoding requires user input. Please disable it or use the interactive menu.",
+      )));
+    }
+    let mut item = self.get_item(item_id)?;
+    if item.r#type != "movie" && item.r#type != "episode" {
+      return Err(PuddlerError::Unsupported(format!(
+        "\"{}\" is a {} and cannot be played directly.",
+        item.title, item.r#type
+      )));
+    }
+    // Don't ask which file should be played.
+    if let Some(media_files) = item.Media.as_mut() {
+      media_files.retain(|f| f.deletedAt.is_none());
+      media_files.truncate(1);
+    }
+    let settings = self.get_settings().clone();
+    let mut player = Player::new(self.get_config_handle().clone(), settings);
+    self.play_item(&mut player, item, &mut None)?;
+    Ok(())
+  }
 
+  fn receiver(&mut self) -> Result<(), PuddlerError> {
+    if !self.get_settings().plex_companion {
+      return Err(PuddlerError::Unsupported(String::from(
+        "The receiver mode needs \"Plex Companion\" to be enabled in the settings.",
+      )));
+    }
+    if self.get_config_handle().config.transcoding {
+      return Err(PuddlerError::Unsupported(String::from(
+        "Transcoding requires user input. Please disable it or use the interactive menu.",
+      )));
+    }
+    let info = self.companion_info(None);
+    let runtime = Runtime::new()?;
+    println!(
+      "Waiting for Plex apps to play something on {}.",
+      self.config_handle.config.server_name.clone().cyan()
+    );
     loop {
-      match interactive_select(options.clone()) {
-        (selection, _, InteractiveOptionType::Button) => {
-          self.process_item(current_items[selection.0].clone());
+      let request = match runtime.block_on(companion::wait_for_play_request(info.clone())) {
+        Ok(request) => request,
+        Err(err) => {
+          print_message(
+            PrintMessageType::Error,
+            format!("{} Retrying in {} seconds.", err, RECONNECT_DELAY.as_secs()).as_str(),
+          );
+          sleep(RECONNECT_DELAY);
           continue;
         },
-        (_, Some(mut search), InteractiveOptionType::TextInput) => {
-          search = search.trim().to_owned();
-          if let Ok(items) = self.get_items(
-            format!("hubs/search?query={}", urlencoding::encode(&search)),
-            true,
-          ) {
-            options.clear();
-            options.append(&mut vec![InteractiveOption {
-              text: format!("Search-Result for \"{}\":", search.cyan()),
-              option_type: InteractiveOptionType::Header,
-            }]);
-            for item in items.clone() {
-              options.append(&mut vec![InteractiveOption {
-                text: item.to_string_ext(),
-                option_type: InteractiveOptionType::Button,
-              }]);
-            }
-            options.append(&mut vec![
-              InteractiveOption {
-                text: String::from("Back"),
-                option_type: InteractiveOptionType::Special,
-              },
-              InteractiveOption {
-                text: format!("Return to {} Menu", APPNAME),
-                option_type: InteractiveOptionType::Special,
-              },
-            ]);
-            current_items = items;
-          } else {
-            exit(1);
-          };
-        },
-        (_, Some(option), InteractiveOptionType::Special) => {
-          if option == *"Back" {
-            options = menu.clone();
-            current_items = total.clone();
-          } else if option == format!("Return to {} Menu", APPNAME) {
-            return;
-          }
-        },
-        _ => panic!("UNKOWN OPTION TYPE"),
+      };
+      if let Err(err) = play_request_items(self, request) {
+        print_message(PrintMessageType::Error, &err.to_string());
       }
     }
   }
 
-  fn get(&mut self, mut url: String) -> Result<Response, Response> {
+  fn print_listing(&mut self, listing: Listing, output: OutputFormat) -> Result<(), PuddlerError> {
+    let items = match listing {
+      Listing::Search(term) => self.search(&parse_search(&term)?, 0)?.0,
+      Listing::Resume => self.continue_watching()?,
+      Listing::NextUp => {
+        return Err(PuddlerError::Unsupported(String::from(
+          "Plex doesn't have a separate \"Next Up\" list. Use \"resume\" instead.",
+        )));
+      },
+      Listing::Latest => self.latest()?,
+    };
+    print_items(&items, output);
+    Ok(())
+  }
+
+  fn get(&mut self, mut url: String) -> Result<Response, PuddlerError> {
     let user = self.get_config_handle().get_active_user().unwrap();
     if !url.contains('?') {
       url.push('?')
@@ -565,24 +639,20 @@ impl MediaCenter for PlexServer {
       user.access_token,
       self.config_handle.get_device_id()
     );
-    let client = Client::new();
-    let request = client
+    let client = self.get_client();
+    let response = client
       .get(url.clone())
       .timeout(Duration::from_secs(15))
       .header("Content-Type", "application/json")
       .header("accept", "application/json")
-      .send();
-
-    let response = if let Err(res) = request {
-      print_message(PrintMessageType::Error, res.to_string().as_str());
-      exit(1);
-    } else {
-      request.unwrap()
-    };
+      .send()?;
 
     match response.status() {
       StatusCode::OK => Ok(response),
-      _ => Err(response),
+      status => Err(PuddlerError::from_status(
+        status,
+        response.text().unwrap_or_default(),
+      )),
     }
   }
 
@@ -618,6 +688,7 @@ impl MediaCenter for PlexServer {
       item_id
     );
     url += &format!("&state={}", state);
+    url += &format!("&type={}", timeline_type(&playback_info));
     url += &format!("&time={}", actual_time_position);
     url += &format!(
       "&duration={}",
@@ -629,7 +700,37 @@ impl MediaCenter for PlexServer {
     if let Err(err) = self.async_get(url).await {
       print_message(
         PrintMessageType::Error,
-        format!("Failed to report PlaySession: {}", err.status()).as_str(),
+        format!("Failed to report PlaySession: {}", err).as_str(),
+      );
+    }
+  }
+
+  async fn stop_track_playback(&mut self, item_id: String, time_pos: f64) {
+    let playback_info = self.get_plex_playback_info();
+    let mut url = ":/timeline".to_string();
+    url += &format!(
+      "?X-Plex-Platform={}",
+      urlencoding::encode("Plex Home Theater")
+    );
+    url += &format!("&ratingKey={}", item_id);
+    url += &format!(
+      "&key={}{}",
+      urlencoding::encode("/library/metadata/"),
+      item_id
+    );
+    url += "&state=stopped";
+    url += &format!("&type={}", timeline_type(&playback_info));
+    url += &format!("&time={}", (time_pos * 1000.0).round() as u64);
+    url += &format!(
+      "&duration={}",
+      playback_info.Media.unwrap()[0].Part[0].duration
+    );
+    url += &format!("&X-Plex-Product={}&X-Plex-Device-Name={}", APPNAME, VERSION);
+    url += "&hasMDE=1";
+    if let Err(err) = self.async_get(url).await {
+      print_message(
+        PrintMessageType::Error,
+        format!("Failed to log playback progress to your server: {}", err).as_str(),
       );
     }
   }
@@ -722,11 +823,7 @@ impl MediaCenter for PlexServer {
         Err(err) => {
           print_message(
             PrintMessageType::Error,
-            format!(
-              "Failed to log playback progress to your server: {}",
-              err.status()
-            )
-            .as_str(),
+            format!("Failed to log playback progress to your server: {}", err).as_str(),
           );
         },
       }
@@ -741,38 +838,381 @@ impl MediaCenter for PlexServer {
     }
   }
 
-  fn item_set_playstate(&mut self, key: String, played: bool) {
-    let status_str = if played { "Played" } else { "Un-Played" };
+  // Libraries aren't items on Plex, they're browsed as "section".
+  fn browse(&mut self, parent: Option<&MediaItem>) -> Result<Vec<MediaItem>, PuddlerError> {
+    let Some(parent) = parent else {
+      let response = self.get(String::from("library/sections"))?;
+      let sections =
+        serde_json::from_str::<Value>(&response.text()?)?["MediaContainer"]["Directory"]
+          .as_array()
+          .cloned()
+          .unwrap_or_default();
+      return Ok(
+        sections
+          .iter()
+          .filter_map(|section| {
+            Some(MediaItem {
+              id: section["key"].as_str()?.to_string(),
+              kind: MediaKind::Other(String::from("section")),
+              titles: vec![section["title"].as_str().unwrap_or("???").to_string()],
+              played: false,
+              playback_position: 0,
+              runtime: 0,
+            })
+          })
+          .collect(),
+      );
+    };
+    let url = if parent.kind == MediaKind::Other(String::from("section")) {
+      format!("library/sections/{}/all", parent.id)
+    } else {
+      format!("library/metadata/{}/children", parent.id)
+    };
+    let items = self.get_items(url, false)?;
+    Ok(items.iter().map(MediaItem::from).collect())
+  }
+
+  fn resume(&mut self) -> Result<Vec<MediaItem>, PuddlerError> {
+    let items = self.continue_watching()?;
+    Ok(items.iter().map(MediaItem::from).collect())
+  }
+
+  // Direct-play only, like `play_from_id`.
+  fn resolve_stream(&mut self, item_id: &str) -> Result<PlaybackSource, PuddlerError> {
+    if self.get_config_handle().config.transcoding {
+      return Err(PuddlerError::Unsupported(String::from(
+        "Transcoding requires user input. Please disable it or use the interactive menu.",
+      )));
+    }
+    let mut item = self.get_item(item_id.to_string())?;
+    if let Some(media_files) = item.Media.as_mut() {
+      media_files.retain(|f| f.deletedAt.is_none());
+      media_files.truncate(1);
+    }
+    if item
+      .Media
+      .as_ref()
+      .is_none_or(|media_files| media_files.is_empty())
+    {
+      return Err(PuddlerError::Unsupported(format!(
+        "\"{}\" doesn't have any playable files.",
+        item.title
+      )));
+    }
+    let handle = self.get_config_handle();
+    let auth = format!(
+      "X-Plex-Token={}&X-Plex-Client-Identifier={}",
+      handle.get_active_user().unwrap().access_token,
+      handle.get_device_id()
+    );
+    let server_address = self.get_address();
+    Ok(self.playback_source(&item, &server_address, &auth))
+  }
+
+  fn set_playstate(&mut self, key: &str, played: bool) -> Result<(), PuddlerError> {
     let mut url = if played {
       String::from(":/scrobble")
     } else {
       String::from(":/unscrobble")
     };
     url += &format!("?identifier=com.plexapp.plugins.library&key={}", key);
-    if let Err(err) = self.get(url) {
-      print_message(
-        PrintMessageType::Error,
-        format!("Failed to mark item as {}: {}", status_str, err.status()).as_str(),
-      );
+    self.get(url)?;
+    Ok(())
+  }
+
+  fn series_of(&mut self, item_id: &str) -> Result<Option<MediaItem>, PuddlerError> {
+    let item = self.get_item(item_id.to_string())?;
+    let series_key = match item.r#type.as_str() {
+      "show" => return Ok(Some(MediaItem::from(&item))),
+      "season" => item.parentRatingKey,
+      "episode" => item.grandparentRatingKey,
+      _ => None,
+    };
+    match series_key {
+      Some(series_key) => Ok(Some(MediaItem::from(&self.get_item(series_key)?))),
+      None => Ok(None),
     }
   }
-}
 
-impl PlexServer {
-  fn series_set_playstate(&mut self, series: Series, indexes: Vec<usize>, played: bool) {
-    let mut index = 0;
-    for season in series.seasons {
-      for episode in season.episodes {
-        if indexes.contains(&index) {
-          self.item_set_playstate(episode.ratingKey, played);
+  // Offsets are in milliseconds.
+  fn play_media_item(
+    &mut self,
+    player: &mut Player,
+    item: &MediaItem,
+    transcoding_settings: &mut Option<(bool, Option<u32>, Option<u32>, String)>,
+    unattended: bool,
+  ) -> Result<Video, PuddlerError> {
+    let mut plex_item = self.get_item(item.id.clone())?;
+    plex_item.viewOffset = (item.playback_position != 0).then_some(item.playback_position * 1000);
+    if unattended && let Some(media_files) = plex_item.Media.as_mut() {
+      media_files.retain(|f| f.deletedAt.is_none());
+      media_files.truncate(1);
+    }
+    self.play_item(player, plex_item, transcoding_settings)
+  }
+
+  fn get_playable_item(&mut self, item_id: &str) -> Result<MediaItem, PuddlerError> {
+    let item = self.get_item(item_id.to_string())?;
+    if (item.r#type != "movie" && item.r#type != "episode")
+      || item.Media.as_ref().is_none_or(|media| media.is_empty())
+    {
+      return Err(PuddlerError::Unsupported(format!(
+        "\"{}\" can't be played.",
+        item.title
+      )));
+    }
+    Ok(MediaItem::from(&item))
+  }
+
+  fn latest_items(&mut self) -> Result<Vec<MediaItem>, PuddlerError> {
+    let items = self.latest()?;
+    Ok(items.iter().map(MediaItem::from).collect())
+  }
+
+  fn search_items(
+    &mut self,
+    query: &SearchQuery,
+    page: usize,
+  ) -> Result<(Vec<MediaItem>, bool), PuddlerError> {
+    let (items, more) = self.search(query, page)?;
+    Ok((items.iter().map(MediaItem::from).collect(), more))
+  }
+
+  fn libraries(&mut self) -> Result<Vec<Library>, PuddlerError> {
+    Ok(
+      self
+        .get_sections()?
+        .iter()
+        .map(|section| {
+          let key = section["key"].as_str().unwrap_or_default();
+          Library {
+            name: section["title"].as_str().unwrap_or("???").to_string(),
+            id: key.to_string(),
+            items_url: format!("library/sections/{}/all", key),
+            folders_url: format!("library/sections/{}/folder", key),
+            collections_url: format!("library/sections/{}/collections", key),
+          }
+        })
+        .collect(),
+    )
+  }
+
+  fn library_categories(
+    &mut self,
+    library: &Library,
+    category: &str,
+  ) -> Result<Vec<Category>, PuddlerError> {
+    let filter = match category {
+      "Genres" => "genre",
+      "Studios" => "studio",
+      _ => "year",
+    };
+    self.get_categories(&library.id, filter)
+  }
+
+  fn library_page(
+    &mut self,
+    url: &str,
+    sort: &SortOrder,
+    page: usize,
+  ) -> Result<Vec<MediaItem>, PuddlerError> {
+    let items = self.get_items(plex_page_url(url, Some(sort), page), false)?;
+    Ok(items.iter().map(MediaItem::from).collect())
+  }
+
+  fn folder_url(&mut self, item: &MediaItem) -> Option<String> {
+    (item.kind == MediaKind::Other(String::from("collection")))
+      .then(|| format!("library/collections/{}/children", item.id))
+  }
+
+  fn browse_folders(&mut self, library: &Library) -> Result<(), PuddlerError> {
+    self.browse_folder(library.name.clone(), library.folders_url.clone())
+  }
+
+  // Music sections list their artists. Albums are the children of an artist and tracks those of an
+  // album.
+  fn music_children(&mut self, parent: Option<&MediaItem>) -> Result<Vec<MediaItem>, PuddlerError> {
+    let items = match parent {
+      None => {
+        let mut artists: Vec<PlexItem> = vec![];
+        for section in self.get_sections()? {
+          if section["type"] == "artist"
+            && let Some(key) = section["key"].as_str()
+          {
+            artists.extend(self.get_items(format!("library/sections/{}/all", key), false)?);
+          }
+        }
+        artists
+      },
+      Some(track) if track.kind == MediaKind::Track => {
+        match self.get_item(track.id.clone())?.parentRatingKey {
+          Some(album_key) => {
+            self.get_items(format!("library/metadata/{}/children", album_key), false)?
+          },
+          None => vec![],
         }
-        index += 1;
+      },
+      Some(parent) => self.get_items(format!("library/metadata/{}/children", parent.id), false)?,
+    };
+    Ok(items.iter().map(MediaItem::from).collect())
+  }
+
+  // Always direct-play, like with Jellyfin.
+  fn track_source(&mut self, track: &MediaItem) -> Result<Option<PlaybackSource>, PuddlerError> {
+    let mut item = self.get_item(track.id.clone())?;
+    if let Some(media_files) = item.Media.as_mut() {
+      media_files.retain(|f| f.deletedAt.is_none());
+    }
+    let Some(part) = item
+      .Media
+      .as_ref()
+      .and_then(|media| media.first())
+      .and_then(|media| media.Part.first())
+    else {
+      return Ok(None);
+    };
+    let handle = self.get_config_handle();
+    let user = handle.get_active_user().unwrap();
+    let auth = format!(
+      "X-Plex-Token={}&X-Plex-Client-Identifier={}",
+      user.access_token,
+      handle.get_device_id()
+    );
+    Ok(Some(PlaybackSource {
+      stream_url: format!(
+        "{}{}?{}",
+        self.get_address(),
+        part.key.trim_start_matches('/'),
+        auth
+      ),
+      external_subtitles: vec![],
+      playback_info: Some(serde_json::to_string(&item).unwrap()),
+    }))
+  }
+
+  // The original file of the first part, never a transcode.
+  fn create_download(&mut self, item_id: String) -> Result<Download, PuddlerError> {
+    let item = self.get_item(item_id)?;
+    let handle = self.get_config_handle();
+    let user = handle.get_active_user().unwrap();
+    let auth = format!(
+      "X-Plex-Token={}&X-Plex-Client-Identifier={}",
+      user.access_token,
+      handle.get_device_id()
+    );
+    let server_address = self.get_address();
+    let part = if let Some(part) = item
+      .Media
+      .as_ref()
+      .and_then(|media| media.first())
+      .and_then(|media| media.Part.first())
+    {
+      part
+    } else {
+      return Err(PuddlerError::Unsupported(format!(
+        "\"{}\" doesn't have any downloadable files.",
+        item.title
+      )));
+    };
+    let extension = part
+      .key
+      .rsplit_once('.')
+      .map(|(_, extension)| extension.to_string())
+      .unwrap_or(String::from("mkv"));
+
+    let mut subtitles: Vec<DownloadSubtitle> = vec![];
+    for stream in part.Stream.clone().unwrap_or_default() {
+      if let Some(key) = &stream.key {
+        subtitles.push(DownloadSubtitle {
+          url: format!("{}{}?{}", server_address, key.trim_start_matches('/'), auth),
+          extension: stream.codec.clone().unwrap_or(String::from("srt")),
+          title: stream
+            .displayTitle
+            .clone()
+            .unwrap_or(String::from("Undefined")),
+          language: stream.language.clone().unwrap_or(String::from("und")),
+        });
       }
     }
+
+    Ok(Download {
+      url: format!(
+        "{}{}?{}",
+        server_address,
+        part.key.trim_start_matches('/'),
+        auth
+      ),
+      extension,
+      subtitles,
+      title: item.to_string_split(),
+      movie: item.r#type == "movie",
+      total_runtime: item.duration.unwrap_or(0) / 1000,
+      playback_position: item.viewOffset.unwrap_or(0) / 1000,
+      played: item.viewCount.unwrap_or(0) > 0,
+      item_id: item.ratingKey.clone(),
+    })
+  }
+
+  fn get_playlists(&mut self) -> Result<Vec<MediaItem>, PuddlerError> {
+    let response = self.get(String::from("playlists?playlistType=video"))?;
+    let playlists = serde_json::from_str::<Value>(&response.text()?)?["MediaContainer"]["Metadata"]
+      .as_array()
+      .cloned()
+      .unwrap_or_default();
+    Ok(
+      playlists
+        .iter()
+        .filter_map(|playlist| {
+          Some(MediaItem {
+            id: playlist["ratingKey"].as_str()?.to_string(),
+            kind: MediaKind::Other(String::from("playlist")),
+            titles: vec![playlist["title"].as_str().unwrap_or("???").to_string()],
+            played: false,
+            playback_position: 0,
+            runtime: 0,
+          })
+        })
+        .collect(),
+    )
+  }
+
+  fn get_playlist_items(&mut self, playlist_id: &str) -> Result<Vec<MediaItem>, PuddlerError> {
+    let items = self.get_items(format!("playlists/{}/items", playlist_id), false)?;
+    Ok(
+      items
+        .iter()
+        .filter(|item| item.r#type == "movie" || item.r#type == "episode")
+        .map(MediaItem::from)
+        .collect(),
+    )
+  }
+
+  // Playlists reference their items through a uri of this server.
+  fn save_playlist(&mut self, name: &str, items: &[MediaItem]) -> Result<(), PuddlerError> {
+    let uri = format!(
+      "server://{}/com.plexapp.plugins.library/library/metadata/{}",
+      self.machine_identifier()?,
+      items
+        .iter()
+        .map(|item| item.id.clone())
+        .collect::<Vec<String>>()
+        .join(",")
+    );
+    self.send_query(
+      Method::POST,
+      format!(
+        "playlists?type=video&smart=0&title={}&uri={}",
+        urlencoding::encode(name),
+        urlencoding::encode(&uri)
+      ),
+    )?;
+    Ok(())
   }
+}
 
+impl PlexServer {
   // Just like `async_post`. reqwest::blocking::client isn't allowed in an asynchronous context -_-
-  async fn async_get(&mut self, mut url: String) -> Result<reqwest::Response, reqwest::Response> {
+  async fn async_get(&mut self, mut url: String) -> Result<reqwest::Response, PuddlerError> {
     let user = self.get_config_handle().get_active_user().unwrap();
     if !url.contains('?') {
       url.push('?')
@@ -787,24 +1227,20 @@ impl PlexServer {
       self.config_handle.get_device_id()
     );
     let client = reqwest::Client::new();
-    let request = client
+    let response = client
       .get(url.clone())
       .timeout(Duration::from_secs(15))
       .header("Content-Type", "application/json")
       .header("accept", "application/json")
       .send()
-      .await;
-
-    let response = if let Err(res) = request {
-      print_message(PrintMessageType::Error, res.to_string().as_str());
-      exit(1);
-    } else {
-      request.unwrap()
-    };
+      .await?;
 
     match response.status() {
       StatusCode::OK => Ok(response),
-      _ => Err(response),
+      status => Err(PuddlerError::from_status(
+        status,
+        response.text().await.unwrap_or_default(),
+      )),
     }
   }
 
@@ -819,7 +1255,7 @@ impl PlexServer {
     if let Err(err) = self.async_get(url).await {
       print_message(
         PrintMessageType::Error,
-        format!("Failed to mark item as {}: {}", status_str, err.status()).as_str(),
+        format!("Failed to mark item as {}: {}", status_str, err).as_str(),
       );
     }
   }
@@ -828,41 +1264,154 @@ impl PlexServer {
     self.playback_info.clone().unwrap()
   }
 
-  fn process_item(&mut self, item: PlexItem) {
-    println!("Selected: {}", item.to_string_full().cyan());
-    let mut playlist: Vec<PlexItem> = vec![];
-    match item.r#type.as_str() {
-      "movie" => {
-        playlist.push(item);
-      },
-      "episode" => {
-        let series = self.resolve_series(item.clone());
-        let mut found = false;
-        for season in series.seasons {
-          if found {
-            playlist.extend(season.episodes);
+  pub fn get_sections(&mut self) -> Result<Vec<Value>, PuddlerError> {
+    let response = self.get(String::from("library/sections"))?;
+    Ok(
+      serde_json::from_str::<Value>(&response.text()?)?["MediaContainer"]["Directory"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default(),
+    )
+  }
+
+  // Plex calls the filters "genre", "studio" and "year".
+  pub fn get_categories(
+    &mut self,
+    section_key: &str,
+    filter: &str,
+  ) -> Result<Vec<Category>, PuddlerError> {
+    let response = self.get(format!("library/sections/{}/{}", section_key, filter))?;
+    let entries = serde_json::from_str::<Value>(&response.text()?)?["MediaContainer"]["Directory"]
+      .as_array()
+      .cloned()
+      .unwrap_or_default();
+    Ok(
+      entries
+        .iter()
+        .map(|entry| Category {
+          name: entry["title"].as_str().unwrap_or("???").to_string(),
+          url: if let Some(fast_key) = entry["fastKey"].as_str() {
+            fast_key.trim_start_matches('/').to_string()
           } else {
-            for episode in season.episodes {
-              if found {
-                playlist.push(episode);
-              } else if episode.ratingKey == item.ratingKey {
-                found = true;
-                playlist.push(episode);
-              }
+            format!(
+              "library/sections/{}/all?{}={}",
+              section_key,
+              filter,
+              entry["key"].as_str().unwrap_or_default()
+            )
+          },
+        })
+        .collect(),
+    )
+  }
+
+  // Folders don't have any metadata, just a key to their content.
+  fn browse_folder(&mut self, title: String, url: String) -> Result<(), PuddlerError> {
+    let mut page = 0;
+    loop {
+      let response = self.get(plex_page_url(&url, None, page))?;
+      let entries = serde_json::from_str::<Value>(&response.text()?)?["MediaContainer"]["Metadata"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+      let names: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+          let title = entry["title"].as_str().unwrap_or("???");
+          if entry["ratingKey"].is_null() {
+            format!("{}/", title)
+          } else {
+            title.to_string()
+          }
+        })
+        .collect();
+      match select_from_page(
+        format!("{} (page {}):", title.clone().cyan(), page + 1),
+        &names,
+        page,
+        entries.len() == PAGE_SIZE,
+        false,
+      ) {
+        PageAction::Open(index) => {
+          let entry = &entries[index];
+          let result = if let Some(rating_key) = entry["ratingKey"].as_str() {
+            match self.get_item(rating_key.to_string()) {
+              Ok(item) => process_item(self, MediaItem::from(&item), vec![]),
+              Err(err) => Err(err),
             }
+          } else {
+            let key = entry["key"].as_str().unwrap_or_default();
+            self.browse_folder(
+              names[index].clone(),
+              key.trim_start_matches('/').to_string(),
+            )
+          };
+          if let Err(err) = result {
+            print_message(PrintMessageType::Error, &err.to_string());
           }
-        }
-      },
-      "show" | "season" => {
-        let series = self.resolve_series(item);
-        playlist = self.choose_from_series(series);
-      },
-      _ => (),
+        },
+        PageAction::Next => page += 1,
+        PageAction::Previous => page -= 1,
+        _ => return Ok(()),
+      }
+    }
+  }
+
+  fn machine_identifier(&mut self) -> Result<String, PuddlerError> {
+    if let Some(machine_identifier) = &self.machine_identifier {
+      return Ok(machine_identifier.clone());
+    }
+    let response = self.get(String::from("identity"))?;
+    let identity = serde_json::from_str::<Value>(&response.text()?)?;
+    let machine_identifier = identity["MediaContainer"]["machineIdentifier"]
+      .as_str()
+      .map(|id| id.to_string())
+      .ok_or_else(|| {
+        PuddlerError::Deserialization(String::from("missing field `machineIdentifier`"))
+      })?;
+    self.machine_identifier = Some(machine_identifier.clone());
+    Ok(machine_identifier)
+  }
+
+  // The apps need the server's machine identifier to show what is playing.
+  fn companion_info(&mut self, item: Option<&PlexItem>) -> CompanionInfo {
+    if let Err(err) = self.machine_identifier() {
+      print_message(
+        PrintMessageType::Warning,
+        format!("Failed to get the identity of the server: {}", err).as_str(),
+      );
     }
-    if playlist.is_empty() {
-      return;
+    let streams = item
+      .and_then(|item| item.Media.as_ref())
+      .and_then(|media| media.first())
+      .and_then(|media| media.Part.first())
+      .and_then(|part| part.Stream.as_ref())
+      .map(|streams| {
+        streams
+          .iter()
+          .map(|stream| CompanionStream {
+            id: stream.id,
+            stream_type: stream.streamType,
+            index: stream.index,
+            key: stream.key.clone(),
+          })
+          .collect()
+      })
+      .unwrap_or_default();
+    CompanionInfo {
+      client_identifier: self.config_handle.get_device_id(),
+      server_identifier: self.machine_identifier.clone().unwrap_or_default(),
+      server_address: self.get_address(),
+      streams,
     }
+  }
 
+  fn play_item(
+    &mut self,
+    player: &mut Player,
+    mut item: PlexItem,
+    transcoding_settings: &mut Option<(bool, Option<u32>, Option<u32>, String)>,
+  ) -> Result<Video, PuddlerError> {
     let handle = self.get_config_handle();
     let user = handle.get_active_user().unwrap();
     let device_id = handle.get_device_id();
@@ -871,158 +1420,230 @@ impl PlexServer {
       user.access_token, device_id
     );
     let server_address = self.get_address();
+    self.create_transcoding_info(&mut item, transcoding_settings)?;
+    let (segments, chapters) = self.get_timeline(&item.ratingKey);
+    self.insert_value(
+      MediaCenterValues::PlaybackInfo,
+      serde_json::to_string(&item).unwrap(),
+    );
+    self.update_player(player);
+    // anyone on the network could control this player
+    if self.get_settings().plex_companion {
+      let companion = self.companion_info(Some(&item));
+      player.set_companion(companion);
+    }
+    let source = self.playback_source(&item, &server_address, &auth);
+    player.set_video(&MediaItem::from(&item), source, transcoding_settings);
+    player.set_timeline(segments, chapters, None);
+    player.play()
+  }
 
-    let settings = self.get_settings().clone();
-    let mut player = Player::new(self.get_config_handle().clone(), settings.clone());
+  // The universal transcoder, or the file itself with its external subtitles.
+  fn playback_source(
+    &mut self,
+    item: &PlexItem,
+    server_address: &str,
+    auth: &str,
+  ) -> PlaybackSource {
+    let handle = self.get_config_handle();
+    let stream_url = if handle.config.transcoding {
+      format!(
+        "{}video/:/transcode/universal/start.mkv?{}&path={}&subtitles=embedded&directPlay=0&directStream=1&session={}&protocol=http&X-Plex-Platform={}&fastSeek=1&offset={}",
+        server_address,
+        auth,
+        urlencoding::encode(format!("/library/metadata/{}", item.ratingKey).as_str()),
+        handle.get_device_id(),
+        urlencoding::encode("Plex Home Theater"),
+        item.viewOffset.unwrap_or(0) / 1000
+      )
+    } else {
+      format!(
+        "{}{}?{}&X-Plex-Platform={}",
+        server_address,
+        item.Media.clone().unwrap()[0].Part[0]
+          .key
+          .trim_start_matches('/'),
+        auth,
+        urlencoding::encode("Plex Home Theater")
+      )
+    };
 
-    let mut transcoding_settings = None;
-    let mut index = 0;
-    let mut stdout = stdout();
-    while index < playlist.len() {
-      let item = if let Ok(full_item) = self.get_item(playlist[index].clone().ratingKey) {
-        full_item
-      } else {
-        print_message(
-          PrintMessageType::Error,
-          "Failed to retrieve item metadata. Please make sure this item still exists.",
-        );
-        return;
-      };
-      let mut next_index = index + 1;
-      let mut streamable_item = item.clone();
-      if self
-        .create_transcoding_info(&mut streamable_item, &mut transcoding_settings)
-        .is_ok()
+    let mut external_subtitles: Vec<[String; 4]> = vec![];
+    if !handle.config.transcoding {
+      for stream in item.Media.clone().unwrap()[0].Part[0]
+        .Stream
+        .clone()
+        .unwrap()
       {
-        self.insert_value(
-          MediaCenterValues::PlaybackInfo,
-          serde_json::to_string(&streamable_item).unwrap(),
-        );
-        self.update_player(&mut player);
-        player.set_plex_video(
-          streamable_item,
-          server_address.clone(),
-          auth.clone(),
-          &mut transcoding_settings,
-        );
-        let ret = player.play();
-        if let Some((_, audio, subtitle, ..)) = transcoding_settings.as_mut() {
-          *audio = ret.preferred_audio_track;
-          *subtitle = ret.preferred_subtitle_track
-        }
-        'playback_done: loop {
-          let mut options: Vec<InteractiveOption> = vec![];
-          execute!(stdout, DisableLineWrap).unwrap();
-          if !ret.played {
-            if let Ok(updated_item) = self.get_item(item.ratingKey.clone()) {
-              playlist[index] = updated_item;
-            } else {
-              print_message(
-                PrintMessageType::Error,
-                format!(
-                  "Failed to get updated information for {}.",
-                  item.to_string()
-                )
-                .as_str(),
-              )
-            }
-            options.append(&mut vec![
-              InteractiveOption {
-                text: format!("Finish: {}", item.to_string_ext()),
-                option_type: InteractiveOptionType::Button,
-              },
-              InteractiveOption {
-                text: format!("Mark as played: {}", item.to_string_ext()),
-                option_type: InteractiveOptionType::Button,
-              },
-            ]);
-          }
-          while let Some(next_item) = playlist.get(index + 1) {
-            // skip every item that has been played already
-            // (might want to use unmark in the menu before watching a series again)
-            if next_item.viewCount.is_none() {
-              options.push(InteractiveOption {
-                text: format!("Continue with: {}", next_item.to_string_ext()),
-                option_type: InteractiveOptionType::Button5s,
-              });
-              break;
-            }
-            next_index += 1;
-          }
-          if options.is_empty() {
-            print_message(
-              PrintMessageType::Warning,
-              "Playlist done. Returning to menu.",
-            );
-            return;
-          }
-          options.append(&mut vec![
-            InteractiveOption {
-              text: "Back to Menu".to_string(),
-              option_type: InteractiveOptionType::Special,
-            },
-            InteractiveOption {
-              text: "Exit Application".to_string(),
-              option_type: InteractiveOptionType::Special,
-            },
-          ]);
-          match interactive_select(options) {
-            ((_, _), Some(text), InteractiveOptionType::Button) => {
-              if text.starts_with("Finish") {
-                transcoding_settings.as_mut().unwrap().0 = true;
-                break 'playback_done;
-              } else if text.starts_with("Mark") {
-                self.item_set_playstate(item.ratingKey.clone(), true);
-                continue 'playback_done;
-              } else if text.starts_with("Continue") {
-                index = next_index;
-                break 'playback_done;
-              }
-            },
-            ((_, _), Some(text), InteractiveOptionType::Special) => match text.as_str() {
-              "Back to Menu" => {
-                execute!(stdout, EnableLineWrap).unwrap();
-                return;
-              },
-              _ => {
-                execute!(stdout, EnableLineWrap).unwrap();
-                exit(0)
-              },
-            },
-            _ => (),
-          }
-          execute!(stdout, EnableLineWrap).unwrap();
+        if let Some(key) = &stream.key {
+          let media_url = format!("{}{}?{}", server_address, key.trim_start_matches('/'), auth);
+          let language = if let Some(language) = stream.language.clone() {
+            language
+          } else {
+            String::from("und")
+          };
+          let title = if let Some(title) = stream.displayTitle.clone() {
+            title
+          } else {
+            String::from("Undefined")
+          };
+          let formatted_title = format!(r#""{}""#, title);
+          external_subtitles.push([media_url, "auto".to_string(), formatted_title, language]);
         }
       }
     }
+
+    PlaybackSource {
+      stream_url,
+      external_subtitles,
+      playback_info: None,
+    }
+  }
+
+  // A plain search goes through the hubs, which return the best matches of every type at once.
+  // Filters need "library/all" with one type per request.
+  pub fn search(
+    &mut self,
+    query: &SearchQuery,
+    page: usize,
+  ) -> Result<(Vec<PlexItem>, bool), PuddlerError> {
+    if !query.has_filters() {
+      // Hubs don't have a start index, only a limit per hub. Every page asks for one page more
+      // and leaves out what has been shown already. A full hub might have more.
+      let limit = (page + 1) * PAGE_SIZE;
+      let hubs = self.get_hubs(format!(
+        "hubs/search?query={}&limit={}",
+        urlencoding::encode(&query.term),
+        limit
+      ))?;
+      let more = hubs.iter().any(|hub| hub.len() >= limit);
+      let items = hubs
+        .into_iter()
+        .flat_map(|hub| hub.into_iter().skip(page * PAGE_SIZE))
+        .collect();
+      return Ok((items, more));
+    }
+    let types = match query.item_type.as_deref() {
+      Some("movie") => vec![1],
+      Some("series") => vec![2],
+      Some("episode") => vec![4],
+      Some("artist") => vec![8],
+      Some("album") => vec![9],
+      Some("track") => vec![10],
+      _ => vec![1, 2],
+    };
+    let mut filters = String::new();
+    if !query.term.is_empty() {
+      filters += &format!("&title={}", urlencoding::encode(&query.term));
+    }
+    // ">>=" and "<<=" are "greater than" and "less than"
+    if let Some((from, to)) = query.years {
+      filters += &format!(
+        "&year%3E%3E={}&year%3C%3C={}",
+        from.saturating_sub(1),
+        to.saturating_add(1)
+      );
+    }
+    if let Some(genre) = &query.genre {
+      filters += &format!("&genre={}", self.tag_id("genre", genre)?);
+    }
+    if let Some(played) = query.played {
+      filters += &format!("&unwatched={}", if played { 0 } else { 1 });
+    }
+    // Plex only knows "greater than" and ratings have a single decimal
+    if let Some(rating) = query.min_rating {
+      filters += &format!("&audienceRating%3E%3E={}", rating - 0.05);
+    }
+    if let Some(person) = &query.person {
+      let tag = if query.person_type.as_deref() == Some("Director") {
+        "director"
+      } else {
+        "actor"
+      };
+      filters += &format!("&{}={}", tag, self.tag_id(tag, person)?);
+    }
+    let mut items: Vec<PlexItem> = vec![];
+    let mut more = false;
+    for item_type in types {
+      let page_items = self.get_items(
+        plex_page_url(
+          &format!("library/all?type={}{}", item_type, filters),
+          None,
+          page,
+        ),
+        false,
+      )?;
+      more |= page_items.len() == PAGE_SIZE;
+      items.extend(page_items);
+    }
+    Ok((items, more))
+  }
+
+  // Genres and people are filtered by their id. Tags are shared between all libraries.
+  fn tag_id(&mut self, tag: &str, name: &str) -> Result<String, PuddlerError> {
+    let response = self.get(String::from("library/sections"))?;
+    let sections = serde_json::from_str::<Value>(&response.text()?)?["MediaContainer"]["Directory"]
+      .as_array()
+      .cloned()
+      .unwrap_or_default();
+    for section in sections {
+      let response = self.get(format!(
+        "library/sections/{}/{}",
+        section["key"].as_str().unwrap_or_default(),
+        tag
+      ))?;
+      let entries =
+        serde_json::from_str::<Value>(&response.text()?)?["MediaContainer"]["Directory"]
+          .as_array()
+          .cloned()
+          .unwrap_or_default();
+      if let Some(entry) = entries.iter().find(|entry| {
+        entry["title"]
+          .as_str()
+          .is_some_and(|title| title.eq_ignore_ascii_case(name))
+      }) {
+        return Ok(entry["key"].as_str().unwrap_or_default().to_string());
+      }
+    }
+    Err(PuddlerError::Unsupported(format!(
+      "There is no {} called \"{}\".",
+      tag, name
+    )))
+  }
+
+  #[cfg(test)]
+  pub fn set_plex_tv_address(&mut self, address: String) {
+    self.plex_tv_address = address;
   }
 
-  fn get_user(&mut self, access_token: String) -> Result<PlexTVUser, StatusCode> {
+  fn plex_tv(
+    &mut self,
+    request_type: RequestType,
+    user: Option<UserConfig>,
+    url: String,
+  ) -> Result<Response, PuddlerError> {
     let device_id = self.get_config_handle().get_device_id();
+    plex_tv(
+      &self.client,
+      &self.plex_tv_address,
+      request_type,
+      user,
+      device_id,
+      url,
+    )
+  }
+
+  fn get_user(&mut self, access_token: String) -> Result<PlexTVUser, PuddlerError> {
     let url = format!("api/v2/user?X-Plex-Token={}", access_token);
-    match plex_tv(RequestType::Get, None, device_id, url) {
-      Ok(req) => {
-        if let Ok(json) = serde_json::from_str::<PlexTVUser>(&req.text().unwrap()) {
-          Ok(json)
-        } else {
-          exit(1);
-        }
-      },
-      Err(err) => {
-        print_message(
-          PrintMessageType::Error,
-          format!("Failed to get user information: {}", err.status()).as_str(),
-        );
-        Err(err.status())
-      },
-    }
+    let response = self.plex_tv(RequestType::Get, None, url)?;
+    Ok(serde_json::from_str::<PlexTVUser>(&response.text()?)?)
   }
 
   fn create_transcoding_info(
     &mut self,
     item: &mut PlexItem,
     previous_settings: &mut Option<(bool, Option<u32>, Option<u32>, String)>,
-  ) -> Result<(), ()> {
+  ) -> Result<(), PuddlerError> {
     let handle = self.get_config_handle();
     let user = handle.get_active_user().unwrap();
     let mut stdout = stdout();
@@ -1041,7 +1662,10 @@ impl PlexServer {
         .cloned()
         .collect()
     } else {
-      return Err(());
+      return Err(PuddlerError::Unsupported(format!(
+        "\"{}\" doesn't have any playable files.",
+        item.title
+      )));
     };
 
     // This is the only setting which isn't saved across the playlist. Don't really see the point in that tbh.
@@ -1259,7 +1883,11 @@ impl PlexServer {
           }
         }
       } else {
-        panic!("Did item doesn't have any streams?? That's weird.");
+        execute!(stdout, LeaveAlternateScreen).unwrap();
+        return Err(PuddlerError::ServerApi(
+          StatusCode::OK,
+          String::from("This item doesn't have any streams."),
+        ));
       }
       if audio_tracks.len() > 1 {
         let mut skip = false;
@@ -1331,11 +1959,8 @@ impl PlexServer {
       }
 
       if let Err(err) = self.put(selected_tracks) {
-        print_message(
-          PrintMessageType::Error,
-          format!("Failed to set audio/subtitle tracks: {}", err.status()).as_str(),
-        );
-        return Err(());
+        execute!(stdout, LeaveAlternateScreen).unwrap();
+        return Err(err);
       }
 
       enable_raw_mode().unwrap();
@@ -1393,21 +2018,16 @@ impl PlexServer {
     decision_url += "&path=";
     decision_url += &urlencoding::encode(&format!("/library/metadata/{}", metadata_key));
 
-    if let Err(err) = self.get(decision_url) {
-      print_message(
-        PrintMessageType::Error,
-        format!(
-          "Failed to post playback information: {}",
-          err.text().unwrap()
-        )
-        .as_str(),
-      );
-      return Err(());
-    }
+    self.get(decision_url)?;
     Ok(())
   }
 
-  fn put(&mut self, mut url: String) -> Result<Response, Response> {
+  fn put(&mut self, url: String) -> Result<Response, PuddlerError> {
+    self.send_query(Method::PUT, url)
+  }
+
+  // Requests that only carry query parameters.
+  fn send_query(&mut self, method: Method, mut url: String) -> Result<Response, PuddlerError> {
     let user = self.get_config_handle().get_active_user().unwrap();
     if !url.contains('?') {
       url.push('?')
@@ -1421,290 +2041,223 @@ impl PlexServer {
       user.access_token,
       self.config_handle.get_device_id()
     );
-    let client = Client::new();
-    let request = client
-      .put(url)
+    let client = self.get_client();
+    let response = client
+      .request(method, url)
       .timeout(Duration::from_secs(15))
       .header("Content-Type", "application/json")
       .header("accept", "application/json")
-      .send();
-
-    let response = if let Err(res) = request {
-      print_message(PrintMessageType::Error, res.to_string().as_str());
-      exit(1);
-    } else {
-      request.unwrap()
-    };
+      .send()?;
 
     match response.status() {
       StatusCode::OK => Ok(response),
-      _ => Err(response),
+      status => Err(PuddlerError::from_status(
+        status,
+        response.text().unwrap_or_default(),
+      )),
     }
   }
 
-  fn generate_series_structure(&mut self, series: &Series) -> Vec<String> {
-    let mut trans_item = series.seasons[0].item.clone();
-    trans_item.r#type = String::from("show");
-    trans_item.title = trans_item.clone().parentTitle.unwrap_or("???".to_string());
+  pub fn get_item(&mut self, ratingKey: String) -> Result<PlexItem, PuddlerError> {
+    let url = format!("library/metadata/{}", ratingKey);
+    let result = self.get(url)?;
+    let library = serde_json::from_str::<PlexLibrary>(&result.text()?)?;
+    if let Some(item) = library
+      .MediaContainer
+      .Metadata
+      .and_then(|items| items.into_iter().next())
+    {
+      Ok(item)
+    } else {
+      Err(PuddlerError::ServerApi(
+        StatusCode::NOT_FOUND,
+        format!("No metadata for \"{}\".", ratingKey),
+      ))
+    }
+  }
 
-    let full_size = {
-      let mut size = 0;
-      series.seasons.iter().for_each(|s| size += s.episodes.len());
-      size
-    };
-    let zero_pad_amount = (full_size as f64).log10().floor() as usize + 1;
-    let mut just_text: Vec<String> = vec![format!(" {}", trans_item.to_string().bold())];
-    let mut index = 0;
-    for (season_index, season) in series.seasons.iter().enumerate() {
-      let mut line: String = String::new();
-      if season_index == series.seasons.len() - 1 {
-        line.push_str("  └─ ");
-      } else {
-        line.push_str("  ├─ ");
-      }
-      line.push_str(format!("{}", season.item.title.clone().bold()).as_str());
-      just_text.push(line.clone());
-      line.clear();
-      for (episode_index, episode) in season.clone().episodes.iter().enumerate() {
-        let prefix: &str = if season_index == series.seasons.len() - 1 {
-          "       "
-        } else {
-          "  │    "
-        };
-        if episode_index == season.episodes.len() - 1 {
-          line.push_str(format!("{}└── ", prefix).as_str());
-        } else {
-          line.push_str(format!("{}├── ", prefix).as_str());
-        }
-        line.push_str(format!("[{:0zero_pad_amount$}] ", index).as_str());
-        let terminal_size = terminal::size().unwrap().0 as usize;
-        if 13 + zero_pad_amount + index.to_string().len() + episode.to_string_ext().len()
-          > terminal_size
-        {
-          line.push_str(format!("{}...", episode.to_string_ext()).as_str());
-          just_text.push(line.clone());
-          line.clear();
-        } else {
-          line.push_str(episode.to_string_ext().as_str());
-          just_text.push(line.clone());
-          line.clear();
-        }
-        index += 1;
+  // Partially watched items first, then the next episodes of the shows that are being watched.
+  // Servers older than the "continueWatching" hub (1.32) only have the "On Deck" list.
+  pub fn continue_watching(&mut self) -> Result<Vec<PlexItem>, PuddlerError> {
+    let mut items = self
+      .hub_items(String::from("hubs/home/continueWatching"), None)
+      .unwrap_or_default();
+    let on_deck = self.get_items(String::from("library/onDeck"), false)?;
+    for item in on_deck {
+      if !items.iter().any(|i| i.ratingKey == item.ratingKey) {
+        items.push(item);
       }
     }
-    just_text
+    Ok(items)
   }
 
-  fn choose_from_series(&mut self, mut series: Series) -> Vec<PlexItem> {
-    loop {
-      let mut selection: usize;
-      match plex_series_select(self.generate_series_structure(&series), series.clone()) {
-        (SeriesOptions::Back, _) => {
-          return vec![];
-        },
-        (SeriesOptions::Play, Some(index)) => {
-          selection = index[0];
-        },
-        (SeriesOptions::Played, Some(indexes)) => {
-          self.series_set_playstate(series.clone(), indexes, true);
-          series = self.resolve_series(series.seasons[0].episodes[0].clone());
-          continue;
-        },
-        (SeriesOptions::UnPlayed, Some(indexes)) => {
-          self.series_set_playstate(series.clone(), indexes, false);
-          series = self.resolve_series(series.seasons[0].episodes[0].clone());
-          continue;
-        },
-        _ => panic!("What?!"),
-      }
-      let mut items: Vec<PlexItem> = vec![];
-      let mut record = false;
-      for season in series.seasons.clone() {
-        if record {
-          items.extend(season.episodes);
-        } else if season.episodes.len() > selection {
-          for episode in season.episodes {
-            if record {
-              items.push(episode.clone());
-            } else if selection == 0 {
-              record = true;
-              items.push(episode.clone());
-            } else {
-              selection -= 1;
-            }
-          }
-        } else {
-          selection -= season.episodes.len();
-        }
+  // The "Recently Added" hub of every movie and show library.
+  pub fn latest(&mut self) -> Result<Vec<PlexItem>, PuddlerError> {
+    let response = self.get(String::from("library/sections"))?;
+    let sections = serde_json::from_str::<Value>(&response.text()?)?;
+    let mut items: Vec<PlexItem> = vec![];
+    for section in sections["MediaContainer"]["Directory"]
+      .as_array()
+      .cloned()
+      .unwrap_or_default()
+    {
+      if (section["type"] == "movie" || section["type"] == "show")
+        && let Some(key) = section["key"].as_str()
+      {
+        let mut latest = self.hub_items(format!("hubs/sections/{}", key), Some("recentlyadded"))?;
+        latest.truncate(10);
+        items.extend(latest);
       }
-      return items;
     }
+    Ok(items)
   }
 
-  fn resolve_series(&mut self, item: PlexItem) -> Series {
-    let series_id: String = match item.r#type.as_str() {
-      "episode" => item.grandparentRatingKey.unwrap(),
-      "season" => item.parentRatingKey.unwrap(),
-      "show" => item.ratingKey,
-      _ => panic!("This object cannot be part of a series."),
-    };
-
-    let mut series = Series {
-      item_id: series_id,
-      seasons: vec![],
-    };
-
-    if let Ok(items) = self.get_items(
-      format!("library/metadata/{}/children", series.item_id),
-      false,
-    ) {
-      for season in items {
-        series.seasons.append(&mut vec![Season {
-          item: season,
-          episodes: vec![],
-        }]);
-      }
-    } else {
-      exit(1);
-    };
+  // Every item of the hubs whose identifier contains `identifier`, or all of them.
+  fn hub_items(
+    &mut self,
+    url: String,
+    identifier: Option<&str>,
+  ) -> Result<Vec<PlexItem>, PuddlerError> {
+    let result = self.get(url)?;
+    let library = serde_json::from_str::<PlexLibrary>(&result.text()?)?;
+    Ok(
+      library
+        .MediaContainer
+        .Hub
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|hub| {
+          identifier.is_none_or(|identifier| {
+            hub
+              .hubIdentifier
+              .as_ref()
+              .is_some_and(|id| id.to_lowercase().contains(identifier))
+          })
+        })
+        .flat_map(|hub| hub.Metadata.unwrap_or_default())
+        .collect(),
+    )
+  }
 
-    let mut episode_ids: Vec<String> = vec![];
-    for (season_index, season) in series.seasons.clone().iter().enumerate() {
-      if let Ok(items) = self.get_items(
-        format!("library/metadata/{}/children", season.item.ratingKey),
+  // Intro and credit markers, and the chapters of the file. Without a Plex Pass the server doesn't
+  // detect any markers.
+  pub fn get_timeline(&mut self, rating_key: &str) -> (Vec<Segment>, Vec<Chapter>) {
+    let Ok(Some(item)) = self
+      .get_items(
+        format!(
+          "library/metadata/{}?includeMarkers=1&includeChapters=1",
+          rating_key
+        ),
         false,
-      ) {
-        for episode in items {
-          if !episode_ids.contains(&episode.ratingKey) {
-            episode_ids.push(episode.ratingKey.clone());
-            series.seasons[season_index]
-              .episodes
-              .append(&mut vec![episode]);
-          }
-        }
+      )
+      .map(|items| items.into_iter().next())
+    else {
+      return (vec![], vec![]);
+    };
+    let mut segments: Vec<Segment> = item
+      .Marker
+      .unwrap_or_default()
+      .iter()
+      .filter_map(|marker| marker.to_segment())
+      .filter(|segment| segment.end > segment.start)
+      .collect();
+    segments.sort_by(|a, b| a.start.total_cmp(&b.start));
+    let chapters: Vec<Chapter> = item
+      .Chapter
+      .unwrap_or_default()
+      .into_iter()
+      .map(|chapter| Chapter {
+        title: chapter.tag.unwrap_or(format!("Chapter {}", chapter.index)),
+        start: chapter.startTimeOffset as f64 / 1000.0,
+      })
+      .collect();
+    (segments, chapters)
+  }
 
-        if series.seasons[season_index].episodes.is_empty() {
-          series.seasons.remove(season_index);
-        }
-      } else {
-        exit(1);
-      };
+  pub fn get_items(&mut self, url: String, hubs: bool) -> Result<Vec<PlexItem>, PuddlerError> {
+    if hubs {
+      return Ok(self.get_hubs(url)?.into_iter().flatten().collect());
     }
-    series
+    let result = self.get(url)?;
+    let library = serde_json::from_str::<PlexLibrary>(&result.text()?)?;
+    // an empty MediaContainer doesn't even include the Metadata key
+    Ok(library.MediaContainer.Metadata.unwrap_or_default())
   }
 
-  fn get_item(&mut self, ratingKey: String) -> Result<PlexItem, ()> {
-    let url = format!("library/metadata/{}", ratingKey);
-    match self.get(url.clone()) {
-      Ok(result) => {
-        if let Ok(library) = serde_json::from_str::<PlexLibrary>(&result.text().unwrap()) {
-          if let Some(items) = library.MediaContainer.Metadata {
-            return Ok(items[0].clone());
-          }
-        } else {
-          print_message(
-            PrintMessageType::Error,
-            "The message returned from the server could not be processed.",
-          );
-        }
-      },
-      Err(e) => {
-        print_message(
-          PrintMessageType::Error,
-          format!(
-            "Failed to get item list at \"{}\"\n{}\n",
-            url,
-            e.text().unwrap()
+  // The items of every hub that can be played or browsed, one list per hub.
+  pub fn get_hubs(&mut self, url: String) -> Result<Vec<Vec<PlexItem>>, PuddlerError> {
+    let result = self.get(url)?;
+    let library = serde_json::from_str::<PlexLibrary>(&result.text()?)?;
+    Ok(
+      library
+        .MediaContainer
+        .Hub
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|hub| {
+          matches!(
+            hub.r#type.as_str(),
+            "movie" | "show" | "artist" | "album" | "track"
           )
-          .as_str(),
-        );
-      },
-    }
-    Err(())
-  }
-
-  fn get_items(&mut self, url: String, hubs: bool) -> Result<Vec<PlexItem>, ()> {
-    match self.get(url.clone()) {
-      Ok(result) => {
-        if let Ok(library) = serde_json::from_str::<PlexLibrary>(&result.text().unwrap()) {
-          if hubs {
-            let mut all_items: Vec<PlexItem> = vec![];
-            if let Some(hubs) = library.MediaContainer.Hub {
-              for hub in hubs {
-                match hub.r#type.as_str() {
-                  "movie" | "show" => {
-                    if let Some(items) = hub.Metadata {
-                      all_items.extend(items);
-                    }
-                  },
-                  _ => (),
-                }
-              }
-            }
-            return Ok(all_items);
-          } else if let Some(items) = library.MediaContainer.Metadata {
-            return Ok(items);
-          }
-        } else {
-          print_message(
-            PrintMessageType::Error,
-            "The message returned from the server could not be processed.",
-          );
-        }
-      },
-      Err(e) => {
-        print_message(
-          PrintMessageType::Error,
-          format!(
-            "Failed to get item list at \"{}\"\n{}\n",
-            url,
-            e.text().unwrap()
-          )
-          .as_str(),
-        );
-      },
-    }
-    Err(())
+        })
+        .filter_map(|hub| hub.Metadata)
+        .collect(),
+    )
   }
 
-  fn create_plex_user(&mut self) -> String {
-    let (pin_sender, pin_receiver) = mpsc::channel();
+  pub fn create_plex_user(&mut self) -> Result<String, PuddlerError> {
+    let (pin_sender, pin_receiver) = mpsc::channel::<Result<PlexCreatePin, PuddlerError>>();
     let device_id = self.get_config_handle().get_device_id();
     let queries = format!(
       "?X-Plex-Client-Identifier={}&X-Plex-Device-Name={}",
       device_id, APPNAME
     );
+    let client = self.client.clone();
+    let plex_tv_address = self.plex_tv_address.clone();
     thread::spawn(move || {
       let mut pin: Option<PlexCreatePin> = None;
       loop {
-        let req: Result<Response, Response>;
+        let req: Result<Response, PuddlerError>;
         if let Some(ref old_pin) = pin {
           let get_url = format!("pins/{}.json{}", old_pin.id, queries);
-          req = plex_tv(RequestType::Get, None, device_id.clone(), get_url);
+          req = plex_tv(
+            &client,
+            &plex_tv_address,
+            RequestType::Get,
+            None,
+            device_id.clone(),
+            get_url,
+          );
         } else {
           let post_url = format!("pins.json{}", queries);
-          req = plex_tv(RequestType::Post, None, device_id.clone(), post_url.clone());
+          req = plex_tv(
+            &client,
+            &plex_tv_address,
+            RequestType::Post,
+            None,
+            device_id.clone(),
+            post_url.clone(),
+          );
         }
-        let new_pin: PlexCreatePin;
-        match req {
-          Ok(response) => {
-            let json = serde_json::from_str::<Value>(&response.text().unwrap()).unwrap();
-            if let Ok(res_pin) =
-              serde_json::from_value::<PlexCreatePin>(json.get("pin").unwrap().clone())
-            {
-              new_pin = res_pin;
-            } else {
-              sleep(Duration::from_secs(5));
-              continue;
+        let new_pin = match req.and_then(|response| {
+          let json = serde_json::from_str::<Value>(&response.text()?)?;
+          Ok(serde_json::from_value::<PlexCreatePin>(
+            json["pin"].clone(),
+          )?)
+        }) {
+          Ok(new_pin) => new_pin,
+          Err(err) => {
+            // tried again until it works, but the user should know why nothing happens
+            if pin_sender.send(Err(err)).is_err() {
+              break;
             }
-          },
-          Err(_err) => {
             sleep(Duration::from_secs(5));
             continue;
           },
-        }
-        if Some(new_pin.clone()) != pin {
-          pin_sender.send(new_pin.clone()).unwrap();
+        };
+        // nobody is waiting for the pin anymore
+        if Some(new_pin.clone()) != pin && pin_sender.send(Ok(new_pin.clone())).is_err() {
+          break;
         }
         if new_pin.auth_token.is_some() {
           break;
@@ -1715,14 +2268,34 @@ impl PlexServer {
     });
     let device_id = self.get_config_handle().get_device_id();
     let mut stdout = stdout();
+    let mut code: Option<String> = None;
     let access_token: String;
     loop {
-      if let Ok(pins) = pin_receiver.recv() {
-        if let Some(ref auth) = pins.auth_token {
-          println!(" - {}", "Success".cyan());
-          access_token = auth.to_owned();
-          break;
-        }
+      match pin_receiver.recv() {
+        Ok(Ok(pin)) => {
+          if let Some(ref auth) = pin.auth_token {
+            println!(" - {}", "Success".cyan());
+            access_token = auth.to_owned();
+            break;
+          }
+          code = Some(pin.code);
+        },
+        Ok(Err(err)) => {
+          execute!(stdout, MoveToColumn(0), Clear(ClearType::FromCursorDown)).unwrap();
+          print_message(
+            PrintMessageType::Error,
+            format!("Failed to get a PIN from plex.tv, trying again: {}", err).as_str(),
+          );
+        },
+        // the thread is gone, there won't be any pin
+        Err(_) => {
+          execute!(stdout, Show).unwrap();
+          return Err(PuddlerError::Auth(String::from(
+            "plex.tv stopped answering the PIN request.",
+          )));
+        },
+      }
+      if let Some(ref code) = code {
         execute!(
           stdout,
           MoveToColumn(0),
@@ -1732,8 +2305,8 @@ impl PlexServer {
         .unwrap();
         print!(
           "To link your Plex account visit: {} from a web browser and enter the code: {}",
-          "https://plex.tv/link".cyan().underlined(),
-          pins.code.cyan().bold()
+          format!("{}link", self.plex_tv_address).cyan().underlined(),
+          code.clone().cyan().bold()
         );
         stdout.flush().unwrap();
       }
@@ -1742,144 +2315,124 @@ impl PlexServer {
       .get_config_handle()
       .insert_specific_value(Objective::DeviceID, device_id);
     execute!(stdout, Show).unwrap();
-    access_token
+    Ok(access_token)
   }
 
-  fn choose_servers(&mut self, access_token: String) {
-    let device_id = self.get_config_handle().get_device_id();
+  pub fn get_servers(&mut self, access_token: String) -> Result<Vec<PlexResources>, PuddlerError> {
     let url = format!(
       "api/v2/resources?includeHttps=1&includeRelay=1&X-Plex-Features=external-media&X-Plex-Language=en&X-Plex-Token={}",
       access_token
     );
+    let response = self.plex_tv(RequestType::Get, None, url)?;
+    Ok(serde_json::from_str::<Vec<PlexResources>>(
+      &response.text()?,
+    )?)
+  }
+
+  fn choose_servers(&mut self, access_token: String) -> Result<(), PuddlerError> {
     let server_name: String;
     let address: String;
-    match plex_tv(RequestType::Get, None, device_id, url) {
-      Ok(response) => {
-        if let Ok(json) = serde_json::from_str::<Vec<PlexResources>>(&response.text().unwrap()) {
-          let mut options: Vec<InteractiveOption> = vec![InteractiveOption {
-            text: "Please choose which server you want to use (don't forget ports):".to_string(),
-            option_type: InteractiveOptionType::Header,
-          }];
-          for device in json.clone() {
-            if device.provides == "server" {
-              options.push(InteractiveOption {
-                text: format!("{} - {}", device.name, device.publicAddress),
-                option_type: InteractiveOptionType::Button,
-              })
-            }
-          }
-          options.push(InteractiveOption {
-            text: r#"Enter "{NAME},{ADDRESS}""#.to_string(),
-            option_type: InteractiveOptionType::TextInput,
-          });
-          loop {
-            match interactive_select(options.clone()) {
-              ((index, _), _, InteractiveOptionType::Button) => {
-                server_name = json[index].name.clone();
-                address = json[index].publicAddress.clone();
-                break;
-              },
-              ((_, _), Some(input), InteractiveOptionType::TextInput) => {
-                let split = input.split_terminator(',').collect::<Vec<&str>>();
-                if split.len() != 2 {
-                  continue;
-                } else {
-                  server_name = split[0].to_string();
-                  address = split[1].to_string();
-                  break;
-                }
-              },
-              _ => (),
-            }
+    let json = self.get_servers(access_token)?;
+    let mut options: Vec<InteractiveOption> = vec![InteractiveOption {
+      text: "Please choose which server you want to use (don't forget ports):".to_string(),
+      option_type: InteractiveOptionType::Header,
+    }];
+    for device in json.clone() {
+      if device.provides == "server" {
+        options.push(InteractiveOption {
+          text: format!("{} - {}", device.name, device.publicAddress),
+          option_type: InteractiveOptionType::Button,
+        })
+      }
+    }
+    options.push(InteractiveOption {
+      text: r#"Enter "{NAME},{ADDRESS}""#.to_string(),
+      option_type: InteractiveOptionType::TextInput,
+    });
+    loop {
+      match interactive_select(options.clone()) {
+        ((index, _), _, InteractiveOptionType::Button) => {
+          server_name = json[index].name.clone();
+          address = json[index].publicAddress.clone();
+          break;
+        },
+        ((_, _), Some(input), InteractiveOptionType::TextInput) => {
+          let split = input.split_terminator(',').collect::<Vec<&str>>();
+          if split.len() != 2 {
+            continue;
+          } else {
+            server_name = split[0].to_string();
+            address = split[1].to_string();
+            break;
           }
-        } else {
-          print_message(
-            PrintMessageType::Error,
-            "Failed to serialize server list of user.",
-          );
-          exit(1);
-        }
-      },
-      Err(err) => {
-        print_message(
-          PrintMessageType::Error,
-          format!("Failed to get server list of user: {}", err.status()).as_str(),
-        );
-        exit(1);
-      },
+        },
+        _ => (),
+      }
     }
     let handle = self.get_config_handle();
     handle.config.server_name = server_name;
     handle.insert_specific_value(Objective::Address, address);
+    Ok(())
   }
 
-  fn check_token_valid(&mut self) -> bool {
+  pub fn check_token_valid(&mut self) -> bool {
     // WTF IS THIS. use the local media server to check if the token is valid
-    let device_id = self.get_config_handle().get_device_id();
     let user = self.get_config_handle().get_active_user().unwrap();
     let url = "api/v2/user".to_string();
-    print!(
+    eprint!(
       "Logging in with {} on {} ",
       user.clone().username.cyan(),
       self.get_config_handle().config.server_name.clone().cyan()
     );
-    match plex_tv(RequestType::Get, Some(user), device_id, url) {
+    match self.plex_tv(RequestType::Get, Some(user), url) {
       Ok(_) => {
-        println!("{}\n", "🗸".green());
+        eprintln!("{}\n", "🗸".green());
         true
       },
       Err(err) => {
-        println!("{}", "𐄂".red());
+        eprintln!("{}", "𐄂".red());
         print_message(
           PrintMessageType::Error,
-          format!("Failed to login: {}", err.status()).as_str(),
+          format!("Failed to login: {}", err).as_str(),
         );
         false
       },
     }
   }
 
-  fn get_username(&mut self, access_token: String) {
-    match self.get_user(access_token.clone()) {
-      Ok(json) => {
-        let user = UserConfig {
-          access_token,
-          username: json.username,
-          user_id: json.id.to_string(),
-        };
-        let config = self.get_config_handle();
-        config.insert_specific_value(Objective::User, serde_json::to_string(&user).unwrap());
-        config.set_active_user(user.access_token);
-        if config.check_existing_config() {
-          loop {
-            print_message(
-              PrintMessageType::Error,
-              "A media-center configuration with that file name already exists.\nPlease choose a different file name.",
-            );
-            let file_name = take_string_input(vec![]);
-            config.config.server_name = config.config.server_name.replace(' ', "_");
-            let config_path = dirs::config_dir().unwrap();
-            let config_file_path = format!(
-              "{}/{}/media-center/{}.json",
-              &config_path.display().to_string(),
-              APPNAME.to_lowercase(),
-              file_name
-            );
-            config.path = config_file_path;
-            if !config.check_existing_config() {
-              break;
-            }
-          }
-        }
-        config.save();
-      },
-      Err(err) => {
+  pub fn get_username(&mut self, access_token: String) -> Result<(), PuddlerError> {
+    let json = self.get_user(access_token.clone())?;
+    let user = UserConfig {
+      access_token,
+      username: json.username,
+      user_id: json.id.to_string(),
+    };
+    let config = self.get_config_handle();
+    config.insert_specific_value(Objective::User, serde_json::to_string(&user).unwrap());
+    config.set_active_user(user.access_token);
+    if config.check_existing_config() {
+      loop {
         print_message(
           PrintMessageType::Error,
-          format!("Failed to get user information: {}", err).as_str(),
+          "A media-center configuration with that file name already exists.\nPlease choose a different file name.",
         );
-      },
+        let file_name = take_string_input(vec![]);
+        config.config.server_name = config.config.server_name.replace(' ', "_");
+        let config_path = dirs::config_dir().unwrap();
+        let config_file_path = format!(
+          "{}/{}/media-center/{}.json",
+          &config_path.display().to_string(),
+          APPNAME.to_lowercase(),
+          file_name
+        );
+        config.path = config_file_path;
+        if !config.check_existing_config() {
+          break;
+        }
+      }
     }
+    config.save();
+    Ok(())
   }
 }
 
@@ -1889,14 +2442,25 @@ enum RequestType {
   Post,
 }
 
+// Music and video have separate timelines.
+fn timeline_type(item: &PlexItem) -> &'static str {
+  if item.r#type == "track" {
+    "music"
+  } else {
+    "video"
+  }
+}
+
 /// Function to access the public api at plex.tv. NOT FOR INDIVIDUAL INSTANCES!
 fn plex_tv(
+  client: &Client,
+  address: &str,
   request_type: RequestType,
   user: Option<UserConfig>,
   device_id: String,
   url: String,
-) -> Result<Response, Response> {
-  let mut modded_url = format!("https://plex.tv/{}", url);
+) -> Result<Response, PuddlerError> {
+  let mut modded_url = format!("{}{}", address, url);
   if modded_url.contains('?') {
     modded_url += "&";
   } else {
@@ -1909,29 +2473,24 @@ fn plex_tv(
     modded_url += format!("&X-Plex-Token={}", user.access_token).as_str()
   }
 
-  let client = Client::new();
   let builder = if request_type == RequestType::Get {
     client.get(modded_url)
   } else {
     client.post(modded_url)
   };
 
-  let request = builder
+  let response = builder
     .timeout(Duration::from_secs(15))
     .header("Content-Type", "application/json")
     .header("accept", "application/json")
     .header("User-Agent", APPNAME)
-    .send();
-
-  let response = if let Err(res) = request {
-    print_message(PrintMessageType::Error, res.to_string().as_str());
-    exit(1);
-  } else {
-    request.unwrap()
-  };
+    .send()?;
 
   match response.status() {
     StatusCode::OK | StatusCode::CREATED => Ok(response),
-    _ => Err(response),
+    status => Err(PuddlerError::from_status(
+      status,
+      response.text().unwrap_or_default(),
+    )),
   }
 }
@@ -27,6 +27,8 @@ pub struct PuddlerSettings {
   pub glsl_shaders: Vec<String>,
   pub mpv_config_location: Option<String>,
   pub mpv_debug_log: bool,
+  pub skip_segments: bool,
+  pub plex_companion: bool,
 }
 
 #[derive(Clone)]
@@ -38,6 +40,8 @@ enum PuddlerSettingType {
   GLSL_Shaders,
   MPV_Config_Location,
   MPV_Debug,
+  SkipSegments,
+  PlexCompanion,
 }
 
 impl ToString for PuddlerSettingType {
@@ -50,6 +54,8 @@ impl ToString for PuddlerSettingType {
       PuddlerSettingType::GLSL_Shaders => String::from("GLSL Shaders"),
       PuddlerSettingType::MPV_Config_Location => String::from("MPV Config Location"),
       PuddlerSettingType::MPV_Debug => String::from("MPV Debug Log"),
+      PuddlerSettingType::SkipSegments => String::from("Skip Intros and Credits"),
+      PuddlerSettingType::PlexCompanion => String::from("Plex Companion"),
     }
   }
 }
@@ -64,6 +70,8 @@ impl PuddlerSettingType {
       PuddlerSettingType::GLSL_Shaders,
       PuddlerSettingType::MPV_Config_Location,
       PuddlerSettingType::MPV_Debug,
+      PuddlerSettingType::SkipSegments,
+      PuddlerSettingType::PlexCompanion,
     ]
   }
 }
@@ -138,6 +146,18 @@ impl PuddlerSettings {
                   writeln!(settings_file, "mpv_debug_log = {mpv_debug_log}").unwrap();
                   continue;
                 },
+                "skip_segments" => {
+                  let skip_segments =
+                    Self::ask_for_setting(PuddlerSettingType::SkipSegments).skip_segments;
+                  writeln!(settings_file, "skip_segments = {skip_segments}").unwrap();
+                  continue;
+                },
+                "plex_companion" => {
+                  let plex_companion =
+                    Self::ask_for_setting(PuddlerSettingType::PlexCompanion).plex_companion;
+                  writeln!(settings_file, "plex_companion = {plex_companion}").unwrap();
+                  continue;
+                },
                 something => {
                   print_message(
                     PrintMessageType::Error,
@@ -170,6 +190,8 @@ impl PuddlerSettings {
       PuddlerSettingType::GLSL_Shaders => format!("{:?}", self.glsl_shaders),
       PuddlerSettingType::MPV_Config_Location => format!("{:?}", self.mpv_config_location),
       PuddlerSettingType::MPV_Debug => format!("{}", self.mpv_debug_log),
+      PuddlerSettingType::SkipSegments => format!("{}", self.skip_segments),
+      PuddlerSettingType::PlexCompanion => format!("{}", self.plex_companion),
     }
   }
 
@@ -218,6 +240,8 @@ impl PuddlerSettings {
         self.mpv_config_location = change.mpv_config_location
       },
       PuddlerSettingType::MPV_Debug => self.mpv_debug_log = change.mpv_debug_log,
+      PuddlerSettingType::SkipSegments => self.skip_segments = change.skip_segments,
+      PuddlerSettingType::PlexCompanion => self.plex_companion = change.plex_companion,
     }
   }
 
@@ -232,6 +256,8 @@ impl PuddlerSettings {
       mpv_config_location: Self::ask_for_setting(PuddlerSettingType::MPV_Config_Location)
         .mpv_config_location,
       mpv_debug_log: Self::ask_for_setting(PuddlerSettingType::MPV_Debug).mpv_debug_log,
+      skip_segments: Self::ask_for_setting(PuddlerSettingType::SkipSegments).skip_segments,
+      plex_companion: Self::ask_for_setting(PuddlerSettingType::PlexCompanion).plex_companion,
     }
   }
 
@@ -245,6 +271,8 @@ impl PuddlerSettings {
       glsl_shaders: vec![],
       mpv_config_location: None,
       mpv_debug_log: false,
+      skip_segments: false,
+      plex_companion: false,
     };
     match setting {
       PuddlerSettingType::DefaultMediaServer => {
@@ -362,6 +390,20 @@ impl PuddlerSettings {
           _ => false,
         };
       },
+      PuddlerSettingType::SkipSegments => {
+        print!(
+          "Do you want intros, recaps, previews and credits to be skipped automatically?\n(Otherwise mpv offers to skip them with TAB)\n (Y)es / (N)o"
+        );
+        let skip = getch("YyNn");
+        temp.skip_segments = matches!(skip, 'Y' | 'y');
+      },
+      PuddlerSettingType::PlexCompanion => {
+        print!(
+          "Do you want Plex apps on your network to find and control puddler?\n(Anyone on the network can, there's no authentication on port 32500)\n (Y)es / (N)o"
+        );
+        let companion = getch("YyNn");
+        temp.plex_companion = matches!(companion, 'Y' | 'y');
+      },
     }
     println!();
     temp
@@ -0,0 +1,278 @@
+use colored::Colorize;
+use std::fs;
+
+use crate::{
+  backend::play_playlist,
+  error::PuddlerError,
+  input::{InteractiveOption, InteractiveOptionType, interactive_select},
+  media_center::{MediaCenter, ToStringAdv},
+  media_config::Config,
+  media_item::MediaItem,
+  printing::{PrintMessageType, print_message},
+};
+
+// The "Up next" queue of every media-center is kept next to its config, so it's still there the next
+// time puddler is started. Saving it as a server playlist makes it available to other clients too.
+
+pub enum QueueAction {
+  Edit(usize),
+  Play,
+  Save(String),
+  Playlists,
+  Clear,
+  Back,
+}
+
+pub enum EntryAction {
+  MoveUp,
+  MoveDown,
+  Remove,
+  Back,
+}
+
+pub enum PlaylistAction {
+  Play,
+  Queue,
+  Back,
+}
+
+pub fn select_queue_action(entries: &[String]) -> QueueAction {
+  let mut options: Vec<InteractiveOption> = vec![InteractiveOption {
+    text: String::from("Up next:"),
+    option_type: InteractiveOptionType::Header,
+  }];
+  for entry in entries {
+    options.push(InteractiveOption {
+      text: entry.clone(),
+      option_type: InteractiveOptionType::Button,
+    });
+  }
+  if !entries.is_empty() {
+    options.append(&mut vec![
+      InteractiveOption {
+        text: String::from("Play queue"),
+        option_type: InteractiveOptionType::Special,
+      },
+      InteractiveOption {
+        text: String::from("Save as playlist"),
+        option_type: InteractiveOptionType::TextInput,
+      },
+      InteractiveOption {
+        text: String::from("Clear queue"),
+        option_type: InteractiveOptionType::Special,
+      },
+    ]);
+  }
+  options.append(&mut vec![
+    InteractiveOption {
+      text: String::from("Playlists"),
+      option_type: InteractiveOptionType::Special,
+    },
+    InteractiveOption {
+      text: String::from("Back"),
+      option_type: InteractiveOptionType::Special,
+    },
+  ]);
+  match interactive_select(options) {
+    ((index, _), _, InteractiveOptionType::Button) => QueueAction::Edit(index),
+    (_, Some(name), InteractiveOptionType::TextInput) if !name.trim().is_empty() => {
+      QueueAction::Save(name.trim().to_string())
+    },
+    (_, Some(text), InteractiveOptionType::Special) => match text.as_str() {
+      "Play queue" => QueueAction::Play,
+      "Clear queue" => QueueAction::Clear,
+      "Playlists" => QueueAction::Playlists,
+      _ => QueueAction::Back,
+    },
+    _ => QueueAction::Back,
+  }
+}
+
+pub fn select_entry_action(entry: &str) -> EntryAction {
+  let options: Vec<InteractiveOption> = vec![
+    InteractiveOption {
+      text: format!("{}:", entry.cyan()),
+      option_type: InteractiveOptionType::Header,
+    },
+    InteractiveOption {
+      text: String::from("Move up"),
+      option_type: InteractiveOptionType::Button,
+    },
+    InteractiveOption {
+      text: String::from("Move down"),
+      option_type: InteractiveOptionType::Button,
+    },
+    InteractiveOption {
+      text: String::from("Remove"),
+      option_type: InteractiveOptionType::Button,
+    },
+    InteractiveOption {
+      text: String::from("Back"),
+      option_type: InteractiveOptionType::Special,
+    },
+  ];
+  match interactive_select(options) {
+    ((0, _), _, InteractiveOptionType::Button) => EntryAction::MoveUp,
+    ((1, _), _, InteractiveOptionType::Button) => EntryAction::MoveDown,
+    ((2, _), _, InteractiveOptionType::Button) => EntryAction::Remove,
+    _ => EntryAction::Back,
+  }
+}
+
+pub fn select_playlist_action(playlist: &str) -> PlaylistAction {
+  let options: Vec<InteractiveOption> = vec![
+    InteractiveOption {
+      text: format!("{}:", playlist.cyan()),
+      option_type: InteractiveOptionType::Header,
+    },
+    InteractiveOption {
+      text: String::from("Play"),
+      option_type: InteractiveOptionType::Button,
+    },
+    InteractiveOption {
+      text: String::from("Add to queue"),
+      option_type: InteractiveOptionType::Button,
+    },
+    InteractiveOption {
+      text: String::from("Back"),
+      option_type: InteractiveOptionType::Special,
+    },
+  ];
+  match interactive_select(options) {
+    ((0, _), _, InteractiveOptionType::Button) => PlaylistAction::Play,
+    ((1, _), _, InteractiveOptionType::Button) => PlaylistAction::Queue,
+    _ => PlaylistAction::Back,
+  }
+}
+
+// Moving the first entry up or the last one down does nothing.
+pub fn edit_queue<T>(queue: &mut Vec<T>, index: usize, action: EntryAction) {
+  match action {
+    EntryAction::MoveUp if index > 0 => queue.swap(index, index - 1),
+    EntryAction::MoveDown if index + 1 < queue.len() => queue.swap(index, index + 1),
+    EntryAction::Remove => {
+      queue.remove(index);
+    },
+    _ => (),
+  }
+}
+
+pub fn add_to_queue<T>(queue: &mut Vec<T>, items: Vec<T>) {
+  let count = items.len();
+  queue.extend(items);
+  print_message(
+    PrintMessageType::Success,
+    format!(
+      "Added {} to the queue ({} in total).",
+      if count == 1 {
+        String::from("1 item")
+      } else {
+        format!("{} items", count)
+      },
+      queue.len()
+    )
+    .as_str(),
+  );
+}
+
+pub fn queue_menu<M: MediaCenter>(media_center: &mut M) -> Result<(), PuddlerError> {
+  loop {
+    let entries: Vec<String> = media_center
+      .get_queue()
+      .iter()
+      .map(|item| item.to_string_ext())
+      .collect();
+    match select_queue_action(&entries) {
+      QueueAction::Edit(index) => {
+        let action = select_entry_action(&entries[index]);
+        edit_queue(media_center.get_queue(), index, action);
+        store_queue(media_center);
+      },
+      QueueAction::Play => {
+        let queue = media_center.get_queue().clone();
+        let played = play_playlist(media_center, queue, false)?;
+        media_center
+          .get_queue()
+          .retain(|item| !played.contains(&item.id));
+        store_queue(media_center);
+      },
+      QueueAction::Save(name) => {
+        let queue = media_center.get_queue().clone();
+        media_center.save_playlist(&name, &queue)?;
+        print_message(
+          PrintMessageType::Success,
+          format!("Saved the queue as \"{}\".", name).as_str(),
+        );
+      },
+      QueueAction::Playlists => playlists_menu(media_center)?,
+      QueueAction::Clear => {
+        media_center.get_queue().clear();
+        store_queue(media_center);
+      },
+      QueueAction::Back => return Ok(()),
+    }
+  }
+}
+
+fn playlists_menu<M: MediaCenter>(media_center: &mut M) -> Result<(), PuddlerError> {
+  let playlists = media_center.get_playlists()?;
+  if playlists.is_empty() {
+    print_message(
+      PrintMessageType::Warning,
+      "There are no playlists on this media-center.",
+    );
+    return Ok(());
+  }
+  let mut options: Vec<InteractiveOption> = vec![InteractiveOption {
+    text: String::from("Playlists:"),
+    option_type: InteractiveOptionType::Header,
+  }];
+  for playlist in &playlists {
+    options.push(InteractiveOption {
+      text: playlist.to_string(),
+      option_type: InteractiveOptionType::Button,
+    });
+  }
+  options.push(InteractiveOption {
+    text: String::from("Back"),
+    option_type: InteractiveOptionType::Special,
+  });
+  let playlist = match interactive_select(options) {
+    ((index, _), _, InteractiveOptionType::Button) => playlists[index].clone(),
+    _ => return Ok(()),
+  };
+  let action = select_playlist_action(&playlist.to_string());
+  let items = media_center.get_playlist_items(&playlist.id)?;
+  match action {
+    PlaylistAction::Play => {
+      play_playlist(media_center, items, false)?;
+    },
+    PlaylistAction::Queue => {
+      add_to_queue(media_center.get_queue(), items);
+      store_queue(media_center);
+    },
+    PlaylistAction::Back => (),
+  }
+  Ok(())
+}
+
+pub fn load_queue(config: &Config) -> Vec<MediaItem> {
+  fs::read_to_string(config.queue_path())
+    .ok()
+    .and_then(|content| serde_json::from_str::<Vec<MediaItem>>(&content).ok())
+    .unwrap_or_default()
+}
+
+// Written after every change, an empty queue doesn't need a file.
+pub fn store_queue<M: MediaCenter + ?Sized>(media_center: &mut M) {
+  let queue = media_center.get_queue().clone();
+  let path = media_center.get_config_handle().queue_path();
+  if queue.is_empty() {
+    let _ = fs::remove_file(path);
+  } else if let Err(err) = fs::write(path, serde_json::to_string(&queue).unwrap()) {
+    print_message(
+      PrintMessageType::Error,
+      format!("Failed to save the queue: {}", err).as_str(),
+    );
+  }
+}
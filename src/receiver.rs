@@ -0,0 +1,219 @@
+use colored::Colorize;
+use futures::{SinkExt, StreamExt};
+use std::time::{Duration, Instant};
+use tokio::{runtime::Runtime, time::timeout};
+use tokio_tungstenite::{
+  connect_async,
+  tungstenite::{Bytes, Message},
+};
+
+use crate::{
+  error::PuddlerError,
+  media_center::MediaCenter,
+  media_config::MediaCenterType,
+  media_item::MediaItem,
+  mpv::{Player, WebSocketMessage, websocket_url},
+  printing::{PrintMessageType, print_message},
+  remote::{PlayRequest, RemoteRequest},
+};
+
+// Jellyfin drops sessions whose websocket stays silent for too long.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+pub const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+// Only "Play" messages matter while nothing is playing, everything else is handled by the player.
+pub fn play_request(message: &WebSocketMessage) -> Option<PlayRequest> {
+  if message.MessageType != "Play" {
+    return None;
+  }
+  serde_json::from_value::<PlayRequest>(message.Data.clone())
+    .ok()
+    .filter(|request| !request.ItemIds.is_empty())
+}
+
+// Waits for other clients to cast something to this session. Runs until puddler gets killed.
+pub fn run_receiver<M: MediaCenter + ?Sized>(media_center: &mut M) -> Result<(), PuddlerError> {
+  let config = &media_center.get_config_handle().config;
+  if config.media_center_type == MediaCenterType::Plex {
+    return Err(PuddlerError::Unsupported(String::from(
+      "The receiver mode is only available for Jellyfin and Emby.",
+    )));
+  }
+  if config.transcoding {
+    return Err(PuddlerError::Unsupported(String::from(
+      "Transcoding requires user input. Please disable it or use the interactive menu.",
+    )));
+  }
+  // the session has to advertise "Play" before any client shows it as a cast target
+  media_center.report_session_capabilities()?;
+
+  let runtime = Runtime::new()?;
+  println!(
+    "Waiting for other clients to play something on {}.",
+    media_center
+      .get_config_handle()
+      .config
+      .server_name
+      .clone()
+      .cyan()
+  );
+  let mut reconnecting = false;
+  loop {
+    // the session might have expired while the connection was gone
+    if reconnecting {
+      if let Err(err) = media_center.report_session_capabilities() {
+        wait_before_reconnect(err);
+        continue;
+      }
+      reconnecting = false;
+    }
+    let request = match runtime.block_on(wait_for_play_request(websocket_url(media_center))) {
+      Ok(request) => request,
+      Err(err) => {
+        wait_before_reconnect(err);
+        reconnecting = true;
+        continue;
+      },
+    };
+    if let Err(err) = play_request_items(media_center, request) {
+      print_message(PrintMessageType::Error, &err.to_string());
+    }
+  }
+}
+
+fn wait_before_reconnect(err: PuddlerError) {
+  print_message(
+    PrintMessageType::Error,
+    format!(
+      "{} Reconnecting in {} seconds.",
+      err,
+      RECONNECT_DELAY.as_secs()
+    )
+    .as_str(),
+  );
+  std::thread::sleep(RECONNECT_DELAY);
+}
+
+async fn wait_for_play_request(url: String) -> Result<PlayRequest, PuddlerError> {
+  let (mut socket, _) = connect_async(url)
+    .await
+    .map_err(|err| PuddlerError::Network(err.to_string()))?;
+  let mut last_ping = Instant::now();
+  loop {
+    if last_ping.elapsed() > PING_INTERVAL {
+      socket
+        .send(Message::Ping(Bytes::new()))
+        .await
+        .map_err(|err| PuddlerError::Network(err.to_string()))?;
+      last_ping = Instant::now();
+    }
+    let message = match timeout(Duration::from_secs(1), socket.next()).await {
+      Ok(Some(Ok(message))) => message,
+      Ok(_) => {
+        return Err(PuddlerError::Network(String::from(
+          "The websocket connection has been closed.",
+        )));
+      },
+      Err(_) => continue,
+    };
+    if let Ok(message) = serde_json::from_str::<WebSocketMessage>(&message.to_string())
+      && let Some(request) = play_request(&message)
+    {
+      return Ok(request);
+    }
+  }
+}
+
+// Returns the playlist index to continue with, if the requests changed what should be played.
+pub fn apply_remote_requests<M: MediaCenter + ?Sized>(
+  media_center: &mut M,
+  playlist: &mut Vec<MediaItem>,
+  index: usize,
+  requests: Vec<RemoteRequest>,
+) -> Option<usize> {
+  let mut jump: Option<usize> = None;
+  for request in requests {
+    match request {
+      RemoteRequest::NextTrack => jump = Some(jump.unwrap_or(index) + 1),
+      RemoteRequest::PreviousTrack => jump = Some(jump.unwrap_or(index).saturating_sub(1)),
+      RemoteRequest::Play(item_ids, play_command, start_position_ticks) => {
+        let mut items: Vec<MediaItem> = vec![];
+        for item_id in item_ids {
+          match media_center.get_playable_item(&item_id) {
+            Ok(item) => items.push(item),
+            Err(PuddlerError::Unsupported(reason)) => print_message(
+              PrintMessageType::Warning,
+              format!("Remote control: {}", reason).as_str(),
+            ),
+            Err(err) => print_message(
+              PrintMessageType::Error,
+              format!("Remote control: failed to get item {}: {}", item_id, err).as_str(),
+            ),
+          }
+        }
+        // ticks to seconds
+        if let Some(ticks) = start_position_ticks
+          && let Some(first) = items.first_mut()
+        {
+          first.playback_position = ticks / 10000000;
+        }
+        if items.is_empty() {
+          continue;
+        }
+        match play_command.as_str() {
+          "PlayNow" => {
+            *playlist = items;
+            jump = Some(0);
+          },
+          "PlayNext" => {
+            let position = jump.unwrap_or(index) + 1;
+            playlist.splice(position..position, items);
+          },
+          _ => playlist.extend(items),
+        }
+      },
+    }
+  }
+  jump.filter(|index| *index < playlist.len())
+}
+
+// The player opens its own websocket, so remote control keeps working during playback.
+// Plex' companion receiver plays its requests the same way.
+pub fn play_request_items<M: MediaCenter + ?Sized>(
+  media_center: &mut M,
+  request: PlayRequest,
+) -> Result<(), PuddlerError> {
+  let mut playlist = vec![];
+  let mut index = match apply_remote_requests(
+    media_center,
+    &mut playlist,
+    0,
+    vec![RemoteRequest::Play(
+      request.ItemIds,
+      String::from("PlayNow"),
+      request.StartPositionTicks,
+    )],
+  ) {
+    Some(index) => index,
+    None => return Ok(()),
+  };
+  let settings = media_center.get_settings().clone();
+  while let Some(item) = playlist.get(index) {
+    println!("Playing: {}", item.to_string().cyan());
+    let mut player = Player::new(media_center.get_config_handle().clone(), settings.clone());
+    // nobody is there to choose a file
+    let ret = media_center.play_media_item(&mut player, item, &mut None, true)?;
+    if let Some(remote_index) =
+      apply_remote_requests(media_center, &mut playlist, index, ret.remote_requests)
+    {
+      index = remote_index;
+    } else if ret.played {
+      index += 1;
+    } else {
+      // stopped by the sender
+      break;
+    }
+  }
+  println!("Waiting for the next request.");
+  Ok(())
+}
@@ -0,0 +1,277 @@
+use libmpv2::Mpv;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::{
+  mpv::WebSocketMessage,
+  printing::{PrintMessageType, print_message},
+};
+
+// Requests that can't be handled by mpv alone. They are handed back to whoever started the playback.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemoteRequest {
+  NextTrack,
+  PreviousTrack,
+  // item ids, "PlayNow"/"PlayNext"/"PlayLast" and the start position in ticks
+  Play(Vec<String>, String, Option<u64>),
+}
+
+#[derive(Debug, Deserialize)]
+struct PlaystateRequest {
+  Command: String,
+  SeekPositionTicks: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeneralCommand {
+  Name: String,
+  #[serde(default)]
+  Arguments: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlayRequest {
+  pub ItemIds: Vec<String>,
+  pub StartPositionTicks: Option<u64>,
+  pub PlayCommand: String,
+}
+
+// Jellyfin-web skips 10s back and 30s forward by default.
+const REWIND_SECONDS: &str = "-10";
+const FASTFORWARD_SECONDS: &str = "30";
+
+// Handles `Playstate`, `GeneralCommand` and `Play` messages of the session websocket.
+// `start_offset` is the position at which a transcode started, since mpv's time starts at 0 there.
+// Returns true if something changed, that should be reported to the server right away.
+pub fn handle_message(
+  mpv: &Mpv,
+  message: &WebSocketMessage,
+  start_offset: u64,
+  requests: &mut Vec<RemoteRequest>,
+) -> bool {
+  match message.MessageType.as_str() {
+    "Playstate" => {
+      if let Ok(request) = serde_json::from_value::<PlaystateRequest>(message.Data.clone()) {
+        handle_playstate(mpv, request, start_offset, requests)
+      } else {
+        false
+      }
+    },
+    "GeneralCommand" => {
+      if let Ok(command) = serde_json::from_value::<GeneralCommand>(message.Data.clone()) {
+        handle_general_command(mpv, command, requests)
+      } else {
+        false
+      }
+    },
+    "Play" => {
+      if let Ok(request) = serde_json::from_value::<PlayRequest>(message.Data.clone()) {
+        let play_now = request.PlayCommand == "PlayNow";
+        requests.push(RemoteRequest::Play(
+          request.ItemIds,
+          request.PlayCommand,
+          request.StartPositionTicks,
+        ));
+        if play_now {
+          run(mpv, "quit", &["0"]);
+        }
+      }
+      false
+    },
+    _ => false,
+  }
+}
+
+// Inside a queue of tracks, skipping is done by mpv itself.
+pub fn playlist_command(message: &WebSocketMessage) -> Option<&'static str> {
+  let command = match message.MessageType.as_str() {
+    "Playstate" => {
+      serde_json::from_value::<PlaystateRequest>(message.Data.clone())
+        .ok()?
+        .Command
+    },
+    "GeneralCommand" => {
+      serde_json::from_value::<GeneralCommand>(message.Data.clone())
+        .ok()?
+        .Name
+    },
+    _ => return None,
+  };
+  match command.as_str() {
+    "NextTrack" | "PlayNext" => Some("playlist-next"),
+    "PreviousTrack" => Some("playlist-prev"),
+    _ => None,
+  }
+}
+
+fn handle_playstate(
+  mpv: &Mpv,
+  request: PlaystateRequest,
+  start_offset: u64,
+  requests: &mut Vec<RemoteRequest>,
+) -> bool {
+  match request.Command.as_str() {
+    "PlayPause" => run(mpv, "cycle", &["pause"]),
+    "Pause" => set(mpv, "pause", true),
+    "Unpause" => set(mpv, "pause", false),
+    "Stop" => run(mpv, "quit", &["0"]),
+    "Seek" => {
+      if let Some(ticks) = request.SeekPositionTicks {
+        let position = (ticks / 10000000).saturating_sub(start_offset);
+        run(mpv, "seek", &[&position.to_string(), "absolute"]);
+      }
+    },
+    "Rewind" => run(mpv, "seek", &[REWIND_SECONDS]),
+    "FastForward" => run(mpv, "seek", &[FASTFORWARD_SECONDS]),
+    "NextTrack" => {
+      requests.push(RemoteRequest::NextTrack);
+      run(mpv, "quit", &["0"]);
+    },
+    "PreviousTrack" => {
+      requests.push(RemoteRequest::PreviousTrack);
+      run(mpv, "quit", &["0"]);
+    },
+    _ => return false,
+  }
+  true
+}
+
+fn handle_general_command(
+  mpv: &Mpv,
+  command: GeneralCommand,
+  requests: &mut Vec<RemoteRequest>,
+) -> bool {
+  let argument = |name: &str| command.Arguments.get(name).cloned().unwrap_or_default();
+  match command.Name.as_str() {
+    "SetAudioStreamIndex" => {
+      if let Ok(index) = argument("Index").parse::<i64>() {
+        select_track(mpv, "audio", "aid", index, "");
+      }
+    },
+    "SetSubtitleStreamIndex" => {
+      if let Ok(index) = argument("Index").parse::<i64>() {
+        // -1 disables subtitles
+        if index < 0 {
+          set(mpv, "sid", "no");
+        } else {
+          select_track(mpv, "sub", "sid", index, &argument("StreamKey"));
+        }
+      }
+    },
+    "SetVolume" => {
+      if let Ok(volume) = argument("Volume").parse::<i64>() {
+        set(mpv, "volume", volume);
+      }
+    },
+    "VolumeUp" => run(mpv, "add", &["volume", "5"]),
+    "VolumeDown" => run(mpv, "add", &["volume", "-5"]),
+    "ToggleMute" => run(mpv, "cycle", &["mute"]),
+    "Mute" => set(mpv, "mute", true),
+    "Unmute" => set(mpv, "mute", false),
+    "ToggleFullscreen" => run(mpv, "cycle", &["fullscreen"]),
+    "TakeScreenshot" => run(mpv, "screenshot", &[]),
+    // there is no ui to navigate, so these behave like the arrow keys in mpv
+    "MoveLeft" => run(mpv, "seek", &["-5"]),
+    "MoveRight" => run(mpv, "seek", &["5"]),
+    "MoveUp" => run(mpv, "seek", &["60"]),
+    "MoveDown" => run(mpv, "seek", &["-60"]),
+    "Select" => run(mpv, "cycle", &["pause"]),
+    "Back" | "GoHome" => run(mpv, "quit", &["0"]),
+    "GoToSettings" => run(mpv, "script-binding", &["stats/display-stats-toggle"]),
+    "DisplayContent" => show_text(mpv, &argument("ItemName"), 3000),
+    "DisplayMessage" => {
+      let text = match (argument("Header"), argument("Text")) {
+        (header, text) if header.is_empty() => text,
+        (header, text) if text.is_empty() => header,
+        (header, text) => format!("{}\n{}", header, text),
+      };
+      show_text(
+        mpv,
+        &text,
+        argument("TimeoutMs").parse::<u64>().unwrap_or(5000),
+      );
+    },
+    "PlayNext" => {
+      requests.push(RemoteRequest::NextTrack);
+      run(mpv, "quit", &["0"]);
+    },
+    "PlayMediaSource" => {
+      let item_id = argument("ItemId");
+      if !item_id.is_empty() {
+        requests.push(RemoteRequest::Play(
+          vec![item_id],
+          String::from("PlayNow"),
+          argument("StartPositionTicks").parse::<u64>().ok(),
+        ));
+        run(mpv, "quit", &["0"]);
+      }
+    },
+    _ => return false,
+  }
+  true
+}
+
+// The servers use the stream index of the file, which is what mpv calls "src-id".
+// External subtitles are added by url, which contains the index (or Plex' stream key) as well.
+fn select_track(mpv: &Mpv, track_type: &str, property: &str, index: i64, stream_key: &str) {
+  let count = mpv.get_property::<i64>("track-list/count").unwrap_or(0);
+  for track in 0..count {
+    if mpv
+      .get_property::<String>(&format!("track-list/{}/type", track))
+      .ok()
+      .as_deref()
+      != Some(track_type)
+    {
+      continue;
+    }
+    let external = mpv
+      .get_property::<String>(&format!("track-list/{}/external-filename", track))
+      .ok();
+    let matches = if let Some(filename) = external {
+      if stream_key.is_empty() {
+        filename.contains(&format!("/Subtitles/{}/", index))
+      } else {
+        filename.contains(stream_key)
+      }
+    } else {
+      mpv
+        .get_property::<i64>(&format!("track-list/{}/src-id", track))
+        .ok()
+        == Some(index)
+    };
+    if matches && let Ok(id) = mpv.get_property::<i64>(&format!("track-list/{}/id", track)) {
+      set(mpv, property, id);
+      return;
+    }
+  }
+  print_message(
+    PrintMessageType::Warning,
+    format!(
+      "Remote control: there is no {} track with index {}.",
+      track_type, index
+    )
+    .as_str(),
+  );
+}
+
+fn show_text(mpv: &Mpv, text: &str, duration_ms: u64) {
+  run(mpv, "show-text", &[text, &duration_ms.to_string()]);
+}
+
+fn run(mpv: &Mpv, command: &str, args: &[&str]) {
+  if let Err(err) = mpv.command(command, args) {
+    print_message(
+      PrintMessageType::Error,
+      format!("Remote control: \"{}\" failed: {}", command, err).as_str(),
+    );
+  }
+}
+
+fn set<T: libmpv2::SetData>(mpv: &Mpv, property: &str, value: T) {
+  if let Err(err) = mpv.set_property(property, value) {
+    print_message(
+      PrintMessageType::Error,
+      format!("Remote control: setting \"{}\" failed: {}", property, err).as_str(),
+    );
+  }
+}
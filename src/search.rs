@@ -0,0 +1,209 @@
+use colored::Colorize;
+
+use crate::{
+  APPNAME,
+  error::PuddlerError,
+  input::{InteractiveOption, InteractiveOptionType},
+  library::PAGE_SIZE,
+};
+
+// Everything after the search term can narrow it down:
+// `bebop type:series year:1995-2000 genre:anime is:unplayed rating:8 actor:"Koichi Yamadera"`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+  pub term: String,
+  // movie, series, episode, artist, album or track
+  pub item_type: Option<String>,
+  pub years: Option<(u32, u32)>,
+  pub genre: Option<String>,
+  pub played: Option<bool>,
+  pub min_rating: Option<f64>,
+  pub person: Option<String>,
+  // "Actor" or "Director", any role if not set
+  pub person_type: Option<String>,
+}
+
+pub const SEARCH_TYPES: [&str; 6] = ["movie", "series", "episode", "artist", "album", "track"];
+
+pub fn parse_search(input: &str) -> Result<SearchQuery, PuddlerError> {
+  let mut query = SearchQuery::default();
+  let mut term: Vec<String> = vec![];
+  for word in split_words(input) {
+    let Some((key, value)) = word.split_once(':') else {
+      term.push(word);
+      continue;
+    };
+    match key.to_lowercase().as_str() {
+      "type" => {
+        let value = match value.to_lowercase().as_str() {
+          "show" | "shows" | "series" | "tv" => String::from("series"),
+          value => value.strip_suffix('s').unwrap_or(value).to_string(),
+        };
+        if !SEARCH_TYPES.contains(&value.as_str()) {
+          return Err(PuddlerError::Unsupported(format!(
+            "Unknown type \"{}\". Use one of: {}.",
+            value,
+            SEARCH_TYPES.join(", ")
+          )));
+        }
+        query.item_type = Some(value);
+      },
+      "year" => query.years = Some(parse_years(value)?),
+      "genre" => query.genre = Some(value.to_string()),
+      "is" => match value.to_lowercase().as_str() {
+        "played" | "watched" => query.played = Some(true),
+        "unplayed" | "unwatched" => query.played = Some(false),
+        _ => {
+          return Err(PuddlerError::Unsupported(format!(
+            "\"is:{}\" is neither \"is:played\" nor \"is:unplayed\".",
+            value
+          )));
+        },
+      },
+      "rating" => {
+        query.min_rating = Some(
+          value
+            .parse::<f64>()
+            .map_err(|_| PuddlerError::Unsupported(format!("\"{}\" is not a rating.", value)))?,
+        )
+      },
+      "person" => query.person = Some(value.to_string()),
+      "actor" | "director" => {
+        query.person = Some(value.to_string());
+        query.person_type = Some(if key.eq_ignore_ascii_case("actor") {
+          String::from("Actor")
+        } else {
+          String::from("Director")
+        });
+      },
+      // "Re:Zero" is still a title
+      _ => term.push(word),
+    }
+  }
+  query.term = term.join(" ");
+  Ok(query)
+}
+
+// Splits on whitespace, but keeps "quoted words" together.
+fn split_words(input: &str) -> Vec<String> {
+  let mut words: Vec<String> = vec![];
+  let mut word = String::new();
+  let mut quoted = false;
+  for character in input.chars() {
+    match character {
+      '"' => quoted = !quoted,
+      c if c.is_whitespace() && !quoted => {
+        if !word.is_empty() {
+          words.push(word.clone());
+          word.clear();
+        }
+      },
+      c => word.push(c),
+    }
+  }
+  if !word.is_empty() {
+    words.push(word);
+  }
+  words
+}
+
+fn parse_years(value: &str) -> Result<(u32, u32), PuddlerError> {
+  let invalid =
+    || PuddlerError::Unsupported(format!("\"{}\" is not a year or range of years.", value));
+  let (from, to) = value.split_once('-').unwrap_or((value, value));
+  let from = from.parse::<u32>().map_err(|_| invalid())?;
+  let to = to.parse::<u32>().map_err(|_| invalid())?;
+  // they end up in dates
+  if from > to || to > 9999 {
+    return Err(invalid());
+  }
+  Ok((from, to))
+}
+
+impl SearchQuery {
+  pub fn has_filters(&self) -> bool {
+    self.item_type.is_some()
+      || self.years.is_some()
+      || self.genre.is_some()
+      || self.played.is_some()
+      || self.min_rating.is_some()
+      || self.person.is_some()
+  }
+
+  // Jellyfin and Emby share the filters of the "Items" endpoint.
+  pub fn jellyfin_url(&self, user_id: &str, page: usize) -> String {
+    let item_types = match self.item_type.as_deref() {
+      Some("movie") => "Movie",
+      Some("series") => "Series",
+      Some("episode") => "Episode",
+      Some("artist") => "MusicArtist",
+      Some("album") => "MusicAlbum",
+      Some("track") => "Audio",
+      _ => "Series,Movie,MusicArtist,MusicAlbum,Audio",
+    };
+    let mut url = format!(
+      "Items?UserId={}&Recursive=true&IncludeItemTypes={}",
+      user_id, item_types
+    );
+    if self.term.is_empty() {
+      url += "&SortBy=SortName";
+    } else {
+      url += &format!("&SearchTerm={}", urlencoding::encode(&self.term));
+    }
+    // "Years" would need every single year of the range
+    if let Some((from, to)) = self.years {
+      url += &format!(
+        "&MinPremiereDate={}-01-01T00:00:00Z&MaxPremiereDate={}-12-31T23:59:59Z",
+        from, to
+      );
+    }
+    if let Some(genre) = &self.genre {
+      url += &format!("&Genres={}", urlencoding::encode(genre));
+    }
+    if let Some(played) = self.played {
+      url += &format!("&IsPlayed={}", played);
+    }
+    if let Some(rating) = self.min_rating {
+      url += &format!("&MinCommunityRating={}", rating);
+    }
+    if let Some(person) = &self.person {
+      url += &format!("&Person={}", urlencoding::encode(person));
+      if let Some(person_type) = &self.person_type {
+        url += &format!("&PersonTypes={}", person_type);
+      }
+    }
+    url += &format!("&StartIndex={}&Limit={}", page * PAGE_SIZE, PAGE_SIZE);
+    url
+  }
+}
+
+// The search results in the media-center menu. More pages are appended to the same list.
+pub fn search_options(search: &str, entries: &[String], more: bool) -> Vec<InteractiveOption> {
+  let mut options: Vec<InteractiveOption> = vec![InteractiveOption {
+    text: format!("Search-Result for \"{}\":", search.cyan()),
+    option_type: InteractiveOptionType::Header,
+  }];
+  for entry in entries {
+    options.push(InteractiveOption {
+      text: entry.clone(),
+      option_type: InteractiveOptionType::Button,
+    });
+  }
+  if more {
+    options.push(InteractiveOption {
+      text: String::from("Load more"),
+      option_type: InteractiveOptionType::Special,
+    });
+  }
+  options.append(&mut vec![
+    InteractiveOption {
+      text: String::from("Back"),
+      option_type: InteractiveOptionType::Special,
+    },
+    InteractiveOption {
+      text: format!("Return to {} Menu", APPNAME),
+      option_type: InteractiveOptionType::Special,
+    },
+  ]);
+  options
+}
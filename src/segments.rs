@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::error::PuddlerError;
+
+// Everything mpv gets to know about the timeline of a video, besides the video itself.
+
+// Parts of a video that can be skipped. Jellyfin (10.10+) calls them media segments, Plex markers.
+// Emby has neither, its items just don't have any segments.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SegmentType {
+  Intro,
+  Outro,
+  Recap,
+  Preview,
+  Commercial,
+}
+
+impl fmt::Display for SegmentType {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      SegmentType::Intro => write!(f, "Intro"),
+      SegmentType::Outro => write!(f, "Credits"),
+      SegmentType::Recap => write!(f, "Recap"),
+      SegmentType::Preview => write!(f, "Preview"),
+      SegmentType::Commercial => write!(f, "Commercial"),
+    }
+  }
+}
+
+// Times are in seconds from the start of the item, even when transcoding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+  pub segment_type: SegmentType,
+  pub start: f64,
+  pub end: f64,
+}
+
+#[derive(Deserialize)]
+struct MediaSegmentDto {
+  Type: String,
+  StartTicks: u64,
+  EndTicks: u64,
+}
+
+#[derive(Deserialize)]
+struct MediaSegmentList {
+  Items: Vec<MediaSegmentDto>,
+}
+
+pub fn parse_media_segments(json: &str) -> Result<Vec<Segment>, PuddlerError> {
+  let list = serde_json::from_str::<MediaSegmentList>(json)?;
+  let mut segments: Vec<Segment> = list
+    .Items
+    .into_iter()
+    .filter_map(|segment| {
+      let segment_type = match segment.Type.as_str() {
+        "Intro" => SegmentType::Intro,
+        "Outro" => SegmentType::Outro,
+        "Recap" => SegmentType::Recap,
+        "Preview" => SegmentType::Preview,
+        "Commercial" => SegmentType::Commercial,
+        _ => return None,
+      };
+      Some(Segment {
+        segment_type,
+        start: segment.StartTicks as f64 / 10000000.0,
+        end: segment.EndTicks as f64 / 10000000.0,
+      })
+    })
+    .filter(|segment| segment.end > segment.start)
+    .collect();
+  segments.sort_by(|a, b| a.start.total_cmp(&b.start));
+  Ok(segments)
+}
+
+// The last second doesn't count, otherwise seeking to the end would land in the segment again.
+pub fn segment_at(segments: &[Segment], time: f64) -> Option<usize> {
+  segments
+    .iter()
+    .position(|segment| time >= segment.start && time < segment.end - 1.0)
+}
+
+// A chapter as the server knows it, it lasts until the next one starts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+  pub title: String,
+  pub start: f64,
+}
+
+// Jellyfin's trickplay tiles: `url` points to a tile sheet, with "%d" where its index goes.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Trickplay {
+  pub url: String,
+  pub width: u32,
+  pub height: u32,
+  pub tile_width: u32,
+  pub tile_height: u32,
+  pub thumbnail_count: u32,
+  // milliseconds between two thumbnails
+  pub interval: u32,
+}
+
+// mpv can't add chapters through libmpv, but it loads them from an ffmetadata file.
+// Segments cut through the chapters of the server, whatever chapter they interrupt goes on after them.
+// `offset` is where a transcode starts, everything before it is cut off.
+pub fn chapters_file(
+  chapters: &[Chapter],
+  segments: &[Segment],
+  runtime: u64,
+  offset: u64,
+  label: &str,
+) -> String {
+  let mut chapters = chapters.to_vec();
+  chapters.sort_by(|a, b| a.start.total_cmp(&b.start));
+  let title_at = |time: f64| {
+    chapters
+      .iter()
+      .rev()
+      .find(|chapter| chapter.start <= time)
+      .map(|chapter| chapter.title.clone())
+      .unwrap_or(label.to_string())
+  };
+  let inside_segment = |time: f64| {
+    segments
+      .iter()
+      .any(|segment| time >= segment.start && time < segment.end)
+  };
+
+  let mut marks: Vec<(f64, String)> = vec![(0.0, title_at(0.0))];
+  marks.extend(
+    chapters
+      .iter()
+      .map(|chapter| (chapter.start, chapter.title.clone())),
+  );
+  marks.retain(|(time, _)| !inside_segment(*time));
+  for segment in segments {
+    marks.push((segment.start, segment.segment_type.to_string()));
+    // the server's chapters often start right where a segment ends
+    let followed = chapters
+      .iter()
+      .any(|chapter| chapter.start >= segment.end && chapter.start - segment.end < 0.5);
+    if !inside_segment(segment.end) && !followed {
+      marks.push((segment.end, title_at(segment.end)));
+    }
+  }
+  marks.sort_by(|a, b| a.0.total_cmp(&b.0));
+  marks.dedup_by(|next, previous| next.0 - previous.0 < 0.5);
+
+  let mut file = String::from(";FFMETADATA1\n");
+  for (index, (start, title)) in marks.iter().enumerate() {
+    let end = marks.get(index + 1).map_or(runtime as f64, |next| next.0);
+    let start = ((start - offset as f64).max(0.0) * 1000.0).round() as u64;
+    let end = ((end - offset as f64) * 1000.0).round() as i64;
+    if end <= start as i64 {
+      continue;
+    }
+    file += &format!(
+      "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+      start,
+      end,
+      escape_metadata(title)
+    );
+  }
+  file
+}
+
+fn escape_metadata(value: &str) -> String {
+  let mut escaped = String::new();
+  for character in value.chars() {
+    if matches!(character, '=' | ';' | '#' | '\\' | '\n') {
+      escaped.push('\\');
+    }
+    escaped.push(character);
+  }
+  escaped
+}
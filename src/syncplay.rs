@@ -0,0 +1,561 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use crossterm::event::{Event, KeyEventKind, poll, read};
+use futures::StreamExt;
+use libmpv2::Mpv;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+use tokio::{net::TcpStream, runtime::Runtime, time::timeout};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
+
+use crate::{
+  error::PuddlerError,
+  input::{InteractiveOption, InteractiveOptionType, interactive_select},
+  media_center::MediaCenter,
+  mpv::{Player, WebSocketMessage, websocket_url},
+  printing::{PrintMessageType, print_message},
+};
+
+// Jellyfin only. Emby doesn't have anything like it and Plex' "Watch Together" is closed.
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GroupInfo {
+  pub GroupId: String,
+  pub GroupName: String,
+  pub State: String,
+  pub Participants: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SyncPlayCommand {
+  pub GroupId: String,
+  pub PlaylistItemId: String,
+  pub When: DateTime<Utc>,
+  pub PositionTicks: Option<u64>,
+  pub Command: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GroupUpdate {
+  GroupId: String,
+  Type: String,
+  Data: Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct QueueItem {
+  pub ItemId: String,
+  pub PlaylistItemId: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlayQueueUpdate {
+  pub Playlist: Vec<QueueItem>,
+  pub PlayingItemIndex: i32,
+  pub StartPositionTicks: u64,
+  pub IsPlaying: bool,
+}
+
+impl PlayQueueUpdate {
+  pub fn playing_item(&self) -> Option<&QueueItem> {
+    if self.PlayingItemIndex < 0 {
+      None
+    } else {
+      self.Playlist.get(self.PlayingItemIndex as usize)
+    }
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct UtcTime {
+  RequestReceptionTime: DateTime<Utc>,
+  ResponseTransmissionTime: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+struct ReadyRequest {
+  When: DateTime<Utc>,
+  PositionTicks: u64,
+  IsPlaying: bool,
+  PlaylistItemId: String,
+}
+
+// Everything `Player::play` needs to follow a group.
+pub struct SyncPlay {
+  pub group_id: String,
+  pub playlist_item_id: String,
+  // server time - local time
+  time_offset: TimeDelta,
+  // commands are scheduled for a specific (server) time, which might still be in the future
+  pending: Vec<SyncPlayCommand>,
+  // the last pause/unpause; the expected position is calculated from it
+  reference: Option<SyncPlayCommand>,
+  group_playing: bool,
+  expected_pause: bool,
+  buffering: bool,
+  ready_pending: bool,
+  speed_changed: bool,
+  // set once the group moves on to another item
+  pub next: Option<PlayQueueUpdate>,
+  pub left: bool,
+}
+
+impl SyncPlay {
+  pub fn new(
+    group_id: String,
+    playlist_item_id: String,
+    group_playing: bool,
+    time_offset: TimeDelta,
+  ) -> Self {
+    SyncPlay {
+      group_id,
+      playlist_item_id,
+      time_offset,
+      pending: vec![],
+      reference: None,
+      group_playing,
+      expected_pause: true,
+      buffering: false,
+      ready_pending: false,
+      speed_changed: false,
+      next: None,
+      left: false,
+    }
+  }
+
+  pub fn server_time(&self) -> DateTime<Utc> {
+    Utc::now() + self.time_offset
+  }
+
+  // Nothing is played until the group says so.
+  pub fn start(&mut self, mpv: &Mpv) {
+    if let Err(err) = mpv.set_property("pause", true) {
+      print_message(
+        PrintMessageType::Error,
+        format!("Failed to pause: {}", err).as_str(),
+      );
+    }
+    self.expected_pause = true;
+  }
+
+  pub fn file_loaded(&mut self) {
+    self.ready_pending = true;
+  }
+
+  pub fn receive(&mut self, message: &WebSocketMessage) {
+    match message.MessageType.as_str() {
+      "SyncPlayCommand" => {
+        if let Ok(command) = serde_json::from_value::<SyncPlayCommand>(message.Data.clone())
+          && command.GroupId == self.group_id
+        {
+          self.pending.push(command);
+          self.pending.sort_by_key(|command| command.When);
+        }
+      },
+      "SyncPlayGroupUpdate" => {
+        let update = if let Ok(update) = serde_json::from_value::<GroupUpdate>(message.Data.clone())
+        {
+          update
+        } else {
+          return;
+        };
+        if update.GroupId != self.group_id {
+          return;
+        }
+        match update.Type.as_str() {
+          "PlayQueue" => {
+            if let Ok(queue) = serde_json::from_value::<PlayQueueUpdate>(update.Data)
+              && let Some(item) = queue.playing_item()
+              && item.PlaylistItemId != self.playlist_item_id
+            {
+              self.next = Some(queue);
+            }
+          },
+          "UserJoined" => print_message(
+            PrintMessageType::Warning,
+            format!(
+              "{} joined the group.",
+              update.Data.as_str().unwrap_or("Someone")
+            )
+            .as_str(),
+          ),
+          "UserLeft" => print_message(
+            PrintMessageType::Warning,
+            format!(
+              "{} left the group.",
+              update.Data.as_str().unwrap_or("Someone")
+            )
+            .as_str(),
+          ),
+          "GroupLeft" | "NotInGroup" | "GroupDoesNotExist" => {
+            print_message(
+              PrintMessageType::Warning,
+              "You are no longer part of the group.",
+            );
+            self.left = true;
+          },
+          _ => (),
+        }
+      },
+      _ => (),
+    }
+  }
+
+  // Called on every iteration of the player loop. Returns true, if the group wants us to stop.
+  pub async fn update(&mut self, mpv: &Mpv, media_center: &mut Box<dyn MediaCenter>) -> bool {
+    if self.left {
+      return false;
+    }
+    if self.next.is_some() {
+      return true;
+    }
+    let time_pos = mpv.get_property::<f64>("time-pos").unwrap_or(0.0);
+
+    while self
+      .pending
+      .first()
+      .is_some_and(|command| command.When <= self.server_time())
+    {
+      let command = self.pending.remove(0);
+      if command.PlaylistItemId != self.playlist_item_id {
+        continue;
+      }
+      let position = command.PositionTicks.map(|ticks| ticks as f64 / 10000000.0);
+      match command.Command.as_str() {
+        "Unpause" => {
+          // the command might be late, so catch up
+          if let Some(position) = position {
+            let expected = position + self.seconds_since(command.When);
+            if (expected - time_pos).abs() > 0.5 {
+              seek(mpv, expected);
+            }
+          }
+          set_pause(mpv, false);
+          self.expected_pause = false;
+          self.group_playing = true;
+          self.reference = Some(command);
+        },
+        "Pause" => {
+          set_pause(mpv, true);
+          if let Some(position) = position {
+            seek(mpv, position);
+          }
+          self.expected_pause = true;
+          self.group_playing = false;
+          self.reference = Some(command);
+        },
+        "Seek" => {
+          set_pause(mpv, true);
+          self.expected_pause = true;
+          if let Some(position) = position {
+            seek(mpv, position);
+          }
+          self.ready_pending = true;
+        },
+        "Stop" => return true,
+        _ => (),
+      }
+    }
+
+    let paused = mpv.get_property::<bool>("pause").unwrap_or(true);
+    let seeking = mpv.get_property::<bool>("seeking").unwrap_or(true);
+    let paused_for_cache = mpv
+      .get_property::<bool>("paused-for-cache")
+      .unwrap_or(false);
+
+    if self.ready_pending && !seeking {
+      self.ready_pending = false;
+      self
+        .send_state("SyncPlay/Ready", time_pos, media_center)
+        .await;
+    }
+
+    // the user pressed pause/unpause inside of mpv
+    if paused != self.expected_pause {
+      if paused {
+        self.expected_pause = true;
+        send(media_center, "SyncPlay/Pause", String::new()).await;
+      } else {
+        // unpausing has to be scheduled by the server, so that everyone starts at the same time
+        set_pause(mpv, true);
+        send(media_center, "SyncPlay/Unpause", String::new()).await;
+      }
+    }
+
+    if paused_for_cache != self.buffering {
+      self.buffering = paused_for_cache;
+      let url = if paused_for_cache {
+        "SyncPlay/Buffering"
+      } else {
+        "SyncPlay/Ready"
+      };
+      self.send_state(url, time_pos, media_center).await;
+    }
+
+    if self.group_playing && !paused && !self.buffering && !seeking {
+      self.correct_drift(mpv, time_pos);
+    }
+    false
+  }
+
+  // Small differences are evened out by changing the speed, big ones by seeking.
+  fn correct_drift(&mut self, mpv: &Mpv, time_pos: f64) {
+    let expected = if let Some(reference) = &self.reference
+      && let Some(ticks) = reference.PositionTicks
+    {
+      ticks as f64 / 10000000.0 + self.seconds_since(reference.When)
+    } else {
+      return;
+    };
+    let drift = time_pos - expected;
+    if drift.abs() > 1.0 {
+      seek(mpv, expected);
+      self.set_speed(mpv, 1.0);
+    } else if drift.abs() > 0.1 {
+      self.set_speed(mpv, if drift > 0.0 { 0.95 } else { 1.05 });
+    } else if drift.abs() < 0.05 && self.speed_changed {
+      self.set_speed(mpv, 1.0);
+    }
+  }
+
+  fn set_speed(&mut self, mpv: &Mpv, speed: f64) {
+    if mpv.set_property("speed", speed).is_ok() {
+      self.speed_changed = speed != 1.0;
+    }
+  }
+
+  fn seconds_since(&self, when: DateTime<Utc>) -> f64 {
+    (self.server_time() - when).num_milliseconds() as f64 / 1000.0
+  }
+
+  async fn send_state(&self, url: &str, time_pos: f64, media_center: &mut Box<dyn MediaCenter>) {
+    let request = ReadyRequest {
+      When: self.server_time(),
+      PositionTicks: (time_pos * 10000000.0).round() as u64,
+      IsPlaying: !self.expected_pause,
+      PlaylistItemId: self.playlist_item_id.clone(),
+    };
+    send(media_center, url, serde_json::to_string(&request).unwrap()).await;
+  }
+}
+
+async fn send(media_center: &mut Box<dyn MediaCenter>, url: &str, body: String) {
+  if let Err(err) = media_center.async_post(url.to_string(), body).await {
+    print_message(
+      PrintMessageType::Error,
+      format!("Failed to send {}: {}", url, err).as_str(),
+    );
+  }
+}
+
+fn set_pause(mpv: &Mpv, pause: bool) {
+  if let Err(err) = mpv.set_property("pause", pause) {
+    print_message(
+      PrintMessageType::Error,
+      format!("Failed to (un)pause: {}", err).as_str(),
+    );
+  }
+}
+
+fn seek(mpv: &Mpv, position: f64) {
+  if let Err(err) = mpv.command("seek", &[&position.to_string(), "absolute"]) {
+    print_message(
+      PrintMessageType::Error,
+      format!("Failed to seek: {}", err).as_str(),
+    );
+  }
+}
+
+// NTP-like: the request takes time in both directions, so the average is used.
+pub fn get_time_offset<M: MediaCenter + ?Sized>(
+  media_center: &mut M,
+) -> Result<TimeDelta, PuddlerError> {
+  let request_time = Utc::now();
+  let response = media_center.get(String::from("GetUtcTime"))?;
+  let response_time = Utc::now();
+  let utc_time = serde_json::from_str::<UtcTime>(&response.text()?)?;
+  Ok(
+    ((utc_time.RequestReceptionTime - request_time)
+      + (utc_time.ResponseTransmissionTime - response_time))
+      / 2,
+  )
+}
+
+pub fn get_groups<M: MediaCenter + ?Sized>(
+  media_center: &mut M,
+) -> Result<Vec<GroupInfo>, PuddlerError> {
+  let response = media_center.get(String::from("SyncPlay/List"))?;
+  Ok(serde_json::from_str::<Vec<GroupInfo>>(&response.text()?)?)
+}
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// The group tells us what it's playing right after joining. If nothing is playing yet, we wait.
+async fn wait_for_play_queue(
+  socket: &mut Socket,
+  group_id: &str,
+) -> Result<Option<PlayQueueUpdate>, PuddlerError> {
+  loop {
+    if poll(Duration::ZERO).unwrap_or(false)
+      && let Ok(Event::Key(key)) = read()
+      && key.kind == KeyEventKind::Press
+    {
+      return Ok(None);
+    }
+    let message = match timeout(Duration::from_millis(250), socket.next()).await {
+      Ok(Some(Ok(message))) => message,
+      Ok(_) => {
+        return Err(PuddlerError::Network(String::from(
+          "The websocket connection has been closed.",
+        )));
+      },
+      Err(_) => continue,
+    };
+    let message =
+      if let Ok(message) = serde_json::from_str::<WebSocketMessage>(&message.to_string()) {
+        message
+      } else {
+        continue;
+      };
+    if message.MessageType != "SyncPlayGroupUpdate" {
+      continue;
+    }
+    let update = serde_json::from_value::<GroupUpdate>(message.Data)?;
+    if update.GroupId != group_id {
+      continue;
+    }
+    match update.Type.as_str() {
+      "PlayQueue" => {
+        let queue = serde_json::from_value::<PlayQueueUpdate>(update.Data)?;
+        if queue.playing_item().is_some() {
+          return Ok(Some(queue));
+        }
+      },
+      "NotInGroup" | "GroupDoesNotExist" | "LibraryAccessDenied" => {
+        return Err(PuddlerError::Unsupported(format!(
+          "Failed to join the group: {}",
+          update.Type
+        )));
+      },
+      _ => (),
+    }
+  }
+}
+
+pub fn syncplay_menu<M: MediaCenter + ?Sized>(media_center: &mut M) -> Result<(), PuddlerError> {
+  if media_center.get_config_handle().config.transcoding {
+    return Err(PuddlerError::Unsupported(String::from(
+      "SyncPlay only works with direct-play. Please disable transcoding for this media-center.",
+    )));
+  }
+  let groups = get_groups(media_center)?;
+  let mut options: Vec<InteractiveOption> = vec![InteractiveOption {
+    text: String::from("SyncPlay groups:"),
+    option_type: InteractiveOptionType::Header,
+  }];
+  for group in &groups {
+    options.push(InteractiveOption {
+      text: format!(
+        "{} ({}) - {}",
+        group.GroupName,
+        group.State,
+        group.Participants.join(", ")
+      ),
+      option_type: InteractiveOptionType::Button,
+    });
+  }
+  options.append(&mut vec![
+    InteractiveOption {
+      text: String::from("Create new group"),
+      option_type: InteractiveOptionType::Button,
+    },
+    InteractiveOption {
+      text: String::from("Back"),
+      option_type: InteractiveOptionType::Special,
+    },
+  ]);
+  let selection = match interactive_select(options) {
+    ((index, _), _, InteractiveOptionType::Button) => index,
+    _ => return Ok(()),
+  };
+
+  // connect first, otherwise we'd miss the updates that are sent right after joining
+  let runtime = Runtime::new()?;
+  let url = websocket_url(media_center);
+  let (mut socket, _) = runtime
+    .block_on(connect_async(url))
+    .map_err(|err| PuddlerError::Network(err.to_string()))?;
+
+  let group_id = if let Some(group) = groups.get(selection) {
+    media_center.post(
+      String::from("SyncPlay/Join"),
+      serde_json::json!({ "GroupId": group.GroupId }).to_string(),
+    )?;
+    print_message(
+      PrintMessageType::Success,
+      format!("Joined \"{}\".", group.GroupName).as_str(),
+    );
+    group.GroupId.clone()
+  } else {
+    let user = media_center.get_config_handle().get_active_user().unwrap();
+    media_center.post(
+      String::from("SyncPlay/New"),
+      serde_json::json!({ "GroupName": format!("{}'s group", user.username) }).to_string(),
+    )?;
+    // the response doesn't contain the id of the new group
+    let new_groups = get_groups(media_center)?;
+    if let Some(group) = new_groups.iter().find(|group| {
+      !groups
+        .iter()
+        .any(|old_group| old_group.GroupId == group.GroupId)
+    }) {
+      print_message(
+        PrintMessageType::Success,
+        format!("Created \"{}\".", group.GroupName).as_str(),
+      );
+      group.GroupId.clone()
+    } else {
+      return Err(PuddlerError::ServerApi(
+        reqwest::StatusCode::OK,
+        String::from("The new group couldn't be found."),
+      ));
+    }
+  };
+
+  println!("Waiting for the group to play something. Press any key to leave the group.");
+  let result = runtime.block_on(wait_for_play_queue(&mut socket, &group_id));
+  drop(socket);
+  let result = match result {
+    Ok(Some(mut queue)) => {
+      let time_offset = get_time_offset(media_center)?;
+      let settings = media_center.get_settings().clone();
+      loop {
+        let item = queue.playing_item().unwrap().clone();
+        let mut video = media_center.get_item(item.ItemId)?;
+        video.UserData.PlaybackPositionTicks = queue.StartPositionTicks;
+        let mut player = Player::new(media_center.get_config_handle().clone(), settings.clone());
+        player.set_syncplay(SyncPlay::new(
+          group_id.clone(),
+          item.PlaylistItemId,
+          queue.IsPlaying,
+          time_offset,
+        ));
+        media_center.play_item(&mut player, video, &mut None)?;
+        match player.take_syncplay().and_then(|syncplay| syncplay.next) {
+          Some(next) => queue = next,
+          None => break,
+        }
+      }
+      Ok(())
+    },
+    Ok(None) => Ok(()),
+    Err(err) => Err(err),
+  };
+  if let Err(err) = media_center.post(String::from("SyncPlay/Leave"), String::new()) {
+    print_message(
+      PrintMessageType::Error,
+      format!("Failed to leave the group: {}", err).as_str(),
+    );
+  }
+  result
+}
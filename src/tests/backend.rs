@@ -0,0 +1,274 @@
+use crate::{
+  backend::{Backend, series_seasons},
+  error::PuddlerError,
+  input::series_structure,
+  media_center::MediaCenter,
+  media_item::{MediaItem, MediaKind},
+  tests::{
+    EPISODE_ID, JELLYFIN_TOKEN, JELLYFIN_USER_ID, logged_in_jellyfin, logged_in_plex,
+    mock_server::MockServer,
+  },
+};
+
+const ITEMS: &str = include_str!("fixtures/jellyfin/items.json");
+
+fn media_item(id: &str, kind: MediaKind) -> MediaItem {
+  MediaItem {
+    id: id.to_string(),
+    kind,
+    titles: vec![id.to_string()],
+    played: false,
+    playback_position: 0,
+    runtime: 0,
+  }
+}
+
+#[test]
+fn jellyfin_browses_views_and_children() {
+  let mock = MockServer::start();
+  mock.route(
+    "GET",
+    &format!("Users/{}/Views", JELLYFIN_USER_ID),
+    200,
+    ITEMS,
+  );
+  mock.route(
+    "GET",
+    &format!("Users/{}/Items", JELLYFIN_USER_ID),
+    200,
+    ITEMS,
+  );
+  let mut server = logged_in_jellyfin(&mock);
+
+  let views = Backend::browse(&mut server, None).unwrap();
+  assert_eq!(views[0].id, EPISODE_ID);
+  assert_eq!(views[0].kind, MediaKind::Episode);
+
+  let parent = media_item("5e000000000000000000000000000001", MediaKind::Series);
+  Backend::browse(&mut server, Some(&parent)).unwrap();
+  let request = mock
+    .find("GET", &format!("Users/{}/Items", JELLYFIN_USER_ID))
+    .unwrap();
+  assert!(
+    request
+      .url
+      .contains("ParentId=5e000000000000000000000000000001")
+  );
+}
+
+#[test]
+fn jellyfin_streams_are_resolved_without_transcoding() {
+  let mock = MockServer::start();
+  let items = serde_json::from_str::<serde_json::Value>(ITEMS).unwrap();
+  mock.route(
+    "GET",
+    &format!("Users/{}/Items/{}", JELLYFIN_USER_ID, EPISODE_ID),
+    200,
+    &items["Items"][0].to_string(),
+  );
+  let mut server = logged_in_jellyfin(&mock);
+  let episode = media_item(EPISODE_ID, MediaKind::Episode);
+
+  let source = Backend::resolve_stream(&mut server, &episode).unwrap();
+  assert_eq!(
+    source.stream_url,
+    format!(
+      "{}Videos/{}/stream?Container=mkv&Static=true&api_key={}",
+      mock.address, EPISODE_ID, JELLYFIN_TOKEN
+    )
+  );
+
+  Backend::get_config_handle(&mut server).config.transcoding = true;
+  assert!(Backend::resolve_stream(&mut server, &episode).is_err());
+}
+
+#[test]
+fn jellyfin_progress_and_playstate() {
+  let mock = MockServer::start();
+  mock.route(
+    "POST",
+    &format!("Users/{}/PlayedItems/{}", JELLYFIN_USER_ID, EPISODE_ID),
+    200,
+    "{}",
+  );
+  mock.route(
+    "DELETE",
+    &format!("Users/{}/PlayedItems/{}", JELLYFIN_USER_ID, EPISODE_ID),
+    200,
+    "{}",
+  );
+  let mut server = logged_in_jellyfin(&mock);
+  let episode = media_item(EPISODE_ID, MediaKind::Episode);
+
+  // the progress belongs to a playback session, nothing is sent without one
+  Backend::report_progress(&mut server, &episode, 600).unwrap();
+  assert!(mock.find("POST", "Sessions/Playing/Stopped").is_none());
+
+  Backend::set_playstate(&mut server, &episode, true).unwrap();
+  Backend::set_playstate(&mut server, &episode, false).unwrap();
+  let path = format!("Users/{}/PlayedItems/{}", JELLYFIN_USER_ID, EPISODE_ID);
+  assert!(mock.find("POST", &path).is_some());
+  assert!(mock.find("DELETE", &path).is_some());
+}
+
+#[test]
+fn plex_browses_sections_and_children() {
+  let pms = MockServer::start();
+  pms.route(
+    "GET",
+    "library/sections",
+    200,
+    r#"{"MediaContainer":{"size":1,"Directory":[{"key":"2","type":"show","title":"Shows"}]}}"#,
+  );
+  pms.route(
+    "GET",
+    "library/sections/2/all",
+    200,
+    r#"{"MediaContainer":{"size":1,"Metadata":[
+      {"ratingKey":"3001","guid":"plex://show/3001","type":"show","title":"Cowboy Bebop"}
+    ]}}"#,
+  );
+  pms.route(
+    "GET",
+    "library/metadata/3001/children",
+    200,
+    r#"{"MediaContainer":{"size":2,"Metadata":[
+      {"ratingKey":"3002","guid":"plex://season/3002","type":"season","title":"Season 1"},
+      {"ratingKey":"3003","guid":"plex://season/3003","type":"season","title":"Season 2"}
+    ]}}"#,
+  );
+  pms.route(
+    "GET",
+    "library/metadata/3002/children",
+    200,
+    r#"{"MediaContainer":{"size":1,"Metadata":[
+      {"ratingKey":"3005","guid":"plex://episode/3005","type":"episode","title":"Asteroid Blues","viewCount":1}
+    ]}}"#,
+  );
+  pms.route(
+    "GET",
+    "library/metadata/3003/children",
+    200,
+    r#"{"MediaContainer":{"size":0}}"#,
+  );
+  let mut server = logged_in_plex(&MockServer::start(), &pms);
+
+  let sections = Backend::browse(&mut server, None).unwrap();
+  assert_eq!(sections.len(), 1);
+  assert_eq!(sections[0].kind, MediaKind::Other(String::from("section")));
+  assert_eq!(sections[0].to_string(), "Shows");
+
+  let shows = Backend::browse(&mut server, Some(&sections[0])).unwrap();
+  assert_eq!(shows[0].kind, MediaKind::Series);
+
+  // empty seasons are left out
+  let seasons = series_seasons(&mut server, &shows[0]).unwrap();
+  assert_eq!(seasons.len(), 1);
+  assert_eq!(seasons[0].0.id, "3002");
+  assert_eq!(seasons[0].1[0].id, "3005");
+  assert!(seasons[0].1[0].played);
+}
+
+#[test]
+fn plex_series_are_found_from_their_episodes() {
+  let pms = MockServer::start();
+  pms.route(
+    "GET",
+    "library/metadata/3005",
+    200,
+    r#"{"MediaContainer":{"size":1,"Metadata":[
+      {"ratingKey":"3005","guid":"plex://episode/3005","type":"episode","title":"Asteroid Blues","grandparentRatingKey":"3001"}
+    ]}}"#,
+  );
+  pms.route(
+    "GET",
+    "library/metadata/3001",
+    200,
+    r#"{"MediaContainer":{"size":1,"Metadata":[
+      {"ratingKey":"3001","guid":"plex://show/3001","type":"show","title":"Cowboy Bebop"}
+    ]}}"#,
+  );
+  pms.route(
+    "GET",
+    "library/metadata/3001/children",
+    200,
+    r#"{"MediaContainer":{"size":2,"Metadata":[
+      {"ratingKey":"3000","guid":"plex://season/3000","type":"season","title":"Specials"},
+      {"ratingKey":"3002","guid":"plex://season/3002","type":"season","title":"Season 1"}
+    ]}}"#,
+  );
+  pms.route(
+    "GET",
+    "library/metadata/3000/children",
+    200,
+    r#"{"MediaContainer":{"size":2,"Metadata":[
+      {"ratingKey":"3005","guid":"plex://episode/3005","type":"episode","title":"Asteroid Blues"},
+      {"ratingKey":"3009","guid":"plex://episode/3009","type":"episode","title":"Mushroom Samba"}
+    ]}}"#,
+  );
+  pms.route(
+    "GET",
+    "library/metadata/3002/children",
+    200,
+    r#"{"MediaContainer":{"size":1,"Metadata":[
+      {"ratingKey":"3005","guid":"plex://episode/3005","type":"episode","title":"Asteroid Blues"}
+    ]}}"#,
+  );
+  let mut server = logged_in_plex(&MockServer::start(), &pms);
+
+  let series = Backend::series_of(&mut server, "3005").unwrap().unwrap();
+  assert_eq!(series.id, "3001");
+  assert_eq!(series.kind, MediaKind::Series);
+
+  // specials come last and nothing is listed twice
+  let seasons = series_seasons(&mut server, &series).unwrap();
+  assert_eq!(seasons.len(), 2);
+  assert_eq!(seasons[0].0.id, "3002");
+  assert_eq!(seasons[1].0.id, "3000");
+  assert_eq!(seasons[1].1.len(), 1);
+  assert_eq!(seasons[1].1[0].id, "3009");
+
+  // without any media it can't be sent to the player by remote control
+  assert!(matches!(
+    server.get_playable_item("3005"),
+    Err(PuddlerError::Unsupported(_))
+  ));
+}
+
+#[test]
+fn plex_progress_and_playstate() {
+  let pms = MockServer::start();
+  pms.route("GET", ":/unscrobble", 200, "");
+  let mut server = logged_in_plex(&MockServer::start(), &pms);
+  let episode = media_item("3005", MediaKind::Episode);
+
+  Backend::report_progress(&mut server, &episode, 600).unwrap();
+  assert!(pms.find("GET", ":/progress").is_none());
+
+  Backend::set_playstate(&mut server, &episode, false).unwrap();
+  assert!(pms.find("GET", ":/unscrobble").is_some());
+  // unrouted paths fail
+  assert!(Backend::set_playstate(&mut server, &episode, true).is_err());
+}
+
+#[test]
+fn series_structure_numbers_every_episode() {
+  let text = series_structure(
+    String::from("Cowboy Bebop"),
+    vec![
+      (
+        String::from("Season 1"),
+        vec![String::from("a"), String::from("b")],
+      ),
+      (String::from("Specials"), vec![String::from("c")]),
+    ],
+  );
+
+  assert_eq!(text.len(), 6);
+  assert!(text[0].contains("Cowboy Bebop"));
+  assert!(text[1].starts_with("  ├─ "));
+  assert_eq!(text[2], "  │    ├── [0] a");
+  assert_eq!(text[3], "  │    └── [1] b");
+  assert!(text[4].starts_with("  └─ "));
+  assert_eq!(text[5], "       └── [2] c");
+}
@@ -0,0 +1,155 @@
+use std::{
+  collections::HashMap,
+  io::{Read, Write},
+  net::TcpStream,
+};
+use tokio::{
+  net::TcpListener,
+  runtime::Runtime,
+  sync::{mpsc, watch},
+};
+
+use crate::{
+  companion::{CompanionInfo, CompanionStream, Timeline, serve, timeline_xml, translate_command},
+  mpv::WebSocketMessage,
+};
+
+fn info() -> CompanionInfo {
+  CompanionInfo {
+    client_identifier: String::from("puddler-device"),
+    server_identifier: String::from("plex-server"),
+    server_address: String::from("http://192.168.1.10:32400/"),
+    streams: vec![
+      CompanionStream {
+        id: 101,
+        stream_type: 2,
+        index: Some(1),
+        key: None,
+      },
+      CompanionStream {
+        id: 102,
+        stream_type: 3,
+        index: Some(3),
+        key: None,
+      },
+      CompanionStream {
+        id: 103,
+        stream_type: 3,
+        index: None,
+        key: Some(String::from("/library/streams/103")),
+      },
+    ],
+  }
+}
+
+fn query(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+  pairs
+    .iter()
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect()
+}
+
+#[test]
+fn playback_commands_are_translated() {
+  let streams = info().streams;
+  let pause = translate_command("pause", &query(&[]), &streams).unwrap();
+  assert_eq!(pause.MessageType, "Playstate");
+  assert_eq!(pause.Data["Command"], "Pause");
+
+  let seek = translate_command("seekTo", &query(&[("offset", "90000")]), &streams).unwrap();
+  assert_eq!(seek.Data["Command"], "Seek");
+  assert_eq!(seek.Data["SeekPositionTicks"], 900000000);
+
+  let audio =
+    translate_command("setStreams", &query(&[("audioStreamID", "101")]), &streams).unwrap();
+  assert_eq!(audio.Data["Name"], "SetAudioStreamIndex");
+  assert_eq!(audio.Data["Arguments"]["Index"], "1");
+
+  let external = translate_command(
+    "setStreams",
+    &query(&[("subtitleStreamID", "103")]),
+    &streams,
+  )
+  .unwrap();
+  assert_eq!(
+    external.Data["Arguments"]["StreamKey"],
+    "/library/streams/103"
+  );
+
+  let off =
+    translate_command("setStreams", &query(&[("subtitleStreamID", "0")]), &streams).unwrap();
+  assert_eq!(off.Data["Arguments"]["Index"], "-1");
+
+  let play = translate_command(
+    "playMedia",
+    &query(&[("key", "/library/metadata/4242"), ("offset", "1000")]),
+    &streams,
+  )
+  .unwrap();
+  assert_eq!(play.MessageType, "Play");
+  assert_eq!(play.Data["ItemIds"][0], "4242");
+  assert_eq!(play.Data["StartPositionTicks"], 10000000);
+
+  // an audio stream can't be used as subtitle
+  assert!(translate_command("setStreams", &query(&[("audioStreamID", "102")]), &streams).is_none());
+  assert!(translate_command("teleport", &query(&[]), &streams).is_none());
+}
+
+#[test]
+fn timeline_contains_the_playing_item() {
+  let timeline = Timeline {
+    state: String::from("paused"),
+    rating_key: Some(String::from("4242")),
+    time: 61000,
+    duration: 1440000,
+    volume: 80,
+  };
+  let xml = timeline_xml(&timeline, &info(), "7");
+  assert!(xml.contains(r#"commandID="7""#));
+  assert!(xml.contains(r#"state="paused" time="61000" duration="1440000" ratingKey="4242""#));
+  assert!(xml.contains(
+    r#"machineIdentifier="plex-server" address="192.168.1.10" port="32400" protocol="http""#
+  ));
+
+  let idle = timeline_xml(&Timeline::default(), &info(), "8");
+  assert!(idle.contains(r#"<Timeline type="video" state="stopped"/>"#));
+  assert!(!timeline.differs(&Timeline {
+    time: 62000,
+    ..timeline.clone()
+  }));
+  assert!(timeline.differs(&Timeline {
+    time: 120000,
+    ..timeline.clone()
+  }));
+}
+
+fn http_get(port: u16, path: &str) -> String {
+  let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+  write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+  let mut response = String::new();
+  stream.read_to_string(&mut response).unwrap();
+  response
+}
+
+#[test]
+fn listener_forwards_commands() {
+  let runtime = Runtime::new().unwrap();
+  let listener = runtime.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+  let port = listener.local_addr().unwrap().port();
+  let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
+  let (_timeline_sender, timeline) = watch::channel(Timeline::default());
+  runtime.spawn(serve(listener, info(), sender, timeline));
+
+  let response = http_get(port, "/player/playback/pause?commandID=1&type=video");
+  assert!(response.starts_with("HTTP/1.1 200 OK"));
+  assert!(response.contains("X-Plex-Client-Identifier: puddler-device"));
+  let message = serde_json::from_str::<WebSocketMessage>(&receiver.try_recv().unwrap()).unwrap();
+  assert_eq!(message.Data["Command"], "Pause");
+
+  let response = http_get(port, "/player/timeline/poll?wait=0&commandID=2");
+  assert!(response.contains(r#"commandID="2""#));
+  assert!(
+    http_get(port, "/resources").contains(r#"protocolCapabilities="timeline,playback,playqueues""#)
+  );
+  assert!(http_get(port, "/nothing").starts_with("HTTP/1.1 404"));
+}
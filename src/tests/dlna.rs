@@ -0,0 +1,195 @@
+use crate::{
+  backend::Backend,
+  dlna::{DlnaServer, parse_browse_response, parse_description, parse_duration},
+  media_center::parse_ssdp_answer,
+  media_config::{MediaCenterType, Objective, Playstate},
+  media_item::{MediaItem, MediaKind},
+  tests::{mock_server::MockServer, test_config, test_settings},
+};
+
+const DESCRIPTION: &str = include_str!("fixtures/dlna/description.xml");
+const BROWSE: &str = include_str!("fixtures/dlna/browse.xml");
+
+fn dlna_server(mock: &MockServer) -> DlnaServer {
+  mock.route("GET", "rootDesc.xml", 200, DESCRIPTION);
+  let mut config = test_config(MediaCenterType::Dlna, &mock.address);
+  config.insert_specific_value(Objective::Location, format!("{}rootDesc.xml", mock.address));
+  DlnaServer::new(config, test_settings())
+}
+
+// Only what BrowseMetadata would answer for a single movie.
+fn metadata_response(id: &str, title: &str) -> String {
+  let didl = format!(
+    "<DIDL-Lite xmlns:dc=\"http://purl.org/dc/elements/1.1/\" xmlns:upnp=\"urn:schemas-upnp-org:metadata-1-0/upnp/\" xmlns=\"urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/\"><item id=\"{}\" parentID=\"64$0\" restricted=\"1\"><dc:title>{}</dc:title><upnp:class>object.item.videoItem</upnp:class><res duration=\"1:30:00\">http://192.168.1.2:8200/MediaItems/{}.mkv</res></item></DIDL-Lite>",
+    id, title, id
+  );
+  format!(
+    "<s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\"><s:Body><u:BrowseResponse xmlns:u=\"urn:schemas-upnp-org:service:ContentDirectory:1\"><Result>{}</Result><NumberReturned>1</NumberReturned><TotalMatches>1</TotalMatches></u:BrowseResponse></s:Body></s:Envelope>",
+    didl
+      .replace('&', "&amp;")
+      .replace('<', "&lt;")
+      .replace('>', "&gt;")
+  )
+}
+
+#[test]
+fn description_control_url_is_resolved() {
+  let description = parse_description(DESCRIPTION, "http://192.168.1.2:8200/rootDesc.xml").unwrap();
+  assert_eq!(description.name, "Living Room NAS");
+  assert_eq!(description.udn, "uuid:4d696e69-444c-164e-9d41-b827eb8c1d2f");
+  assert_eq!(
+    description.control_url,
+    "http://192.168.1.2:8200/ctl/ContentDir"
+  );
+
+  // <URLBase> wins over the location of the description
+  let with_base = DESCRIPTION.replace(
+    "<device>",
+    "<URLBase>http://192.168.1.3:9000/dms/</URLBase><device>",
+  );
+  let with_base = with_base.replace("/ctl/ContentDir", "ctl/ContentDir");
+  let description = parse_description(&with_base, "http://192.168.1.2:8200/rootDesc.xml").unwrap();
+  assert_eq!(
+    description.control_url,
+    "http://192.168.1.3:9000/dms/ctl/ContentDir"
+  );
+
+  let renderer = DESCRIPTION.replace("ContentDirectory", "AVTransport");
+  assert!(parse_description(&renderer, "http://192.168.1.2:8200/rootDesc.xml").is_err());
+}
+
+#[test]
+fn didl_objects_become_media_items() {
+  let mock = MockServer::start();
+  mock.route("POST", "ctl/ContentDir", 200, BROWSE);
+  let mut server = dlna_server(&mock);
+
+  let items = Backend::browse(&mut server, None).unwrap();
+  let request = mock.find("POST", "ctl/ContentDir").unwrap();
+  assert_eq!(
+    request.header("SOAPAction").unwrap(),
+    "\"urn:schemas-upnp-org:service:ContentDirectory:1#Browse\""
+  );
+  assert!(request.body.contains("<ObjectID>0</ObjectID>"));
+  assert!(
+    request
+      .body
+      .contains("<BrowseFlag>BrowseDirectChildren</BrowseFlag>")
+  );
+
+  // the photo can't be played
+  assert_eq!(items.len(), 4);
+  assert_eq!(items[0].kind, MediaKind::Other(String::from("folder")));
+  assert_eq!(items[1].kind, MediaKind::Album);
+  assert_eq!(items[2].id, "64$0$1");
+  assert_eq!(items[2].kind, MediaKind::Movie);
+  assert_eq!(items[2].runtime, 596);
+  assert_eq!(items[3].kind, MediaKind::Track);
+  assert_eq!(
+    items[3].titles,
+    vec![
+      String::from("Radiohead - Reckoner"),
+      String::from("Radiohead (In Rainbows)"),
+      String::from("Reckoner"),
+    ]
+  );
+
+  Backend::browse(&mut server, Some(&items[0])).unwrap();
+  let request = mock.find("POST", "ctl/ContentDir").unwrap();
+  assert!(request.body.contains("<ObjectID>64$0</ObjectID>"));
+
+  // folders don't have any resources
+  assert!(Backend::resolve_stream(&mut server, &items[0]).is_err());
+}
+
+#[test]
+fn playstates_are_kept_in_the_config() {
+  let mock = MockServer::start();
+  mock.route(
+    "POST",
+    "ctl/ContentDir",
+    200,
+    &metadata_response("64$0$1", "Big Buck Bunny"),
+  );
+  let mut server = dlna_server(&mock);
+  let movie = Backend::browse(&mut server, None).unwrap().remove(0);
+  assert_eq!(movie.runtime, 5400);
+
+  let source = Backend::resolve_stream(&mut server, &movie).unwrap();
+  let request = mock.find("POST", "ctl/ContentDir").unwrap();
+  assert!(request.body.contains("<ObjectID>64$0$1</ObjectID>"));
+  assert!(
+    request
+      .body
+      .contains("<BrowseFlag>BrowseMetadata</BrowseFlag>")
+  );
+  assert_eq!(
+    source.stream_url,
+    "http://192.168.1.2:8200/MediaItems/64$0$1.mkv"
+  );
+
+  Backend::report_progress(&mut server, &movie, 1234).unwrap();
+  assert_eq!(
+    Backend::get_config_handle(&mut server)
+      .get_playstates()
+      .get("64$0$1"),
+    Some(&Playstate {
+      played: false,
+      playback_position: 1234,
+      titles: vec![String::from("Big Buck Bunny")],
+      kind: Some(MediaKind::Movie),
+      runtime: 5400,
+    })
+  );
+  // everything comes from the config, the server isn't asked again
+  let requests = mock.requests().len();
+  let resume = Backend::resume(&mut server).unwrap();
+  assert_eq!(
+    resume,
+    vec![MediaItem {
+      playback_position: 1234,
+      ..movie.clone()
+    }]
+  );
+  assert_eq!(mock.requests().len(), requests);
+
+  Backend::set_playstate(&mut server, &movie, true).unwrap();
+  assert!(Backend::resume(&mut server).unwrap().is_empty());
+  assert!(Backend::browse(&mut server, None).unwrap()[0].played);
+
+  // neither played nor started isn't worth keeping
+  Backend::set_playstate(&mut server, &movie, false).unwrap();
+  assert!(
+    Backend::get_config_handle(&mut server)
+      .get_playstates()
+      .is_empty()
+  );
+}
+
+#[test]
+fn browse_response_total_and_durations() {
+  let (objects, total) = parse_browse_response(BROWSE).unwrap();
+  assert_eq!(objects.len(), 5);
+  assert_eq!(total, 5);
+  assert_eq!(
+    objects[2].url.as_deref(),
+    Some("http://192.168.1.2:8200/MediaItems/22.mkv")
+  );
+
+  assert_eq!(parse_duration("0:09:56.458"), Some(596));
+  assert_eq!(parse_duration("1:30:00"), Some(5400));
+  assert_eq!(parse_duration("--:--"), None);
+}
+
+#[test]
+fn ssdp_answers_point_to_the_description() {
+  let answer = "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=1810\r\nDATE: Sat, 17 Oct 2026 12:00:00 GMT\r\nST: urn:schemas-upnp-org:device:MediaServer:1\r\nUSN: uuid:4d696e69-444c-164e-9d41-b827eb8c1d2f::urn:schemas-upnp-org:device:MediaServer:1\r\nEXT:\r\nSERVER: Debian DLNADOC/1.50 UPnP/1.0 MiniDLNA/1.3.0\r\nLOCATION: http://192.168.1.2:8200/rootDesc.xml\r\nContent-Length: 0\r\n\r\n";
+  let parsed = parse_ssdp_answer(answer).unwrap();
+  assert_eq!(parsed.Address, "http://192.168.1.2:8200/rootDesc.xml");
+  assert_eq!(parsed.Id, "uuid:4d696e69-444c-164e-9d41-b827eb8c1d2f");
+
+  // renderers answer as well, when they ignore the search target
+  let renderer = answer.replace("MediaServer", "MediaRenderer");
+  assert!(parse_ssdp_answer(&renderer).is_none());
+  assert!(parse_ssdp_answer("NOTIFY * HTTP/1.1\r\n\r\n").is_none());
+}
@@ -0,0 +1,79 @@
+use chrono::{Local, TimeZone};
+use std::fs::{remove_file, write};
+
+use crate::history::{History, HistoryEntry};
+
+fn entry(server_name: &str, item_id: &str, played: bool) -> HistoryEntry {
+  HistoryEntry {
+    server_name: server_name.to_string(),
+    media_center_type: String::from("Jellyfin"),
+    item_id: item_id.to_string(),
+    title: String::from("Cowboy Bebop (1998-1999) - S01E05 - Ballad of Fallen Angels"),
+    started_at: Local.with_ymd_and_hms(2025, 3, 1, 20, 15, 0).unwrap(),
+    stopped_at: Local.with_ymd_and_hms(2025, 3, 1, 20, 40, 0).unwrap(),
+    start_position: 0,
+    stop_position: 1460,
+    duration: 1474,
+    audio_track: Some(1),
+    subtitle_track: None,
+    played,
+  }
+}
+
+fn temp_history() -> (History, std::path::PathBuf) {
+  let path = std::env::temp_dir().join(format!("puddler-history-{}.jsonl", uuid::Uuid::new_v4()));
+  (History::at(path.clone()), path)
+}
+
+#[test]
+fn entries_are_listed_newest_first() {
+  let (history, path) = temp_history();
+  assert!(history.entries(None).unwrap().is_empty());
+
+  history.add(&entry("Jelly", "1", true)).unwrap();
+  history.add(&entry("Plex_Server", "2", false)).unwrap();
+  history.add(&entry("Jelly", "3", false)).unwrap();
+
+  let entries = history.entries(None).unwrap();
+  assert_eq!(
+    entries
+      .iter()
+      .map(|e| e.item_id.as_str())
+      .collect::<Vec<_>>(),
+    vec!["3", "2", "1"]
+  );
+  assert_eq!(entries[2], entry("Jelly", "1", true));
+  remove_file(path).unwrap();
+}
+
+#[test]
+fn entries_can_be_filtered_by_server() {
+  let (history, path) = temp_history();
+  history.add(&entry("Jelly", "1", true)).unwrap();
+  history.add(&entry("Plex_Server", "2", false)).unwrap();
+
+  let entries = history.entries(Some("plex server")).unwrap();
+  assert_eq!(entries.len(), 1);
+  assert_eq!(entries[0].item_id, "2");
+  remove_file(path).unwrap();
+}
+
+#[test]
+fn broken_lines_are_skipped() {
+  let (history, path) = temp_history();
+  history.add(&entry("Jelly", "1", true)).unwrap();
+  let content = std::fs::read_to_string(&path).unwrap();
+  write(&path, format!("{}{{\"server_name\":\n", content)).unwrap();
+  history.add(&entry("Jelly", "2", true)).unwrap();
+
+  assert_eq!(history.entries(None).unwrap().len(), 2);
+  remove_file(path).unwrap();
+}
+
+#[test]
+fn entry_to_string() {
+  assert_eq!(
+    entry("Jelly", "1", true).to_string(),
+    "2025-03-01 20:15 | Jelly | Cowboy Bebop (1998-1999) - S01E05 - Ballad of Fallen Angels (00:24:20/00:24:34) [Played]"
+  );
+}
@@ -0,0 +1,803 @@
+use reqwest::{
+  blocking::Client,
+  header::{HeaderMap, HeaderValue},
+};
+use serde_json::Value;
+
+use crate::{
+  emby::EmbyServer,
+  error::PuddlerError,
+  jellyfin::JellyfinServer,
+  media_center::{Item, MediaCenter, MediaCenterValues, ToStringAdv, UserCredentials},
+  media_config::{MediaCenterType, Objective},
+  media_item::{MediaItem, MediaKind},
+  offline::{Download, OfflineLibrary},
+  receiver::apply_remote_requests,
+  remote::RemoteRequest,
+  search::parse_search,
+  tests::{
+    EPISODE_ID, JELLYFIN_TOKEN, JELLYFIN_USER_ID, add_user, logged_in_jellyfin,
+    mock_server::MockServer, offline::temp_folder, test_config, test_settings,
+  },
+};
+
+const AUTHENTICATE: &str = include_str!("fixtures/jellyfin/authenticate.json");
+const SESSIONS: &str = include_str!("fixtures/jellyfin/sessions.json");
+const ITEMS: &str = include_str!("fixtures/jellyfin/items.json");
+const LATEST: &str = include_str!("fixtures/jellyfin/latest.json");
+const USER: &str = include_str!("fixtures/jellyfin/user.json");
+const PLAYBACK_INFO: &str = include_str!("fixtures/jellyfin/playbackinfo.json");
+
+const SESSION_ID: &str = "0d9c8b7a6f5e4d3c2b1a09f8e7d6c5b4";
+
+fn playing_server(mock: &MockServer) -> JellyfinServer {
+  let mut server = logged_in_jellyfin(mock);
+  server.insert_value(MediaCenterValues::SessionID, SESSION_ID.to_string());
+  server.insert_value(MediaCenterValues::PlaybackInfo, PLAYBACK_INFO.to_string());
+  server
+}
+
+fn episode() -> Item {
+  let json = serde_json::from_str::<Value>(ITEMS).unwrap();
+  serde_json::from_value::<Item>(json["Items"][0].clone()).unwrap()
+}
+
+fn credentials() -> UserCredentials {
+  UserCredentials {
+    username: String::from("alice"),
+    password: String::from("hunter2"),
+  }
+}
+
+#[test]
+fn authenticate_stores_user_and_session() {
+  let mock = MockServer::start();
+  mock.route("POST", "Users/AuthenticateByName", 200, AUTHENTICATE);
+  let config = test_config(MediaCenterType::Jellyfin, &mock.address);
+  let mut server = JellyfinServer::new(config, test_settings());
+
+  assert!(server.authenticate(credentials()).is_ok());
+
+  let request = mock.find("POST", "Users/AuthenticateByName").unwrap();
+  assert_eq!(request.body, r#"{"Username":"alice","pw":"hunter2"}"#);
+  assert!(
+    request
+      .header("Authorization")
+      .unwrap()
+      .contains("DeviceId=puddler-test-device")
+  );
+
+  let user = server.get_config_handle().get_active_user().unwrap();
+  assert_eq!(user.access_token, JELLYFIN_TOKEN);
+  assert_eq!(user.username, "alice");
+  assert_eq!(user.user_id, JELLYFIN_USER_ID);
+  assert_eq!(server.get_session_id(), Some(SESSION_ID.to_string()));
+  assert_eq!(server.get_headers()[2].1, JELLYFIN_TOKEN);
+}
+
+#[test]
+fn authenticate_on_emby_uses_emby_prefix() {
+  let mock = MockServer::start();
+  mock.route("POST", "emby/Users/AuthenticateByName", 200, AUTHENTICATE);
+  let config = test_config(MediaCenterType::Emby, &mock.address);
+  let mut server = EmbyServer::new(config, test_settings());
+
+  assert!(server.authenticate(credentials()).is_ok());
+  assert!(mock.find("POST", "emby/Users/AuthenticateByName").is_some());
+}
+
+#[test]
+fn authenticate_with_wrong_password() {
+  let mock = MockServer::start();
+  mock.route(
+    "POST",
+    "Users/AuthenticateByName",
+    401,
+    "Error processing request.",
+  );
+  let config = test_config(MediaCenterType::Jellyfin, &mock.address);
+  let mut server = JellyfinServer::new(config, test_settings());
+
+  assert!(server.authenticate(credentials()).is_err());
+  assert!(server.get_config_handle().get_active_user().is_none());
+}
+
+#[test]
+fn re_authenticate_restores_session() {
+  let mock = MockServer::start();
+  mock.route("GET", "Sessions", 200, SESSIONS);
+  mock.route("POST", "Sessions/Capabilities/Full", 204, "");
+  let mut config = test_config(MediaCenterType::Jellyfin, &mock.address);
+  add_user(&mut config, JELLYFIN_TOKEN, "alice", JELLYFIN_USER_ID);
+  let mut server = JellyfinServer::new(config, test_settings());
+
+  assert!(server.re_authenticate().is_ok());
+
+  let request = mock.find("GET", "Sessions").unwrap();
+  assert_eq!(request.url, "Sessions?DeviceId=puddler-test-device");
+  assert_eq!(
+    request.header("X-Emby-Token"),
+    Some(JELLYFIN_TOKEN.to_string())
+  );
+  assert_eq!(server.get_session_id(), Some(SESSION_ID.to_string()));
+  assert!(mock.find("POST", "Users/AuthenticateByName").is_none());
+}
+
+#[test]
+fn expired_session_removes_user() {
+  let mock = MockServer::start();
+  mock.route("GET", "Sessions", 401, "");
+  let mut config = test_config(MediaCenterType::Jellyfin, &mock.address);
+  add_user(&mut config, JELLYFIN_TOKEN, "alice", JELLYFIN_USER_ID);
+  let mut server = JellyfinServer::new(config, test_settings());
+
+  assert!(!server.restore_session());
+  assert!(server.get_config_handle().get_active_user().is_none());
+}
+
+#[test]
+fn get_items_parses_item_list() {
+  let mock = MockServer::start();
+  let path = format!("Users/{}/Items", JELLYFIN_USER_ID);
+  mock.route("GET", &path, 200, ITEMS);
+  let mut server = logged_in_jellyfin(&mock);
+
+  let items = server
+    .get_items(
+      format!("{}?ParentId=5a000000000000000000000000000001", path),
+      false,
+    )
+    .unwrap();
+
+  assert_eq!(items.len(), 2);
+  assert_eq!(items[0].Name, "Asteroid Blues");
+  assert!(items[0].UserData.Played);
+  assert_eq!(items[1].UserData.PlaybackPositionTicks, 4628360000);
+  assert!(items[1].MediaSources.is_none());
+
+  let request = mock.find("GET", &path).unwrap();
+  assert!(
+    request
+      .url
+      .contains("ParentId=5a000000000000000000000000000001&Fields=PremiereDate,MediaSources")
+  );
+  assert_eq!(
+    request.header("X-Emby-Token"),
+    Some(JELLYFIN_TOKEN.to_string())
+  );
+}
+
+#[test]
+fn injected_client_is_used() {
+  let mock = MockServer::start();
+  let path = format!("Users/{}/Items", JELLYFIN_USER_ID);
+  mock.route("GET", &path, 200, ITEMS);
+  let mut server = logged_in_jellyfin(&mock);
+  let mut headers = HeaderMap::new();
+  headers.insert("X-Puddler-Test", HeaderValue::from_static("injected"));
+  server.set_client(Client::builder().default_headers(headers).build().unwrap());
+
+  assert!(server.get_items(path.clone(), false).is_ok());
+  let request = mock.find("GET", &path).unwrap();
+  assert_eq!(
+    request.header("X-Puddler-Test"),
+    Some(String::from("injected"))
+  );
+}
+
+#[test]
+fn get_items_parses_raw_list() {
+  let mock = MockServer::start();
+  let path = format!("Users/{}/Items/Latest", JELLYFIN_USER_ID);
+  mock.route("GET", &path, 200, LATEST);
+  let mut server = logged_in_jellyfin(&mock);
+
+  let items = server
+    .get_items(format!("{}?IncludeItemTypes=Movie", path), true)
+    .unwrap();
+
+  assert_eq!(items.len(), 1);
+  assert_eq!(items[0].Type, "Movie");
+  assert_eq!(items[0].to_string(), "Perfect Blue (1998)");
+}
+
+#[test]
+fn menu_and_cli_list_the_same_items() {
+  let mock = MockServer::start();
+  mock.route(
+    "GET",
+    &format!("Users/{}/Items/Resume", JELLYFIN_USER_ID),
+    200,
+    ITEMS,
+  );
+  mock.route("GET", "Shows/NextUp", 200, ITEMS);
+  mock.route(
+    "GET",
+    &format!("Users/{}/Items/Latest", JELLYFIN_USER_ID),
+    200,
+    LATEST,
+  );
+  let mut server = logged_in_jellyfin(&mock);
+
+  // next up doesn't repeat what is being watched already
+  let items = server.get_resume_items().unwrap();
+  assert_eq!(
+    MediaCenter::resume(&mut server).unwrap(),
+    items
+      .iter()
+      .map(MediaItem::from)
+      .collect::<Vec<MediaItem>>()
+  );
+  assert_eq!(items[0].Id, EPISODE_ID);
+  assert_eq!(items.iter().filter(|i| i.Id == EPISODE_ID).count(), 1);
+
+  // episodes and movies
+  let items = server.get_latest_items().unwrap();
+  assert_eq!(items.len(), 2);
+  assert_eq!(
+    server.latest_items().unwrap(),
+    items
+      .iter()
+      .map(MediaItem::from)
+      .collect::<Vec<MediaItem>>()
+  );
+}
+
+#[test]
+fn get_items_fails_on_server_error() {
+  let mock = MockServer::start();
+  let mut server = logged_in_jellyfin(&mock);
+
+  assert!(
+    server
+      .get_items(format!("Users/{}/Items", JELLYFIN_USER_ID), false)
+      .is_err()
+  );
+}
+
+#[test]
+fn request_errors_are_typed() {
+  let mock = MockServer::start();
+  let path = format!("Users/{}/Items", JELLYFIN_USER_ID);
+  let mut server = logged_in_jellyfin(&mock);
+
+  mock.route("GET", &path, 401, "");
+  assert!(matches!(
+    server.get_items(path.clone(), false),
+    Err(PuddlerError::Auth(_))
+  ));
+
+  mock.route("GET", &path, 500, "Database is locked");
+  match server.get_items(path.clone(), false) {
+    Err(PuddlerError::ServerApi(status, body)) => {
+      assert_eq!(status.as_u16(), 500);
+      assert_eq!(body, "Database is locked");
+    },
+    other => panic!("unexpected result: {:?}", other),
+  }
+
+  mock.route("GET", &path, 200, "<html>not json</html>");
+  assert!(matches!(
+    server.get_items(path.clone(), false),
+    Err(PuddlerError::Deserialization(_))
+  ));
+
+  // Nothing is listening on port 1.
+  server
+    .get_config_handle()
+    .insert_specific_value(Objective::Address, String::from("http://127.0.0.1:1/"));
+  assert!(matches!(
+    server.get_items(path, false),
+    Err(PuddlerError::Network(_))
+  ));
+}
+
+#[test]
+fn post_playbackinfo_for_direct_play() {
+  let mock = MockServer::start();
+  mock.route("GET", &format!("Users/{}", JELLYFIN_USER_ID), 200, USER);
+  mock.route(
+    "GET",
+    &format!("Items/{}/PlaybackInfo", EPISODE_ID),
+    200,
+    PLAYBACK_INFO,
+  );
+  let mut server = logged_in_jellyfin(&mock);
+  let mut item = episode();
+  let mut transcoding_settings = None;
+
+  let playback_info = server
+    .post_playbackinfo(&mut item, &mut transcoding_settings)
+    .unwrap();
+
+  assert_eq!(
+    playback_info.PlaySessionId,
+    "7a1b2c3d4e5f60718293a4b5c6d7e8f9"
+  );
+  assert_eq!(playback_info.MediaSources.len(), 1);
+  assert_eq!(playback_info.MediaSources[0].MediaStreams.len(), 3);
+  assert_eq!(
+    transcoding_settings,
+    Some((false, Some(1), None, String::new()))
+  );
+  let request = mock
+    .find("GET", &format!("Items/{}/PlaybackInfo", EPISODE_ID))
+    .unwrap();
+  assert!(
+    request
+      .url
+      .contains(&format!("UserId={}", JELLYFIN_USER_ID))
+  );
+}
+
+#[test]
+fn stop_playback_marks_item_as_played() {
+  let mock = MockServer::start();
+  let path = format!("Users/{}/PlayedItems/{}", JELLYFIN_USER_ID, EPISODE_ID);
+  mock.route("POST", &path, 200, "{}");
+  let mut server = playing_server(&mock);
+
+  let played = tokio::runtime::Runtime::new()
+    .unwrap()
+    .block_on(server.stop_playback(EPISODE_ID.to_string(), 0, 1474, 1460.0));
+
+  assert!(played);
+  assert!(mock.find("POST", &path).is_some());
+  assert!(mock.find("POST", "Sessions/Playing/Stopped").is_none());
+}
+
+#[test]
+fn stop_playback_reports_position() {
+  let mock = MockServer::start();
+  mock.route("POST", "Sessions/Playing/Stopped", 204, "");
+  let mut server = playing_server(&mock);
+
+  let played = tokio::runtime::Runtime::new()
+    .unwrap()
+    .block_on(server.stop_playback(EPISODE_ID.to_string(), 0, 1474, 600.0));
+
+  assert!(!played);
+  let request = mock.find("POST", "Sessions/Playing/Stopped").unwrap();
+  let body = serde_json::from_str::<Value>(&request.body).unwrap();
+  assert_eq!(body["ItemId"], EPISODE_ID);
+  assert_eq!(body["SessionId"], SESSION_ID);
+  assert_eq!(body["PlaySessionId"], "7a1b2c3d4e5f60718293a4b5c6d7e8f9");
+  assert_eq!(body["PositionTicks"], "6000000000");
+}
+
+#[test]
+fn report_playback_sends_progress() {
+  let mock = MockServer::start();
+  mock.route("POST", "Sessions/Playing/Progress", 204, "");
+  let mut server = playing_server(&mock);
+
+  tokio::runtime::Runtime::new()
+    .unwrap()
+    .block_on(server.report_playback(EPISODE_ID.to_string(), 0, 12.5, 1, 2, true, false, 80));
+
+  let request = mock.find("POST", "Sessions/Playing/Progress").unwrap();
+  let body = serde_json::from_str::<Value>(&request.body).unwrap();
+  assert_eq!(body["PositionTicks"], 125000000);
+  assert_eq!(body["IsPaused"], true);
+  assert_eq!(body["EventName"], "Pause");
+  assert_eq!(body["PlayMethod"], "DirectPlay");
+  assert_eq!(body["AudioStreamIndex"], 1);
+  assert_eq!(body["SubtitleStreamIndex"], 2);
+  assert_eq!(body["VolumeLevel"], 80);
+}
+
+#[test]
+fn create_download_uses_static_stream() {
+  let mock = MockServer::start();
+  let mut json = serde_json::from_str::<Value>(ITEMS).unwrap()["Items"][0].take();
+  json["MediaSources"][0]["MediaStreams"]
+    .as_array_mut()
+    .unwrap()
+    .push(serde_json::json!({
+      "Codec": "srt",
+      "Language": "eng",
+      "Index": 2,
+      "Type": "Subtitle",
+      "DisplayTitle": "English - SRT",
+      "IsDefault": false,
+      "IsExternal": true,
+      "SupportsExternalStream": true,
+      "Path": "/media/shows/Cowboy Bebop/Season 01/Cowboy Bebop - S01E01.en.srt"
+    }));
+  mock.route(
+    "GET",
+    &format!("Users/{}/Items/{}", JELLYFIN_USER_ID, EPISODE_ID),
+    200,
+    &json.to_string(),
+  );
+  let mut server = logged_in_jellyfin(&mock);
+
+  let download = server.create_download(EPISODE_ID.to_string()).unwrap();
+
+  assert_eq!(
+    download.url,
+    format!(
+      "{}Videos/{}/stream?Static=true&MediaSourceId={}&api_key={}",
+      mock.address, EPISODE_ID, EPISODE_ID, JELLYFIN_TOKEN
+    )
+  );
+  assert_eq!(download.extension, "mkv");
+  assert_eq!(download.total_runtime, 1474);
+  assert!(download.played);
+  assert_eq!(download.subtitles.len(), 1);
+  assert_eq!(
+    download.subtitles[0].url,
+    format!(
+      "{}Videos/{}/{}/Subtitles/2/Stream.srt?api_key={}",
+      mock.address, EPISODE_ID, EPISODE_ID, JELLYFIN_TOKEN
+    )
+  );
+}
+
+#[test]
+fn offline_playstate_is_synced() {
+  let mock = MockServer::start();
+  let path = format!("Users/{}/PlayedItems/{}", JELLYFIN_USER_ID, EPISODE_ID);
+  mock.route("POST", &path, 200, "");
+  mock.route("GET", "video", 200, "not really a video");
+  let mut server = logged_in_jellyfin(&mock);
+  let folder = temp_folder();
+  let mut library = OfflineLibrary::at(folder.clone()).unwrap();
+  let download = Download {
+    item_id: EPISODE_ID.to_string(),
+    title: episode().to_string_split(),
+    movie: false,
+    extension: String::from("mkv"),
+    url: format!("{}video", mock.address),
+    subtitles: vec![],
+    total_runtime: 1474,
+    playback_position: 0,
+    played: false,
+  };
+  library
+    .download(server.get_config_handle(), download)
+    .unwrap();
+
+  // nothing to sync yet
+  library.sync_playstate(&mut server).unwrap();
+  assert!(mock.find("POST", &path).is_none());
+
+  library.items[0].played = true;
+  library.items[0].synced = false;
+  library.sync_playstate(&mut server).unwrap();
+
+  assert!(mock.find("POST", &path).is_some());
+  assert!(OfflineLibrary::at(folder.clone()).unwrap().items[0].synced);
+  std::fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+fn failed_offline_sync_is_retried() {
+  let mock = MockServer::start();
+  let path = format!("Users/{}/PlayedItems/{}", JELLYFIN_USER_ID, EPISODE_ID);
+  mock.route("POST", &path, 500, "");
+  mock.route("GET", "video", 200, "not really a video");
+  let mut server = logged_in_jellyfin(&mock);
+  let folder = temp_folder();
+  let mut library = OfflineLibrary::at(folder.clone()).unwrap();
+  let download = Download {
+    item_id: EPISODE_ID.to_string(),
+    title: episode().to_string_split(),
+    movie: false,
+    extension: String::from("mkv"),
+    url: format!("{}video", mock.address),
+    subtitles: vec![],
+    total_runtime: 1474,
+    playback_position: 0,
+    played: false,
+  };
+  library
+    .download(server.get_config_handle(), download)
+    .unwrap();
+  library.items[0].played = true;
+  library.items[0].synced = false;
+
+  library.sync_playstate(&mut server).unwrap();
+  assert!(mock.find("POST", &path).is_some());
+  assert!(!library.items[0].synced);
+
+  // the server is back
+  mock.route("POST", &path, 200, "");
+  library.sync_playstate(&mut server).unwrap();
+  assert!(library.items[0].synced);
+  std::fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+fn remote_requests_change_the_playlist() {
+  let mock = MockServer::start();
+  let items = serde_json::from_str::<Value>(ITEMS).unwrap();
+  let mut movie = items["Items"][0].clone();
+  movie["Id"] = Value::from("m1000000000000000000000000000001");
+  movie["Type"] = Value::from("Movie");
+  mock.route(
+    "GET",
+    &format!(
+      "Users/{}/Items/m1000000000000000000000000000001",
+      JELLYFIN_USER_ID
+    ),
+    200,
+    &movie.to_string(),
+  );
+  // without media-sources, like a series
+  mock.route(
+    "GET",
+    &format!(
+      "Users/{}/Items/e1000000000000000000000000000002",
+      JELLYFIN_USER_ID
+    ),
+    200,
+    &items["Items"][1].to_string(),
+  );
+  let mut server = logged_in_jellyfin(&mock);
+  let mut playlist = vec![MediaItem::from(&episode()), MediaItem::from(&episode())];
+
+  let play_next = RemoteRequest::Play(
+    vec![String::from("m1000000000000000000000000000001")],
+    String::from("PlayNext"),
+    None,
+  );
+  assert_eq!(
+    apply_remote_requests(&mut server, &mut playlist, 0, vec![play_next]),
+    None
+  );
+  assert_eq!(playlist.len(), 3);
+  assert_eq!(playlist[1].kind, MediaKind::Movie);
+
+  assert_eq!(
+    apply_remote_requests(
+      &mut server,
+      &mut playlist,
+      2,
+      vec![RemoteRequest::PreviousTrack]
+    ),
+    Some(1)
+  );
+  // nothing left to skip to
+  assert_eq!(
+    apply_remote_requests(
+      &mut server,
+      &mut playlist,
+      2,
+      vec![RemoteRequest::NextTrack]
+    ),
+    None
+  );
+
+  let unplayable = RemoteRequest::Play(
+    vec![String::from("e1000000000000000000000000000002")],
+    String::from("PlayNow"),
+    None,
+  );
+  assert_eq!(
+    apply_remote_requests(&mut server, &mut playlist, 0, vec![unplayable]),
+    None
+  );
+  assert_eq!(playlist.len(), 3);
+
+  let play_now = RemoteRequest::Play(
+    vec![String::from("m1000000000000000000000000000001")],
+    String::from("PlayNow"),
+    Some(600000000),
+  );
+  assert_eq!(
+    apply_remote_requests(&mut server, &mut playlist, 1, vec![play_now]),
+    Some(0)
+  );
+  assert_eq!(playlist.len(), 1);
+  assert_eq!(playlist[0].playback_position, 60);
+}
+
+fn track() -> Item {
+  serde_json::from_value::<Item>(serde_json::json!({
+    "Name": "Tank!",
+    "Id": "a1000000000000000000000000000001",
+    "IndexNumber": 1,
+    "ParentIndexNumber": 1,
+    "RunTimeTicks": 2090000000u64,
+    "Type": "Audio",
+    "UserData": { "PlaybackPositionTicks": 0, "Played": false },
+    "ProductionYear": 1998,
+    "Album": "Cowboy Bebop",
+    "AlbumId": "b1000000000000000000000000000001",
+    "AlbumArtist": "The Seatbelts"
+  }))
+  .unwrap()
+}
+
+#[test]
+fn tracks_are_titled_by_artist_and_album() {
+  let track = track();
+  assert_eq!(
+    track.to_string(),
+    "The Seatbelts - Cowboy Bebop - 01. Tank!"
+  );
+  assert_eq!(
+    track.to_string_split(),
+    vec![
+      String::from("The Seatbelts - Cowboy Bebop - 01. Tank!"),
+      String::from("The Seatbelts - Cowboy Bebop"),
+      String::from("01. Tank!"),
+    ]
+  );
+}
+
+#[test]
+fn session_capabilities_include_audio() {
+  let mock = MockServer::start();
+  mock.route("POST", "Sessions/Capabilities/Full", 204, "");
+  let mut server = playing_server(&mock);
+
+  server.report_session_capabilities().unwrap();
+
+  let request = mock.find("POST", "Sessions/Capabilities/Full").unwrap();
+  let body = serde_json::from_str::<Value>(&request.body).unwrap();
+  assert_eq!(body["PlayableMediaTypes"], "Video,Audio");
+}
+
+#[test]
+fn search_includes_music() {
+  let mock = MockServer::start();
+  mock.route("GET", "Items", 200, ITEMS);
+  let mut server = logged_in_jellyfin(&mock);
+
+  server.search(&parse_search("bebop").unwrap(), 0).unwrap();
+
+  let request = mock.find("GET", "Items").unwrap();
+  assert!(
+    request
+      .url
+      .contains("IncludeItemTypes=Series,Movie,MusicArtist,MusicAlbum,Audio")
+  );
+}
+
+#[test]
+fn search_filters_and_pages() {
+  let mock = MockServer::start();
+  mock.route("GET", "Items", 200, ITEMS);
+  let mut server = logged_in_jellyfin(&mock);
+  let query =
+    parse_search("type:movie year:1997-1998 genre:Anime is:unplayed actor:\"Megumi Hayashibara\"")
+      .unwrap();
+
+  let (_, more) = server.search(&query, 2).unwrap();
+
+  assert!(!more);
+  let request = mock.find("GET", "Items").unwrap();
+  assert!(!request.url.contains("SearchTerm"));
+  assert!(request.url.contains("IncludeItemTypes=Movie&"));
+  assert!(
+    request
+      .url
+      .contains("MinPremiereDate=1997-01-01T00:00:00Z&MaxPremiereDate=1998-12-31T23:59:59Z")
+  );
+  assert!(request.url.contains("Genres=Anime"));
+  assert!(request.url.contains("IsPlayed=false"));
+  assert!(
+    request
+      .url
+      .contains("Person=Megumi%20Hayashibara&PersonTypes=Actor")
+  );
+  assert!(request.url.contains("StartIndex=100&Limit=50"));
+}
+
+#[test]
+fn saving_the_queue_creates_a_playlist() {
+  let mock = MockServer::start();
+  mock.route("POST", "Playlists", 200, r#"{"Id":"p1"}"#);
+  let mut server = logged_in_jellyfin(&mock);
+  let queue = vec![MediaItem::from(&episode()), MediaItem::from(&track())];
+
+  server.save_playlist("Late night", &queue).unwrap();
+
+  let request = mock.find("POST", "Playlists").unwrap();
+  assert!(request.url.contains("Name=Late%20night"));
+  assert!(request.url.contains(&format!(
+    "Ids={},a1000000000000000000000000000001",
+    EPISODE_ID
+  )));
+  assert!(
+    request
+      .url
+      .contains(&format!("UserId={}", JELLYFIN_USER_ID))
+  );
+}
+
+#[test]
+fn stopped_tracks_only_report_the_position() {
+  let mock = MockServer::start();
+  mock.route("POST", "Sessions/Playing/Stopped", 204, "");
+  let mut server = playing_server(&mock);
+  let track = track();
+
+  tokio::runtime::Runtime::new()
+    .unwrap()
+    .block_on(server.stop_track_playback(track.Id.clone(), 208.5));
+
+  let request = mock.find("POST", "Sessions/Playing/Stopped").unwrap();
+  let body = serde_json::from_str::<Value>(&request.body).unwrap();
+  assert_eq!(body["ItemId"], track.Id);
+  assert_eq!(body["PositionTicks"], "2085000000");
+  // the server marks it as played by itself
+  assert!(mock.find("POST", "PlayedItems").is_none());
+}
+
+#[test]
+fn media_segments_are_fetched() {
+  let mock = MockServer::start();
+  mock.route(
+    "GET",
+    &format!("MediaSegments/{}", EPISODE_ID),
+    200,
+    r#"{"Items":[{"Type":"Intro","StartTicks":0,"EndTicks":900000000}]}"#,
+  );
+  let mut server = logged_in_jellyfin(&mock);
+
+  let segments = server.get_segments(EPISODE_ID);
+
+  assert_eq!(segments.len(), 1);
+  assert_eq!(segments[0].end, 90.0);
+}
+
+#[test]
+fn servers_without_media_segments_play_without() {
+  let mock = MockServer::start();
+  let mut server = logged_in_jellyfin(&mock);
+
+  assert!(server.get_segments(EPISODE_ID).is_empty());
+  assert!(
+    mock
+      .find("GET", &format!("MediaSegments/{}", EPISODE_ID))
+      .is_some()
+  );
+}
+
+#[test]
+fn chapters_and_trickplay_are_fetched() {
+  let mock = MockServer::start();
+  let mut item = serde_json::from_str::<Value>(ITEMS).unwrap()["Items"][0].clone();
+  item["Chapters"] = serde_json::json!([
+    {"StartPositionTicks": 0, "Name": "Opening", "ImageDateModified": "0001-01-01T00:00:00.0000000Z"},
+    {"StartPositionTicks": 3000000000_u64, "ImageDateModified": "0001-01-01T00:00:00.0000000Z"}
+  ]);
+  item["Trickplay"] = serde_json::json!({"m1": {
+    "320": {"Width": 320, "Height": 180, "TileWidth": 10, "TileHeight": 10, "ThumbnailCount": 142, "Interval": 10000, "Bandwidth": 0},
+    "160": {"Width": 160, "Height": 90, "TileWidth": 10, "TileHeight": 10, "ThumbnailCount": 142, "Interval": 10000, "Bandwidth": 0}
+  }});
+  mock.route(
+    "GET",
+    &format!("Users/{}/Items/{}", JELLYFIN_USER_ID, EPISODE_ID),
+    200,
+    &item.to_string(),
+  );
+  let mut server = logged_in_jellyfin(&mock);
+
+  let item = server.get_item(EPISODE_ID.to_string()).unwrap();
+  let (chapters, trickplay) = server.get_chapters(&item, "m1");
+
+  assert_eq!(chapters.len(), 2);
+  assert_eq!(chapters[0].title, "Opening");
+  assert_eq!(chapters[1].title, "Chapter 2");
+  assert_eq!(chapters[1].start, 300.0);
+  let trickplay = trickplay.unwrap();
+  assert_eq!(trickplay.width, 160);
+  assert_eq!(
+    trickplay.url,
+    format!(
+      "{}Videos/{}/Trickplay/160/%d.jpg?MediaSourceId=m1&api_key={}",
+      mock.address, EPISODE_ID, JELLYFIN_TOKEN
+    )
+  );
+  let request = mock
+    .find(
+      "GET",
+      &format!("Users/{}/Items/{}", JELLYFIN_USER_ID, EPISODE_ID),
+    )
+    .unwrap();
+  assert!(request.url.contains("Chapters,Trickplay"));
+}
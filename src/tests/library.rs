@@ -0,0 +1,156 @@
+use crate::{
+  library::{PAGE_SIZE, SORT_ORDERS, get_categories, get_libraries, page_url, plex_page_url},
+  media_center::MediaCenter,
+  media_item::MediaKind,
+  tests::{
+    EPISODE_ID, JELLYFIN_USER_ID, logged_in_jellyfin, logged_in_plex, mock_server::MockServer,
+  },
+};
+
+const ITEMS: &str = include_str!("fixtures/jellyfin/items.json");
+
+#[test]
+fn pages_are_requested_with_sorting() {
+  assert_eq!(
+    page_url("Users/u/Items?ParentId=p", &SORT_ORDERS[1], 2),
+    format!(
+      "Users/u/Items?ParentId=p&SortBy=DateCreated,SortName&SortOrder=Descending&StartIndex={}&Limit={}",
+      PAGE_SIZE * 2,
+      PAGE_SIZE
+    )
+  );
+}
+
+#[test]
+fn plex_pages_start_the_query_if_needed() {
+  assert_eq!(
+    plex_page_url("library/sections/1/all", Some(&SORT_ORDERS[0]), 0),
+    format!(
+      "library/sections/1/all?X-Plex-Container-Start=0&X-Plex-Container-Size={}&sort=titleSort",
+      PAGE_SIZE
+    )
+  );
+  assert_eq!(
+    plex_page_url("library/sections/1/folder?parent=12", None, 1),
+    format!(
+      "library/sections/1/folder?parent=12&X-Plex-Container-Start={}&X-Plex-Container-Size={}",
+      PAGE_SIZE, PAGE_SIZE
+    )
+  );
+}
+
+#[test]
+fn jellyfin_drills_into_genres_and_years() {
+  let mock = MockServer::start();
+  mock.route(
+    "GET",
+    &format!("Users/{}/Views", JELLYFIN_USER_ID),
+    200,
+    r#"{"Items":[{"Name":"Shows","Id":"f0000000000000000000000000000001","CollectionType":"tvshows"}]}"#,
+  );
+  mock.route(
+    "GET",
+    "Genres",
+    200,
+    r#"{"Items":[{"Name":"Science Fiction","Id":"9e000000000000000000000000000001"}]}"#,
+  );
+  mock.route(
+    "GET",
+    "Years",
+    200,
+    r#"{"Items":[{"Name":"1998","Id":"a1000000000000000000000000000001"}]}"#,
+  );
+  mock.route(
+    "GET",
+    &format!("Users/{}/Items", JELLYFIN_USER_ID),
+    200,
+    ITEMS,
+  );
+  let mut server = logged_in_jellyfin(&mock);
+
+  let libraries = get_libraries(&mut server).unwrap();
+  assert_eq!(libraries[0].name, "Shows");
+
+  let genres = get_categories(&mut server, &libraries[0], "Genres").unwrap();
+  assert_eq!(genres[0].name, "Science Fiction");
+  assert_eq!(
+    mock.find("GET", "Genres").unwrap().url,
+    format!(
+      "Genres?UserId={}&ParentId=f0000000000000000000000000000001&Recursive=true&SortBy=SortName",
+      JELLYFIN_USER_ID
+    )
+  );
+
+  let items = server
+    .get_items(page_url(&genres[0].url, &SORT_ORDERS[0], 0), false)
+    .unwrap();
+  assert_eq!(items[0].Id, EPISODE_ID);
+  let request = mock
+    .find("GET", &format!("Users/{}/Items", JELLYFIN_USER_ID))
+    .unwrap();
+  assert!(request.url.contains(
+    "ParentId=f0000000000000000000000000000001&Recursive=true&IncludeItemTypes=Series&GenreIds=9e000000000000000000000000000001&SortBy=SortName"
+  ));
+
+  // years are filtered by their name
+  let years = get_categories(&mut server, &libraries[0], "Years").unwrap();
+  assert!(
+    years[0]
+      .url
+      .ends_with("&IncludeItemTypes=Series&Years=1998")
+  );
+}
+
+#[test]
+fn plex_drills_into_genres_and_years() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  pms.route(
+    "GET",
+    "library/sections",
+    200,
+    r#"{"MediaContainer":{"Directory":[{"key":"2","title":"Movies","type":"movie"}]}}"#,
+  );
+  pms.route(
+    "GET",
+    "library/sections/2/genre",
+    200,
+    r#"{"MediaContainer":{"Directory":[{"key":"77","title":"Thriller","fastKey":"/library/sections/2/all?genre=77"}]}}"#,
+  );
+  pms.route(
+    "GET",
+    "library/sections/2/year",
+    200,
+    r#"{"MediaContainer":{"Directory":[{"key":"1998","title":"1998"}]}}"#,
+  );
+  pms.route(
+    "GET",
+    "library/sections/2/all",
+    200,
+    r#"{"MediaContainer":{"size":1,"Metadata":[{"ratingKey":"2001","guid":"plex://movie/2001","type":"movie","title":"Perfect Blue"}]}}"#,
+  );
+  let mut server = logged_in_plex(&plex_tv, &pms);
+
+  let libraries = server.libraries().unwrap();
+  assert_eq!(libraries[0].name, "Movies");
+  assert_eq!(libraries[0].items_url, "library/sections/2/all");
+
+  let genres = server.library_categories(&libraries[0], "Genres").unwrap();
+  assert_eq!(genres[0].name, "Thriller");
+  assert_eq!(genres[0].url, "library/sections/2/all?genre=77");
+
+  let items = server
+    .library_page(&genres[0].url, &SORT_ORDERS[0], 0)
+    .unwrap();
+  assert_eq!(items[0].id, "2001");
+  assert_eq!(items[0].kind, MediaKind::Movie);
+  let request = pms.find("GET", "library/sections/2/all").unwrap();
+  assert!(request.url.starts_with(&format!(
+    "library/sections/2/all?genre=77&X-Plex-Container-Start=0&X-Plex-Container-Size={}&sort=titleSort",
+    PAGE_SIZE
+  )));
+
+  // without a fastKey the filter is built from the key
+  let years = server.library_categories(&libraries[0], "Years").unwrap();
+  assert_eq!(years[0].url, "library/sections/2/all?year=1998");
+}
@@ -0,0 +1,182 @@
+use std::{fs, path::PathBuf};
+
+use crate::{
+  backend::{Backend, series_seasons},
+  input::series_structure,
+  local::{LocalServer, index_folder, parse_video},
+  media_config::{MediaCenterType, Objective},
+  media_item::MediaKind,
+  tests::{test_config, test_settings},
+};
+
+// Empty files are enough, nothing is ever played.
+// The folder and the config file are removed again once the library is dropped (even if the test
+// fails).
+struct Library {
+  folder: PathBuf,
+  server: LocalServer,
+}
+
+impl Library {
+  fn new(files: &[&str]) -> Self {
+    let folder = std::env::temp_dir().join(format!("puddler-test-{}", uuid::Uuid::new_v4()));
+    for file in files {
+      let path = folder.join(file);
+      fs::create_dir_all(path.parent().unwrap()).unwrap();
+      fs::write(path, "").unwrap();
+    }
+    let mut config = test_config(MediaCenterType::Local, "");
+    config.insert_specific_value(Objective::Folder, folder.display().to_string());
+    Library {
+      server: LocalServer::new(config, test_settings()),
+      folder,
+    }
+  }
+}
+
+impl Drop for Library {
+  fn drop(&mut self) {
+    let _ = fs::remove_dir_all(&self.folder);
+    let _ = fs::remove_file(&Backend::get_config_handle(&mut self.server).path);
+  }
+}
+
+#[test]
+fn episode_names_are_parsed() {
+  let episode = parse_video("The Wire/The.Wire.S01E02.The.Detail.720p.mkv")
+    .episode
+    .unwrap();
+  assert_eq!(episode.series, "The Wire");
+  assert_eq!((episode.season, episode.episode), (1, 2));
+  assert_eq!(episode.title, "The Detail 720p");
+
+  // the series comes from the folders, season folders are skipped
+  let episode = parse_video("Mr. Robot/Season 2/s02e11.mkv")
+    .episode
+    .unwrap();
+  assert_eq!(episode.series, "Mr Robot");
+  assert_eq!((episode.season, episode.episode), (2, 11));
+  assert_eq!(episode.title, "");
+
+  let episode = parse_video("Fargo - S03E01E02 - The Law of Vacant Places.mp4")
+    .episode
+    .unwrap();
+  assert_eq!(episode.series, "Fargo");
+  assert_eq!(episode.episode, 1);
+  assert_eq!(episode.title, "The Law of Vacant Places");
+
+  let movie = parse_video("Movies/Se7en (1995).mkv");
+  assert!(movie.episode.is_none());
+  assert_eq!(movie.title, "Se7en (1995)");
+}
+
+#[test]
+fn only_visible_videos_are_indexed() {
+  let library = Library::new(&[
+    "Movies/Heat (1995).mkv",
+    "Movies/Heat (1995).srt",
+    "Movies/.hidden.mkv",
+    ".trash/Old.Show.S01E01.mkv",
+    "Arrival.MP4",
+  ]);
+  let videos = index_folder(&library.folder).unwrap();
+  let paths: Vec<String> = videos.iter().map(|video| video.path.clone()).collect();
+  assert_eq!(
+    paths,
+    vec![
+      String::from("Arrival.MP4"),
+      PathBuf::from("Movies")
+        .join("Heat (1995).mkv")
+        .display()
+        .to_string(),
+    ]
+  );
+}
+
+#[test]
+fn episodes_are_grouped_into_series_and_seasons() {
+  let mut library = Library::new(&[
+    "Dark/Dark.S02E01.mkv",
+    "Dark/Dark.S01E02.mkv",
+    "Dark/Dark.S01E01.mkv",
+    "dark.s01e03.mkv",
+    "Arrival (2016).mkv",
+  ]);
+  let server = &mut library.server;
+
+  let items = Backend::browse(server, None).unwrap();
+  assert_eq!(items.len(), 2);
+  assert_eq!(items[0].kind, MediaKind::Series);
+  assert_eq!(items[0].to_string(), "Dark");
+  assert_eq!(items[1].kind, MediaKind::Movie);
+  assert_eq!(items[1].to_string(), "Arrival (2016)");
+
+  let seasons = series_seasons(server, &items[0]).unwrap();
+  assert_eq!(seasons.len(), 2);
+  let episodes: Vec<String> = seasons[0]
+    .1
+    .iter()
+    .map(|episode| episode.to_string())
+    .collect();
+  assert_eq!(
+    episodes,
+    vec![
+      String::from("Dark - S01E01"),
+      String::from("Dark - S01E02"),
+      String::from("Dark - S01E03"),
+    ]
+  );
+
+  let structure = series_structure(
+    items[0].to_string(),
+    seasons
+      .iter()
+      .map(|(season, episodes)| {
+        (
+          season.to_string(),
+          episodes.iter().map(|episode| episode.to_string()).collect(),
+        )
+      })
+      .collect(),
+  );
+  assert_eq!(structure.len(), 7);
+  assert!(structure[5].contains("Season 2"));
+  assert_eq!(structure[6], "       └── [3] Dark - S02E01");
+
+  let source = Backend::resolve_stream(server, &seasons[1].1[0]).unwrap();
+  assert_eq!(
+    source.stream_url,
+    library
+      .folder
+      .join("Dark")
+      .join("Dark.S02E01.mkv")
+      .display()
+      .to_string()
+  );
+}
+
+#[test]
+fn playstates_are_kept_locally() {
+  let mut library = Library::new(&["Dark.S01E01.mkv", "Dark.S01E02.mkv", "Arrival.mkv"]);
+  let server = &mut library.server;
+  let items = Backend::browse(server, None).unwrap();
+  let movie = items[1].clone();
+
+  Backend::report_progress(server, &movie, 600).unwrap();
+  let resume = Backend::resume(server).unwrap();
+  assert_eq!(resume.len(), 1);
+  assert_eq!(resume[0].id, "Arrival.mkv");
+  assert_eq!(resume[0].playback_position, 600);
+
+  Backend::set_playstate(server, &movie, true).unwrap();
+  assert!(Backend::resume(server).unwrap().is_empty());
+
+  // the whole series at once
+  Backend::set_playstate(server, &items[0], true).unwrap();
+  let items = Backend::browse(server, None).unwrap();
+  assert!(items[0].played);
+  assert_eq!(Backend::get_config_handle(server).get_playstates().len(), 3);
+
+  Backend::set_playstate(server, &items[0], false).unwrap();
+  assert!(!Backend::browse(server, None).unwrap()[0].played);
+}
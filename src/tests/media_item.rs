@@ -0,0 +1,70 @@
+use serde_json::Value;
+
+use crate::{
+  cli::Listable,
+  input::{SeriesOptions, series_playlist},
+  media_center::{Item, ToStringAdv},
+  media_item::{MediaItem, MediaKind},
+  plex::PlexItem,
+};
+
+const JELLYFIN_ITEMS: &str = include_str!("fixtures/jellyfin/items.json");
+const PLEX_METADATA: &str = include_str!("fixtures/plex/metadata.json");
+
+#[test]
+fn jellyfin_items_are_converted() {
+  let json = serde_json::from_str::<Value>(JELLYFIN_ITEMS).unwrap();
+  let item = serde_json::from_value::<Item>(json["Items"][0].clone()).unwrap();
+  let media_item = MediaItem::from(&item);
+
+  assert_eq!(media_item.id, "e1000000000000000000000000000001");
+  assert_eq!(media_item.kind, MediaKind::Episode);
+  assert_eq!(media_item.item_type(), "episode");
+  assert_eq!(media_item.titles, item.to_string_split());
+  assert!(media_item.played);
+  assert_eq!(media_item.playback_position, 0);
+  assert_eq!(media_item.runtime, 1474);
+}
+
+#[test]
+fn plex_items_are_converted() {
+  let json = serde_json::from_str::<Value>(PLEX_METADATA).unwrap();
+  let item =
+    serde_json::from_value::<PlexItem>(json["MediaContainer"]["Metadata"][0].clone()).unwrap();
+  let media_item = MediaItem::from(&item);
+
+  assert_eq!(media_item.id, "3005");
+  assert_eq!(media_item.kind, MediaKind::Episode);
+  assert_eq!(media_item.titles, item.to_string_split());
+  assert!(!media_item.played);
+  assert_eq!(media_item.playback_position, 600);
+  assert!(media_item.to_string_ext().ends_with(" 41%"));
+}
+
+#[test]
+fn unknown_kinds_keep_their_name() {
+  let item = MediaItem {
+    id: String::from("1"),
+    kind: MediaKind::Other(String::from("BoxSet")),
+    titles: vec![],
+    played: false,
+    playback_position: 0,
+    runtime: 0,
+  };
+  assert_eq!(item.item_type(), "boxset");
+  assert_eq!(item.to_string(), "");
+}
+
+#[test]
+fn series_playlists_follow_the_mode() {
+  let episodes = vec!["a", "b", "c", "d"];
+
+  assert_eq!(
+    series_playlist(episodes.clone(), &SeriesOptions::Play, vec![1]),
+    (vec!["b", "c", "d"], true)
+  );
+  assert_eq!(
+    series_playlist(episodes, &SeriesOptions::Shuffle, vec![3, 0, 7]),
+    (vec!["d", "a"], false)
+  );
+}
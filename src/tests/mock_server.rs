@@ -0,0 +1,171 @@
+use std::{
+  io::{BufRead, BufReader, Read, Write},
+  net::{TcpListener, TcpStream},
+  sync::{Arc, Mutex},
+  thread,
+};
+
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+  pub method: String,
+  pub url: String,
+  pub headers: Vec<(String, String)>,
+  pub body: String,
+}
+
+impl RecordedRequest {
+  pub fn path(&self) -> &str {
+    self.url.split('?').next().unwrap()
+  }
+
+  pub fn header(&self, name: &str) -> Option<String> {
+    self
+      .headers
+      .iter()
+      .find(|(key, _)| key.eq_ignore_ascii_case(name))
+      .map(|(_, value)| value.clone())
+  }
+}
+
+#[derive(Debug, Clone)]
+struct Route {
+  method: String,
+  path: String,
+  status: u16,
+  body: String,
+}
+
+/// Minimal HTTP/1.1 server answering with canned responses and recording every request.
+/// Routes are matched by method and path (without the query), the most recently added route wins.
+pub struct MockServer {
+  pub address: String,
+  routes: Arc<Mutex<Vec<Route>>>,
+  requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl MockServer {
+  pub fn start() -> Self {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = format!("http://{}/", listener.local_addr().unwrap());
+    let routes: Arc<Mutex<Vec<Route>>> = Arc::new(Mutex::new(vec![]));
+    let requests: Arc<Mutex<Vec<RecordedRequest>>> = Arc::new(Mutex::new(vec![]));
+    thread::spawn({
+      let routes = Arc::clone(&routes);
+      let requests = Arc::clone(&requests);
+      move || {
+        for stream in listener.incoming().flatten() {
+          let routes = Arc::clone(&routes);
+          let requests = Arc::clone(&requests);
+          thread::spawn(move || handle_connection(stream, routes, requests));
+        }
+      }
+    });
+    MockServer {
+      address,
+      routes,
+      requests,
+    }
+  }
+
+  pub fn route(&self, method: &str, path: &str, status: u16, body: &str) {
+    self.routes.lock().unwrap().push(Route {
+      method: method.to_string(),
+      path: path.trim_start_matches('/').to_string(),
+      status,
+      body: body.to_string(),
+    });
+  }
+
+  pub fn requests(&self) -> Vec<RecordedRequest> {
+    self.requests.lock().unwrap().clone()
+  }
+
+  pub fn find(&self, method: &str, path: &str) -> Option<RecordedRequest> {
+    self
+      .requests()
+      .into_iter()
+      .rev()
+      .find(|r| r.method == method && r.path() == path)
+  }
+}
+
+fn handle_connection(
+  stream: TcpStream,
+  routes: Arc<Mutex<Vec<Route>>>,
+  requests: Arc<Mutex<Vec<RecordedRequest>>>,
+) {
+  let mut reader = BufReader::new(stream.try_clone().unwrap());
+  let mut request_line = String::new();
+  if reader.read_line(&mut request_line).is_err() || request_line.is_empty() {
+    return;
+  }
+  let mut parts = request_line.split_whitespace();
+  let method = parts.next().unwrap_or_default().to_string();
+  let url = parts
+    .next()
+    .unwrap_or_default()
+    .trim_start_matches('/')
+    .to_string();
+
+  let mut headers: Vec<(String, String)> = vec![];
+  loop {
+    let mut line = String::new();
+    if reader.read_line(&mut line).is_err() || line.trim().is_empty() {
+      break;
+    }
+    if let Some((key, value)) = line.split_once(':') {
+      headers.push((key.trim().to_string(), value.trim().to_string()));
+    }
+  }
+
+  let content_length = headers
+    .iter()
+    .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+    .and_then(|(_, value)| value.parse::<usize>().ok())
+    .unwrap_or(0);
+  let mut body = vec![0; content_length];
+  reader.read_exact(&mut body).unwrap();
+
+  let request = RecordedRequest {
+    method,
+    url,
+    headers,
+    body: String::from_utf8_lossy(&body).to_string(),
+  };
+  let route = routes
+    .lock()
+    .unwrap()
+    .iter()
+    .rev()
+    .find(|r| r.method == request.method && r.path == request.path())
+    .cloned();
+  requests.lock().unwrap().push(request);
+
+  let (status, body) = if let Some(route) = route {
+    (route.status, route.body)
+  } else {
+    (404, String::new())
+  };
+  let response = format!(
+    "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+    status,
+    reason(status),
+    body.len(),
+    body
+  );
+  let mut stream = stream;
+  let _ = stream.write_all(response.as_bytes());
+  let _ = stream.flush();
+}
+
+fn reason(status: u16) -> &'static str {
+  match status {
+    200 => "OK",
+    201 => "Created",
+    204 => "No Content",
+    400 => "Bad Request",
+    401 => "Unauthorized",
+    404 => "Not Found",
+    _ => "Internal Server Error",
+  }
+}
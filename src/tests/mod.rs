@@ -0,0 +1,88 @@
+use crate::{
+  jellyfin::JellyfinServer,
+  media_center::MediaCenter,
+  media_config::{Config, MediaCenterType, Objective, UserConfig},
+  plex::PlexServer,
+  puddler_settings::PuddlerSettings,
+  tests::mock_server::MockServer,
+};
+
+mod backend;
+mod companion;
+mod dlna;
+mod history;
+mod jellyfin;
+mod library;
+mod local;
+mod media_item;
+mod mock_server;
+mod offline;
+mod plex;
+mod queue;
+mod receiver;
+mod search;
+mod segments;
+mod shuffle;
+mod syncplay;
+
+pub const JELLYFIN_TOKEN: &str = "c0ffee00c0ffee00c0ffee00c0ffee00";
+pub const JELLYFIN_USER_ID: &str = "4f1c2d3e4b5a69788796a5b4c3d2e1f0";
+// the first item of fixtures/jellyfin/items.json
+pub const EPISODE_ID: &str = "e1000000000000000000000000000001";
+
+pub const PLEX_TOKEN: &str = "pLeXt0kEnpLeXt0kEn00";
+pub const PLEX_USER_ID: &str = "48151623";
+
+pub fn test_settings() -> PuddlerSettings {
+  PuddlerSettings {
+    default_media_server: None,
+    discord_presence: false,
+    fullscreen: false,
+    gpu: false,
+    glsl_shaders: vec![],
+    mpv_config_location: None,
+    mpv_debug_log: false,
+    skip_segments: false,
+    plex_companion: false,
+  }
+}
+
+// Never points to the real media-center folder.
+pub fn test_config(media_center_type: MediaCenterType, address: &str) -> Config {
+  let mut config = Config::default();
+  config.config.media_center_type = media_center_type;
+  config.config.server_name = String::from("Mock");
+  config.path = std::env::temp_dir()
+    .join(format!("puddler-test-{}.json", uuid::Uuid::new_v4()))
+    .display()
+    .to_string();
+  config.insert_specific_value(Objective::Address, address.to_string());
+  config.insert_specific_value(Objective::DeviceID, String::from("puddler-test-device"));
+  config
+}
+
+pub fn add_user(config: &mut Config, access_token: &str, username: &str, user_id: &str) {
+  let user = UserConfig {
+    access_token: access_token.to_string(),
+    username: username.to_string(),
+    user_id: user_id.to_string(),
+  };
+  config.insert_specific_value(Objective::User, serde_json::to_string(&user).unwrap());
+}
+
+pub fn logged_in_jellyfin(mock: &MockServer) -> JellyfinServer {
+  let mut config = test_config(MediaCenterType::Jellyfin, &mock.address);
+  add_user(&mut config, JELLYFIN_TOKEN, "alice", JELLYFIN_USER_ID);
+  let mut server = JellyfinServer::new(config, test_settings());
+  server.write_headers();
+  server
+}
+
+// plex.tv and the media server are two different hosts, so every test gets two mock servers.
+pub fn logged_in_plex(plex_tv: &MockServer, pms: &MockServer) -> PlexServer {
+  let mut config = test_config(MediaCenterType::Plex, &pms.address);
+  add_user(&mut config, PLEX_TOKEN, "alice", PLEX_USER_ID);
+  let mut server = PlexServer::new(config, test_settings());
+  server.set_plex_tv_address(plex_tv.address.clone());
+  server
+}
@@ -0,0 +1,128 @@
+use std::{fs, path::PathBuf};
+
+use crate::{
+  media_config::MediaCenterType,
+  offline::{Download, DownloadSubtitle, OfflineLibrary, watched_enough},
+  tests::{mock_server::MockServer, test_config},
+};
+
+pub fn temp_folder() -> PathBuf {
+  std::env::temp_dir().join(format!("puddler-offline-{}", uuid::Uuid::new_v4()))
+}
+
+fn download(mock: &MockServer) -> Download {
+  Download {
+    item_id: String::from("1234"),
+    title: vec![String::from("Perfect Blue (1997)")],
+    movie: true,
+    extension: String::from("mkv"),
+    url: format!("{}video?api_key=secret", mock.address),
+    subtitles: vec![
+      DownloadSubtitle {
+        url: format!("{}subtitle?api_key=secret", mock.address),
+        extension: String::from("srt"),
+        title: String::from("English"),
+        language: String::from("eng"),
+      },
+      DownloadSubtitle {
+        url: format!("{}missing?api_key=secret", mock.address),
+        extension: String::from("ass"),
+        title: String::from("Signs"),
+        language: String::from("eng"),
+      },
+    ],
+    total_runtime: 4860,
+    playback_position: 120,
+    played: false,
+  }
+}
+
+#[test]
+fn download_stores_files_and_index() {
+  let mock = MockServer::start();
+  mock.route("GET", "video", 200, "not really a video");
+  mock.route(
+    "GET",
+    "subtitle",
+    200,
+    "1\n00:00:01,000 --> 00:00:02,000\nHi\n",
+  );
+  let config = test_config(MediaCenterType::Jellyfin, &mock.address);
+  let folder = temp_folder();
+  let mut library = OfflineLibrary::at(folder.clone()).unwrap();
+
+  library.download(&config, download(&mock)).unwrap();
+
+  // the failed subtitle doesn't stop the download
+  let library = OfflineLibrary::at(folder.clone()).unwrap();
+  assert_eq!(library.items.len(), 1);
+  let item = &library.items[0];
+  assert_eq!(item.server_path, config.path);
+  assert_eq!(item.playback_position, 120);
+  assert!(item.synced);
+  assert_eq!(
+    fs::read_to_string(library.get_path(&item.file)).unwrap(),
+    "not really a video"
+  );
+  assert_eq!(item.subtitles.len(), 1);
+  assert!(library.get_path(&item.subtitles[0].file).is_file());
+  assert!(!library.get_path(&item.file).with_extension("part").exists());
+  fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+fn download_replaces_existing_item() {
+  let mock = MockServer::start();
+  mock.route("GET", "video", 200, "version 1");
+  let config = test_config(MediaCenterType::Jellyfin, &mock.address);
+  let folder = temp_folder();
+  let mut library = OfflineLibrary::at(folder.clone()).unwrap();
+
+  library.download(&config, download(&mock)).unwrap();
+  mock.route("GET", "video", 200, "version 2");
+  library.download(&config, download(&mock)).unwrap();
+
+  assert_eq!(library.items.len(), 1);
+  assert_eq!(
+    fs::read_to_string(library.get_path(&library.items[0].file)).unwrap(),
+    "version 2"
+  );
+  fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+fn failed_download_is_not_added() {
+  let mock = MockServer::start();
+  let config = test_config(MediaCenterType::Jellyfin, &mock.address);
+  let folder = temp_folder();
+  let mut library = OfflineLibrary::at(folder.clone()).unwrap();
+
+  assert!(library.download(&config, download(&mock)).is_err());
+  assert!(library.items.is_empty());
+  fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+fn remove_deletes_files() {
+  let mock = MockServer::start();
+  mock.route("GET", "video", 200, "not really a video");
+  let config = test_config(MediaCenterType::Jellyfin, &mock.address);
+  let folder = temp_folder();
+  let mut library = OfflineLibrary::at(folder.clone()).unwrap();
+  library.download(&config, download(&mock)).unwrap();
+  let file = library.get_path(&library.items[0].file);
+
+  library.remove(0).unwrap();
+
+  assert!(!file.exists());
+  assert!(OfflineLibrary::at(folder.clone()).unwrap().items.is_empty());
+  fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+fn watched_enough_matches_the_servers() {
+  assert!(watched_enough(1474, 1460.0));
+  // less than 5 minutes left
+  assert!(watched_enough(7200, 6950.0));
+  assert!(!watched_enough(1474, 600.0));
+}
@@ -0,0 +1,626 @@
+use std::{fs::remove_file, path::Path};
+
+use crate::{
+  error::PuddlerError,
+  media_center::{MediaCenter, MediaCenterValues, UDPAnswer, dedup_answers, parse_gdm_answer},
+  media_config::{MediaCenterType, Objective},
+  plex::{PlexItem, PlexServer},
+  search::parse_search,
+  segments::SegmentType,
+  tests::{
+    PLEX_TOKEN, PLEX_USER_ID, add_user, logged_in_plex, mock_server::MockServer, test_config,
+    test_settings,
+  },
+};
+
+const PIN: &str = include_str!("fixtures/plex/pin.json");
+const USER: &str = include_str!("fixtures/plex/user.json");
+const RESOURCES: &str = include_str!("fixtures/plex/resources.json");
+const SEARCH: &str = include_str!("fixtures/plex/search.json");
+const METADATA: &str = include_str!("fixtures/plex/metadata.json");
+
+const EPISODE_KEY: &str = "3005";
+
+fn plex_server(plex_tv: &MockServer, pms: &MockServer) -> PlexServer {
+  let config = test_config(MediaCenterType::Plex, &pms.address);
+  let mut server = PlexServer::new(config, test_settings());
+  server.set_plex_tv_address(plex_tv.address.clone());
+  server
+}
+
+fn playing_server(plex_tv: &MockServer, pms: &MockServer) -> PlexServer {
+  let mut server = logged_in_plex(plex_tv, pms);
+  server.insert_value(
+    MediaCenterValues::PlaybackInfo,
+    serde_json::to_string(&episode()).unwrap(),
+  );
+  server
+}
+
+fn episode() -> PlexItem {
+  let json = serde_json::from_str::<serde_json::Value>(METADATA).unwrap();
+  serde_json::from_value::<PlexItem>(json["MediaContainer"]["Metadata"][0].clone()).unwrap()
+}
+
+#[test]
+fn create_plex_user_links_account() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  plex_tv.route("POST", "pins.json", 201, PIN);
+  let mut server = plex_server(&plex_tv, &pms);
+
+  assert_eq!(server.create_plex_user().unwrap(), PLEX_TOKEN);
+
+  let request = plex_tv.find("POST", "pins.json").unwrap();
+  assert!(
+    request
+      .url
+      .contains("X-Plex-Client-Identifier=puddler-test-device")
+  );
+  assert!(request.url.contains("X-Plex-Device-Name="));
+  assert!(pms.requests().is_empty());
+}
+
+#[test]
+fn get_servers_lists_resources() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  plex_tv.route("GET", "api/v2/resources", 200, RESOURCES);
+  let mut server = plex_server(&plex_tv, &pms);
+
+  let servers = server.get_servers(PLEX_TOKEN.to_string()).unwrap();
+
+  assert_eq!(servers.len(), 2);
+  let request = plex_tv.find("GET", "api/v2/resources").unwrap();
+  assert!(
+    request
+      .url
+      .contains(&format!("X-Plex-Token={}", PLEX_TOKEN))
+  );
+}
+
+#[test]
+fn plex_tv_errors_are_returned() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  plex_tv.route("GET", "api/v2/resources", 401, "");
+  let mut server = plex_server(&plex_tv, &pms);
+
+  assert!(matches!(
+    server.get_servers(PLEX_TOKEN.to_string()),
+    Err(PuddlerError::Auth(_))
+  ));
+
+  // Nothing is listening on port 1. This used to exit the whole process.
+  server.set_plex_tv_address(String::from("http://127.0.0.1:1/"));
+  assert!(matches!(
+    server.get_servers(PLEX_TOKEN.to_string()),
+    Err(PuddlerError::Network(_))
+  ));
+}
+
+#[test]
+fn check_token_valid() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  plex_tv.route("GET", "api/v2/user", 200, USER);
+  let mut server = logged_in_plex(&plex_tv, &pms);
+
+  assert!(server.check_token_valid());
+  let request = plex_tv.find("GET", "api/v2/user").unwrap();
+  assert!(
+    request
+      .url
+      .contains(&format!("X-Plex-Token={}", PLEX_TOKEN))
+  );
+
+  plex_tv.route("GET", "api/v2/user", 401, "");
+  assert!(!server.check_token_valid());
+}
+
+#[test]
+fn get_username_saves_user() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  plex_tv.route("GET", "api/v2/user", 200, USER);
+  let mut server = plex_server(&plex_tv, &pms);
+
+  assert!(server.get_username(PLEX_TOKEN.to_string()).is_ok());
+
+  let config = server.get_config_handle();
+  let user = config.get_active_user().unwrap();
+  assert_eq!(user.access_token, PLEX_TOKEN);
+  assert_eq!(user.username, "alice");
+  assert_eq!(user.user_id, PLEX_USER_ID);
+  assert!(Path::new(&config.path).is_file());
+  remove_file(&config.path).unwrap();
+}
+
+#[test]
+fn search_collects_movie_and_show_hubs() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  pms.route("GET", "hubs/search", 200, SEARCH);
+  let mut server = logged_in_plex(&plex_tv, &pms);
+
+  let (items, more) = server
+    .search(&parse_search("cowboy bebop").unwrap(), 0)
+    .unwrap();
+
+  assert!(!more);
+  assert_eq!(items.len(), 2);
+  assert_eq!(items[0].title, "Perfect Blue");
+  assert_eq!(items[1].r#type, "show");
+  let request = pms.find("GET", "hubs/search").unwrap();
+  assert!(request.url.contains("query=cowboy%20bebop&limit=50"));
+  assert!(
+    request
+      .url
+      .contains(&format!("X-Plex-Token={}", PLEX_TOKEN))
+  );
+  assert!(plex_tv.requests().is_empty());
+}
+
+#[test]
+fn search_hubs_are_paged_by_their_limit() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  let movies: Vec<String> = (0..60)
+    .map(|index| {
+      format!(
+        r#"{{"ratingKey":"{}","guid":"plex://movie/{}","title":"Movie {}","type":"movie"}}"#,
+        index, index, index
+      )
+    })
+    .collect();
+  let hubs = |size: usize| {
+    format!(
+      r#"{{"MediaContainer":{{"size":2,"Hub":[{{"type":"movie","size":{},"Metadata":[{}]}},{{"type":"show","size":1,"Metadata":[{{"ratingKey":"900","guid":"plex://show/900","title":"Show","type":"show"}}]}}]}}}}"#,
+      size,
+      movies[..size].join(",")
+    )
+  };
+  pms.route("GET", "hubs/search", 200, &hubs(50));
+  let mut server = logged_in_plex(&plex_tv, &pms);
+
+  let (items, more) = server.search(&parse_search("movie").unwrap(), 0).unwrap();
+  assert!(more);
+  assert_eq!(items.len(), 51);
+
+  // the second page asks for both pages and only returns what's new
+  pms.route("GET", "hubs/search", 200, &hubs(60));
+  let (items, more) = server.search(&parse_search("movie").unwrap(), 1).unwrap();
+  assert!(!more);
+  assert_eq!(items.len(), 10);
+  assert_eq!(items[0].title, "Movie 50");
+  let request = pms.find("GET", "hubs/search").unwrap();
+  assert!(request.url.contains("limit=100"));
+}
+
+#[test]
+fn filtered_search_resolves_tags() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  pms.route(
+    "GET",
+    "library/sections",
+    200,
+    r#"{"MediaContainer":{"Directory":[{"key":"1","title":"Movies","type":"movie"}]}}"#,
+  );
+  pms.route(
+    "GET",
+    "library/sections/1/genre",
+    200,
+    r#"{"MediaContainer":{"Directory":[{"key":"77","title":"Thriller"}]}}"#,
+  );
+  pms.route(
+    "GET",
+    "library/all",
+    200,
+    r#"{"MediaContainer":{"size":0}}"#,
+  );
+  let mut server = logged_in_plex(&plex_tv, &pms);
+
+  let (items, more) = server
+    .search(&parse_search("blue genre:thriller type:movie").unwrap(), 1)
+    .unwrap();
+
+  assert!(items.is_empty());
+  assert!(!more);
+  let request = pms.find("GET", "library/all").unwrap();
+  assert!(request.url.contains("type=1&title=blue&genre=77"));
+  assert!(
+    request
+      .url
+      .contains("X-Plex-Container-Start=50&X-Plex-Container-Size=50")
+  );
+
+  server
+    .search(&parse_search("year:1997-1998").unwrap(), 0)
+    .unwrap();
+  let request = pms.find("GET", "library/all").unwrap();
+  assert!(request.url.contains("year%3E%3E=1996&year%3C%3C=1999"));
+}
+
+#[test]
+fn unknown_genres_are_reported() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  pms.route("GET", "library/sections", 200, r#"{"MediaContainer":{}}"#);
+  let mut server = logged_in_plex(&plex_tv, &pms);
+
+  let result = server.search(&parse_search("genre:polka").unwrap(), 0);
+
+  assert!(matches!(result, Err(PuddlerError::Unsupported(_))));
+  assert!(pms.find("GET", "library/all").is_none());
+}
+
+#[test]
+fn get_item_parses_metadata() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  let path = format!("library/metadata/{}", EPISODE_KEY);
+  pms.route("GET", &path, 200, METADATA);
+  let mut server = logged_in_plex(&plex_tv, &pms);
+
+  let item = server.get_item(EPISODE_KEY.to_string()).unwrap();
+
+  assert_eq!(item.ratingKey, EPISODE_KEY);
+  assert_eq!(item.viewOffset, Some(600000));
+  let media = item.Media.unwrap();
+  assert_eq!(
+    media[0].Part[0].key,
+    "/library/parts/5001/1600000000/file.mkv"
+  );
+  assert_eq!(media[0].Part[0].Stream.as_ref().unwrap().len(), 3);
+
+  pms.route("GET", &path, 404, "");
+  assert!(server.get_item(EPISODE_KEY.to_string()).is_err());
+}
+
+#[test]
+fn report_playback_sends_timeline() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  pms.route("GET", ":/timeline", 200, "");
+  let mut server = playing_server(&plex_tv, &pms);
+
+  tokio::runtime::Runtime::new()
+    .unwrap()
+    .block_on(server.report_playback(EPISODE_KEY.to_string(), 0, 12.5, 1, 2, true, false, 80));
+
+  let request = pms.find("GET", ":/timeline").unwrap();
+  assert!(request.url.contains("ratingKey=3005"));
+  assert!(request.url.contains("state=paused"));
+  assert!(request.url.contains("time=12500"));
+  assert!(request.url.contains("duration=1474000"));
+  assert!(
+    request
+      .url
+      .contains(&format!("X-Plex-Token={}", PLEX_TOKEN))
+  );
+}
+
+#[test]
+fn tracks_use_the_music_timeline() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  pms.route("GET", ":/timeline", 200, "");
+  let mut server = logged_in_plex(&plex_tv, &pms);
+  let mut json =
+    serde_json::from_str::<serde_json::Value>(METADATA).unwrap()["MediaContainer"]["Metadata"][0]
+      .take();
+  json["type"] = serde_json::json!("track");
+  json["title"] = serde_json::json!("Tank!");
+  json["parentTitle"] = serde_json::json!("Cowboy Bebop");
+  json["grandparentTitle"] = serde_json::json!("The Seatbelts");
+  json["index"] = serde_json::json!(1);
+  for key in ["width", "height", "videoCodec"] {
+    json["Media"][0].as_object_mut().unwrap().remove(key);
+  }
+  let track = serde_json::from_value::<PlexItem>(json).unwrap();
+  assert_eq!(
+    track.to_string(),
+    "The Seatbelts - Cowboy Bebop - 01. Tank!"
+  );
+  server.insert_value(
+    MediaCenterValues::PlaybackInfo,
+    serde_json::to_string(&track).unwrap(),
+  );
+
+  let runtime = tokio::runtime::Runtime::new().unwrap();
+  runtime.block_on(server.report_playback(
+    track.ratingKey.clone(),
+    0,
+    12.5,
+    1,
+    0,
+    false,
+    false,
+    80,
+  ));
+  runtime.block_on(server.stop_track_playback(track.ratingKey.clone(), 200.0));
+
+  let requests = pms.requests();
+  assert_eq!(requests.len(), 2);
+  assert!(requests[0].url.contains("type=music"));
+  assert!(requests[0].url.contains("state=playing"));
+  assert!(requests[1].url.contains("type=music"));
+  assert!(requests[1].url.contains("state=stopped"));
+  assert!(requests[1].url.contains("time=200000"));
+  // no scrobbling, plex counts the play by itself
+  assert!(pms.find("GET", ":/scrobble").is_none());
+}
+
+#[test]
+fn stop_playback_scrobbles_watched_item() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  pms.route("GET", ":/scrobble", 200, "");
+  let mut server = playing_server(&plex_tv, &pms);
+
+  let played = tokio::runtime::Runtime::new()
+    .unwrap()
+    .block_on(server.stop_playback(EPISODE_KEY.to_string(), 0, 1474, 1460.0));
+
+  assert!(played);
+  let request = pms.find("GET", ":/scrobble").unwrap();
+  assert!(
+    request
+      .url
+      .contains("identifier=com.plexapp.plugins.library&key=3005")
+  );
+  assert!(pms.find("GET", ":/timeline").is_none());
+}
+
+#[test]
+fn stop_playback_reports_position() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  pms.route("GET", ":/timeline", 200, "");
+  let mut server = playing_server(&plex_tv, &pms);
+
+  let played = tokio::runtime::Runtime::new()
+    .unwrap()
+    .block_on(server.stop_playback(EPISODE_KEY.to_string(), 0, 1474, 600.0));
+
+  assert!(!played);
+  let request = pms.find("GET", ":/timeline").unwrap();
+  assert!(request.url.contains("state=stopped"));
+  assert!(request.url.contains("time=600000"));
+  assert!(pms.find("GET", ":/scrobble").is_none());
+}
+
+#[test]
+fn set_playstate_unscrobbles() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  pms.route("GET", ":/unscrobble", 200, "");
+  let mut server = logged_in_plex(&plex_tv, &pms);
+
+  server.set_playstate(EPISODE_KEY, false).unwrap();
+
+  let request = pms.find("GET", ":/unscrobble").unwrap();
+  assert!(request.url.contains("key=3005"));
+  assert!(pms.find("GET", ":/scrobble").is_none());
+}
+
+#[test]
+fn create_download_uses_part_key() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  let mut json = serde_json::from_str::<serde_json::Value>(METADATA).unwrap();
+  json["MediaContainer"]["Metadata"][0]["Media"][0]["Part"][0]["Stream"][2]["key"] =
+    serde_json::json!("/library/streams/6003");
+  pms.route(
+    "GET",
+    &format!("library/metadata/{}", EPISODE_KEY),
+    200,
+    &json.to_string(),
+  );
+  let mut server = logged_in_plex(&plex_tv, &pms);
+
+  let download = server.create_download(EPISODE_KEY.to_string()).unwrap();
+
+  assert_eq!(
+    download.url,
+    format!(
+      "{}library/parts/5001/1600000000/file.mkv?X-Plex-Token={}&X-Plex-Client-Identifier=puddler-test-device",
+      pms.address, PLEX_TOKEN
+    )
+  );
+  assert_eq!(download.extension, "mkv");
+  assert_eq!(download.playback_position, 600);
+  assert_eq!(download.subtitles.len(), 1);
+  assert_eq!(download.subtitles[0].extension, "ass");
+  assert!(
+    download.subtitles[0]
+      .url
+      .starts_with(&format!("{}library/streams/6003?", pms.address))
+  );
+}
+
+#[test]
+fn continue_watching_includes_on_deck() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  let episode = serde_json::to_value(episode()).unwrap();
+  let movie = serde_json::json!({"ratingKey": "2001", "guid": "plex://movie/2001", "type": "movie", "title": "Perfect Blue"});
+  pms.route(
+    "GET",
+    "hubs/home/continueWatching",
+    200,
+    &serde_json::json!({"MediaContainer": {"size": 1, "Hub": [
+      {"type": "mixed", "hubIdentifier": "home.continue", "size": 1, "Metadata": [episode]}
+    ]}})
+    .to_string(),
+  );
+  pms.route(
+    "GET",
+    "library/onDeck",
+    200,
+    &serde_json::json!({"MediaContainer": {"size": 2, "Metadata": [movie, episode]}}).to_string(),
+  );
+  let mut server = logged_in_plex(&plex_tv, &pms);
+
+  let items = server.continue_watching().unwrap();
+
+  let keys: Vec<&str> = items.iter().map(|item| item.ratingKey.as_str()).collect();
+  assert_eq!(keys, [EPISODE_KEY, "2001"]);
+}
+
+#[test]
+fn older_servers_only_have_on_deck() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  pms.route(
+    "GET",
+    "library/onDeck",
+    200,
+    &serde_json::json!({"MediaContainer": {"size": 1, "Metadata": [episode()]}}).to_string(),
+  );
+  let mut server = logged_in_plex(&plex_tv, &pms);
+
+  let items = server.continue_watching().unwrap();
+
+  assert_eq!(items.len(), 1);
+  assert!(pms.find("GET", "hubs/home/continueWatching").is_some());
+}
+
+#[test]
+fn latest_uses_the_hubs_of_every_library() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  pms.route(
+    "GET",
+    "library/sections",
+    200,
+    r#"{"MediaContainer":{"size":3,"Directory":[
+      {"key":"1","type":"movie","title":"Movies"},
+      {"key":"2","type":"show","title":"Shows"},
+      {"key":"3","type":"artist","title":"Music"}
+    ]}}"#,
+  );
+  pms.route("GET", "hubs/sections/1", 200, SEARCH);
+  pms.route(
+    "GET",
+    "hubs/sections/2",
+    200,
+    &serde_json::json!({"MediaContainer": {"size": 2, "Hub": [
+      {"type": "episode", "hubIdentifier": "tv.recentlyadded.2", "size": 1, "Metadata": [episode()]},
+      {"type": "show", "hubIdentifier": "tv.inprogress.2", "size": 1, "Metadata": [episode()]}
+    ]}})
+    .to_string(),
+  );
+  let mut server = logged_in_plex(&plex_tv, &pms);
+
+  let items = server.latest().unwrap();
+
+  assert_eq!(items.len(), 1);
+  assert_eq!(items[0].ratingKey, EPISODE_KEY);
+  assert!(pms.find("GET", "hubs/sections/3").is_none());
+}
+
+#[test]
+fn markers_and_chapters_are_fetched() {
+  let plex_tv = MockServer::start();
+  let pms = MockServer::start();
+  let mut json = serde_json::from_str::<serde_json::Value>(METADATA).unwrap();
+  json["MediaContainer"]["Metadata"][0]["Marker"] = serde_json::json!([
+    {"id": 2, "type": "credits", "startTimeOffset": 1300000, "endTimeOffset": 1420000, "final": true},
+    {"id": 1, "type": "intro", "startTimeOffset": 45000, "endTimeOffset": 135500}
+  ]);
+  json["MediaContainer"]["Metadata"][0]["Chapter"] = serde_json::json!([
+    {"id": 7, "tag": "Opening", "index": 1, "startTimeOffset": 0, "endTimeOffset": 600000},
+    {"id": 8, "index": 2, "startTimeOffset": 600000, "endTimeOffset": 1440000}
+  ]);
+  pms.route(
+    "GET",
+    &format!("library/metadata/{}", EPISODE_KEY),
+    200,
+    &json.to_string(),
+  );
+  let mut server = logged_in_plex(&plex_tv, &pms);
+
+  let (segments, chapters) = server.get_timeline(EPISODE_KEY);
+
+  assert_eq!(segments.len(), 2);
+  assert_eq!(segments[0].segment_type, SegmentType::Intro);
+  assert_eq!((segments[0].start, segments[0].end), (45.0, 135.5));
+  assert_eq!(segments[1].segment_type, SegmentType::Outro);
+  assert_eq!(chapters.len(), 2);
+  assert_eq!(chapters[0].title, "Opening");
+  assert_eq!(chapters[1].title, "Chapter 2");
+  assert_eq!(chapters[1].start, 600.0);
+  let request = pms
+    .find("GET", &format!("library/metadata/{}", EPISODE_KEY))
+    .unwrap();
+  assert!(request.url.contains("includeMarkers=1&includeChapters=1"));
+}
+
+#[test]
+fn gdm_answers_of_servers_are_parsed() {
+  let answer = parse_gdm_answer(
+    "HTTP/1.0 200 OK\r\nContent-Type: plex/media-server\r\nResource-Identifier: 0123456789abcdef\r\nName: Basement\r\nPort: 32401\r\nUpdated-At: 1700000000\r\nVersion: 1.40.0\r\n\r\n",
+    "192.168.1.20",
+  )
+  .unwrap();
+  assert_eq!(answer.Address, "http://192.168.1.20:32401/");
+  assert_eq!(answer.Name, "Basement");
+  assert_eq!(answer.Id, "0123456789abcdef");
+  assert_eq!(answer.Version.as_deref(), Some("1.40.0"));
+
+  // players use GDM as well
+  assert!(
+    parse_gdm_answer(
+      "HTTP/1.0 200 OK\r\nContent-Type: plex/media-player\r\nResource-Identifier: abc\r\nName: Puddler\r\n\r\n",
+      "192.168.1.21",
+    )
+    .is_none()
+  );
+  assert!(parse_gdm_answer("M-SEARCH * HTTP/1.1\r\n\r\n", "192.168.1.22").is_none());
+}
+
+#[test]
+fn servers_answering_twice_are_listed_once() {
+  let answer = |address: &str, name: &str, id: &str| UDPAnswer {
+    Address: address.to_string(),
+    Name: name.to_string(),
+    Id: id.to_string(),
+    Version: None,
+  };
+  let answers = dedup_answers(vec![
+    answer("http://192.168.1.20:32400/", "Basement", "abc"),
+    // same server, reached through another interface
+    answer("http://10.0.0.20:32400/", "Basement", "abc"),
+    answer("http://192.168.1.30:8096", "Attic", ""),
+    answer("http://192.168.1.30:8096/", "Attic", ""),
+    answer("http://192.168.1.40:8096", "Garage", ""),
+  ]);
+  let names: Vec<&str> = answers.iter().map(|answer| answer.Name.as_str()).collect();
+  assert_eq!(names, vec!["Basement", "Attic", "Garage"]);
+}
+
+#[test]
+fn machine_identifier_survives_other_settings() {
+  let pms = MockServer::start();
+  let mut config = test_config(MediaCenterType::Plex, &pms.address);
+  config.insert_specific_value(
+    Objective::MachineIdentifier,
+    String::from("0123456789abcdef"),
+  );
+  add_user(&mut config, PLEX_TOKEN, "alice", PLEX_USER_ID);
+  assert_eq!(
+    config.get_machine_identifier(),
+    Some(String::from("0123456789abcdef"))
+  );
+  // the other configs don't get an empty field
+  let jellyfin = test_config(MediaCenterType::Jellyfin, &pms.address);
+  assert!(
+    jellyfin
+      .config
+      .specific_values
+      .get("machine_identifier")
+      .is_none()
+  );
+}
@@ -0,0 +1,87 @@
+use std::path::Path;
+
+use crate::{
+  jellyfin::JellyfinServer,
+  media_center::MediaCenter,
+  media_item::{MediaItem, MediaKind},
+  queue::{EntryAction, add_to_queue, edit_queue, store_queue},
+  tests::{logged_in_jellyfin, logged_in_plex, mock_server::MockServer, test_settings},
+};
+
+fn episode(id: &str) -> MediaItem {
+  MediaItem {
+    id: id.to_string(),
+    kind: MediaKind::Episode,
+    titles: vec![id.to_string()],
+    played: false,
+    playback_position: 0,
+    runtime: 0,
+  }
+}
+
+#[test]
+fn entries_move_within_the_queue() {
+  let mut queue = vec!["a", "b", "c"];
+
+  edit_queue(&mut queue, 2, EntryAction::MoveUp);
+  assert_eq!(queue, ["a", "c", "b"]);
+  edit_queue(&mut queue, 0, EntryAction::MoveDown);
+  assert_eq!(queue, ["c", "a", "b"]);
+  edit_queue(&mut queue, 0, EntryAction::MoveUp);
+  edit_queue(&mut queue, 2, EntryAction::MoveDown);
+  assert_eq!(queue, ["c", "a", "b"]);
+  edit_queue(&mut queue, 1, EntryAction::Remove);
+  assert_eq!(queue, ["c", "b"]);
+}
+
+#[test]
+fn added_items_go_to_the_end() {
+  let mut queue = vec!["movie"];
+
+  add_to_queue(&mut queue, vec!["episode 1", "episode 2"]);
+
+  assert_eq!(queue, ["movie", "episode 1", "episode 2"]);
+}
+
+#[test]
+fn the_queue_is_kept_between_sessions() {
+  let mock = MockServer::start();
+  let mut server = logged_in_jellyfin(&mock);
+  let path = server.get_config_handle().queue_path();
+
+  add_to_queue(server.get_queue(), vec![episode("e1"), episode("e2")]);
+  store_queue(&mut server);
+  let config = server.get_config_handle().clone();
+  let mut restarted = JellyfinServer::new(config, test_settings());
+  assert_eq!(restarted.get_queue(), &vec![episode("e1"), episode("e2")]);
+
+  // an empty queue doesn't leave a file behind
+  restarted.get_queue().clear();
+  store_queue(&mut restarted);
+  assert!(!Path::new(&path).exists());
+}
+
+#[test]
+fn plex_queue_is_saved_as_playlist() {
+  let pms = MockServer::start();
+  pms.route(
+    "GET",
+    "identity",
+    200,
+    r#"{"MediaContainer":{"machineIdentifier":"abc123"}}"#,
+  );
+  pms.route("POST", "playlists", 200, "{}");
+  let mut server = logged_in_plex(&MockServer::start(), &pms);
+  server.get_queue().push(episode("3005"));
+  server.get_queue().push(episode("3006"));
+  let queue = server.get_queue().clone();
+
+  server.save_playlist("Late night", &queue).unwrap();
+
+  let request = pms.find("POST", "playlists").unwrap();
+  assert!(request.url.contains("title=Late%20night"));
+  assert!(request.url.contains(&format!(
+    "uri={}",
+    urlencoding::encode("server://abc123/com.plexapp.plugins.library/library/metadata/3005,3006")
+  )));
+}
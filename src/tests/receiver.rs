@@ -0,0 +1,47 @@
+use serde_json::json;
+
+use crate::{
+  media_center::MediaCenter,
+  mpv::WebSocketMessage,
+  receiver::{play_request, run_receiver},
+  tests::{logged_in_jellyfin, mock_server::MockServer},
+};
+
+fn message(message_type: &str, data: serde_json::Value) -> WebSocketMessage {
+  WebSocketMessage {
+    MessageType: String::from(message_type),
+    Data: data,
+  }
+}
+
+#[test]
+fn only_play_messages_are_requests() {
+  let request = play_request(&message(
+    "Play",
+    json!({ "ItemIds": ["e1", "e2"], "StartPositionTicks": 600000000, "PlayCommand": "PlayNow" }),
+  ))
+  .unwrap();
+  assert_eq!(request.ItemIds, vec!["e1", "e2"]);
+  assert_eq!(request.StartPositionTicks, Some(600000000));
+
+  assert!(
+    play_request(&message(
+      "Play",
+      json!({ "ItemIds": [], "PlayCommand": "PlayNow" })
+    ))
+    .is_none()
+  );
+  assert!(play_request(&message("Playstate", json!({ "Command": "Stop" }))).is_none());
+  assert!(play_request(&message("ForceKeepAlive", json!(60))).is_none());
+}
+
+#[test]
+fn receiver_refuses_transcoding() {
+  let mock = MockServer::start();
+  let mut server = logged_in_jellyfin(&mock);
+  server.get_config_handle().config.transcoding = true;
+
+  assert!(run_receiver(&mut server).is_err());
+  // nothing has been sent to the server
+  assert!(mock.requests().is_empty());
+}
@@ -0,0 +1,49 @@
+use crate::{
+  error::PuddlerError,
+  search::{SearchQuery, parse_search},
+};
+
+#[test]
+fn filters_are_taken_out_of_the_term() {
+  let query =
+    parse_search("cowboy type:shows bebop year:1998 rating:8.5 director:\"Shinichiro Watanabe\"")
+      .unwrap();
+
+  assert_eq!(
+    query,
+    SearchQuery {
+      term: String::from("cowboy bebop"),
+      item_type: Some(String::from("series")),
+      years: Some((1998, 1998)),
+      min_rating: Some(8.5),
+      person: Some(String::from("Shinichiro Watanabe")),
+      person_type: Some(String::from("Director")),
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn unknown_keys_stay_in_the_term() {
+  let query = parse_search("Re:Zero").unwrap();
+
+  assert_eq!(query.term, "Re:Zero");
+  assert!(!query.has_filters());
+}
+
+#[test]
+fn invalid_filters_are_rejected() {
+  for input in [
+    "type:podcast",
+    "year:2001-1999",
+    "year:0-99999",
+    "rating:good",
+    "is:favorite",
+  ] {
+    assert!(
+      matches!(parse_search(input), Err(PuddlerError::Unsupported(_))),
+      "{}",
+      input
+    );
+  }
+}
@@ -0,0 +1,96 @@
+use crate::segments::{
+  Chapter, Segment, SegmentType, chapters_file, parse_media_segments, segment_at,
+};
+
+const SEGMENTS: &str = r#"{"Items":[
+  {"Id":"s2","ItemId":"e1","Type":"Outro","StartTicks":13200000000,"EndTicks":14100000000},
+  {"Id":"s1","ItemId":"e1","Type":"Intro","StartTicks":600000000,"EndTicks":1500000000},
+  {"Id":"s0","ItemId":"e1","Type":"Unknown","StartTicks":0,"EndTicks":100000000}
+],"TotalRecordCount":3,"StartIndex":0}"#;
+
+fn intro() -> Segment {
+  Segment {
+    segment_type: SegmentType::Intro,
+    start: 60.0,
+    end: 150.0,
+  }
+}
+
+#[test]
+fn media_segments_are_sorted_and_converted() {
+  let segments = parse_media_segments(SEGMENTS).unwrap();
+
+  assert_eq!(
+    segments,
+    [
+      intro(),
+      Segment {
+        segment_type: SegmentType::Outro,
+        start: 1320.0,
+        end: 1410.0,
+      },
+    ]
+  );
+}
+
+#[test]
+fn the_last_second_is_not_part_of_a_segment() {
+  let segments = [intro()];
+
+  assert_eq!(segment_at(&segments, 59.5), None);
+  assert_eq!(segment_at(&segments, 60.0), Some(0));
+  assert_eq!(segment_at(&segments, 148.5), Some(0));
+  assert_eq!(segment_at(&segments, 149.5), None);
+}
+
+#[test]
+fn chapters_cover_the_whole_video() {
+  let chapters = chapters_file(&[], &[intro()], 1440, 0, "Episode");
+
+  assert!(chapters.starts_with(";FFMETADATA1\n"));
+  assert!(chapters.contains("START=0\nEND=60000\ntitle=Episode\n"));
+  assert!(chapters.contains("START=60000\nEND=150000\ntitle=Intro\n"));
+  assert!(chapters.contains("START=150000\nEND=1440000\ntitle=Episode\n"));
+}
+
+#[test]
+fn chapters_start_where_the_transcode_starts() {
+  let chapters = chapters_file(&[], &[intro()], 1440, 100, "Episode");
+
+  assert_eq!(chapters.matches("[CHAPTER]").count(), 2);
+  assert!(chapters.contains("START=0\nEND=50000\ntitle=Intro\n"));
+  assert!(chapters.contains("START=50000\nEND=1340000\ntitle=Episode\n"));
+}
+
+#[test]
+fn segments_interrupt_the_chapters_of_the_server() {
+  let chapters = [
+    Chapter {
+      title: String::from("Cold open"),
+      start: 0.0,
+    },
+    Chapter {
+      title: String::from("Part A"),
+      start: 150.2,
+    },
+    Chapter {
+      title: String::from("Part B; the end"),
+      start: 700.0,
+    },
+  ];
+  let commercial = Segment {
+    segment_type: SegmentType::Commercial,
+    start: 900.0,
+    end: 960.0,
+  };
+
+  let file = chapters_file(&chapters, &[intro(), commercial], 1440, 0, "Episode");
+
+  assert_eq!(file.matches("[CHAPTER]").count(), 6);
+  assert!(file.contains("START=0\nEND=60000\ntitle=Cold open\n"));
+  assert!(file.contains("START=60000\nEND=150200\ntitle=Intro\n"));
+  assert!(file.contains("START=150200\nEND=700000\ntitle=Part A\n"));
+  assert!(file.contains("START=700000\nEND=900000\ntitle=Part B\\; the end\n"));
+  assert!(file.contains("START=900000\nEND=960000\ntitle=Commercial\n"));
+  assert!(file.contains("START=960000\nEND=1440000\ntitle=Part B\\; the end\n"));
+}
@@ -0,0 +1,30 @@
+use crate::input::{random_episodes, shuffle_episodes};
+
+#[test]
+fn shuffling_keeps_the_selection() {
+  let watched = [true, false, false, true, false];
+
+  let mut shuffled = shuffle_episodes(&[0, 1, 3, 9], &watched, false);
+  shuffled.sort();
+  assert_eq!(shuffled, [0, 1, 3]);
+
+  let mut unplayed = shuffle_episodes(&[0, 1, 2, 3, 4], &watched, true);
+  unplayed.sort();
+  assert_eq!(unplayed, [1, 2, 4]);
+}
+
+#[test]
+fn random_episodes_prefer_unwatched_ones() {
+  let watched = [true, false, true, false, true];
+
+  let mut picked = random_episodes(2, &watched);
+  picked.sort();
+  assert_eq!(picked, [1, 3]);
+
+  let picked = random_episodes(4, &watched);
+  assert_eq!(picked.len(), 4);
+  assert!(!watched[picked[0]] && !watched[picked[1]]);
+  assert!(watched[picked[2]] && watched[picked[3]]);
+
+  assert_eq!(random_episodes(10, &watched).len(), 5);
+}
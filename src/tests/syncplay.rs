@@ -0,0 +1,116 @@
+use chrono::{TimeDelta, Utc};
+use serde_json::json;
+
+use crate::{
+  mpv::WebSocketMessage,
+  syncplay::{SyncPlay, get_groups, get_time_offset},
+  tests::{logged_in_jellyfin, mock_server::MockServer},
+};
+
+const GROUP_ID: &str = "9f1c2d3e4b5a69788796a5b4c3d2e1f0";
+
+fn group_update(update_type: &str, data: serde_json::Value) -> WebSocketMessage {
+  WebSocketMessage {
+    MessageType: String::from("SyncPlayGroupUpdate"),
+    Data: json!({ "GroupId": GROUP_ID, "Type": update_type, "Data": data }),
+  }
+}
+
+fn play_queue(playlist_item_id: &str) -> serde_json::Value {
+  json!({
+    "Reason": "NextItem",
+    "LastUpdate": "2025-03-01T20:15:00.0000000Z",
+    "Playlist": [
+      { "ItemId": "e1", "PlaylistItemId": "p1" },
+      { "ItemId": "e2", "PlaylistItemId": playlist_item_id }
+    ],
+    "PlayingItemIndex": 1,
+    "StartPositionTicks": 0,
+    "IsPlaying": true,
+    "ShuffleMode": "Sorted",
+    "RepeatMode": "RepeatNone"
+  })
+}
+
+#[test]
+fn get_groups_lists_groups() {
+  let mock = MockServer::start();
+  mock.route(
+    "GET",
+    "SyncPlay/List",
+    200,
+    &json!([{
+      "GroupId": GROUP_ID,
+      "GroupName": "Movie night",
+      "State": "Playing",
+      "Participants": ["alice", "bob"],
+      "LastUpdatedAt": "2025-03-01T20:15:00.0000000Z"
+    }])
+    .to_string(),
+  );
+  let mut server = logged_in_jellyfin(&mock);
+
+  let groups = get_groups(&mut server).unwrap();
+
+  assert_eq!(groups.len(), 1);
+  assert_eq!(groups[0].GroupName, "Movie night");
+  assert_eq!(groups[0].Participants, vec!["alice", "bob"]);
+}
+
+#[test]
+fn time_offset_uses_server_time() {
+  let mock = MockServer::start();
+  let server_time = (Utc::now() + TimeDelta::minutes(10)).to_rfc3339();
+  mock.route(
+    "GET",
+    "GetUtcTime",
+    200,
+    &json!({
+      "RequestReceptionTime": server_time,
+      "ResponseTransmissionTime": server_time
+    })
+    .to_string(),
+  );
+  let mut server = logged_in_jellyfin(&mock);
+
+  let offset = get_time_offset(&mut server).unwrap();
+
+  assert!((offset - TimeDelta::minutes(10)).num_seconds().abs() <= 1);
+}
+
+#[test]
+fn play_queue_update_with_new_item_is_remembered() {
+  let mut syncplay = SyncPlay::new(
+    GROUP_ID.to_string(),
+    String::from("p1"),
+    true,
+    TimeDelta::zero(),
+  );
+
+  // the item that is currently playing
+  syncplay.receive(&group_update("PlayQueue", play_queue("p1")));
+  assert!(syncplay.next.is_none());
+
+  syncplay.receive(&group_update("PlayQueue", play_queue("p2")));
+  let next = syncplay.next.unwrap();
+  assert_eq!(next.playing_item().unwrap().ItemId, "e2");
+}
+
+#[test]
+fn leaving_the_group_is_noticed() {
+  let mut syncplay = SyncPlay::new(
+    GROUP_ID.to_string(),
+    String::from("p1"),
+    true,
+    TimeDelta::zero(),
+  );
+
+  syncplay.receive(&WebSocketMessage {
+    MessageType: String::from("SyncPlayGroupUpdate"),
+    Data: json!({ "GroupId": "someone-else", "Type": "GroupLeft", "Data": null }),
+  });
+  assert!(!syncplay.left);
+
+  syncplay.receive(&group_update("GroupLeft", json!(GROUP_ID)));
+  assert!(syncplay.left);
+}